@@ -0,0 +1,48 @@
+//! The `rox` command, which runs scripts with either implementation of the
+//! language: `--backend=ast` for the tree-walking interpreter and
+//! `--backend=vm` for the bytecode VM. Every other argument goes to the
+//! chosen backend's own command line, so the same script and flags can be
+//! tried on both.
+
+use std::{env, process};
+
+use rox_lang::cli as vm;
+use rox_script::cli as ast;
+
+enum Backend {
+    Ast,
+    Vm,
+}
+
+fn main() -> anyhow::Result<()> {
+    // An executable made by `rox pack` runs the script packed into it, and
+    // every argument it is given goes to the script, `--backend` included.
+    ast::run_if_packed()?;
+
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // The tree-walking interpreter runs the whole language, while the VM
+    // doesn't have its extensions yet, so it is the default until it does.
+    let mut backend = Backend::Ast;
+    // Only the flags before the script are the command's; the ones after it
+    // are the script's.
+    let flags = args.iter().take_while(|arg| arg.starts_with("--")).count();
+    if let Some(index) = args[..flags]
+        .iter()
+        .position(|arg| arg.starts_with("--backend="))
+    {
+        backend = match &args.remove(index)["--backend=".len()..] {
+            "ast" => Backend::Ast,
+            "vm" => Backend::Vm,
+            other => {
+                eprintln!("Unknown backend '{other}', expected 'ast' or 'vm'.");
+                process::exit(64);
+            }
+        };
+    }
+
+    match backend {
+        Backend::Ast => ast::main(args)?,
+        Backend::Vm => vm::main(args)?,
+    }
+    Ok(())
+}
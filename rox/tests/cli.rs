@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Writes `source` to a scratch file no other call shares and runs it with
+/// `args` before its path.
+fn run(args: &[&str], source: &str) -> Output {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!("rox_cli_test_{}_{n}.lox", std::process::id()));
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_rox"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn both_backends_run_the_same_script() {
+    let source = "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nprint fib(10);\n";
+    for backend in ["--backend=ast", "--backend=vm"] {
+        let output = run(&[backend], source);
+        assert_eq!(stdout(&output), "55\n", "{backend}");
+        assert_eq!(output.status.code(), Some(0), "{backend}");
+    }
+}
+
+#[test]
+fn the_tree_walker_is_the_default() {
+    // Only the tree-walking interpreter converts numbers when concatenating.
+    let source = "print \"n: \" + 1;\n";
+    let output = run(&[], source);
+    assert_eq!(stdout(&output), "n: 1\n");
+
+    let output = run(&["--backend=vm"], source);
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn other_arguments_go_to_the_backend() {
+    let output = run(&["--backend=vm", "disasm"], "print 1;\n");
+    assert!(stdout(&output).starts_with("== <script> ==\n"));
+
+    let output = run(&["--resolve", "--backend=ast", "ast"], "print 1;\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("print"));
+}
+
+#[test]
+fn unknown_backends_are_a_usage_error() {
+    let output = run(&["--backend=jit"], "print 1;\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Unknown backend 'jit', expected 'ast' or 'vm'.\n"
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
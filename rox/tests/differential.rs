@@ -0,0 +1,109 @@
+//! Runs the same scripts through both backends and checks they agree: the
+//! same stdout, the same exit status and so the same class of error, 65 for
+//! compile errors and 70 for runtime errors. The messages themselves aren't
+//! compared, as each backend words and places them its own way.
+//!
+//! The scripts are the ones below rox_script's `tests/scripts` that stick to
+//! the language from the book, which both backends implement. Set
+//! `ROX_DIFFERENTIAL_DIR` to a directory to run the scripts below it instead.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The directories below rox_script's `tests/scripts` whose scripts only use
+/// the book's language.
+const BOOK_SCRIPTS: &[&str] = &[
+    "arithmetic",
+    "assignment",
+    "classes",
+    "closures",
+    "control_flow",
+    "functions",
+    "scopes",
+];
+
+/// Scripts in those directories that use the tree-walking interpreter's
+/// extensions after all, which the VM doesn't have.
+const EXTENSIONS: &[&str] = &[
+    // `try`, arrays and maps.
+    "arithmetic/operand_types.rox",
+    // The `bindThis` native.
+    "classes/bind_this.rox",
+    // Arrays.
+    "closures/counter.rox",
+    "closures/loop_variable.rox",
+    // The `globals` and `locals` natives.
+    "scopes/globals.rox",
+    "scopes/locals.rox",
+];
+
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            found.extend(scripts(&path));
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "rox" || ext == "lox")
+        {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+fn run(backend: &str, script: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rox"))
+        .arg(format!("--backend={backend}"))
+        .arg(script)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn backends_agree() {
+    let scripts: Vec<PathBuf> = match env::var_os("ROX_DIFFERENTIAL_DIR") {
+        Some(dir) => scripts(Path::new(&dir)),
+        None => {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../rox_script/tests/scripts");
+            BOOK_SCRIPTS
+                .iter()
+                .flat_map(|dir| scripts(&root.join(dir)))
+                .filter(|script| {
+                    !EXTENSIONS
+                        .iter()
+                        .any(|extension| script.ends_with(extension))
+                })
+                .collect()
+        }
+    };
+    assert!(!scripts.is_empty(), "no scripts to run");
+
+    let mut disagreements = vec![];
+    for script in &scripts {
+        let ast = run("ast", script);
+        let vm = run("vm", script);
+        if ast.stdout != vm.stdout || ast.status.code() != vm.status.code() {
+            disagreements.push(format!(
+                "{}:\n  ast exited with {:?} after printing {:?}\n  vm exited with {:?} after printing {:?}\n  vm stderr: {:?}",
+                script.display(),
+                ast.status.code(),
+                String::from_utf8_lossy(&ast.stdout),
+                vm.status.code(),
+                String::from_utf8_lossy(&vm.stdout),
+                String::from_utf8_lossy(&vm.stderr),
+            ));
+        }
+    }
+    assert!(
+        disagreements.is_empty(),
+        "the backends disagree on {} of {} scripts:\n{}",
+        disagreements.len(),
+        scripts.len(),
+        disagreements.join("\n")
+    );
+}
@@ -0,0 +1,11 @@
+//! The parts of Lox both backends share: the tokens and the scanner that
+//! makes them, the natives both define and the rules their values follow. The tree-walking interpreter in
+//! `rox_script` collects the tokens up front and parses them into a syntax
+//! tree; the bytecode compiler in `rox_lang` pulls them one at a time as it
+//! compiles. Changes to what the language's tokens are happen here, once,
+//! for both.
+
+pub mod natives;
+pub mod scanner;
+pub mod token;
+pub mod value;
@@ -0,0 +1,49 @@
+//! The natives both backends define, so scripts calling them run the same
+//! on either. Each is listed once in [`natives`] with its name, arity and
+//! implementation; a backend implements [`Host`] to give the
+//! implementations its values and its clock, then defines every entry as a
+//! global, along with the numbers in [`CONSTANTS`].
+
+/// What the shared natives need from the backend calling them.
+pub trait Host {
+    /// The backend's runtime value.
+    type Value;
+
+    /// The value for `number`.
+    fn number(&self, number: f64) -> Self::Value;
+
+    /// Milliseconds since the Unix epoch, as `clock()` returns them.
+    fn clock(&mut self) -> f64;
+}
+
+/// The implementation of a native, called with the backend and exactly as
+/// many arguments as its arity.
+pub type NativeFn<H> = fn(&mut H, &[<H as Host>::Value]) -> anyhow::Result<<H as Host>::Value>;
+
+/// The numbers both backends define as globals, by name. They aren't
+/// literals, so scripts can still use the names for their own variables.
+pub const CONSTANTS: &[(&str, f64)] = &[("nan", f64::NAN), ("inf", f64::INFINITY)];
+
+/// A native as the registry lists it.
+pub struct Native<H: Host> {
+    pub name: &'static str,
+    /// How many arguments it takes. Backends check this before calling
+    /// `function`.
+    pub arity: usize,
+    pub function: NativeFn<H>,
+}
+
+/// Every shared native, in the order they are defined.
+pub fn natives<H: Host>() -> Vec<Native<H>> {
+    vec![Native {
+        name: "clock",
+        arity: 0,
+        function: clock,
+    }]
+}
+
+/// `clock()`: milliseconds since the Unix epoch.
+fn clock<H: Host>(host: &mut H, _: &[H::Value]) -> anyhow::Result<H::Value> {
+    let millis = host.clock();
+    Ok(host.number(millis))
+}
@@ -0,0 +1,403 @@
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::token::TokenType::{
+    BANG, BANGEQUAL, COLON, COMMA, DOT, EOF, EQUAL, EQUALEQUAL, ERROR, GREATER, GREATEREQUAL,
+    IDENTIFIER, LANG, LEFTBRACE, LEFTBRACKET, LEFTPAREN, LESS, LESSEQUAL, MINUS, NUMBER, PLUS,
+    RIGHTBRACE, RIGHTBRACKET, RIGHTPAREN, SEMICOLON, SLASH, STAR, STRING,
+};
+use crate::token::{keyword, Literal, Token, TokenType};
+
+/// Scans source into tokens one at a time, as [`Scanner::scan_token`] is
+/// called. After an `ERROR` token it carries on past the characters it
+/// complained about, and once the source runs out it keeps returning `EOF`.
+#[derive(Debug, Default)]
+pub struct Scanner {
+    source: String,
+    start: u32,
+    current: u32,
+    line: u32,
+    /// The byte offset the current line starts at.
+    line_start: u32,
+    /// The column the current token starts at.
+    column: u32,
+    /// The offset `column` was counted up to, so the next token's column is
+    /// counted on from there rather than from the start of the line.
+    column_offset: u32,
+}
+
+impl Scanner {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            line: 1,
+            column: 1,
+            ..Scanner::default()
+        }
+    }
+
+    pub fn scan_token(&mut self) -> Token {
+        self.skip_white_spaces();
+        // We are at the beginning of the next lexeme.
+        self.start = self.current;
+        self.column = if self.column_offset >= self.line_start {
+            self.column + self.count_chars(self.column_offset, self.start)
+        } else {
+            self.count_chars(self.line_start, self.start) + 1
+        };
+        self.column_offset = self.start;
+        if self.is_at_end() {
+            return self.make_token(EOF, None);
+        }
+
+        let current_char = self.advance();
+        match current_char {
+            '(' => self.make_token(LEFTPAREN, None),
+            ')' => self.make_token(RIGHTPAREN, None),
+            '{' => self.make_token(LEFTBRACE, None),
+            '}' => self.make_token(RIGHTBRACE, None),
+            '[' => self.make_token(LEFTBRACKET, None),
+            ']' => self.make_token(RIGHTBRACKET, None),
+            ',' => self.make_token(COMMA, None),
+            ':' => self.make_token(COLON, None),
+            '.' => self.make_token(DOT, None),
+            '-' => self.make_token(MINUS, None),
+            '+' => self.make_token(PLUS, None),
+            ';' => self.make_token(SEMICOLON, None),
+            '*' => self.make_token(STAR, None),
+            '/' => self.make_token(SLASH, None),
+            '!' => self.one_or_two('=', BANGEQUAL, BANG),
+            '=' => self.one_or_two('=', EQUALEQUAL, EQUAL),
+            '<' => self.one_or_two('=', LESSEQUAL, LESS),
+            '>' => self.one_or_two('=', GREATEREQUAL, GREATER),
+            '#' if self.start == 0 && self.source.starts_with("#lang") => self.lang_directive(),
+            '#' if self.source[self.start as usize..].starts_with("#lang") => {
+                self.misplaced_lang_directive()
+            }
+            '"' => self.string(),
+            _ if Self::is_digit(current_char) => self.number(),
+            _ if Self::is_alpha(current_char) => self.identifier(),
+            _ => {
+                // Skip the rest of a multi-byte character, so the next token
+                // starts on a character boundary.
+                while self.peek_byte().is_some_and(|byte| (byte & 0xC0) == 0x80) {
+                    self.current += 1;
+                }
+                self.error("Unexpected character.")
+            }
+        }
+    }
+
+    /// Skips whitespace and comments, counting the lines they end.
+    fn skip_white_spaces(&mut self) {
+        loop {
+            match self.peek() {
+                ' ' | '\r' | '\t' => {
+                    self.advance();
+                }
+                '\n' => {
+                    self.advance();
+                    self.line += 1;
+                    self.line_start = self.current;
+                }
+                '/' if self.double_peek() == '/' => {
+                    // A comment goes until the end of the line.
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// A `two` token if the next character is `second`, which it consumes,
+    /// and a `one` token otherwise.
+    fn one_or_two(&mut self, second: char, two: TokenType, one: TokenType) -> Token {
+        let token_type = if self.next_is(second) { two } else { one };
+        self.make_token(token_type, None)
+    }
+
+    /// Reads the `#lang N` directive a script can start with, for the
+    /// parser to check the script against.
+    fn lang_directive(&mut self) -> Token {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+        let version = &self.source["#lang".len()..self.current as usize];
+        match version.trim().parse::<u32>() {
+            Ok(version) => self.make_token(LANG, Some(Literal::Number(version as f64))),
+            Err(_) => self.error("Expect a version number after '#lang'."),
+        }
+    }
+
+    /// Skips a `#lang` directive after the start of the script, reporting
+    /// it as one error rather than one per character.
+    fn misplaced_lang_directive(&mut self) -> Token {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+        self.error("'#lang' must be the first line of the script.")
+    }
+
+    fn is_digit(input: char) -> bool {
+        input.is_ascii_digit()
+    }
+
+    fn is_alpha(input: char) -> bool {
+        input.is_ascii_alphabetic() || input == '_'
+    }
+
+    fn is_alpha_numeric(input: char) -> bool {
+        input.is_ascii_alphanumeric() || input == '_'
+    }
+
+    /// Reads a number literal: decimal digits with an optional fraction and
+    /// exponent, like `1.5e-3`, or hex digits after `0x`, like `0xFF`.
+    /// Underscores can separate digits, as in `1_000_000`.
+    fn number(&mut self) -> Token {
+        let first = self.source.as_bytes()[self.start as usize];
+        if first == b'0' && matches!(self.peek(), 'x' | 'X') {
+            self.advance();
+            if !self.peek().is_ascii_hexdigit() {
+                return self.error("Expect hex digits after '0x'.");
+            }
+            if let Err(error) = self.digits(|digit| digit.is_ascii_hexdigit()) {
+                return error;
+            }
+            let value = self.digits_read(2).chars().fold(0.0, |value, digit| {
+                value * 16.0 + f64::from(digit.to_digit(16).unwrap_or_default())
+            });
+            return self.make_token(NUMBER, Some(Literal::Number(value)));
+        }
+
+        if let Err(error) = self.digits(Self::is_digit) {
+            return error;
+        }
+        if self.peek() == '.' && Self::is_digit(self.double_peek()) {
+            // this consumes the .
+            self.advance();
+            if let Err(error) = self.digits(Self::is_digit) {
+                return error;
+            }
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            let signed = matches!(self.double_peek(), '+' | '-');
+            let first_digit = if signed { 2 } else { 1 };
+            if Self::is_digit(self.peek_ahead(first_digit)) {
+                for _ in 0..first_digit {
+                    self.advance();
+                }
+                if let Err(error) = self.digits(Self::is_digit) {
+                    return error;
+                }
+            }
+        }
+        match f64::from_str(&self.digits_read(0)) {
+            Ok(value) => self.make_token(NUMBER, Some(Literal::Number(value))),
+            Err(_) => self.error("Invalid number."),
+        }
+    }
+
+    /// Reads the rest of a run of digits `is_digit` accepts, which may be
+    /// separated by single underscores.
+    fn digits(&mut self, is_digit: fn(char) -> bool) -> Result<(), Token> {
+        loop {
+            let next = self.peek();
+            if is_digit(next) {
+                self.advance();
+            } else if next == '_' {
+                self.advance();
+                if !is_digit(self.peek()) {
+                    return Err(self.error("Underscores in numbers must be between digits."));
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The number read so far without its first `prefix` characters and
+    /// without underscores.
+    fn digits_read(&self, prefix: u32) -> String {
+        self.source[(self.start + prefix) as usize..self.current as usize].replace('_', "")
+    }
+
+    /// Reads an identifier or a keyword.
+    fn identifier(&mut self) -> Token {
+        while Self::is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+        let text = &self.source[self.start as usize..self.current as usize];
+        self.make_token(keyword(text).unwrap_or(IDENTIFIER), None)
+    }
+
+    /// Reads a string literal, translating the escape sequences `\n`, `\t`,
+    /// `\r`, `\0`, `\"`, `\\` and `\u{XXXX}` into the characters they stand for.
+    fn string(&mut self) -> Token {
+        let mut value = Vec::new();
+        // Where the first invalid escape sequence's backslash is, as a line,
+        // the offset that line starts at and an offset.
+        let mut invalid_escape = None;
+        let terminated = loop {
+            if self.is_at_end() {
+                break false;
+            }
+            let byte = self.source.as_bytes()[self.current as usize];
+            self.current += 1;
+            match byte {
+                b'"' => break true,
+                b'\\' => {
+                    let backslash = self.current - 1;
+                    let location = (self.line, self.line_start, backslash);
+                    match self.escape() {
+                        Some(escaped) => {
+                            let mut buffer = [0; 4];
+                            value.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        None => {
+                            invalid_escape.get_or_insert(location);
+                        }
+                    }
+                }
+                b'\n' => {
+                    self.line += 1;
+                    self.line_start = self.current;
+                    value.push(byte);
+                }
+                // A line break in the string is `\n` whichever line endings
+                // the script was saved with.
+                b'\r' if self.peek() == '\n' => {}
+                _ => value.push(byte),
+            }
+        };
+
+        if let Some((line, line_start, offset)) = invalid_escape {
+            let column = self.count_chars(line_start, offset) + 1;
+            return self.error_at(line, column, offset, 1, "Invalid escape sequence.");
+        }
+        if !terminated {
+            return self.error("Unterminated string.");
+        }
+        let value = String::from_utf8(value).expect("the source and its escapes are UTF-8");
+        self.make_token(STRING, Some(Literal::String(value)))
+    }
+
+    /// The character the escape sequence after a backslash stands for, or
+    /// `None` if it isn't a valid one.
+    fn escape(&mut self) -> Option<char> {
+        // A line break is left for the string to count.
+        if self.is_at_end() || self.peek() == '\n' {
+            return None;
+        }
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '"' => '"',
+            '\\' => '\\',
+            'u' => {
+                if !self.next_is('{') {
+                    return None;
+                }
+                let digits_start = self.current as usize;
+                while self.peek().is_ascii_hexdigit() {
+                    self.advance();
+                }
+                let digits_end = self.current as usize;
+                if !self.next_is('}') {
+                    return None;
+                }
+                return u32::from_str_radix(&self.source[digits_start..digits_end], 16)
+                    .ok()
+                    .and_then(char::from_u32);
+            }
+            _ => return None,
+        };
+        Some(escaped)
+    }
+
+    fn peek_byte(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.current as usize).copied()
+    }
+
+    fn peek(&self) -> char {
+        self.peek_ahead(0)
+    }
+
+    fn double_peek(&self) -> char {
+        self.peek_ahead(1)
+    }
+
+    /// The character `distance` characters past the next one.
+    fn peek_ahead(&self, distance: u32) -> char {
+        let index = (self.current + distance) as usize;
+        match self.source.as_bytes().get(index) {
+            Some(byte) => *byte as char,
+            None => '\0',
+        }
+    }
+
+    fn next_is(&mut self, item: char) -> bool {
+        if self.is_at_end() || self.peek() != item {
+            return false;
+        }
+        // consume the matched item
+        self.current += 1;
+        true
+    }
+
+    /// How many characters the bytes from `start` to `end` hold.
+    fn count_chars(&self, start: u32, end: u32) -> u32 {
+        let bytes = &self.source.as_bytes()[start as usize..end as usize];
+        // Counting the bytes that don't continue a UTF-8 sequence counts the
+        // characters.
+        bytes.iter().filter(|byte| (**byte & 0xC0) != 0x80).count() as u32
+    }
+
+    fn make_token(&self, token_type: TokenType, literal: Option<Literal>) -> Token {
+        Token {
+            token_type,
+            lexeme: Rc::from(&self.source[self.start as usize..self.current as usize]),
+            literal,
+            line: self.line,
+            column: self.column,
+            offset: self.start,
+            length: self.current - self.start,
+        }
+    }
+
+    /// An error about the characters read since the current token started.
+    fn error(&self, message: &str) -> Token {
+        self.error_at(
+            self.line,
+            self.column,
+            self.start,
+            self.current - self.start,
+            message,
+        )
+    }
+
+    fn error_at(&self, line: u32, column: u32, offset: u32, length: u32, message: &str) -> Token {
+        Token {
+            token_type: ERROR,
+            lexeme: Rc::from(message),
+            literal: None,
+            line,
+            column,
+            offset,
+            length,
+        }
+    }
+
+    fn advance(&mut self) -> char {
+        let item = self.source.as_bytes()[self.current as usize] as char;
+        self.current += 1;
+        item
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.current as usize >= self.source.len()
+    }
+}
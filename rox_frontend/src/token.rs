@@ -0,0 +1,136 @@
+use std::rc::Rc;
+
+/// The reserved words and the tokens they scan to.
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("and", TokenType::AND),
+    ("assert", TokenType::ASSERT),
+    ("case", TokenType::CASE),
+    ("catch", TokenType::CATCH),
+    ("class", TokenType::CLASS),
+    ("default", TokenType::DEFAULT),
+    ("else", TokenType::ELSE),
+    ("export", TokenType::EXPORT),
+    ("false", TokenType::FALSE),
+    ("for", TokenType::FOR),
+    ("finally", TokenType::FINALLY),
+    ("fun", TokenType::FUN),
+    ("if", TokenType::IF),
+    ("import", TokenType::IMPORT),
+    ("nil", TokenType::NIL),
+    ("or", TokenType::OR),
+    ("print", TokenType::PRINT),
+    ("return", TokenType::RETURN),
+    ("super", TokenType::SUPER),
+    ("switch", TokenType::SWITCH),
+    ("this", TokenType::THIS),
+    ("throw", TokenType::THROW),
+    ("true", TokenType::TRUE),
+    ("try", TokenType::TRY),
+    ("var", TokenType::VAR),
+    ("while", TokenType::WHILE),
+];
+
+/// The token `text` scans to if it is a reserved word.
+pub fn keyword(text: &str) -> Option<TokenType> {
+    KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == text)
+        .map(|(_, token_type)| *token_type)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum TokenType {
+    // Single character token
+    LEFTPAREN,
+    RIGHTPAREN,
+    LEFTBRACE,
+    RIGHTBRACE,
+    LEFTBRACKET,
+    RIGHTBRACKET,
+    COMMA,
+    COLON,
+    DOT,
+    MINUS,
+    PLUS,
+    SEMICOLON,
+    SLASH,
+    STAR,
+
+    // One or two character token
+    BANG,
+    BANGEQUAL,
+    EQUAL,
+    EQUALEQUAL,
+    GREATER,
+    GREATEREQUAL,
+    LESS,
+    LESSEQUAL,
+
+    // Literals
+    IDENTIFIER,
+    STRING,
+    NUMBER,
+
+    // Keywords
+    AND,
+    ASSERT,
+    CASE,
+    CATCH,
+    CLASS,
+    DEFAULT,
+    ELSE,
+    EXPORT,
+    FALSE,
+    FINALLY,
+    FUN,
+    FOR,
+    IF,
+    IMPORT,
+    NIL,
+    OR,
+    PRINT,
+    RETURN,
+    SUPER,
+    SWITCH,
+    THIS,
+    THROW,
+    TRUE,
+    TRY,
+    VAR,
+    WHILE,
+
+    /// A `#lang N` directive, which can only start a script.
+    LANG,
+
+    /// Something the scanner couldn't make a token of. Its lexeme is the
+    /// message saying why.
+    ERROR,
+    EOF,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub token_type: TokenType,
+    /// The source text of the token, or the message of an `ERROR` token.
+    pub lexeme: Rc<str>,
+    /// The value of a `NUMBER` or `STRING` token, or the version of a `LANG`
+    /// token.
+    pub literal: Option<Literal>,
+    pub line: u32,
+    /// Which character of its line the token starts at, counting from 1.
+    pub column: u32,
+    /// Byte offset of the token in the source. For an `ERROR` token, of the
+    /// characters the error is about.
+    pub offset: u32,
+    /// How many bytes of the source the token covers.
+    pub length: u32,
+}
+
+/// The value a literal token stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(f64),
+    /// The string's text, with its escape sequences translated.
+    String(String),
+}
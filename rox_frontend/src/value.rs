@@ -0,0 +1,28 @@
+//! The rules about values both backends follow. Each backend has a value
+//! type of its own, but how those values print and behave in the language
+//! is decided here, once, so the same script runs the same on either. A
+//! backend implements [`LoxValue`] for its value type to get the rules.
+
+use std::fmt::{self, Formatter};
+
+/// Writes `number` as `print` shows it: whole numbers without a fraction,
+/// and `nan`, `inf` and `-inf` spelled as the globals naming them.
+pub fn fmt_number(number: f64, f: &mut Formatter<'_>) -> fmt::Result {
+    if number.is_nan() {
+        return write!(f, "nan");
+    }
+    write!(f, "{number}")
+}
+
+/// What the language's rules need to know about a backend's value.
+pub trait LoxValue {
+    fn is_nil(&self) -> bool;
+
+    /// The boolean the value is, if it is one.
+    fn as_bool(&self) -> Option<bool>;
+
+    /// Lox truthiness: `nil` and `false` are falsey, every other value is truthy.
+    fn truthy(&self) -> bool {
+        !self.is_nil() && self.as_bool() != Some(false)
+    }
+}
@@ -0,0 +1,226 @@
+use rox_frontend::scanner::Scanner;
+use rox_frontend::token::TokenType::*;
+use rox_frontend::token::{Literal, Token, TokenType, KEYWORDS};
+
+/// Every token in `source`, up to and including the first `EOF`.
+fn scan(source: &str) -> Vec<Token> {
+    let mut scanner = Scanner::new(source);
+    let mut tokens = vec![];
+    loop {
+        let token = scanner.scan_token();
+        let at_end = token.token_type == EOF;
+        tokens.push(token);
+        if at_end {
+            return tokens;
+        }
+    }
+}
+
+fn types(source: &str) -> Vec<TokenType> {
+    scan(source).iter().map(|token| token.token_type).collect()
+}
+
+#[test]
+fn scans_operators_keywords_and_identifiers() {
+    assert_eq!(
+        types("var x = a <= b != !c; // done\nfor (;;) {}"),
+        vec![
+            VAR, IDENTIFIER, EQUAL, IDENTIFIER, LESSEQUAL, IDENTIFIER, BANGEQUAL, BANG, IDENTIFIER,
+            SEMICOLON, FOR, LEFTPAREN, SEMICOLON, SEMICOLON, RIGHTPAREN, LEFTBRACE, RIGHTBRACE,
+            EOF
+        ]
+    );
+    assert_eq!(
+        types("fun fn classy class or orchid"),
+        vec![FUN, IDENTIFIER, IDENTIFIER, CLASS, OR, IDENTIFIER, EOF]
+    );
+    assert_eq!(types("nan inf"), vec![IDENTIFIER, IDENTIFIER, EOF]);
+}
+
+#[test]
+fn literals_carry_their_values() {
+    let tokens = scan(r#"1_000 0xFF 1.5e-3 "a\tb\u{41}""#);
+    let literals: Vec<_> = tokens.iter().map(|token| token.literal.clone()).collect();
+    assert_eq!(literals[0], Some(Literal::Number(1000.0)));
+    assert_eq!(literals[1], Some(Literal::Number(255.0)));
+    assert_eq!(literals[2], Some(Literal::Number(0.0015)));
+    assert_eq!(literals[3], Some(Literal::String("a\tbA".to_string())));
+    assert_eq!(&*tokens[3].lexeme, r#""a\tb\u{41}""#);
+}
+
+#[test]
+fn tokens_know_where_they_are() {
+    let tokens = scan("\"é\" b\n  c");
+    let places: Vec<_> = tokens
+        .iter()
+        .map(|token| (token.line, token.column, token.offset))
+        .collect();
+    assert_eq!(places, vec![(1, 1, 0), (1, 5, 5), (2, 3, 9), (2, 4, 10)]);
+}
+
+#[test]
+fn lang_directive_after_the_first_line_is_one_error() {
+    let tokens = scan("a\n#lang 1\nb");
+    assert_eq!(
+        types("a\n#lang 1\nb"),
+        vec![IDENTIFIER, ERROR, IDENTIFIER, EOF]
+    );
+    assert_eq!(
+        (&*tokens[1].lexeme, tokens[1].line, tokens[1].length),
+        ("'#lang' must be the first line of the script.", 2, 7)
+    );
+}
+
+#[test]
+fn errors_are_tokens_and_scanning_carries_on() {
+    let tokens = scan("1 @ é \"a\\q\" 1__0 \"open");
+    let errors: Vec<_> = tokens
+        .iter()
+        .filter(|token| token.token_type == ERROR)
+        .map(|token| (&*token.lexeme, token.column, token.length))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            ("Unexpected character.", 3, 1),
+            ("Unexpected character.", 5, 2),
+            ("Invalid escape sequence.", 9, 1),
+            ("Underscores in numbers must be between digits.", 13, 2),
+            ("Unterminated string.", 18, 5),
+        ]
+    );
+    assert_eq!(tokens.first().unwrap().token_type, NUMBER);
+    assert_eq!(tokens.last().unwrap().token_type, EOF);
+}
+
+#[test]
+fn keywords_are_recognized_wherever_they_are() {
+    assert_eq!(
+        types("var a = 1; if (a) print false; else print true;"),
+        vec![
+            VAR, IDENTIFIER, EQUAL, NUMBER, SEMICOLON, IF, LEFTPAREN, IDENTIFIER, RIGHTPAREN,
+            PRINT, FALSE, SEMICOLON, ELSE, PRINT, TRUE, SEMICOLON, EOF
+        ]
+    );
+}
+
+#[test]
+fn only_whole_words_are_keywords() {
+    for (keyword, token_type) in KEYWORDS {
+        assert_eq!(types(keyword), vec![*token_type, EOF], "{keyword}");
+        let prefix = &keyword[..keyword.len() - 1];
+        assert_eq!(types(prefix), vec![IDENTIFIER, EOF], "{prefix}");
+        let longer = format!("{keyword}s");
+        assert_eq!(types(&longer), vec![IDENTIFIER, EOF], "{longer}");
+        let capitalized = keyword.to_uppercase();
+        assert_eq!(types(&capitalized), vec![IDENTIFIER, EOF], "{capitalized}");
+    }
+    assert_eq!(
+        types("f t fa th _if if_ if2"),
+        [vec![IDENTIFIER; 7], vec![EOF]].concat()
+    );
+}
+
+#[test]
+fn scans_a_class_with_inheritance() {
+    let source = "class B < A {\n  init(x) { this.x = x; }\n  get() { return super.get() + this.x; }\n}\nfor (var i = 0; i < 3; i = i + 1) print B(i).get();\n";
+    let lexemes: Vec<_> = scan(source)
+        .iter()
+        .map(|token| format!("{:?} {}", token.token_type, token.lexeme))
+        .collect();
+    assert_eq!(
+        lexemes.join(" | "),
+        "CLASS class | IDENTIFIER B | LESS < | IDENTIFIER A | LEFTBRACE { | \
+         IDENTIFIER init | LEFTPAREN ( | IDENTIFIER x | RIGHTPAREN ) | LEFTBRACE { | THIS this | DOT . | IDENTIFIER x | EQUAL = | IDENTIFIER x | SEMICOLON ; | RIGHTBRACE } | \
+         IDENTIFIER get | LEFTPAREN ( | RIGHTPAREN ) | LEFTBRACE { | RETURN return | SUPER super | DOT . | IDENTIFIER get | LEFTPAREN ( | RIGHTPAREN ) | PLUS + | THIS this | DOT . | IDENTIFIER x | SEMICOLON ; | RIGHTBRACE } | \
+         RIGHTBRACE } | \
+         FOR for | LEFTPAREN ( | VAR var | IDENTIFIER i | EQUAL = | NUMBER 0 | SEMICOLON ; | IDENTIFIER i | LESS < | NUMBER 3 | SEMICOLON ; | IDENTIFIER i | EQUAL = | IDENTIFIER i | PLUS + | NUMBER 1 | RIGHTPAREN ) | \
+         PRINT print | IDENTIFIER B | LEFTPAREN ( | IDENTIFIER i | RIGHTPAREN ) | DOT . | IDENTIFIER get | LEFTPAREN ( | RIGHTPAREN ) | SEMICOLON ; | EOF "
+    );
+    let lines: Vec<_> = scan(source).iter().map(|token| token.line).collect();
+    assert_eq!(lines.iter().filter(|line| **line == 2).count(), 12);
+    assert_eq!(*lines.last().unwrap(), 6);
+}
+
+/// Checks that scanning `source` ends at an `EOF` that keeps being
+/// returned, with every token inside the source and none going backwards.
+fn assert_scans_to_the_end(source: &str) {
+    let mut scanner = Scanner::new(source);
+    let mut offset = 0;
+    // Every token but the `EOF` covers at least one byte.
+    for _ in 0..=source.len() {
+        let token = scanner.scan_token();
+        assert!(token.offset >= offset, "{source:?} went back at {token:?}");
+        assert!(
+            (token.offset + token.length) as usize <= source.len(),
+            "{source:?} went past the end at {token:?}"
+        );
+        offset = token.offset;
+        if token.token_type == EOF {
+            assert_eq!(scanner.scan_token().token_type, EOF, "{source:?}");
+            return;
+        }
+    }
+    panic!("{source:?} didn't reach the end");
+}
+
+#[test]
+fn incomplete_tokens_end_without_hanging() {
+    for source in [
+        "",
+        "/",
+        "a /",
+        "/ /",
+        "//",
+        "// no newline",
+        "\"",
+        "\"\\",
+        "\"\\u{",
+        "\"\\u{41",
+        "0x",
+        "1_",
+        "1.",
+        "1e",
+        "1e+",
+        "#",
+        "#lang",
+        "#lang x",
+        "!",
+        "=",
+        "\r",
+        "\0",
+        "é",
+        "a\u{0301}",
+    ] {
+        assert_scans_to_the_end(source);
+    }
+    assert_eq!(types("/"), vec![SLASH, EOF]);
+    assert_eq!(
+        types("a / b // c"),
+        vec![IDENTIFIER, SLASH, IDENTIFIER, EOF]
+    );
+}
+
+#[test]
+fn arbitrary_bytes_scan_to_the_end() {
+    // A fixed xorshift generator, so a failure can be reproduced.
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    // Bytes that start or end tokens are likelier than the rest.
+    let interesting = b"/\"\\\n\r #0x_.e+-=!<>(){}[];,u";
+    for _ in 0..2000 {
+        let length = (next() % 40) as usize;
+        let bytes: Vec<u8> = (0..length)
+            .map(|_| match next() % 3 {
+                0 => next() as u8,
+                _ => interesting[(next() % interesting.len() as u64) as usize],
+            })
+            .collect();
+        assert_scans_to_the_end(&String::from_utf8_lossy(&bytes));
+    }
+}
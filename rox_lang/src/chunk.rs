@@ -1,8 +1,12 @@
 use crate::chunk::OpCode::{OpNegate, OpReturn};
+use crate::value::{Obj, Value};
 use anyhow::anyhow;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 #[repr(u8)]
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum OpCode {
     OpConstant,
     OpAdd,
@@ -11,6 +15,39 @@ pub enum OpCode {
     OpDivide,
     OpNegate,
     OpReturn,
+    OpNil,
+    OpTrue,
+    OpFalse,
+    OpNot,
+    OpEqual,
+    OpGreater,
+    OpLess,
+    OpPrint,
+    OpPop,
+    OpDefineGlobal,
+    OpGetGlobal,
+    OpSetGlobal,
+    OpGetLocal,
+    OpSetLocal,
+    OpJump,
+    OpJumpIfFalse,
+    OpLoop,
+    OpCall,
+    OpGetUpvalue,
+    OpSetUpvalue,
+    OpClosure,
+    OpCloseUpvalue,
+    OpGetProperty,
+    OpSetProperty,
+    OpClass,
+    OpMethod,
+    OpInvoke,
+    OpInherit,
+    OpGetSuper,
+    OpSuperInvoke,
+    /// OpConstant for constants past index 255, with a three-byte
+    /// big-endian index.
+    OpConstantLong,
 }
 
 impl TryFrom<&u8> for OpCode {
@@ -25,6 +62,37 @@ impl TryFrom<&u8> for OpCode {
             4 => Ok(OpCode::OpDivide),
             5 => Ok(OpNegate),
             6 => Ok(OpReturn),
+            7 => Ok(OpCode::OpNil),
+            8 => Ok(OpCode::OpTrue),
+            9 => Ok(OpCode::OpFalse),
+            10 => Ok(OpCode::OpNot),
+            11 => Ok(OpCode::OpEqual),
+            12 => Ok(OpCode::OpGreater),
+            13 => Ok(OpCode::OpLess),
+            14 => Ok(OpCode::OpPrint),
+            15 => Ok(OpCode::OpPop),
+            16 => Ok(OpCode::OpDefineGlobal),
+            17 => Ok(OpCode::OpGetGlobal),
+            18 => Ok(OpCode::OpSetGlobal),
+            19 => Ok(OpCode::OpGetLocal),
+            20 => Ok(OpCode::OpSetLocal),
+            21 => Ok(OpCode::OpJump),
+            22 => Ok(OpCode::OpJumpIfFalse),
+            23 => Ok(OpCode::OpLoop),
+            24 => Ok(OpCode::OpCall),
+            25 => Ok(OpCode::OpGetUpvalue),
+            26 => Ok(OpCode::OpSetUpvalue),
+            27 => Ok(OpCode::OpClosure),
+            28 => Ok(OpCode::OpCloseUpvalue),
+            29 => Ok(OpCode::OpGetProperty),
+            30 => Ok(OpCode::OpSetProperty),
+            31 => Ok(OpCode::OpClass),
+            32 => Ok(OpCode::OpMethod),
+            33 => Ok(OpCode::OpInvoke),
+            34 => Ok(OpCode::OpInherit),
+            35 => Ok(OpCode::OpGetSuper),
+            36 => Ok(OpCode::OpSuperInvoke),
+            37 => Ok(OpCode::OpConstantLong),
             _ => Err(anyhow!("No enum variant for {value}")),
         }
     }
@@ -32,11 +100,32 @@ impl TryFrom<&u8> for OpCode {
 
 /// Chunk has a constant field which when a constant
 /// is added via add_const it returns the index of the constant
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct Chunk {
     pub code: Vec<u8>,
-    pub lines: Vec<u32>,
-    pub constant: Vec<f64>,
+    /// The source line of each byte of code, run-length encoded: most
+    /// lines compile to several bytes in a row. Read with
+    /// [`Chunk::get_line`].
+    pub lines: Vec<LineRun>,
+    pub constant: Vec<Value>,
+    /// Where each constant [`Chunk::add_const`] added is in the pool, so
+    /// adding one already there doesn't search the pool for it.
+    constant_index: HashMap<ConstantKey, usize>,
+}
+
+/// What tells constants apart: a number's bits, so 0 and -0 stay apart, or
+/// which object it is, since strings are interned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Number(u64),
+    Obj(*const Obj),
+}
+
+/// A run of consecutive bytes of code compiled from the same line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRun {
+    pub line: u32,
+    pub count: usize,
 }
 
 impl Chunk {
@@ -46,19 +135,41 @@ impl Chunk {
 
     /// Adds the opcode to the code byte array, and also the corresponding
     /// line the code exist in the source
-    pub fn write(&mut self, code: u8, line: u32) -> () {
+    pub fn write(&mut self, code: u8, line: u32) {
         self.code.push(code);
-        self.lines.push(line);
+        match self.lines.last_mut() {
+            Some(run) if run.line == line => run.count += 1,
+            _ => self.lines.push(LineRun { line, count: 1 }),
+        }
+    }
+
+    /// The source line the byte of code at `offset` was compiled from.
+    pub fn get_line(&self, offset: usize) -> Option<u32> {
+        let mut end = 0;
+        for run in &self.lines {
+            end += run.count;
+            if offset < end {
+                return Some(run.line);
+            }
+        }
+        None
     }
 
-    /// Adds a constant to the constant pool and return the index
-    // TDOO optimise for same value using same index
-    //
-    pub fn add_const(&mut self, constant: f64) -> u8 {
-        if self.constant.len() + 1 > 256 {
-            panic!("Constant pool currently can support only 255 constants")
+    /// Adds a constant to the constant pool and return the index. A
+    /// constant the same as one already in the pool reuses its index.
+    pub fn add_const(&mut self, constant: Value) -> usize {
+        let key = match &constant {
+            Value::Number(n) => ConstantKey::Number(n.to_bits()),
+            Value::Obj(obj) => ConstantKey::Obj(Rc::as_ptr(obj)),
+            Value::Bool(_) | Value::Nil => {
+                unreachable!("booleans and nil have their own opcodes")
+            }
+        };
+        if let Some(index) = self.constant_index.get(&key) {
+            return *index;
         }
         self.constant.push(constant);
-        (self.constant.len() - 1) as u8
+        self.constant_index.insert(key, self.constant.len() - 1);
+        self.constant.len() - 1
     }
 }
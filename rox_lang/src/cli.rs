@@ -0,0 +1,121 @@
+//! The bytecode VM's command line: running a script or the REPL, printing
+//! a script's bytecode, and compiling a script to a `.roxc` file to run
+//! later.
+
+use crate::compiler::compile;
+use crate::debug::disassemble_function;
+use crate::roxc;
+use crate::strings::Strings;
+use crate::vm::{InterpretResult, VM};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::path::Path;
+use std::{fs, process};
+
+/// Runs the bytecode VM's command line with `args`, the ones after the
+/// executable's name.
+pub fn main(mut args: Vec<String>) -> anyhow::Result<()> {
+    let mut vm = VM::new();
+    args.retain(|arg| match arg.as_str() {
+        "--stress-gc" => {
+            vm.heap.stress = true;
+            false
+        }
+        "--log-gc" => {
+            vm.heap.log = true;
+            false
+        }
+        _ => true,
+    });
+
+    if args.first().map(String::as_str) == Some("disasm") {
+        if args.len() != 2 {
+            println!("Usage: rox disasm <script>");
+            process::exit(1);
+        }
+        return disassemble(&args[1]);
+    }
+
+    if args.first().map(String::as_str) == Some("compile") {
+        if !(2..=3).contains(&args.len()) {
+            println!("Usage: rox compile <script> [output]");
+            process::exit(1);
+        }
+        return compile_to_file(&args[1], args.get(2));
+    }
+
+    if args.len() > 1 {
+        println!("Usage: rox [--stress-gc] [--log-gc] [script | compiled.roxc]");
+        println!("       rox disasm <script>");
+        println!("       rox compile <script> [output]");
+        process::exit(1);
+    }
+
+    if args.len() == 1 {
+        // Compiled scripts are told apart by their header, not their name.
+        let bytes = fs::read(args.remove(0))?;
+        let result = if bytes.starts_with(roxc::MAGIC) {
+            vm.interpret_compiled(&bytes)
+        } else {
+            vm.interpret(&String::from_utf8(bytes)?)
+        };
+        match result {
+            InterpretResult::InterpretOk => {}
+            InterpretResult::InterpretCompileError => process::exit(65),
+            InterpretResult::InterpretRuntimeError => process::exit(70),
+        }
+    } else {
+        let mut rl = DefaultEditor::new()?;
+        rl.load_history("history_rox.txt").ok();
+
+        loop {
+            let readline = rl.readline(">> ");
+            match readline {
+                Ok(line) => {
+                    vm.interpret(&line);
+                }
+                Err(ReadlineError::Interrupted) => {
+                    println!("CTRL-C");
+                    break;
+                }
+                Err(ReadlineError::Eof) => {
+                    println!("CTRL-D");
+                    break;
+                }
+                Err(err) => {
+                    println!("Error: {:?}", err);
+                    break;
+                }
+            }
+        }
+        rl.save_history("history_rox.txt").ok();
+    }
+
+    Ok(())
+}
+
+/// Compiles the script at `path` and prints the bytecode of every function
+/// in it, without running it.
+fn disassemble(path: &str) -> anyhow::Result<()> {
+    let source = fs::read_to_string(path)?;
+    let Some(script) = compile(&source, &mut Strings::new()) else {
+        process::exit(65);
+    };
+    print!("{}", disassemble_function(&script)?);
+    Ok(())
+}
+
+/// Compiles the script at `path` and saves it in the `.roxc` format to
+/// `output`, or next to the script with a `.roxc` extension.
+fn compile_to_file(path: &str, output: Option<&String>) -> anyhow::Result<()> {
+    let source = fs::read_to_string(path)?;
+    let Some(script) = compile(&source, &mut Strings::new()) else {
+        process::exit(65);
+    };
+    let output = match output {
+        Some(output) => output.into(),
+        None => Path::new(path).with_extension("roxc"),
+    };
+    fs::write(output, roxc::serialize(&script.chunk))?;
+    Ok(())
+}
@@ -1,21 +1,1040 @@
-use crate::scanner::{Scanner, Token, TokenType};
+use crate::chunk::{Chunk, OpCode};
+use crate::strings::Strings;
+use crate::value::{Obj, ObjFunction, Value};
+use rox_frontend::scanner::Scanner;
+use rox_frontend::token::{Literal, Token, TokenType};
+use std::mem;
+use std::rc::Rc;
 
-pub fn compile(source: &str) -> () {
-    let mut scanner = Scanner::new(source.to_string());
-    let mut line = -1;
-    loop {
-         let token: Token = scanner.scan_token();
-         if token.line != line {
-             print!("{:4} ", token.line);
-             line = token.line;
-         } else {
-             print!("   | ");
-         }
-        println!("{:2} '{}'", token.token_type, token.token);
+/// How many constants OpConstantLong's three-byte operand can index.
+const MAX_LONG_CONSTANTS: usize = 1 << 24;
 
-        if token.token_type == TokenType::EOF {
-            break;
+/// Operator precedence, lowest first. Parsing at a precedence consumes every
+/// operator that binds at least as tightly.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    None,
+    Assignment, // =
+    Or,         // or
+    And,        // and
+    Equality,   // == !=
+    Comparison, // < > <= >=
+    Term,       // + -
+    Factor,     // * /
+    Unary,      // ! -
+    Call,       // . ()
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter precedence, for parsing the right operand of a
+    /// left-associative operator.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Parses one piece of an expression. The flag says whether an `=` after
+/// it would assign to it, which only holds at assignment precedence.
+type ParseFn = fn(&mut Compiler, bool);
+
+/// How a token is parsed at the start of an expression, after an operand,
+/// and how tightly it binds as an infix operator.
+struct ParseRule {
+    prefix: Option<ParseFn>,
+    infix: Option<ParseFn>,
+    precedence: Precedence,
+}
+
+fn get_rule(token_type: &TokenType) -> ParseRule {
+    let (prefix, infix, precedence): (Option<ParseFn>, Option<ParseFn>, Precedence) =
+        match token_type {
+            TokenType::LEFTPAREN => (
+                Some(Compiler::grouping),
+                Some(Compiler::call),
+                Precedence::Call,
+            ),
+            TokenType::DOT => (None, Some(Compiler::dot), Precedence::Call),
+            TokenType::THIS => (Some(Compiler::this), None, Precedence::None),
+            TokenType::SUPER => (Some(Compiler::super_), None, Precedence::None),
+            TokenType::MINUS => (
+                Some(Compiler::unary),
+                Some(Compiler::binary),
+                Precedence::Term,
+            ),
+            TokenType::PLUS => (None, Some(Compiler::binary), Precedence::Term),
+            TokenType::SLASH | TokenType::STAR => {
+                (None, Some(Compiler::binary), Precedence::Factor)
+            }
+            TokenType::BANG => (Some(Compiler::unary), None, Precedence::None),
+            TokenType::BANGEQUAL | TokenType::EQUALEQUAL => {
+                (None, Some(Compiler::binary), Precedence::Equality)
+            }
+            TokenType::GREATER
+            | TokenType::GREATEREQUAL
+            | TokenType::LESS
+            | TokenType::LESSEQUAL => (None, Some(Compiler::binary), Precedence::Comparison),
+            TokenType::IDENTIFIER => (Some(Compiler::variable), None, Precedence::None),
+            TokenType::AND => (None, Some(Compiler::and), Precedence::And),
+            TokenType::OR => (None, Some(Compiler::or), Precedence::Or),
+            TokenType::NUMBER => (Some(Compiler::number), None, Precedence::None),
+            TokenType::STRING => (Some(Compiler::string), None, Precedence::None),
+            TokenType::FALSE | TokenType::NIL | TokenType::TRUE => {
+                (Some(Compiler::literal), None, Precedence::None)
+            }
+            _ => (None, None, Precedence::None),
+        };
+    ParseRule {
+        prefix,
+        infix,
+        precedence,
+    }
+}
+
+/// A local variable, which lives in a stack slot rather than the globals
+/// table. Its slot is its index in [`FunctionCompiler::locals`].
+struct Local {
+    name: Rc<str>,
+    /// How many blocks deep it was declared, or `None` while its
+    /// initializer is being compiled.
+    depth: Option<usize>,
+    /// Whether a closure captures it, so it must be moved off the stack
+    /// rather than popped when its block ends.
+    is_captured: bool,
+}
+
+/// A variable of an enclosing function that a function captures.
+#[derive(PartialEq)]
+struct Upvalue {
+    /// The local's slot in the function right around this one, or the index
+    /// of that function's own upvalue.
+    index: u8,
+    is_local: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    Function,
+    /// A class's `init` method, which always returns the instance.
+    Initializer,
+    Method,
+    Script,
+}
+
+/// A class whose body is being compiled.
+struct ClassCompiler {
+    has_superclass: bool,
+}
+
+/// One function being compiled. Function declarations nest, so the compiler
+/// keeps a stack of these, the innermost last.
+struct FunctionCompiler {
+    function: ObjFunction,
+    function_type: FunctionType,
+    /// The locals in scope, innermost last, mirroring the function's window
+    /// of the VM's stack.
+    locals: Vec<Local>,
+    /// How many blocks the code being compiled is nested in; 0 for globals.
+    scope_depth: usize,
+    /// The variables of enclosing functions it captures, in the order the
+    /// VM finds them in its closures.
+    upvalues: Vec<Upvalue>,
+}
+
+impl FunctionCompiler {
+    fn new(function_type: FunctionType, name: Option<String>) -> Self {
+        FunctionCompiler {
+            function: ObjFunction {
+                name,
+                ..ObjFunction::default()
+            },
+            function_type,
+            // Slot 0 holds the function being called, so it can't be
+            // named, except in methods, where it holds the instance.
+            locals: vec![Local {
+                name: Rc::from(match function_type {
+                    FunctionType::Initializer | FunctionType::Method => "this",
+                    FunctionType::Function | FunctionType::Script => "",
+                }),
+                depth: Some(0),
+                is_captured: false,
+            }],
+            scope_depth: 0,
+            upvalues: vec![],
+        }
+    }
+}
+
+/// A single-pass compiler: a Pratt parser that emits bytecode for each piece
+/// of the source as soon as it has parsed it.
+struct Compiler {
+    scanner: Scanner,
+    current: Token,
+    previous: Token,
+    had_error: bool,
+    /// Set after an error until the parser resynchronizes, so one mistake
+    /// doesn't cascade into many reports.
+    panic_mode: bool,
+    /// The functions being compiled, the script first and the innermost
+    /// last.
+    functions: Vec<FunctionCompiler>,
+    /// The classes being compiled, innermost last.
+    classes: Vec<ClassCompiler>,
+    /// Where the string constants are interned, borrowed from the VM.
+    strings: Strings,
+}
+
+/// Compiles `source` to the function for its top-level code, or returns
+/// `None` after reporting its compile errors to stderr. Its string
+/// constants are interned in `strings`.
+pub fn compile(source: &str, strings: &mut Strings) -> Option<ObjFunction> {
+    let mut scanner = Scanner::new(source);
+    let first = scanner.scan_token();
+    let mut compiler = Compiler {
+        scanner,
+        current: first.clone(),
+        previous: first,
+        had_error: false,
+        panic_mode: false,
+        functions: vec![FunctionCompiler::new(FunctionType::Script, None)],
+        classes: vec![],
+        strings: mem::take(strings),
+    };
+    compiler.skip_errors();
+    while !compiler.matches(TokenType::EOF) {
+        compiler.declaration();
+    }
+    let script = compiler.end().function;
+    *strings = compiler.strings;
+    if compiler.had_error {
+        None
+    } else {
+        Some(script)
+    }
+}
+
+impl Compiler {
+    fn current_function(&mut self) -> &mut FunctionCompiler {
+        self.functions
+            .last_mut()
+            .expect("the script is compiled until the end")
+    }
+
+    /// The chunk of the function being compiled.
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current_function().function.chunk
+    }
+
+    fn advance(&mut self) {
+        let next = self.scanner.scan_token();
+        self.previous = std::mem::replace(&mut self.current, next);
+        self.skip_errors();
+    }
+
+    /// Reports and skips the error tokens the scanner produced, leaving the
+    /// first valid token as the current one.
+    fn skip_errors(&mut self) {
+        while self.current.token_type == TokenType::ERROR {
+            let message = self.current.lexeme.to_string();
+            self.error_at_current(&message);
+            self.current = self.scanner.scan_token();
+        }
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) {
+        if self.current.token_type == token_type {
+            self.advance();
+        } else {
+            self.error_at_current(message);
+        }
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.current.token_type == token_type
+    }
+
+    /// Consumes the current token if it is a `token_type`.
+    fn matches(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let line = self.previous.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_bytes(&mut self, first: u8, second: u8) {
+        self.emit_byte(first);
+        self.emit_byte(second);
+    }
+
+    /// Emits `instruction` with a placeholder offset to patch once the
+    /// target is known, and returns where the offset is.
+    fn emit_jump(&mut self, instruction: OpCode) -> usize {
+        self.emit_byte(instruction as u8);
+        self.emit_bytes(0xff, 0xff);
+        self.chunk().code.len() - 2
+    }
+
+    /// Points the jump whose offset is at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        // The jump is relative to the end of its two offset bytes.
+        let jump = self.chunk().code.len() - offset - 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error("Too much code to jump over.");
+            return;
+        };
+        let [high, low] = jump.to_be_bytes();
+        self.chunk().code[offset] = high;
+        self.chunk().code[offset + 1] = low;
+    }
+
+    /// Emits a jump back to `loop_start`.
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_byte(OpCode::OpLoop as u8);
+        let jump = self.chunk().code.len() - loop_start + 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error("Loop body too large.");
+            return;
+        };
+        let [high, low] = jump.to_be_bytes();
+        self.emit_bytes(high, low);
+    }
+
+    /// Emits an instruction loading `value`, which takes OpConstantLong
+    /// once the chunk has more constants than one byte can index.
+    fn emit_constant(&mut self, value: Value) {
+        let index = self.chunk().add_const(value);
+        if let Ok(index) = u8::try_from(index) {
+            self.emit_bytes(OpCode::OpConstant as u8, index);
+        } else if index < MAX_LONG_CONSTANTS {
+            let [_, high, middle, low] = (index as u32).to_be_bytes();
+            self.emit_byte(OpCode::OpConstantLong as u8);
+            self.emit_bytes(high, middle);
+            self.emit_byte(low);
+        } else {
+            self.error("Too many constants in one chunk.");
+        }
+    }
+
+    /// Adds `value` to the constant pool for an instruction whose operand
+    /// is a one-byte index, like the name of a global.
+    fn make_constant(&mut self, value: Value) -> u8 {
+        let index = self.chunk().add_const(value);
+        u8::try_from(index).unwrap_or_else(|_| {
+            self.error("Too many constants in one chunk.");
+            0
+        })
+    }
+
+    /// Returns from the function as falling off its end does: with `nil`,
+    /// or the instance from an initializer.
+    fn emit_return(&mut self) {
+        if self.current_function().function_type == FunctionType::Initializer {
+            self.emit_bytes(OpCode::OpGetLocal as u8, 0);
+        } else {
+            self.emit_byte(OpCode::OpNil as u8);
+        }
+        self.emit_byte(OpCode::OpReturn as u8);
+    }
+
+    /// Finishes the function being compiled and returns it.
+    fn end(&mut self) -> FunctionCompiler {
+        self.emit_return();
+        self.functions
+            .pop()
+            .expect("the script is compiled until the end")
+    }
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    fn declaration(&mut self) {
+        if self.matches(TokenType::CLASS) {
+            self.class_declaration();
+        } else if self.matches(TokenType::FUN) {
+            self.fun_declaration();
+        } else if self.matches(TokenType::VAR) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn class_declaration(&mut self) {
+        self.consume(TokenType::IDENTIFIER, "Expect class name.");
+        let class_name = self.previous.clone();
+        let name_constant = self.identifier_constant(&class_name);
+        self.declare_variable();
+        self.emit_bytes(OpCode::OpClass as u8, name_constant);
+        self.define_variable(name_constant);
+        self.classes.push(ClassCompiler {
+            has_superclass: false,
+        });
+
+        if self.matches(TokenType::LESS) {
+            self.consume(TokenType::IDENTIFIER, "Expect superclass name.");
+            self.variable(false);
+            if self.previous.lexeme == class_name.lexeme {
+                self.error("A class can't inherit from itself.");
+            }
+            // The superclass is kept in a local named `super` in a scope
+            // around the methods, which capture it to find `super` calls.
+            self.begin_scope();
+            self.add_local(Rc::from("super"));
+            self.define_variable(0);
+            self.named_variable(&class_name, false);
+            self.emit_byte(OpCode::OpInherit as u8);
+            if let Some(class) = self.classes.last_mut() {
+                class.has_superclass = true;
+            }
+        }
+
+        // The class goes on the stack while its methods are added to it.
+        self.named_variable(&class_name, false);
+        self.consume(TokenType::LEFTBRACE, "Expect '{' before class body.");
+        while !self.check(TokenType::RIGHTBRACE) && !self.check(TokenType::EOF) {
+            self.method();
+        }
+        self.consume(TokenType::RIGHTBRACE, "Expect '}' after class body.");
+        self.emit_byte(OpCode::OpPop as u8);
+
+        if self.classes.pop().is_some_and(|class| class.has_superclass) {
+            self.end_scope();
+        }
+    }
+
+    fn method(&mut self) {
+        self.consume(TokenType::IDENTIFIER, "Expect method name.");
+        let name = self.previous.clone();
+        let constant = self.identifier_constant(&name);
+        let function_type = if &*name.lexeme == "init" {
+            FunctionType::Initializer
+        } else {
+            FunctionType::Method
+        };
+        self.function(function_type);
+        self.emit_bytes(OpCode::OpMethod as u8, constant);
+    }
+
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        // A function can refer to itself in its body to recurse.
+        self.mark_initialized();
+        self.function(FunctionType::Function);
+        self.define_variable(global);
+    }
+
+    /// Compiles the parameters and body of the function just named into a
+    /// function of its own, and emits it as a constant.
+    fn function(&mut self, function_type: FunctionType) {
+        let name = self.previous.lexeme.to_string();
+        self.functions
+            .push(FunctionCompiler::new(function_type, Some(name)));
+        self.begin_scope();
+
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after function name.");
+        if !self.check(TokenType::RIGHTPAREN) {
+            loop {
+                let function = &mut self.current_function().function;
+                function.arity += 1;
+                if function.arity > u8::MAX as usize {
+                    self.error_at_current("Can't have more than 255 parameters.");
+                }
+                let parameter = self.parse_variable("Expect parameter name.");
+                self.define_variable(parameter);
+                if !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after parameters.");
+        self.consume(TokenType::LEFTBRACE, "Expect '{' before function body.");
+        self.block();
+
+        // The function's locals go with it, so its scope needs no ending.
+        let FunctionCompiler {
+            function, upvalues, ..
+        } = self.end();
+        let constant = self.make_constant(Value::Obj(Rc::new(Obj::Function(function))));
+        self.emit_bytes(OpCode::OpClosure as u8, constant);
+        for upvalue in upvalues {
+            self.emit_bytes(upvalue.is_local as u8, upvalue.index);
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+        if self.matches(TokenType::EQUAL) {
+            self.expression();
+        } else {
+            self.emit_byte(OpCode::OpNil as u8);
+        }
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after variable declaration.",
+        );
+        self.define_variable(global);
+    }
+
+    /// Consumes a variable name and declares it. Returns the index of the
+    /// constant holding the name for a global, and 0 for a local, which
+    /// needs no constant.
+    fn parse_variable(&mut self, message: &str) -> u8 {
+        self.consume(TokenType::IDENTIFIER, message);
+        self.declare_variable();
+        if self.current_function().scope_depth > 0 {
+            return 0;
+        }
+        let name = self.previous.clone();
+        self.identifier_constant(&name)
+    }
+
+    /// Adds the variable just named to the locals, unless it is a global.
+    fn declare_variable(&mut self) {
+        let name = Rc::clone(&self.previous.lexeme);
+        let function = self.current_function();
+        if function.scope_depth == 0 {
+            return;
+        }
+        let scope_depth = function.scope_depth;
+        let redeclared = function
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth.is_none_or(|depth| depth >= scope_depth))
+            .any(|local| local.name == name);
+        if redeclared {
+            self.error("Already a variable with this name in this scope.");
+        }
+        self.add_local(name);
+    }
+
+    fn add_local(&mut self, name: Rc<str>) {
+        if self.current_function().locals.len() > u8::MAX as usize {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.current_function().locals.push(Local {
+            name,
+            depth: None,
+            is_captured: false,
+        });
+    }
+
+    /// Makes the variable just declared usable: a global is defined from the
+    /// value on top of the stack, and a local already lives there.
+    fn define_variable(&mut self, global: u8) {
+        if self.current_function().scope_depth > 0 {
+            self.mark_initialized();
+            return;
+        }
+        self.emit_bytes(OpCode::OpDefineGlobal as u8, global);
+    }
+
+    /// Makes the local declared last readable.
+    fn mark_initialized(&mut self) {
+        let function = self.current_function();
+        if function.scope_depth == 0 {
+            return;
+        }
+        if let Some(local) = function.locals.last_mut() {
+            local.depth = Some(function.scope_depth);
+        }
+    }
+
+    /// The stack slot of the local called `name` in the function at
+    /// `function` in [`Compiler::functions`], if one is in scope there.
+    fn resolve_local(&mut self, function: usize, name: &Token) -> Option<u8> {
+        let (slot, initialized) = self.functions[function]
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, local)| local.name == name.lexeme)
+            .map(|(slot, local)| (slot, local.depth.is_some()))?;
+        if !initialized {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot as u8)
+    }
+
+    /// The index of the upvalue through which the function at `function`
+    /// reaches the variable called `name` of a function enclosing it, if
+    /// one has it in scope. Each function in between captures it too.
+    fn resolve_upvalue(&mut self, function: usize, name: &Token) -> Option<u8> {
+        let enclosing = function.checked_sub(1)?;
+        if let Some(slot) = self.resolve_local(enclosing, name) {
+            self.functions[enclosing].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true));
         }
+        let index = self.resolve_upvalue(enclosing, name)?;
+        Some(self.add_upvalue(function, index, false))
     }
 
-}
\ No newline at end of file
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = Upvalue { index, is_local };
+        let compiler = &mut self.functions[function];
+        if let Some(existing) = compiler.upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if compiler.upvalues.len() > u8::MAX as usize {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+        compiler.upvalues.push(upvalue);
+        compiler.function.upvalue_count = compiler.upvalues.len();
+        (compiler.upvalues.len() - 1) as u8
+    }
+
+    fn identifier_constant(&mut self, name: &Token) -> u8 {
+        let name = self.strings.intern(&*name.lexeme);
+        self.make_constant(Value::Obj(name))
+    }
+
+    fn statement(&mut self) {
+        if self.matches(TokenType::PRINT) {
+            self.print_statement();
+        } else if self.matches(TokenType::FOR) {
+            self.for_statement();
+        } else if self.matches(TokenType::IF) {
+            self.if_statement();
+        } else if self.matches(TokenType::RETURN) {
+            self.return_statement();
+        } else if self.matches(TokenType::WHILE) {
+            self.while_statement();
+        } else if self.matches(TokenType::LEFTBRACE) {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        } else {
+            self.expression_statement();
+        }
+    }
+
+    fn block(&mut self) {
+        while !self.check(TokenType::RIGHTBRACE) && !self.check(TokenType::EOF) {
+            self.declaration();
+        }
+        self.consume(TokenType::RIGHTBRACE, "Expect '}' after block.");
+    }
+
+    fn begin_scope(&mut self) {
+        self.current_function().scope_depth += 1;
+    }
+
+    /// Leaves a block, popping the locals declared in it off the stack and
+    /// moving the captured ones into their upvalues.
+    fn end_scope(&mut self) {
+        let function = self.current_function();
+        function.scope_depth -= 1;
+        let scope_depth = function.scope_depth;
+        while self
+            .current_function()
+            .locals
+            .last()
+            .is_some_and(|local| local.depth.is_none_or(|depth| depth > scope_depth))
+        {
+            let local = self.current_function().locals.pop();
+            if local.is_some_and(|local| local.is_captured) {
+                self.emit_byte(OpCode::OpCloseUpvalue as u8);
+            } else {
+                self.emit_byte(OpCode::OpPop as u8);
+            }
+        }
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
+        self.emit_byte(OpCode::OpPrint as u8);
+    }
+
+    fn return_statement(&mut self) {
+        if self.current_function().function_type == FunctionType::Script {
+            self.error("Can't return from top-level code.");
+        }
+        if self.matches(TokenType::SEMICOLON) {
+            self.emit_return();
+        } else {
+            if self.current_function().function_type == FunctionType::Initializer {
+                self.error("Can't return a value from an initializer.");
+            }
+            self.expression();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after return value.");
+            self.emit_byte(OpCode::OpReturn as u8);
+        }
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after condition.");
+
+        let then_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_byte(OpCode::OpPop as u8);
+        self.statement();
+        let else_jump = self.emit_jump(OpCode::OpJump);
+
+        self.patch_jump(then_jump);
+        self.emit_byte(OpCode::OpPop as u8);
+        if self.matches(TokenType::ELSE) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk().code.len();
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_byte(OpCode::OpPop as u8);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_byte(OpCode::OpPop as u8);
+    }
+
+    /// Compiles a `for` loop into the jumps of a `while` loop. The increment
+    /// comes before the body in the source but runs after it, so the body
+    /// jumps back to the increment, which jumps back to the condition.
+    fn for_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::LEFTPAREN, "Expect '(' after 'for'.");
+        if self.matches(TokenType::SEMICOLON) {
+            // No initializer.
+        } else if self.matches(TokenType::VAR) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.chunk().code.len();
+        let mut exit_jump = None;
+        if !self.matches(TokenType::SEMICOLON) {
+            self.expression();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.");
+            exit_jump = Some(self.emit_jump(OpCode::OpJumpIfFalse));
+            self.emit_byte(OpCode::OpPop as u8);
+        }
+
+        if !self.matches(TokenType::RIGHTPAREN) {
+            let body_jump = self.emit_jump(OpCode::OpJump);
+            let increment_start = self.chunk().code.len();
+            self.expression();
+            self.emit_byte(OpCode::OpPop as u8);
+            self.consume(TokenType::RIGHTPAREN, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_byte(OpCode::OpPop as u8);
+        }
+        self.end_scope();
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
+        self.emit_byte(OpCode::OpPop as u8);
+    }
+
+    /// Skips tokens until one that likely starts a new statement, after a
+    /// compile error.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+        while !self.check(TokenType::EOF) {
+            if self.previous.token_type == TokenType::SEMICOLON {
+                return;
+            }
+            match self.current.token_type {
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::PRINT
+                | TokenType::RETURN => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
+    /// Parses an expression made of operators binding at least as tightly
+    /// as `precedence`: a prefix expression, then any infix operators that
+    /// follow it.
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        self.advance();
+        let Some(prefix) = get_rule(&self.previous.token_type).prefix else {
+            self.error("Expect expression.");
+            return;
+        };
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix(self, can_assign);
+
+        while precedence <= get_rule(&self.current.token_type).precedence {
+            self.advance();
+            if let Some(infix) = get_rule(&self.previous.token_type).infix {
+                infix(self, can_assign);
+            }
+        }
+
+        if can_assign && self.matches(TokenType::EQUAL) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn number(&mut self, _can_assign: bool) {
+        if let Some(Literal::Number(value)) = self.previous.literal {
+            self.emit_constant(Value::Number(value));
+        }
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        let name = self.previous.clone();
+        self.named_variable(&name, can_assign);
+    }
+
+    fn named_variable(&mut self, name: &Token, can_assign: bool) {
+        let function = self.functions.len() - 1;
+        let (get, set, operand) = if let Some(slot) = self.resolve_local(function, name) {
+            (OpCode::OpGetLocal, OpCode::OpSetLocal, slot)
+        } else if let Some(index) = self.resolve_upvalue(function, name) {
+            (OpCode::OpGetUpvalue, OpCode::OpSetUpvalue, index)
+        } else {
+            (
+                OpCode::OpGetGlobal,
+                OpCode::OpSetGlobal,
+                self.identifier_constant(name),
+            )
+        };
+        if can_assign && self.matches(TokenType::EQUAL) {
+            self.expression();
+            self.emit_bytes(set as u8, operand);
+        } else {
+            self.emit_bytes(get as u8, operand);
+        }
+    }
+
+    fn string(&mut self, _can_assign: bool) {
+        if let Some(Literal::String(text)) = &self.previous.literal {
+            let text = self.strings.intern(text.as_str());
+            self.emit_constant(Value::Obj(text));
+        }
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        let op = match self.previous.token_type {
+            TokenType::FALSE => OpCode::OpFalse,
+            TokenType::NIL => OpCode::OpNil,
+            _ => OpCode::OpTrue,
+        };
+        self.emit_byte(op as u8);
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after expression.");
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous.token_type;
+        self.parse_precedence(Precedence::Unary);
+        match operator {
+            TokenType::BANG => self.emit_byte(OpCode::OpNot as u8),
+            TokenType::MINUS => self.emit_byte(OpCode::OpNegate as u8),
+            _ => {}
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous.token_type;
+        let rule = get_rule(&operator);
+        self.parse_precedence(rule.precedence.next());
+        // `a != b` compiles as `!(a == b)`, `a <= b` as `!(a > b)` and
+        // `a >= b` as `!(a < b)`.
+        let (op, negate) = match operator {
+            TokenType::BANGEQUAL => (OpCode::OpEqual, true),
+            TokenType::EQUALEQUAL => (OpCode::OpEqual, false),
+            TokenType::GREATER => (OpCode::OpGreater, false),
+            TokenType::GREATEREQUAL => (OpCode::OpLess, true),
+            TokenType::LESS => (OpCode::OpLess, false),
+            TokenType::LESSEQUAL => (OpCode::OpGreater, true),
+            TokenType::PLUS => (OpCode::OpAdd, false),
+            TokenType::MINUS => (OpCode::OpSubtract, false),
+            TokenType::STAR => (OpCode::OpMultiply, false),
+            TokenType::SLASH => (OpCode::OpDivide, false),
+            _ => return,
+        };
+        self.emit_byte(op as u8);
+        if negate {
+            self.emit_byte(OpCode::OpNot as u8);
+        }
+    }
+
+    /// A call of the value just compiled, whose arguments are left on the
+    /// stack above it.
+    fn call(&mut self, _can_assign: bool) {
+        let arg_count = self.argument_list();
+        self.emit_bytes(OpCode::OpCall as u8, arg_count);
+    }
+
+    /// A property of the instance just compiled: read, assigned or, when
+    /// called right away, invoked without making a bound method.
+    fn dot(&mut self, can_assign: bool) {
+        self.consume(TokenType::IDENTIFIER, "Expect property name after '.'.");
+        let name = self.previous.clone();
+        let name = self.identifier_constant(&name);
+        if can_assign && self.matches(TokenType::EQUAL) {
+            self.expression();
+            self.emit_bytes(OpCode::OpSetProperty as u8, name);
+        } else if self.matches(TokenType::LEFTPAREN) {
+            let arg_count = self.argument_list();
+            self.emit_bytes(OpCode::OpInvoke as u8, name);
+            self.emit_byte(arg_count);
+        } else {
+            self.emit_bytes(OpCode::OpGetProperty as u8, name);
+        }
+    }
+
+    fn this(&mut self, _can_assign: bool) {
+        if self.classes.is_empty() {
+            self.error("Can't use 'this' outside of a class.");
+            return;
+        }
+        self.variable(false);
+    }
+
+    /// `super.name`, which looks `name` up on the superclass and binds it to
+    /// `this`.
+    fn super_(&mut self, _can_assign: bool) {
+        match self.classes.last() {
+            None => self.error("Can't use 'super' outside of a class."),
+            Some(class) if !class.has_superclass => {
+                self.error("Can't use 'super' in a class with no superclass.")
+            }
+            Some(_) => {}
+        }
+        self.consume(TokenType::DOT, "Expect '.' after 'super'.");
+        self.consume(TokenType::IDENTIFIER, "Expect superclass method name.");
+        let name = self.previous.clone();
+        let name = self.identifier_constant(&name);
+
+        let this = self.synthetic_token("this");
+        self.named_variable(&this, false);
+        if self.matches(TokenType::LEFTPAREN) {
+            let arg_count = self.argument_list();
+            let superclass = self.synthetic_token("super");
+            self.named_variable(&superclass, false);
+            self.emit_bytes(OpCode::OpSuperInvoke as u8, name);
+            self.emit_byte(arg_count);
+        } else {
+            let superclass = self.synthetic_token("super");
+            self.named_variable(&superclass, false);
+            self.emit_bytes(OpCode::OpGetSuper as u8, name);
+        }
+    }
+
+    /// An identifier `text` the source doesn't spell out, at the token just
+    /// consumed.
+    fn synthetic_token(&self, text: &str) -> Token {
+        Token {
+            token_type: TokenType::IDENTIFIER,
+            lexeme: Rc::from(text),
+            literal: None,
+            ..self.previous.clone()
+        }
+    }
+
+    fn argument_list(&mut self) -> u8 {
+        let mut arg_count = 0;
+        if !self.check(TokenType::RIGHTPAREN) {
+            loop {
+                self.expression();
+                if arg_count == u8::MAX {
+                    self.error("Can't have more than 255 arguments.");
+                } else {
+                    arg_count += 1;
+                }
+                if !self.matches(TokenType::COMMA) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHTPAREN, "Expect ')' after arguments.");
+        arg_count
+    }
+
+    /// `a and b`: if `a` is falsey it is the result and `b` is skipped.
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        self.emit_byte(OpCode::OpPop as u8);
+        self.parse_precedence(Precedence::And);
+        self.patch_jump(end_jump);
+    }
+
+    /// `a or b`: if `a` is truthy it is the result and `b` is skipped.
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::OpJumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::OpJump);
+        self.patch_jump(else_jump);
+        self.emit_byte(OpCode::OpPop as u8);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    fn error_at_current(&mut self, message: &str) {
+        let token = self.current.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error(&mut self, message: &str) {
+        let token = self.previous.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error_at(&mut self, token: &Token, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.had_error = true;
+        let location = match token.token_type {
+            TokenType::EOF => " at end".to_string(),
+            TokenType::ERROR => String::new(),
+            _ => format!(" at '{}'", token.lexeme),
+        };
+        eprintln!("[line {}] Error{location}: {message}", token.line);
+    }
+}
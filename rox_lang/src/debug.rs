@@ -1,72 +1,271 @@
 use crate::chunk::{Chunk, OpCode};
+use crate::value::ObjFunction;
 use anyhow::anyhow;
+use std::fmt::Write;
 
-pub fn disassemble_chunk(chunk: &Chunk, name: &str) -> anyhow::Result<()> {
-    println!("== {} ==", name);
+/// Renders the whole chunk, one instruction per line, under a `== name ==`
+/// header. The text is returned rather than printed so it can also be handed
+/// back to scripts by the `inspectBytecode(fn)` native.
+pub fn disassemble_chunk(chunk: &Chunk, name: &str) -> anyhow::Result<String> {
+    let mut out = format!("== {} ==\n", name);
     let mut offset = 0;
     while offset < chunk.code.len() {
-        offset = disassemble_instruction(&chunk, offset)?;
+        offset = disassemble_instruction(chunk, offset, &mut out)?;
     }
-    Ok(())
+    Ok(out)
 }
 
-/// returns the offset of the next opcode
-/// Prints ByteOffset SourceOffset Opcode Operand
-pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> anyhow::Result<usize> {
+/// Renders a function's chunk under its name and its constant pool, followed
+/// by the chunks of the functions declared in it.
+pub fn disassemble_function(function: &ObjFunction) -> anyhow::Result<String> {
+    let mut out = disassemble_chunk(&function.chunk, &function.to_string())?;
+    if !function.chunk.constant.is_empty() {
+        out.push_str("-- constants --\n");
+        for (index, constant) in function.chunk.constant.iter().enumerate() {
+            writeln!(out, "{index:4} '{constant}'")?;
+        }
+    }
+    for constant in &function.chunk.constant {
+        if let Some(nested) = constant.as_obj().and_then(|obj| obj.as_function()) {
+            out.push_str(&disassemble_function(nested)?);
+        }
+    }
+    Ok(out)
+}
+
+/// Appends the instruction at `offset` to `out` as
+/// ByteOffset SourceLine Opcode Operand and returns the offset of the next
+/// opcode
+pub fn disassemble_instruction(
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
     // The offset in the byte code
-    print!("{offset:04}");
+    write!(out, "{offset:04}")?;
     // The corresponding line of the byte code in source code
-    if offset > 0 && chunk.lines.get(offset) == chunk.lines.get(offset - 1) {
-        print!(" | ");
+    let line = chunk
+        .get_line(offset)
+        .ok_or(anyhow!("Line value not found"))?;
+    if offset > 0 && chunk.get_line(offset - 1) == Some(line) {
+        write!(out, " | ")?;
     } else {
-        print!(
-            "{:>4} ",
-            chunk
-                .lines
-                .get(offset)
-                .ok_or(anyhow!("Line value not found"))?
-        )
+        write!(out, "{:>4} ", line)?;
     }
 
     match chunk.code.get(offset) {
         None => Err(anyhow!("No op code at given offset {offset}")),
-        Some(code) => {
-            match code {
-                _ if *code == OpCode::OpReturn as u8
-                    || *code == OpCode::OpAdd as u8
-                    || *code == OpCode::OpSubtract as u8
-                    || *code == OpCode::OpMultiply as u8
-                    || *code == OpCode::OpDivide as u8
-                    || *code == OpCode::OpNegate as u8 =>
-                {
-                    Ok(simple_instruction(&code.try_into()?, offset))
-                }
-                _ if *code == OpCode::OpConstant as u8 => {
-                    // Get the index of the operand in the adjacent index
-                    let constant_index = chunk
-                        .code
-                        .get(offset + 1)
-                        .ok_or(anyhow!("Constant index not found"))?;
-
-                    print!("{:<16?} {:>4} ", OpCode::OpConstant, constant_index);
-                    println!(
-                        "'{}'",
-                        chunk
-                            .constant
-                            .get(*constant_index as usize)
-                            .ok_or(anyhow!("Constant value not found"))?
-                    );
-                    Ok(offset + 2_usize)
-                }
-                _ => Err(anyhow!(
-                    "Unrecognized op code {code} at given offset {offset}"
-                )),
+        Some(code) => match code {
+            _ if *code == OpCode::OpReturn as u8
+                || *code == OpCode::OpAdd as u8
+                || *code == OpCode::OpSubtract as u8
+                || *code == OpCode::OpMultiply as u8
+                || *code == OpCode::OpDivide as u8
+                || *code == OpCode::OpNegate as u8
+                || *code == OpCode::OpNil as u8
+                || *code == OpCode::OpTrue as u8
+                || *code == OpCode::OpFalse as u8
+                || *code == OpCode::OpNot as u8
+                || *code == OpCode::OpEqual as u8
+                || *code == OpCode::OpGreater as u8
+                || *code == OpCode::OpLess as u8
+                || *code == OpCode::OpPrint as u8
+                || *code == OpCode::OpPop as u8
+                || *code == OpCode::OpCloseUpvalue as u8
+                || *code == OpCode::OpInherit as u8 =>
+            {
+                simple_instruction(&code.try_into()?, offset, out)
             }
-        }
+            _ if *code == OpCode::OpConstant as u8
+                || *code == OpCode::OpDefineGlobal as u8
+                || *code == OpCode::OpGetGlobal as u8
+                || *code == OpCode::OpSetGlobal as u8
+                || *code == OpCode::OpGetProperty as u8
+                || *code == OpCode::OpSetProperty as u8
+                || *code == OpCode::OpClass as u8
+                || *code == OpCode::OpMethod as u8
+                || *code == OpCode::OpGetSuper as u8 =>
+            {
+                constant_instruction(chunk, &code.try_into()?, offset, out)
+            }
+            _ if *code == OpCode::OpGetLocal as u8
+                || *code == OpCode::OpSetLocal as u8
+                || *code == OpCode::OpCall as u8
+                || *code == OpCode::OpGetUpvalue as u8
+                || *code == OpCode::OpSetUpvalue as u8 =>
+            {
+                byte_instruction(chunk, &code.try_into()?, offset, out)
+            }
+            _ if *code == OpCode::OpClosure as u8 => closure_instruction(chunk, offset, out),
+            _ if *code == OpCode::OpConstantLong as u8 => {
+                constant_long_instruction(chunk, offset, out)
+            }
+            _ if *code == OpCode::OpInvoke as u8 || *code == OpCode::OpSuperInvoke as u8 => {
+                invoke_instruction(chunk, &code.try_into()?, offset, out)
+            }
+            _ if *code == OpCode::OpJump as u8 || *code == OpCode::OpJumpIfFalse as u8 => {
+                jump_instruction(chunk, &code.try_into()?, true, offset, out)
+            }
+            _ if *code == OpCode::OpLoop as u8 => {
+                jump_instruction(chunk, &code.try_into()?, false, offset, out)
+            }
+            _ => Err(anyhow!(
+                "Unrecognized op code {code} at given offset {offset}"
+            )),
+        },
     }
 }
 
-fn simple_instruction(name: &OpCode, offset: usize) -> usize {
-    println!("{:?}", name);
-    offset + 1_usize
+fn simple_instruction(name: &OpCode, offset: usize, out: &mut String) -> anyhow::Result<usize> {
+    writeln!(out, "{:?}", name)?;
+    Ok(offset + 1_usize)
+}
+
+/// Writes an instruction whose operand indexes the constant pool, along
+/// with the constant, and returns the offset of the next opcode.
+fn constant_instruction(
+    chunk: &Chunk,
+    name: &OpCode,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
+    // Get the index of the operand in the adjacent index
+    let constant_index = chunk
+        .code
+        .get(offset + 1)
+        .ok_or(anyhow!("Constant index not found"))?;
+
+    write!(out, "{:<16?} {:>4} ", name, constant_index)?;
+    writeln!(
+        out,
+        "'{}'",
+        chunk
+            .constant
+            .get(*constant_index as usize)
+            .ok_or(anyhow!("Constant value not found"))?
+    )?;
+    Ok(offset + 2_usize)
+}
+
+/// Writes an OpConstantLong with its three-byte index and the constant, and
+/// returns the offset of the next opcode.
+fn constant_long_instruction(
+    chunk: &Chunk,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
+    let operand = chunk
+        .code
+        .get(offset + 1..offset + 4)
+        .ok_or(anyhow!("Constant index not found"))?;
+    let constant_index = u32::from_be_bytes([0, operand[0], operand[1], operand[2]]);
+    write!(
+        out,
+        "{:<16?} {:>4} ",
+        OpCode::OpConstantLong,
+        constant_index
+    )?;
+    writeln!(
+        out,
+        "'{}'",
+        chunk
+            .constant
+            .get(constant_index as usize)
+            .ok_or(anyhow!("Constant value not found"))?
+    )?;
+    Ok(offset + 4)
+}
+
+/// Writes an instruction with a one-byte operand, like a stack slot, and
+/// returns the offset of the next opcode.
+fn byte_instruction(
+    chunk: &Chunk,
+    name: &OpCode,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
+    let operand = chunk
+        .code
+        .get(offset + 1)
+        .ok_or(anyhow!("Operand not found"))?;
+    writeln!(out, "{:<16?} {:>4}", name, operand)?;
+    Ok(offset + 2_usize)
+}
+
+/// Writes a method call with the method's name and the number of arguments,
+/// and returns the offset of the next opcode.
+fn invoke_instruction(
+    chunk: &Chunk,
+    name: &OpCode,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
+    let (Some(constant_index), Some(arg_count)) =
+        (chunk.code.get(offset + 1), chunk.code.get(offset + 2))
+    else {
+        return Err(anyhow!("Invoke at {offset} is missing its operands"));
+    };
+    writeln!(
+        out,
+        "{:<16?} ({} args) {:>4} '{}'",
+        name,
+        arg_count,
+        constant_index,
+        chunk
+            .constant
+            .get(*constant_index as usize)
+            .ok_or(anyhow!("Constant value not found"))?
+    )?;
+    Ok(offset + 3)
+}
+
+/// Writes a closure with the function it closes over, then the variables it
+/// captures one per line, and returns the offset of the next opcode.
+fn closure_instruction(chunk: &Chunk, offset: usize, out: &mut String) -> anyhow::Result<usize> {
+    let mut next = constant_instruction(chunk, &OpCode::OpClosure, offset, out)?;
+    let function = chunk
+        .code
+        .get(offset + 1)
+        .and_then(|index| chunk.constant.get(*index as usize))
+        .and_then(|constant| constant.as_obj())
+        .and_then(|obj| obj.as_function())
+        .ok_or(anyhow!("Closure at {offset} has no function"))?;
+    for _ in 0..function.upvalue_count {
+        let (Some(is_local), Some(index)) = (chunk.code.get(next), chunk.code.get(next + 1)) else {
+            return Err(anyhow!("Closure at {offset} is missing upvalues"));
+        };
+        let kind = if *is_local == 1 { "local" } else { "upvalue" };
+        writeln!(out, "{next:04}    |                     {kind} {index}")?;
+        next += 2;
+    }
+    Ok(next)
+}
+
+/// Writes a jump as the offset it jumps from and the offset it lands on,
+/// and returns the offset of the next opcode. Loops jump `forward: false`.
+fn jump_instruction(
+    chunk: &Chunk,
+    name: &OpCode,
+    forward: bool,
+    offset: usize,
+    out: &mut String,
+) -> anyhow::Result<usize> {
+    let high = chunk
+        .code
+        .get(offset + 1)
+        .ok_or(anyhow!("Jump offset not found"))?;
+    let low = chunk
+        .code
+        .get(offset + 2)
+        .ok_or(anyhow!("Jump offset not found"))?;
+    let jump = u16::from_be_bytes([*high, *low]) as usize;
+    let next = offset + 3;
+    let target = if forward {
+        next + jump
+    } else {
+        next.checked_sub(jump)
+            .ok_or(anyhow!("Loop at {offset} jumps before the chunk"))?
+    };
+    writeln!(out, "{:<16?} {:>4} -> {}", name, offset, target)?;
+    Ok(next)
 }
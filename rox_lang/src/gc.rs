@@ -0,0 +1,235 @@
+//! The garbage collector for the objects the VM makes while running.
+//!
+//! Objects are reference counted, so most are freed as soon as the last
+//! value holding them goes. What counting can't free is a cycle: an
+//! instance whose field holds itself, or a closure that captures the
+//! variable holding it. The collector finds those with mark and sweep: it
+//! marks every object reachable from the roots, working through a gray
+//! list of objects whose references it hasn't traced yet, then empties the
+//! fields, methods and upvalues of the unmarked objects it tracks, which
+//! breaks their cycles and lets counting free them.
+//!
+//! The roots are the stack, the closures of the calls in progress, the
+//! globals and the open upvalues. The table of interned strings is not a
+//! root: strings only it refers to are dropped from it. The compiler finishes before the VM runs
+//! anything, so its temporaries are never live during a collection; the
+//! constants it makes are reached through the functions holding them.
+
+use crate::value::{Obj, Upvalue, Value};
+use crate::vm::VM;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+/// How many bytes the VM allocates before it first collects, and the least
+/// it allocates between collections.
+const FIRST_GC: usize = 1024 * 1024;
+
+/// How much the threshold for the next collection grows past what is
+/// still in use after one.
+const HEAP_GROW_FACTOR: usize = 2;
+
+/// The objects the VM has made and how many bytes they take, roughly.
+pub struct Heap {
+    objects: Vec<(Weak<Obj>, usize)>,
+    bytes_allocated: usize,
+    next_gc: usize,
+    /// Collects before every allocation, to flush out objects that aren't
+    /// rooted while they are still in use.
+    pub stress: bool,
+    /// Prints each allocation, each object freed and a summary of each
+    /// collection.
+    pub log: bool,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            objects: vec![],
+            bytes_allocated: 0,
+            next_gc: FIRST_GC,
+            stress: false,
+            log: false,
+        }
+    }
+}
+
+/// Roughly how many bytes `obj` takes when made.
+fn size_of(obj: &Obj) -> usize {
+    mem::size_of::<Obj>()
+        + match obj {
+            Obj::String(text) => text.len(),
+            Obj::Closure(closure) => closure.upvalues.len() * mem::size_of::<Rc<()>>(),
+            _ => 0,
+        }
+}
+
+/// The objects and upvalues found reachable during one collection.
+#[derive(Default)]
+struct Marks {
+    objects: HashSet<*const Obj>,
+    upvalues: HashSet<*const RefCell<Upvalue>>,
+    /// Marked objects whose references haven't been traced yet.
+    gray: Vec<Rc<Obj>>,
+}
+
+impl Marks {
+    fn mark_value(&mut self, value: &Value) {
+        if let Value::Obj(obj) = value {
+            self.mark_object(obj);
+        }
+    }
+
+    fn mark_object(&mut self, obj: &Rc<Obj>) {
+        if self.objects.insert(Rc::as_ptr(obj)) {
+            self.gray.push(Rc::clone(obj));
+        }
+    }
+
+    fn mark_upvalue(&mut self, upvalue: &Rc<RefCell<Upvalue>>) {
+        if !self.upvalues.insert(Rc::as_ptr(upvalue)) {
+            return;
+        }
+        if let Upvalue::Closed(value) = &*upvalue.borrow() {
+            self.mark_value(value);
+        }
+    }
+
+    /// Marks everything the gray objects refer to, until none are left.
+    fn trace_references(&mut self) {
+        while let Some(obj) = self.gray.pop() {
+            self.blacken(&obj);
+        }
+    }
+
+    fn blacken(&mut self, obj: &Obj) {
+        match obj {
+            Obj::String(_) | Obj::Native(_) => {}
+            Obj::Function(function) => {
+                for constant in &function.chunk.constant {
+                    self.mark_value(constant);
+                }
+            }
+            Obj::Closure(closure) => {
+                self.mark_object(&closure.function);
+                for upvalue in &closure.upvalues {
+                    self.mark_upvalue(upvalue);
+                }
+            }
+            Obj::Class(class) => {
+                for (name, method) in class.methods.borrow().iter() {
+                    self.mark_object(name.as_obj());
+                    self.mark_value(method);
+                }
+            }
+            Obj::Instance(instance) => {
+                self.mark_object(&instance.class);
+                for (name, field) in instance.fields.borrow().iter() {
+                    self.mark_object(name.as_obj());
+                    self.mark_value(field);
+                }
+            }
+            Obj::BoundMethod(bound) => {
+                self.mark_value(&bound.receiver);
+                self.mark_object(&bound.method);
+            }
+        }
+    }
+}
+
+impl VM {
+    /// Puts `obj` on the heap, collecting garbage first if the heap has
+    /// grown enough. Whatever `obj` refers to must be rooted until it is
+    /// made.
+    pub fn allocate(&mut self, obj: Obj) -> Rc<Obj> {
+        let size = size_of(&obj);
+        if self.heap.stress || self.heap.bytes_allocated + size > self.heap.next_gc {
+            self.collect_garbage();
+        }
+        let obj = Rc::new(obj);
+        self.heap.bytes_allocated += size;
+        self.heap.objects.push((Rc::downgrade(&obj), size));
+        if self.heap.log {
+            println!("{:p} allocate {size} for {obj}", Rc::as_ptr(&obj));
+        }
+        obj
+    }
+
+    pub fn collect_garbage(&mut self) {
+        if self.heap.log {
+            println!("-- gc begin");
+        }
+        let before = self.heap.bytes_allocated;
+
+        let mut marks = Marks::default();
+        for value in &self.stack {
+            marks.mark_value(value);
+        }
+        for frame in &self.frames {
+            marks.mark_object(&frame.closure);
+        }
+        for (name, value) in &self.globals {
+            marks.mark_object(name.as_obj());
+            marks.mark_value(value);
+        }
+        marks.mark_object(self.init_string.as_obj());
+        for upvalue in &self.open_upvalues {
+            marks.mark_upvalue(upvalue);
+        }
+        marks.trace_references();
+        self.sweep(&marks);
+        // The table doesn't keep strings alive, so the ones nobody else
+        // refers to go too.
+        self.strings
+            .retain(|string| marks.objects.contains(&Rc::as_ptr(string)));
+
+        self.heap.next_gc = (self.heap.bytes_allocated * HEAP_GROW_FACTOR).max(FIRST_GC);
+        if self.heap.log {
+            println!("-- gc end");
+            println!(
+                "   collected {} bytes (from {before} to {}) next at {}",
+                before - self.heap.bytes_allocated,
+                self.heap.bytes_allocated,
+                self.heap.next_gc
+            );
+        }
+    }
+
+    /// Frees the unmarked objects by emptying whatever in them could hold
+    /// a cycle, and forgets the objects already freed.
+    fn sweep(&mut self, marks: &Marks) {
+        let mut unreachable = vec![];
+        let log = self.heap.log;
+        self.heap.objects.retain(|(obj, _)| {
+            let Some(obj) = obj.upgrade() else {
+                return false;
+            };
+            if marks.objects.contains(&Rc::as_ptr(&obj)) {
+                return true;
+            }
+            if log {
+                println!("{:p} free {obj}", Rc::as_ptr(&obj));
+            }
+            unreachable.push(obj);
+            false
+        });
+        for obj in &unreachable {
+            match obj.as_ref() {
+                Obj::Instance(instance) => instance.fields.borrow_mut().clear(),
+                Obj::Class(class) => class.methods.borrow_mut().clear(),
+                Obj::Closure(closure) => {
+                    // An upvalue shared with a live closure stays.
+                    for upvalue in &closure.upvalues {
+                        if !marks.upvalues.contains(&Rc::as_ptr(upvalue)) {
+                            *upvalue.borrow_mut() = Upvalue::Closed(Value::Nil);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        drop(unreachable);
+        self.heap.bytes_allocated = self.heap.objects.iter().map(|(_, size)| size).sum();
+    }
+}
@@ -0,0 +1,13 @@
+//! A bytecode virtual machine for Lox. [`cli`] is its command line, run by
+//! the `rox_lang` binary and by `rox --backend=vm`.
+
+mod chunk;
+pub mod cli;
+mod compiler;
+mod debug;
+mod gc;
+mod natives;
+mod roxc;
+mod strings;
+mod value;
+mod vm;
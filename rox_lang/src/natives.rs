@@ -0,0 +1,71 @@
+//! Natives: functions written in Rust that scripts call like their own.
+//! [`NATIVES`] lists the ones only the VM has, by the global name each is
+//! defined under and the number of arguments it takes, and
+//! [`define_natives`] gives a VM those and the natives both backends share.
+//! Hosts add their own with [`VM::define_native`].
+
+use crate::debug::disassemble_chunk;
+use crate::value::{NativeFn, Obj, Value};
+use crate::vm::VM;
+use anyhow::anyhow;
+use rox_frontend::natives::{natives, Host, CONSTANTS};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A native as the registry lists it.
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+/// The natives only the VM has, defined after the shared ones.
+/// `inspectBytecode` has no counterpart in the tree-walking interpreter, as
+/// it compiles nothing.
+pub const NATIVES: &[Native] = &[Native {
+    name: "inspectBytecode",
+    arity: 1,
+    function: inspect_bytecode,
+}];
+
+/// Defines the shared natives and constants and every native in
+/// [`NATIVES`] as globals of `vm`.
+pub fn define_natives(vm: &mut VM) {
+    for (name, number) in CONSTANTS {
+        vm.define_global(name, Value::Number(*number));
+    }
+    for native in natives::<VM>() {
+        vm.define_native(native.name, native.arity, native.function);
+    }
+    for native in NATIVES {
+        vm.define_native(native.name, native.arity, native.function);
+    }
+}
+
+impl Host for VM {
+    type Value = Value;
+
+    fn number(&self, number: f64) -> Value {
+        Value::Number(number)
+    }
+
+    fn clock(&mut self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(f64::NAN, |time| time.as_millis() as f64)
+    }
+}
+
+/// `inspectBytecode(fn)`: the disassembly of the chunk `fn` was compiled to.
+fn inspect_bytecode(vm: &mut VM, arguments: &[Value]) -> anyhow::Result<Value> {
+    let function = match &arguments[0] {
+        Value::Obj(obj) => match &**obj {
+            Obj::Function(function) => Some(function),
+            Obj::Closure(closure) => Some(closure.function()),
+            _ => None,
+        },
+        _ => None,
+    }
+    .ok_or(anyhow!("Can only inspect the bytecode of functions."))?;
+    let listing = disassemble_chunk(&function.chunk, &function.to_string())?;
+    Ok(Value::Obj(vm.intern(listing)))
+}
@@ -0,0 +1,222 @@
+//! The `.roxc` format: compiled chunks saved to disk.
+//!
+//! Every file starts with a header of the magic bytes `ROXC`, the format
+//! version as a little-endian u16 and the features the chunk was compiled
+//! with as a little-endian u32. The chunk follows: the code length as a u32,
+//! the code, the source lines as a u32 count of runs and each run's line
+//! and length as u32s, the constant count as a u32 and the constants, all
+//! little-endian. Each constant is a tag byte,
+//! then a f64 for a number or a u32 length and UTF-8 text for a string.
+//! A function is its arity and upvalue count as u32s, its name as a string
+//! (empty for the script) and then its own chunk, laid out the same way.
+//!
+//! Loading checks the header before anything else, so bytecode from a newer
+//! rox or one built with different features is refused with an error
+//! instead of being run.
+
+use crate::chunk::{Chunk, LineRun};
+use crate::strings::Strings;
+use crate::value::{Obj, ObjFunction, Value};
+use anyhow::anyhow;
+use std::rc::Rc;
+
+/// What every `.roxc` file starts with.
+pub const MAGIC: &[u8; 4] = b"ROXC";
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+/// Bumped whenever the layout after the header or the meaning of an opcode
+/// changes.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Values are NaN-boxed rather than stored as tagged enums.
+pub const FEATURE_NAN_BOXING: u32 = 1 << 0;
+/// Constant indexes can be wider than one byte.
+pub const FEATURE_LONG_CONSTANTS: u32 = 1 << 1;
+
+/// The features chunks compiled by this build use, all of which it can run.
+pub const FEATURES: u32 = FEATURE_LONG_CONSTANTS;
+
+/// Saves `chunk`, the chunk of a compiled script.
+pub fn serialize(chunk: &Chunk) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&FEATURES.to_le_bytes());
+    write_chunk(chunk, &mut out);
+    out
+}
+
+fn write_chunk(chunk: &Chunk, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(chunk.code.len() as u32).to_le_bytes());
+    out.extend_from_slice(&chunk.code);
+    out.extend_from_slice(&(chunk.lines.len() as u32).to_le_bytes());
+    for run in &chunk.lines {
+        out.extend_from_slice(&run.line.to_le_bytes());
+        out.extend_from_slice(&(run.count as u32).to_le_bytes());
+    }
+    out.extend_from_slice(&(chunk.constant.len() as u32).to_le_bytes());
+    for constant in &chunk.constant {
+        match constant {
+            Value::Number(n) => {
+                out.push(TAG_NUMBER);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Obj(obj) => match obj.as_ref() {
+                Obj::String(text) => {
+                    out.push(TAG_STRING);
+                    write_string(text, out);
+                }
+                Obj::Function(function) => {
+                    out.push(TAG_FUNCTION);
+                    out.extend_from_slice(&(function.arity as u32).to_le_bytes());
+                    out.extend_from_slice(&(function.upvalue_count as u32).to_le_bytes());
+                    write_string(function.name.as_deref().unwrap_or_default(), out);
+                    write_chunk(&function.chunk, out);
+                }
+                Obj::Closure(_)
+                | Obj::Native(_)
+                | Obj::Class(_)
+                | Obj::Instance(_)
+                | Obj::BoundMethod(_) => {
+                    unreachable!("only numbers, strings and functions are constants")
+                }
+            },
+            Value::Bool(_) | Value::Nil => {
+                unreachable!("booleans and nil have their own opcodes")
+            }
+        }
+    }
+}
+
+fn write_string(text: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Loads a chunk, interning its string constants in `strings`.
+pub fn deserialize(bytes: &[u8], strings: &mut Strings) -> anyhow::Result<Chunk> {
+    let mut reader = Reader {
+        bytes,
+        offset: 0,
+        strings,
+    };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(anyhow!("Not a .roxc file."));
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version > FORMAT_VERSION {
+        return Err(anyhow!(
+            "Compiled with newer rox: .roxc format version {version}, but this rox reads up to version {FORMAT_VERSION}."
+        ));
+    }
+    if version == 0 {
+        return Err(anyhow!("Invalid .roxc format version 0."));
+    }
+    let features = u32::from_le_bytes(reader.array()?);
+    let unsupported = features & !FEATURES;
+    if unsupported != 0 {
+        return Err(anyhow!(
+            "Compiled with rox features this rox doesn't have: {}.",
+            feature_names(unsupported)
+        ));
+    }
+
+    let chunk = reader.chunk()?;
+    if reader.offset != bytes.len() {
+        return Err(anyhow!("Unexpected bytes after the end of the chunk."));
+    }
+    Ok(chunk)
+}
+
+fn feature_names(features: u32) -> String {
+    let mut names = vec![];
+    for bit in 0..u32::BITS {
+        let feature = 1 << bit;
+        if features & feature == 0 {
+            continue;
+        }
+        names.push(match feature {
+            FEATURE_NAN_BOXING => "NaN boxing".to_string(),
+            FEATURE_LONG_CONSTANTS => "long constants".to_string(),
+            _ => format!("unknown feature {feature:#x}"),
+        });
+    }
+    names.join(", ")
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    strings: &'a mut Strings,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + len)
+            .ok_or(anyhow!("Truncated .roxc file."))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn chunk(&mut self) -> anyhow::Result<Chunk> {
+        let mut chunk = Chunk::new();
+        let code_len = self.u32()? as usize;
+        chunk.code = self.take(code_len)?.to_vec();
+        let run_count = self.u32()?;
+        for _ in 0..run_count {
+            let line = self.u32()?;
+            let count = self.u32()? as usize;
+            chunk.lines.push(LineRun { line, count });
+        }
+        let constant_count = self.u32()?;
+        for _ in 0..constant_count {
+            let constant = self.constant()?;
+            chunk.constant.push(constant);
+        }
+        Ok(chunk)
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| anyhow!("Invalid UTF-8 in a string constant."))
+    }
+
+    fn constant(&mut self) -> anyhow::Result<Value> {
+        match self.take(1)?[0] {
+            TAG_NUMBER => Ok(Value::Number(f64::from_le_bytes(self.array()?))),
+            TAG_STRING => {
+                let text = self.string()?;
+                Ok(Value::Obj(self.strings.intern(text)))
+            }
+            TAG_FUNCTION => {
+                let arity = self.u32()? as usize;
+                let upvalue_count = self.u32()? as usize;
+                let name = self.string()?;
+                let chunk = self.chunk()?;
+                Ok(Value::Obj(Rc::new(Obj::Function(ObjFunction {
+                    arity,
+                    upvalue_count,
+                    chunk,
+                    name: Some(name).filter(|name| !name.is_empty()),
+                }))))
+            }
+            tag => Err(anyhow!("Unknown constant tag {tag}.")),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! String interning. Every string the compiler or the VM makes goes through
+//! one [`Strings`] table, which hands back the string already made with the
+//! same text if there is one, so two strings are equal exactly when they
+//! are the same object. Tables keyed by strings, like the globals and an
+//! instance's fields, can then hash and compare their keys by address.
+
+use crate::value::Obj;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// The interned strings. It doesn't keep strings alive on its own: the
+/// garbage collector drops the ones nothing else refers to.
+#[derive(Default)]
+pub struct Strings {
+    table: HashSet<Entry>,
+}
+
+/// A string in the table, hashed and compared by its text so it can be
+/// found from text.
+struct Entry(Rc<Obj>);
+
+impl Entry {
+    fn text(&self) -> &str {
+        match self.0.as_ref() {
+            Obj::String(text) => text,
+            _ => unreachable!("only strings are interned"),
+        }
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.text() == other.text()
+    }
+}
+
+impl Eq for Entry {}
+
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.text().hash(state)
+    }
+}
+
+impl Borrow<str> for Entry {
+    fn borrow(&self) -> &str {
+        self.text()
+    }
+}
+
+impl Strings {
+    pub fn new() -> Self {
+        Strings::default()
+    }
+
+    /// The interned string with `text`, if there is one.
+    pub fn find(&self, text: &str) -> Option<Rc<Obj>> {
+        self.table.get(text).map(|entry| Rc::clone(&entry.0))
+    }
+
+    /// Adds `string`, which must be an [`Obj::String`] whose text isn't
+    /// interned yet.
+    pub fn insert(&mut self, string: Rc<Obj>) {
+        self.table.insert(Entry(string));
+    }
+
+    /// The interned string with `text`, made if there is none yet.
+    pub fn intern(&mut self, text: impl Into<String> + AsRef<str>) -> Rc<Obj> {
+        if let Some(string) = self.find(text.as_ref()) {
+            return string;
+        }
+        let string = Rc::new(Obj::String(text.into()));
+        self.insert(Rc::clone(&string));
+        string
+    }
+
+    /// Drops the strings `keep` says no to.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Rc<Obj>) -> bool) {
+        self.table.retain(|entry| keep(&entry.0));
+    }
+}
+
+/// An interned string as a table key, hashed and compared by address.
+#[derive(Debug, Clone)]
+pub struct StringKey(Rc<Obj>);
+
+impl StringKey {
+    /// The key for `string`, which must be an interned [`Obj::String`].
+    pub fn new(string: Rc<Obj>) -> Self {
+        StringKey(string)
+    }
+
+    pub fn as_obj(&self) -> &Rc<Obj> {
+        &self.0
+    }
+}
+
+impl PartialEq for StringKey {
+    fn eq(&self, other: &StringKey) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for StringKey {}
+
+impl Hash for StringKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
+
+impl Display for StringKey {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
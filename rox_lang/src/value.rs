@@ -0,0 +1,216 @@
+use crate::chunk::Chunk;
+use crate::strings::StringKey;
+use crate::vm::VM;
+use rox_frontend::value::{fmt_number, LoxValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// A value on the VM's stack or in a chunk's constant pool. Numbers,
+/// booleans and nil are stored inline; everything else lives on the heap
+/// behind an [`Obj`].
+#[derive(Debug, Clone)]
+pub enum Value {
+    Number(f64),
+    Bool(bool),
+    Nil,
+    Obj(Rc<Obj>),
+}
+
+/// A heap-allocated value.
+#[derive(Debug)]
+pub enum Obj {
+    String(String),
+    Function(ObjFunction),
+    Closure(ObjClosure),
+    Native(ObjNative),
+    Class(ObjClass),
+    Instance(ObjInstance),
+    BoundMethod(ObjBoundMethod),
+}
+
+/// A function written in Rust, called with the VM and its arguments once
+/// the VM has checked there are as many as its arity.
+pub type NativeFn = fn(&mut VM, &[Value]) -> anyhow::Result<Value>;
+
+#[derive(Debug)]
+pub struct ObjNative {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+/// A function compiled to its own chunk. The top-level script is compiled
+/// to one too, with no name.
+#[derive(Debug, Default)]
+pub struct ObjFunction {
+    pub arity: usize,
+    /// How many variables of enclosing functions it captures.
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+    pub name: Option<String>,
+}
+
+/// A function together with the variables it captured from the functions
+/// enclosing it. Functions are only ever called through closures.
+#[derive(Debug)]
+pub struct ObjClosure {
+    /// Always an [`Obj::Function`].
+    pub function: Rc<Obj>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A variable captured by a closure. It stays open, naming the stack slot
+/// the variable lives in, until that slot is popped and the value moves
+/// into the upvalue itself. Closures capturing the same variable share it.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+#[derive(Debug)]
+pub struct ObjClass {
+    pub name: String,
+    /// Its methods' closures by name, the ones it inherits included.
+    pub methods: RefCell<HashMap<StringKey, Value>>,
+}
+
+#[derive(Debug)]
+pub struct ObjInstance {
+    /// Always an [`Obj::Class`].
+    pub class: Rc<Obj>,
+    pub fields: RefCell<HashMap<StringKey, Value>>,
+}
+
+/// A method taken from an instance, which keeps the instance to call it on.
+#[derive(Debug)]
+pub struct ObjBoundMethod {
+    pub receiver: Value,
+    /// Always an [`Obj::Closure`].
+    pub method: Rc<Obj>,
+}
+
+impl LoxValue for Value {
+    fn is_nil(&self) -> bool {
+        matches!(self, Value::Nil)
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    pub fn as_obj(&self) -> Option<&Obj> {
+        match self {
+            Value::Obj(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&str> {
+        match self {
+            Value::Obj(obj) => match obj.as_ref() {
+                Obj::String(text) => Some(text),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Lox equality: values of different types are never equal, and objects
+/// only equal themselves. Strings are interned, so that makes strings equal
+/// when their text is.
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Obj(a), Value::Obj(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => fmt_number(*n, f),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Nil => write!(f, "nil"),
+            Value::Obj(obj) => write!(f, "{obj}"),
+        }
+    }
+}
+
+impl Display for Obj {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Obj::String(text) => write!(f, "{text}"),
+            Obj::Function(function) => write!(f, "{function}"),
+            Obj::Closure(closure) => write!(f, "{}", closure.function),
+            Obj::Native(native) => write!(f, "<native fn {}>", native.name),
+            Obj::Class(class) => write!(f, "<class {}>", class.name),
+            Obj::Instance(instance) => match instance.class.as_class() {
+                Some(class) => write!(f, "{} instance", class.name),
+                None => unreachable!("instances are only made of classes"),
+            },
+            Obj::BoundMethod(bound) => write!(f, "{}", bound.method),
+        }
+    }
+}
+
+impl Obj {
+    pub fn as_function(&self) -> Option<&ObjFunction> {
+        match self {
+            Obj::Function(function) => Some(function),
+            _ => None,
+        }
+    }
+
+    pub fn as_closure(&self) -> Option<&ObjClosure> {
+        match self {
+            Obj::Closure(closure) => Some(closure),
+            _ => None,
+        }
+    }
+
+    pub fn as_class(&self) -> Option<&ObjClass> {
+        match self {
+            Obj::Class(class) => Some(class),
+            _ => None,
+        }
+    }
+
+    pub fn as_instance(&self) -> Option<&ObjInstance> {
+        match self {
+            Obj::Instance(instance) => Some(instance),
+            _ => None,
+        }
+    }
+}
+
+impl ObjClosure {
+    pub fn function(&self) -> &ObjFunction {
+        self.function
+            .as_function()
+            .expect("closures are only made for functions")
+    }
+}
+
+impl Display for ObjFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<script>"),
+        }
+    }
+}
@@ -1,44 +1,165 @@
 use crate::chunk::{Chunk, OpCode};
+use crate::compiler::compile;
 use crate::debug::disassemble_instruction;
-use crate::vm::InterpretResult::InterpretOk;
+use crate::gc::Heap;
+use crate::natives::define_natives;
+use crate::roxc;
+use crate::strings::{StringKey, Strings};
+use crate::value::{
+    NativeFn, Obj, ObjBoundMethod, ObjClass, ObjClosure, ObjFunction, ObjInstance, ObjNative,
+    Upvalue, Value,
+};
+use crate::vm::InterpretResult::{InterpretCompileError, InterpretOk, InterpretRuntimeError};
 use anyhow::anyhow;
-use std::ops::Deref;
+use rox_frontend::value::LoxValue;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
-use crate::compiler::compile;
+
+/// How deeply calls can nest before the VM reports a stack overflow.
+const FRAMES_MAX: usize = 64;
 
 enum BinaryOp {
     Add,
     Subtract,
     Multiply,
     Divide,
+    Greater,
+    Less,
 }
 
+#[allow(clippy::enum_variant_names)]
 pub enum InterpretResult {
     InterpretOk,
     InterpretCompileError,
     InterpretRuntimeError,
 }
 
+/// A call in progress: the closure running, where it is in its chunk, and
+/// where its window of the stack starts. The first slot of the window holds
+/// the closure itself, followed by its arguments and then its locals.
+pub struct CallFrame {
+    pub closure: Rc<Obj>,
+    /// The index in the chunk's code of the next byte to read.
+    ip: usize,
+    slots: usize,
+}
+
+impl CallFrame {
+    fn closure(&self) -> &ObjClosure {
+        self.closure
+            .as_closure()
+            .expect("frames are only made for closures")
+    }
+
+    fn function(&self) -> &ObjFunction {
+        self.closure().function()
+    }
+}
+
 pub struct VM {
-    pub chunk: Rc<Chunk>,
-    pub ip: u8,
+    /// The calls in progress, the script first and the innermost last.
+    pub frames: Vec<CallFrame>,
+    /// Prints the stack and each instruction before running it.
     pub debug_trace_execution: bool,
-    pub stack: Vec<f64>,
+    pub stack: Vec<Value>,
+    pub globals: HashMap<StringKey, Value>,
+    /// The upvalues still pointing at stack slots, ordered by slot, so
+    /// closures capturing the same variable share one.
+    pub open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    pub heap: Heap,
+    /// Every string the VM and its compiler have made.
+    pub strings: Strings,
+    /// The name of initializers, kept to look them up when making instances.
+    pub init_string: StringKey,
 }
 
 impl VM {
     pub fn new() -> Self {
-        Self {
-            chunk: Rc::new(Chunk::default()),
-            ip: 0,
-            debug_trace_execution: true,
+        let mut strings = Strings::new();
+        let init_string = StringKey::new(strings.intern("init"));
+        let mut vm = Self {
+            frames: vec![],
+            debug_trace_execution: false,
             stack: vec![],
+            globals: HashMap::new(),
+            open_upvalues: vec![],
+            heap: Heap::new(),
+            strings,
+            init_string,
+        };
+        define_natives(&mut vm);
+        vm
+    }
+
+    /// Defines a global `name` that calls `function` with `arity`
+    /// arguments.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = Obj::Native(ObjNative {
+            name: name.to_string(),
+            arity,
+            function,
+        });
+        let native = self.allocate(native);
+        self.define_global(name, Value::Obj(native));
+    }
+
+    /// Defines a global `name` holding `value`.
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        let name = StringKey::new(self.intern(name.to_string()));
+        self.globals.insert(name, value);
+    }
+
+    /// The interned string with `text`, made if there is none yet.
+    pub fn intern(&mut self, text: String) -> Rc<Obj> {
+        if let Some(string) = self.strings.find(&text) {
+            return string;
         }
+        let string = self.allocate(Obj::String(text));
+        self.strings.insert(Rc::clone(&string));
+        string
     }
 
+    /// Compiles `source` and runs it, reporting compile and runtime errors
+    /// to stderr.
     pub fn interpret(&mut self, source: &str) -> InterpretResult {
-        compile(source);
-        InterpretOk
+        let Some(script) = compile(source, &mut self.strings) else {
+            return InterpretCompileError;
+        };
+        self.run_script(script)
+    }
+
+    /// Loads a script compiled to the `.roxc` format and runs it. A file
+    /// that can't be loaded counts as a compile error.
+    pub fn interpret_compiled(&mut self, bytes: &[u8]) -> InterpretResult {
+        match roxc::deserialize(bytes, &mut self.strings) {
+            Ok(chunk) => self.run_script(ObjFunction {
+                chunk,
+                ..ObjFunction::default()
+            }),
+            Err(error) => {
+                eprintln!("{error}");
+                InterpretCompileError
+            }
+        }
+    }
+
+    fn run_script(&mut self, script: ObjFunction) -> InterpretResult {
+        let script = Rc::new(Obj::Closure(ObjClosure {
+            function: Rc::new(Obj::Function(script)),
+            upvalues: vec![],
+        }));
+        self.stack.clear();
+        self.frames.clear();
+        self.open_upvalues.clear();
+        self.push(Value::Obj(Rc::clone(&script)));
+        match self.call(script, 0).and_then(|()| self.run()) {
+            Ok(result) => result,
+            Err(error) => {
+                eprintln!("{error}");
+                InterpretRuntimeError
+            }
+        }
     }
 
     pub fn run(&mut self) -> anyhow::Result<InterpretResult> {
@@ -52,34 +173,263 @@ impl VM {
                 }
                 println!();
 
-                disassemble_instruction(self.chunk.deref(), self.ip as usize)?;
+                let mut instruction = String::new();
+                disassemble_instruction(self.chunk(), self.frame().ip, &mut instruction)?;
+                print!("{}", instruction);
             }
 
-            let ip = self.get_next_ip();
-            let instruction: OpCode = self
-                .chunk
-                .code
-                .get(ip)
-                .ok_or(anyhow!("No instruction found at index"))?
-                .try_into()?;
+            let instruction: OpCode = (&self.read_byte()?).try_into()?;
             match instruction {
                 OpCode::OpConstant => {
-                    let ip = self.get_next_ip();
-                    let constant_index = self
-                        .chunk
-                        .code
-                        .get(ip)
-                        .ok_or(anyhow!("No instruction found at index"))?;
-                    let constant_value = self
-                        .chunk
-                        .constant
-                        .get(*constant_index as usize)
-                        .ok_or(anyhow!("No constant value found at index"))?;
-                    self.push(*constant_value);
-                }
-                OpCode::OpAdd => {
-                    self.binary_op(BinaryOp::Add)?;
+                    let constant = self.read_constant()?;
+                    self.push(constant);
+                }
+                OpCode::OpConstantLong => {
+                    let constant = self.read_constant_long()?;
+                    self.push(constant);
+                }
+                OpCode::OpNil => self.push(Value::Nil),
+                OpCode::OpTrue => self.push(Value::Bool(true)),
+                OpCode::OpFalse => self.push(Value::Bool(false)),
+                OpCode::OpAdd => match (self.peek(1)?, self.peek(0)?) {
+                    (Value::Number(_), Value::Number(_)) => self.binary_op(BinaryOp::Add)?,
+                    (a, b) => {
+                        let joined = match (a.as_string(), b.as_string()) {
+                            (Some(a), Some(b)) => format!("{a}{b}"),
+                            _ => {
+                                return Err(self
+                                    .runtime_error("Operands must be two numbers or two strings."))
+                            }
+                        };
+                        // The operands stay on the stack, rooted, until the
+                        // result is made.
+                        let joined = self.intern(joined);
+                        self.pop()?;
+                        self.pop()?;
+                        self.push(Value::Obj(joined));
+                    }
+                },
+                OpCode::OpPop => {
+                    self.pop()?;
+                }
+                OpCode::OpDefineGlobal => {
+                    let name = self.read_string()?;
+                    let value = self.pop()?;
+                    self.globals.insert(name, value);
+                }
+                OpCode::OpGetGlobal => {
+                    let name = self.read_string()?;
+                    let Some(value) = self.globals.get(&name) else {
+                        return Err(self.runtime_error(&format!("Undefined variable '{name}'.")));
+                    };
+                    self.push(value.clone());
                 }
+                OpCode::OpSetGlobal => {
+                    let name = self.read_string()?;
+                    let value = self.peek(0)?.clone();
+                    let Some(slot) = self.globals.get_mut(&name) else {
+                        return Err(self.runtime_error(&format!("Undefined variable '{name}'.")));
+                    };
+                    // Assignment is an expression, so its value stays on the stack.
+                    *slot = value;
+                }
+                OpCode::OpGetLocal => {
+                    let slot = self.read_byte()?;
+                    let value = self
+                        .stack
+                        .get(self.frame().slots + slot as usize)
+                        .cloned()
+                        .ok_or(anyhow!("No local in slot {slot}"))?;
+                    self.push(value);
+                }
+                OpCode::OpSetLocal => {
+                    let slot = self.read_byte()?;
+                    let value = self.peek(0)?.clone();
+                    let index = self.frame().slots + slot as usize;
+                    *self
+                        .stack
+                        .get_mut(index)
+                        .ok_or(anyhow!("No local in slot {slot}"))? = value;
+                }
+                OpCode::OpGetUpvalue => {
+                    let slot = self.read_byte()?;
+                    let upvalue = self.upvalue(slot)?;
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(index) => self
+                            .stack
+                            .get(*index)
+                            .cloned()
+                            .ok_or(anyhow!("No value in slot {index}"))?,
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                OpCode::OpSetUpvalue => {
+                    let slot = self.read_byte()?;
+                    let upvalue = self.upvalue(slot)?;
+                    let value = self.peek(0)?.clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(index) => {
+                            *self
+                                .stack
+                                .get_mut(*index)
+                                .ok_or(anyhow!("No value in slot {index}"))? = value
+                        }
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                OpCode::OpClosure => {
+                    let function = self.read_constant()?;
+                    let Value::Obj(function) = function else {
+                        return Err(anyhow!("Expected a function constant, got {function}"));
+                    };
+                    let upvalue_count = function
+                        .as_function()
+                        .ok_or(anyhow!("Expected a function constant, got {function}"))?
+                        .upvalue_count;
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+                    for _ in 0..upvalue_count {
+                        let is_local = self.read_byte()? == 1;
+                        let index = self.read_byte()?;
+                        upvalues.push(if is_local {
+                            self.capture_upvalue(self.frame().slots + index as usize)
+                        } else {
+                            self.upvalue(index)?
+                        });
+                    }
+                    let closure = self.allocate(Obj::Closure(ObjClosure { function, upvalues }));
+                    self.push(Value::Obj(closure));
+                }
+                OpCode::OpCloseUpvalue => {
+                    self.close_upvalues(self.stack.len().saturating_sub(1));
+                    self.pop()?;
+                }
+                OpCode::OpGetProperty => {
+                    let name = self.read_string()?;
+                    let Value::Obj(instance) = self.peek(0)?.clone() else {
+                        return Err(self.runtime_error("Only instances have properties."));
+                    };
+                    let Some(instance) = instance.as_instance() else {
+                        return Err(self.runtime_error("Only instances have properties."));
+                    };
+                    // Fields shadow methods.
+                    let field = instance.fields.borrow().get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            self.pop()?;
+                            self.push(value);
+                        }
+                        None => self.bind_method(&instance.class, &name)?,
+                    }
+                }
+                OpCode::OpSetProperty => {
+                    let name = self.read_string()?;
+                    let Value::Obj(instance) = self.peek(1)? else {
+                        return Err(self.runtime_error("Only instances have fields."));
+                    };
+                    let Some(instance) = instance.as_instance() else {
+                        return Err(self.runtime_error("Only instances have fields."));
+                    };
+                    instance
+                        .fields
+                        .borrow_mut()
+                        .insert(name, self.peek(0)?.clone());
+                    // Assignment is an expression, so the value replaces the
+                    // instance on the stack.
+                    let value = self.pop()?;
+                    self.pop()?;
+                    self.push(value);
+                }
+                OpCode::OpClass => {
+                    let name = self.read_string()?;
+                    let class = self.allocate(Obj::Class(ObjClass {
+                        name: name.to_string(),
+                        methods: RefCell::default(),
+                    }));
+                    self.push(Value::Obj(class));
+                }
+                OpCode::OpMethod => {
+                    let name = self.read_string()?;
+                    let method = self.peek(0)?.clone();
+                    self.peek(1)?
+                        .as_obj()
+                        .and_then(Obj::as_class)
+                        .ok_or(anyhow!("Methods can only be added to classes"))?
+                        .methods
+                        .borrow_mut()
+                        .insert(name, method);
+                    self.pop()?;
+                }
+                OpCode::OpInvoke => {
+                    let name = self.read_string()?;
+                    let arg_count = self.read_byte()?;
+                    self.invoke(&name, arg_count)?;
+                }
+                OpCode::OpInherit => {
+                    let Some(superclass) = self.peek(1)?.as_obj().and_then(Obj::as_class) else {
+                        return Err(self.runtime_error("Superclass must be a class."));
+                    };
+                    // Keys hash by address, which their contents can't change.
+                    #[allow(clippy::mutable_key_type)]
+                    let methods = superclass.methods.borrow().clone();
+                    self.peek(0)?
+                        .as_obj()
+                        .and_then(Obj::as_class)
+                        .ok_or(anyhow!("Only classes inherit"))?
+                        .methods
+                        .borrow_mut()
+                        .extend(methods);
+                    self.pop()?;
+                }
+                OpCode::OpGetSuper => {
+                    let name = self.read_string()?;
+                    let Value::Obj(superclass) = self.pop()? else {
+                        return Err(anyhow!("Expected the superclass on the stack"));
+                    };
+                    self.bind_method(&superclass, &name)?;
+                }
+                OpCode::OpSuperInvoke => {
+                    let name = self.read_string()?;
+                    let arg_count = self.read_byte()?;
+                    let Value::Obj(superclass) = self.pop()? else {
+                        return Err(anyhow!("Expected the superclass on the stack"));
+                    };
+                    self.invoke_from_class(&superclass, &name, arg_count)?;
+                }
+                OpCode::OpJump => {
+                    let offset = self.read_short()?;
+                    let frame = self.frame_mut();
+                    frame.ip += offset as usize;
+                }
+                OpCode::OpJumpIfFalse => {
+                    let offset = self.read_short()?;
+                    // The condition stays on the stack; the code jumped to
+                    // pops it.
+                    if !self.peek(0)?.truthy() {
+                        let frame = self.frame_mut();
+                        frame.ip += offset as usize;
+                    }
+                }
+                OpCode::OpLoop => {
+                    let offset = self.read_short()?;
+                    let frame = self.frame_mut();
+                    frame.ip = frame
+                        .ip
+                        .checked_sub(offset as usize)
+                        .ok_or(anyhow!("Loop jumps before the start of the chunk"))?;
+                }
+                OpCode::OpCall => {
+                    let arg_count = self.read_byte()?;
+                    let callee = self.peek(arg_count as usize)?.clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::OpEqual => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.push(Value::Bool(a == b));
+                }
+                OpCode::OpGreater => self.binary_op(BinaryOp::Greater)?,
+                OpCode::OpLess => self.binary_op(BinaryOp::Less)?,
                 OpCode::OpSubtract => {
                     self.binary_op(BinaryOp::Subtract)?;
                 }
@@ -89,50 +439,354 @@ impl VM {
                 OpCode::OpDivide => {
                     self.binary_op(BinaryOp::Divide)?;
                 }
-                OpCode::OpNegate => {
+                OpCode::OpNot => {
                     let value = self.pop()?;
-                    self.push(-value)
+                    self.push(Value::Bool(!value.truthy()))
                 }
-                OpCode::OpReturn => {
+                OpCode::OpNegate => match self.peek(0)? {
+                    Value::Number(value) => {
+                        let value = -value;
+                        self.pop()?;
+                        self.push(Value::Number(value))
+                    }
+                    _ => return Err(self.runtime_error("Operand must be a number.")),
+                },
+                OpCode::OpPrint => {
                     println!("{}", self.pop()?);
-                    return Ok(InterpretOk);
+                }
+                OpCode::OpReturn => {
+                    let result = self.pop()?;
+                    let frame = self.frames.pop().ok_or(anyhow!("No call to return from"))?;
+                    // The callee, its arguments and its locals go with it.
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(InterpretOk);
+                    }
+                    self.push(result);
                 }
             }
         }
     }
 
-    pub fn push(&mut self, value: f64) {
-        self.stack.push(value);
+    /// The innermost call, whose code is running.
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().expect("the script runs in a frame")
     }
 
-    pub fn pop(&mut self) -> anyhow::Result<f64> {
-        self.stack.pop().ok_or(anyhow!("Cannot pop empty stack"))
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("the script runs in a frame")
     }
 
-    fn binary_op(&mut self, op: BinaryOp) -> anyhow::Result<()> {
-        let b = self.pop()?;
-        let a = self.pop()?;
-        match op {
-            BinaryOp::Add => {
-                self.push(a + b);
-            }
-            BinaryOp::Subtract => {
-                self.push(a - b);
-            }
-            BinaryOp::Multiply => {
-                self.push(a * b);
+    /// The chunk of the function running.
+    fn chunk(&self) -> &Chunk {
+        &self.frame().function().chunk
+    }
+
+    /// Calls `callee` with the `arg_count` arguments on top of the stack,
+    /// which is right above it.
+    fn call_value(&mut self, callee: Value, arg_count: u8) -> anyhow::Result<()> {
+        match callee {
+            Value::Obj(obj) => match obj.as_ref() {
+                Obj::Closure(_) => self.call(obj, arg_count),
+                Obj::Native(native) => self.call_native(native, arg_count),
+                Obj::Class(class) => {
+                    // The new instance takes the class's place below the
+                    // arguments, where `init` finds it as `this`.
+                    let instance = Value::Obj(self.allocate(Obj::Instance(ObjInstance {
+                        class: Rc::clone(&obj),
+                        fields: RefCell::default(),
+                    })));
+                    self.set_callee(arg_count, instance)?;
+                    let initializer = class.methods.borrow().get(&self.init_string).cloned();
+                    match initializer {
+                        Some(Value::Obj(initializer)) => self.call(initializer, arg_count),
+                        _ if arg_count != 0 => Err(self
+                            .runtime_error(&format!("Expected 0 arguments but got {arg_count}."))),
+                        _ => Ok(()),
+                    }
+                }
+                Obj::BoundMethod(bound) => {
+                    self.set_callee(arg_count, bound.receiver.clone())?;
+                    self.call(Rc::clone(&bound.method), arg_count)
+                }
+                _ => Err(self.runtime_error("Can only call functions and classes.")),
+            },
+            _ => Err(self.runtime_error("Can only call functions and classes.")),
+        }
+    }
+
+    /// Replaces the value being called, below its `arg_count` arguments.
+    fn set_callee(&mut self, arg_count: u8, value: Value) -> anyhow::Result<()> {
+        let index = self
+            .stack
+            .len()
+            .checked_sub(arg_count as usize + 1)
+            .ok_or(anyhow!("Missing arguments for the call"))?;
+        self.stack[index] = value;
+        Ok(())
+    }
+
+    /// Calls the method `name` of the instance below the `arg_count`
+    /// arguments on top of the stack. A field holding a function is called
+    /// instead if there is one.
+    fn invoke(&mut self, name: &StringKey, arg_count: u8) -> anyhow::Result<()> {
+        let Value::Obj(receiver) = self.peek(arg_count as usize)?.clone() else {
+            return Err(self.runtime_error("Only instances have methods."));
+        };
+        let Some(instance) = receiver.as_instance() else {
+            return Err(self.runtime_error("Only instances have methods."));
+        };
+        let field = instance.fields.borrow().get(name).cloned();
+        if let Some(field) = field {
+            self.set_callee(arg_count, field.clone())?;
+            return self.call_value(field, arg_count);
+        }
+        self.invoke_from_class(&instance.class, name, arg_count)
+    }
+
+    /// Calls the method `name` of `class` on the instance below the
+    /// `arg_count` arguments on top of the stack.
+    fn invoke_from_class(
+        &mut self,
+        class: &Obj,
+        name: &StringKey,
+        arg_count: u8,
+    ) -> anyhow::Result<()> {
+        let method = class
+            .as_class()
+            .ok_or(anyhow!("Expected a class, got {class}"))?
+            .methods
+            .borrow()
+            .get(name)
+            .cloned();
+        match method {
+            Some(Value::Obj(method)) => self.call(method, arg_count),
+            _ => Err(self.runtime_error(&format!("Undefined property '{name}'."))),
+        }
+    }
+
+    /// Replaces the instance on top of the stack with its method `name` of
+    /// `class`, bound to it.
+    fn bind_method(&mut self, class: &Obj, name: &StringKey) -> anyhow::Result<()> {
+        let method = class
+            .as_class()
+            .ok_or(anyhow!("Expected a class, got {class}"))?
+            .methods
+            .borrow()
+            .get(name)
+            .cloned();
+        let Some(Value::Obj(method)) = method else {
+            return Err(self.runtime_error(&format!("Undefined property '{name}'.")));
+        };
+        let receiver = self.peek(0)?.clone();
+        let bound = self.allocate(Obj::BoundMethod(ObjBoundMethod { receiver, method }));
+        self.pop()?;
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    /// Calls `native` with the arguments on top of the stack, replacing them
+    /// and the native below them with its result.
+    fn call_native(&mut self, native: &ObjNative, arg_count: u8) -> anyhow::Result<()> {
+        if arg_count as usize != native.arity {
+            return Err(self.runtime_error(&format!(
+                "Expected {} arguments but got {arg_count}.",
+                native.arity
+            )));
+        }
+        let arguments = self.stack.len() - arg_count as usize;
+        let argument_values = self.stack[arguments..].to_vec();
+        let mut result = (native.function)(self, &argument_values)
+            .map_err(|error| self.runtime_error(&error.to_string()))?;
+        if let Some(text) = result.as_string() {
+            result = Value::Obj(self.intern(text.to_string()));
+        }
+        self.stack.truncate(arguments - 1);
+        self.push(result);
+        Ok(())
+    }
+
+    /// Starts running `closure` in a new frame over the arguments on top of
+    /// the stack and the closure below them.
+    fn call(&mut self, closure: Rc<Obj>, arg_count: u8) -> anyhow::Result<()> {
+        let arity = closure
+            .as_closure()
+            .ok_or(anyhow!("Expected a closure, got {closure}"))?
+            .function()
+            .arity;
+        if arg_count as usize != arity {
+            return Err(
+                self.runtime_error(&format!("Expected {arity} arguments but got {arg_count}."))
+            );
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.runtime_error("Stack overflow."));
+        }
+        let slots = self
+            .stack
+            .len()
+            .checked_sub(arg_count as usize + 1)
+            .ok_or(anyhow!("Missing arguments for {closure}"))?;
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots,
+        });
+        Ok(())
+    }
+
+    /// The running closure's upvalue at `slot`.
+    fn upvalue(&self, slot: u8) -> anyhow::Result<Rc<RefCell<Upvalue>>> {
+        self.frame()
+            .closure()
+            .upvalues
+            .get(slot as usize)
+            .cloned()
+            .ok_or(anyhow!("No upvalue in slot {slot}"))
+    }
+
+    /// The open upvalue for the stack slot at `index`, made if no closure
+    /// has captured it yet.
+    fn capture_upvalue(&mut self, index: usize) -> Rc<RefCell<Upvalue>> {
+        let mut position = self.open_upvalues.len();
+        for (i, upvalue) in self.open_upvalues.iter().enumerate() {
+            match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot == index => return Rc::clone(upvalue),
+                Upvalue::Open(slot) if slot > index => {
+                    position = i;
+                    break;
+                }
+                _ => {}
             }
-            BinaryOp::Divide => {
-                self.push(a / b);
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(index)));
+        self.open_upvalues.insert(position, Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Moves the values of the stack slots from `last` up into the upvalues
+    /// capturing them, before the slots are popped.
+    fn close_upvalues(&mut self, last: usize) {
+        let first_closed = self
+            .open_upvalues
+            .iter()
+            .position(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(slot) if slot >= last))
+            .unwrap_or(self.open_upvalues.len());
+        for upvalue in self.open_upvalues.drain(first_closed..) {
+            let mut upvalue = upvalue.borrow_mut();
+            if let Upvalue::Open(slot) = *upvalue {
+                *upvalue = Upvalue::Closed(self.stack.get(slot).cloned().unwrap_or(Value::Nil));
             }
         }
+    }
+
+    /// Reads the next byte of the running chunk, an opcode or an operand,
+    /// and moves past it.
+    fn read_byte(&mut self) -> anyhow::Result<u8> {
+        let frame = self.frame_mut();
+        let ip = frame.ip;
+        frame.ip += 1;
+        self.chunk()
+            .code
+            .get(ip)
+            .copied()
+            .ok_or(anyhow!("No instruction found at index {ip}"))
+    }
+
+    /// Reads the next two bytes of the instruction being run as a
+    /// big-endian jump offset.
+    fn read_short(&mut self) -> anyhow::Result<u16> {
+        let high = self.read_byte()?;
+        let low = self.read_byte()?;
+        Ok(u16::from_be_bytes([high, low]))
+    }
+
+    /// Reads the operand of the instruction being run as an index into the
+    /// constant pool and returns the constant there.
+    fn read_constant(&mut self) -> anyhow::Result<Value> {
+        let constant_index = self.read_byte()?;
+        self.chunk()
+            .constant
+            .get(constant_index as usize)
+            .cloned()
+            .ok_or(anyhow!("No constant value found at index"))
+    }
+
+    /// [`VM::read_constant`] for OpConstantLong's three-byte operand.
+    fn read_constant_long(&mut self) -> anyhow::Result<Value> {
+        let high = self.read_byte()?;
+        let low = self.read_short()?;
+        let constant_index = (high as usize) << 16 | low as usize;
+        self.chunk()
+            .constant
+            .get(constant_index)
+            .cloned()
+            .ok_or(anyhow!("No constant value found at index"))
+    }
+
+    /// [`VM::read_constant`] for the names of variables and properties,
+    /// which are always string constants.
+    fn read_string(&mut self) -> anyhow::Result<StringKey> {
+        match self.read_constant()? {
+            Value::Obj(string) if matches!(*string, Obj::String(_)) => Ok(StringKey::new(string)),
+            constant => Err(anyhow!("Expected a string constant, got {constant}")),
+        }
+    }
+
+    pub fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    pub fn pop(&mut self) -> anyhow::Result<Value> {
+        self.stack.pop().ok_or(anyhow!("Cannot pop empty stack"))
+    }
+
+    /// The value `distance` slots down from the top of the stack.
+    fn peek(&self, distance: usize) -> anyhow::Result<&Value> {
+        self.stack
+            .len()
+            .checked_sub(distance + 1)
+            .and_then(|index| self.stack.get(index))
+            .ok_or(anyhow!("Cannot peek {distance} below the top of the stack"))
+    }
+
+    /// Applies `op` to the two numbers on top of the stack, leaving the
+    /// result in their place.
+    fn binary_op(&mut self, op: BinaryOp) -> anyhow::Result<()> {
+        let (Value::Number(a), Value::Number(b)) = (self.peek(1)?, self.peek(0)?) else {
+            return Err(self.runtime_error("Operands must be numbers."));
+        };
+        let (a, b) = (*a, *b);
+        self.pop()?;
+        self.pop()?;
+        self.push(match op {
+            BinaryOp::Add => Value::Number(a + b),
+            BinaryOp::Subtract => Value::Number(a - b),
+            BinaryOp::Multiply => Value::Number(a * b),
+            BinaryOp::Divide => Value::Number(a / b),
+            BinaryOp::Greater => Value::Bool(a > b),
+            BinaryOp::Less => Value::Bool(a < b),
+        });
         Ok(())
     }
 
-    fn get_next_ip(&mut self) -> usize {
-        // get's the current value of self.ip, which is index to operate on next
-        // then increments that value
-        self.ip += 1;
-        (self.ip - 1) as usize
+    /// The error for the instruction just read failing with `message`,
+    /// followed by a stack trace naming the line each call in progress is
+    /// on, innermost first.
+    fn runtime_error(&self, message: &str) -> anyhow::Error {
+        let mut error = message.to_string();
+        for frame in self.frames.iter().rev() {
+            let function = frame.function();
+            let line = function
+                .chunk
+                .get_line(frame.ip.saturating_sub(1))
+                .unwrap_or_default();
+            match &function.name {
+                Some(name) => error.push_str(&format!("\n[line {line}] in {name}()")),
+                None => error.push_str(&format!("\n[line {line}] in script")),
+            }
+        }
+        anyhow!(error)
     }
 }
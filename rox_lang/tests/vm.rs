@@ -0,0 +1,746 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh path in the temporary directory, shared with no other call, so
+/// tests running at once never write over each other's files.
+fn scratch_path(extension: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "rox_lang_test_{}_{n}{extension}",
+        std::process::id()
+    ))
+}
+
+/// Writes `source` to a scratch file of its own, returning its path.
+fn script(source: &str) -> PathBuf {
+    let path = scratch_path(".lox");
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn run(source: &str) -> Output {
+    run_with(&[], source)
+}
+
+fn run_with(flags: &[&str], source: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .args(flags)
+        .arg(script(source))
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn arithmetic_follows_precedence() {
+    let output = run("print 1 + 2 * 3;\n");
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "7\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn grouping_and_negation() {
+    let output = run("print -(1 + 2) / 4 - -1; // a comment\n");
+    assert_eq!(stdout(&output), "0.25\n");
+}
+
+#[test]
+fn subtraction_is_left_associative() {
+    let output = run("print 10 - 4 - 3;");
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn compile_errors_are_reported_with_their_line() {
+    let output = run("print (1 +\n");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at end: Expect expression.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+
+    let output = run("print (1 + 2 3;");
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at '3': Expect ')' after expression.\n"
+    );
+}
+
+#[test]
+fn literals_and_not() {
+    assert_eq!(stdout(&run("print nil;")), "nil\n");
+    assert_eq!(stdout(&run("print !nil;")), "true\n");
+    assert_eq!(stdout(&run("print !!false;")), "false\n");
+    // Every number is truthy, zero included.
+    assert_eq!(stdout(&run("print !(1 - 1);")), "false\n");
+}
+
+#[test]
+fn nan_and_inf_are_globals_scripts_can_shadow() {
+    let output = run(
+        "print nan;\nprint -inf;\nprint 0/0;\nprint inf > 1000000;\nprint nan == nan;\nvar inf = 1;\nprint inf;\n",
+    );
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "nan\n-inf\nnan\ntrue\nfalse\n1\n");
+}
+
+#[test]
+fn strings_concatenate() {
+    let output = run("print \"st\" + \"ri\" + \"ng\";");
+    assert_eq!(stdout(&output), "string\n");
+}
+
+#[test]
+fn operands_of_the_wrong_type_are_runtime_errors() {
+    let output = run("print 1 +\n-\"a\";");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "Operand must be a number.\n[line 2] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run("print 1 + \"a\";");
+    assert_eq!(
+        stderr(&output),
+        "Operands must be two numbers or two strings.\n[line 1] in script\n"
+    );
+    let output = run("print true * 2;");
+    assert_eq!(
+        stderr(&output),
+        "Operands must be numbers.\n[line 1] in script\n"
+    );
+}
+
+#[test]
+fn comparisons_and_equality() {
+    let cases = [
+        ("1 < 2", "true"),
+        ("2 <= 2", "true"),
+        ("2 > 3", "false"),
+        ("3 >= 3", "true"),
+        ("!(5 - 4 > 3 * 2 == !nil)", "true"),
+        ("\"a\" + \"b\" == \"ab\"", "true"),
+        ("\"a\" != \"b\"", "true"),
+        ("nil == false", "false"),
+        ("1 == \"1\"", "false"),
+    ];
+    for (source, expected) in cases {
+        let output = run(&format!("print {source};"));
+        assert_eq!(stdout(&output), format!("{expected}\n"), "{source}");
+    }
+
+    let output = run("print 1 < \"x\";");
+    assert_eq!(
+        stderr(&output),
+        "Operands must be numbers.\n[line 1] in script\n"
+    );
+}
+
+#[test]
+fn global_variables() {
+    let source = r#"
+var breakfast = "beignets";
+var beverage = "cafe au lait";
+breakfast = "beignets with " + beverage;
+print breakfast;
+var unset;
+print unset;
+var a = 1;
+print a = a + 1;
+print a;
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "beignets with cafe au lait\nnil\n2\n2\n");
+}
+
+#[test]
+fn undefined_globals_are_runtime_errors() {
+    let output = run("print 1;\nprint missing;");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        stderr(&output),
+        "Undefined variable 'missing'.\n[line 2] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run("missing = 1;");
+    assert_eq!(
+        stderr(&output),
+        "Undefined variable 'missing'.\n[line 1] in script\n"
+    );
+}
+
+#[test]
+fn compile_errors_resynchronize_at_the_next_statement() {
+    let output = run("var a = 1;\na * a = 3;\nprint 1 +;\nprint 2");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at '=': Invalid assignment target.\n\
+         [line 3] Error at ';': Expect expression.\n\
+         [line 4] Error at end: Expect ';' after value.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn locals_shadow_outer_variables_until_their_block_ends() {
+    let source = r#"
+var a = "global a";
+{
+  var a = "outer a";
+  var b = "outer b";
+  {
+    var a = "inner a";
+    print a;
+    print b;
+    b = "changed b";
+  }
+  print a;
+  print b;
+}
+print a;
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(
+        stdout(&output),
+        "inner a\nouter b\nouter a\nchanged b\nglobal a\n"
+    );
+}
+
+#[test]
+fn local_declaration_errors() {
+    let output = run("{\n  var a = 1;\n  {\n    var a = a;\n  }\n}");
+    assert_eq!(
+        stderr(&output),
+        "[line 4] Error at 'a': Can't read local variable in its own initializer.\n"
+    );
+    let output = run("{\n  var a = 1;\n  var a = 2;\n}");
+    assert_eq!(
+        stderr(&output),
+        "[line 3] Error at 'a': Already a variable with this name in this scope.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn if_else_and_logical_operators() {
+    let source = r#"
+if (1 > 2) print "then"; else print "else";
+if (true) { print "block"; }
+print nil or "or";
+print false and "and";
+print 1 and 2;
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "else\nblock\nor\nfalse\n2\n");
+}
+
+#[test]
+fn while_and_for_loops() {
+    let source = r#"
+var i = 3;
+while (i > 0) {
+  print i;
+  i = i - 1;
+}
+var a = 0;
+var temp;
+for (var b = 1; a < 20; b = temp + b) {
+  print a;
+  temp = a;
+  a = b;
+}
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "3\n2\n1\n0\n1\n1\n2\n3\n5\n8\n13\n");
+}
+
+#[test]
+fn functions_take_arguments_and_return_values() {
+    let source = r#"
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 2) + fib(n - 1);
+}
+print fib(10);
+fun greet(greeting, name) {
+  var message = greeting + ", " + name;
+  print message;
+}
+print greet("Hi", "you");
+print fib;
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "55\nHi, you\nnil\n<fn fib>\n");
+}
+
+#[test]
+fn runtime_errors_print_a_stack_trace() {
+    let source = "fun inner() {\n  return 1 + nil;\n}\nfun outer() {\n  inner();\n}\nouter();";
+    let output = run(source);
+    assert_eq!(
+        stderr(&output),
+        "Operands must be two numbers or two strings.\n\
+         [line 2] in inner()\n\
+         [line 5] in outer()\n\
+         [line 7] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run("fun f(a, b) {}\nf(1);");
+    assert_eq!(
+        stderr(&output),
+        "Expected 2 arguments but got 1.\n[line 2] in script\n"
+    );
+    let output = run("var a = \"a\";\na();");
+    assert_eq!(
+        stderr(&output),
+        "Can only call functions and classes.\n[line 2] in script\n"
+    );
+    let output = run("fun f() { f(); }\nf();");
+    assert!(stderr(&output).starts_with("Stack overflow.\n[line 1] in f()\n"));
+}
+
+#[test]
+fn return_outside_a_function_is_a_compile_error() {
+    let output = run("return 1;");
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at 'return': Can't return from top-level code.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn closures_capture_variables_that_outlive_their_function() {
+    let source = r#"
+fun makeCounter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+var counter = makeCounter();
+counter();
+print counter();
+fun outer() {
+  var x = "outside";
+  fun middle() {
+    fun inner() { print x; }
+    return inner;
+  }
+  return middle();
+}
+outer()();
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "2\noutside\n");
+}
+
+#[test]
+fn closures_share_the_variables_they_capture() {
+    let source = r#"
+var get;
+var set;
+{
+  var a = "a";
+  fun g() { print a; }
+  fun s() { a = a + "!"; }
+  get = g;
+  set = s;
+  s();
+  print a;
+}
+set();
+get();
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "a!\na!!\n");
+}
+
+#[test]
+fn natives_are_called_like_functions() {
+    let source = "var start = clock();\nprint start > 0;\nprint clock() >= start;\nprint clock;";
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "true\ntrue\n<native fn clock>\n");
+
+    let output = run("print 1;\nclock(1);");
+    assert_eq!(
+        stderr(&output),
+        "Expected 0 arguments but got 1.\n[line 2] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn inspect_bytecode_disassembles_a_function() {
+    let source =
+        "fun add(a, b) {\n  return a + b;\n}\nprint inspectBytecode(add);\ninspectBytecode(1);";
+    let output = run(source);
+    assert_eq!(
+        stdout(&output),
+        "== <fn add> ==\n0000   2 OpGetLocal    1\n0002 | OpGetLocal    2\n0004 | OpAdd\n0005 | OpReturn\n0006   3 OpNil\n0007 | OpReturn\n\n"
+    );
+    assert_eq!(
+        stderr(&output),
+        "Can only inspect the bytecode of functions.\n[line 5] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn classes_have_fields_methods_and_initializers() {
+    let source = r#"
+class Pair {
+  init(a, b) {
+    this.a = a;
+    this.b = b;
+  }
+  sum() { return this.a + this.b; }
+}
+var pair = Pair(1, 2);
+print pair.sum();
+pair.b = 10;
+var sum = pair.sum;
+print sum();
+print pair;
+print Pair;
+print pair.init(3, 4).a;
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "3\n11\nPair instance\n<class Pair>\n3\n");
+}
+
+#[test]
+fn methods_are_inherited_and_reached_through_super() {
+    let source = r#"
+class A {
+  method() { return "A method"; }
+  name() { return "A"; }
+}
+class B < A {
+  method() { return "B method"; }
+  test() {
+    var get = super.method;
+    print get();
+    print super.name() + this.method();
+  }
+}
+class C < B {}
+C().test();
+"#;
+    let output = run(source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "A method\nAB method\n");
+}
+
+#[test]
+fn class_errors() {
+    let cases = [
+        (
+            "print this;",
+            "[line 1] Error at 'this': Can't use 'this' outside of a class.\n",
+        ),
+        (
+            "super.a();",
+            "[line 1] Error at 'super': Can't use 'super' outside of a class.\n",
+        ),
+        (
+            "class A { f() { super.f(); } }",
+            "[line 1] Error at 'super': Can't use 'super' in a class with no superclass.\n",
+        ),
+        (
+            "class A < A {}",
+            "[line 1] Error at 'A': A class can't inherit from itself.\n",
+        ),
+        (
+            "class A { init() { return 1; } }",
+            "[line 1] Error at 'return': Can't return a value from an initializer.\n",
+        ),
+    ];
+    for (source, expected) in cases {
+        let output = run(source);
+        assert_eq!(stderr(&output), expected, "{source}");
+        assert_eq!(output.status.code(), Some(65));
+    }
+
+    let cases = [
+        ("class A {}\nA().missing;", "Undefined property 'missing'."),
+        (
+            "class A {}\nA().missing();",
+            "Undefined property 'missing'.",
+        ),
+        ("var a = 1;\na.b;", "Only instances have properties."),
+        ("var a = 1;\na.b = 2;", "Only instances have fields."),
+        ("var a = 1;\na.b();", "Only instances have methods."),
+        ("var a = 1;\nclass B < a {}", "Superclass must be a class."),
+        ("class A {}\nA(1);", "Expected 0 arguments but got 1."),
+    ];
+    for (source, expected) in cases {
+        let output = run(source);
+        assert_eq!(
+            stderr(&output),
+            format!("{expected}\n[line 2] in script\n"),
+            "{source}"
+        );
+        assert_eq!(output.status.code(), Some(70));
+    }
+}
+
+#[test]
+fn programs_run_the_same_when_collecting_before_every_allocation() {
+    let source = r#"
+class Greeter {
+  init(greeting) { this.greeting = greeting; }
+  greet(name) {
+    fun message() { return this.greeting + ", " + name; }
+    return message;
+  }
+}
+class Loud < Greeter {
+  greet(name) { return super.greet(name + "!"); }
+}
+var greet = Loud("Hi").greet("you");
+print greet();
+"#;
+    let output = run_with(&["--stress-gc"], source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "Hi, you!\n");
+}
+
+#[test]
+fn the_collector_frees_unreachable_cycles() {
+    let source = r#"
+class Node {}
+for (var i = 0; i < 3; i = i + 1) {
+  var node = Node();
+  node.next = node;
+}
+{
+  fun f() { return f; }
+}
+print "a" + "b";
+"#;
+    let output = run_with(&["--stress-gc", "--log-gc"], source);
+    assert_eq!(stderr(&output), "");
+    let log = stdout(&output);
+    assert!(log.matches(" free Node instance\n").count() >= 2, "{log}");
+    assert!(log.contains(" free <fn f>\n"), "{log}");
+    assert!(log.contains("-- gc begin\n"), "{log}");
+    assert!(log.ends_with("ab\n"), "{log}");
+}
+
+#[test]
+fn strings_with_the_same_text_are_one_interned_string() {
+    let source = r#"
+var key = "ab";
+class A {}
+var a = A();
+a.field = "x";
+for (var i = 0; i < 2; i = i + 1) {
+  var joined = "a" + "b";
+  print joined == key;
+}
+print a.field;
+"#;
+    let output = run_with(&["--stress-gc", "--log-gc"], source);
+    assert_eq!(stderr(&output), "");
+    let log = stdout(&output);
+    assert!(log.ends_with("\ntrue\ntrue\nx\n"), "{log}");
+    // "ab" is already interned as a constant, so joining makes nothing.
+    assert!(!log.contains(" for ab\n"), "{log}");
+}
+
+#[test]
+fn chunks_longer_than_256_bytes_run_and_jump_correctly() {
+    // Each increment is 8 bytes of code, so the loop body jumps over and
+    // back across far more than 256 bytes.
+    let increments = "  t = t + 1;\n".repeat(60);
+    let source = format!(
+        "var t = 0;\nfor (var i = 0; i < 2; i = i + 1) {{\n{increments}}}\nprint t;\nt + nil;\n"
+    );
+    let output = run(&source);
+    assert_eq!(stdout(&output), "120\n");
+    assert_eq!(
+        stderr(&output),
+        "Operands must be two numbers or two strings.\n[line 65] in script\n"
+    );
+}
+
+#[test]
+fn chunks_can_have_more_than_256_constants() {
+    // The name `x` is one constant however often it's used, so the
+    // literals push the pool past what a one-byte index reaches.
+    let additions: String = (1..300).map(|n| format!("x = x + {n}; ")).collect();
+    let source = format!("var x = 0; {additions}print x;");
+    let output = run(&source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "44850\n");
+}
+
+#[test]
+fn large_constant_pools_compile_quickly() {
+    // The function is compiled but never called, so this times the
+    // compiler. Each literal appears twice, so half of them are found in
+    // the pool rather than added.
+    let assignments: String = (0..60_000).map(|n| format!("x = {n} + {n}; ")).collect();
+    let source = format!("var x = 0; fun f() {{ {assignments}}} print \"done\";");
+    let started = std::time::Instant::now();
+    let output = run(&source);
+    assert!(started.elapsed().as_secs() < 10);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "done\n");
+}
+
+#[test]
+fn runtime_errors_name_lines_far_into_the_file() {
+    let source = format!(
+        "fun f(a) {{\n  print a;\n{}  return a + nil;\n}}\nf(1);\n",
+        "\n".repeat(300)
+    );
+    let output = run(&source);
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        stderr(&output),
+        "Operands must be two numbers or two strings.\n[line 303] in f()\n[line 305] in script\n"
+    );
+}
+
+#[test]
+fn disasm_lists_every_function_without_running() {
+    let source = "fun f(a) {\n  if (a > 1) return \"big\";\n  return a;\n}\nprint f(2);\n";
+    let output = run_with(&["disasm"], source);
+    let listing = stdout(&output);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!listing.contains("big\n"), "{listing}");
+    assert!(listing.starts_with("== <script> ==\n"), "{listing}");
+    assert!(listing.contains("OpClosure    1 '<fn f>'"), "{listing}");
+    assert!(listing.contains("== <fn f> ==\n"), "{listing}");
+    assert!(listing.contains("OpJumpIfFalse    5 -> 15"), "{listing}");
+    assert!(
+        listing.contains("-- constants --\n   0 '1'\n   1 'big'\n"),
+        "{listing}"
+    );
+}
+
+#[test]
+fn disasm_reports_compile_errors() {
+    let output = run_with(&["disasm"], "print 1 +;");
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("Expect expression."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn literals_scan_like_the_tree_walker() {
+    let output = run("print \"tab\\there\";\nprint 0xFF + 1_000;\n");
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "tab\there\n1255\n");
+
+    let output = run("print 1;\nprint \"\\q\";\n");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error: Invalid escape sequence.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn truncated_sources_are_compile_errors() {
+    for (name, source) in [
+        ("lone_slash", "/"),
+        ("trailing_slash", "print 1 /"),
+        ("open_string", "print \"abc"),
+        ("open_escape", "print \"\\"),
+        ("bare_hex", "print 0x;"),
+        ("stray_byte", "print 1 @ 2;"),
+    ] {
+        let output = run(source);
+        assert!(!stderr(&output).contains("panicked"), "{name}");
+        assert!(stderr(&output).starts_with("[line 1] Error"), "{name}");
+        assert_eq!(output.status.code(), Some(65), "{name}");
+    }
+}
+
+#[test]
+fn compiled_scripts_run_like_their_source() {
+    let source = "class Greeter {\n  init(name) { this.name = name; }\n  greet() { return \"hi \" + this.name; }\n}\nfun twice(f) { return f() + f(); }\nvar g = Greeter(\"rox\");\nprint twice(g.greet);\nprint clock() > 0;\n";
+    let script = script(source);
+    let compiled = script.with_extension("roxc");
+    let output = Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .arg("compile")
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert_eq!(stderr(&output), "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(fs::read(&compiled).unwrap().starts_with(b"ROXC"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .arg(&compiled)
+        .output()
+        .unwrap();
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "hi roxhi rox\ntrue\n");
+}
+
+#[test]
+fn compiled_scripts_from_a_newer_rox_are_refused() {
+    let script = script("print 1;\n");
+    let compiled = scratch_path(".out");
+    let output = Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .arg("compile")
+        .arg(&script)
+        .arg(&compiled)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    // The version follows the four magic bytes.
+    let mut bytes = fs::read(&compiled).unwrap();
+    bytes[4..6].copy_from_slice(&2u16.to_le_bytes());
+    fs::write(&compiled, &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .arg(&compiled)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "Compiled with newer rox: .roxc format version 2, but this rox reads up to version 1.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+
+    bytes.truncate(bytes.len() - 1);
+    bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+    fs::write(&compiled, &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rox_lang"))
+        .arg(&compiled)
+        .output()
+        .unwrap();
+    assert_eq!(stderr(&output), "Truncated .roxc file.\n");
+}
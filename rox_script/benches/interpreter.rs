@@ -0,0 +1,63 @@
+//! Baselines for the interpreter's hot paths. Each benchmark runs a whole
+//! script in a fresh interpreter, including scanning, parsing and resolving.
+//! Run with `cargo bench -p rox_script`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rox_script::Rox;
+
+const FIB: &str = r#"
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+fib(20);
+"#;
+
+const STRING_CONCAT: &str = r#"
+var s = "";
+for (var i = 0; i < 2000; i = i + 1) {
+  s = s + "x";
+}
+"#;
+
+const METHOD_DISPATCH: &str = r#"
+class Counter {
+  init() {
+    this.count = 0;
+  }
+  increment() {
+    this.count = this.count + 1;
+  }
+}
+var counter = Counter();
+for (var i = 0; i < 5000; i = i + 1) {
+  counter.increment();
+}
+"#;
+
+const CLOSURES: &str = r#"
+fun adder(n) {
+  fun add(x) {
+    return x + n;
+  }
+  return add;
+}
+var total = 0;
+for (var i = 0; i < 2000; i = i + 1) {
+  total = adder(i)(total);
+}
+"#;
+
+fn run(source: &str) {
+    Rox::new().eval(source).unwrap();
+}
+
+fn benchmarks(c: &mut Criterion) {
+    c.bench_function("fib", |b| b.iter(|| run(FIB)));
+    c.bench_function("string_concat", |b| b.iter(|| run(STRING_CONCAT)));
+    c.bench_function("method_dispatch", |b| b.iter(|| run(METHOD_DISPATCH)));
+    c.bench_function("closures", |b| b.iter(|| run(CLOSURES)));
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,299 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::expr::Expr;
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::minify::Minifier;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner;
+use crate::source::{import_path, SourceProvider};
+use crate::stmt::{ImportStmt, Stmt};
+use crate::token::TokenType;
+
+/// Bundles the script at `entry` and every module it imports, directly or
+/// not, into one self-contained script, minified, with the functions,
+/// classes and variables nothing uses left out.
+///
+/// Each module's code is inlined where it is first imported, which is where
+/// it would have run. Its top-level names are prefixed with the module's
+/// name so modules can't clash, and `module.name` reads of its exports are
+/// rewritten to the prefixed name. That only works for namespaces used to
+/// read exports, so using one any other way is an error. Standard modules
+/// stay imports.
+pub fn bundle(entry: &Path, sources: &dyn SourceProvider) -> Result<String> {
+    let mut loader = Loader {
+        sources,
+        loaded: vec![],
+        identifiers: HashSet::new(),
+    };
+    loader.load(entry.to_path_buf())?;
+    let Loader {
+        loaded,
+        identifiers,
+        ..
+    } = loader;
+
+    let mut modules = HashMap::new();
+    let mut taken = identifiers.clone();
+    let mut entry_module = None;
+    for (i, module) in loaded.into_iter().enumerate() {
+        let module = Rc::new(module.finish(i == 0, &mut taken));
+        if i == 0 {
+            entry_module = Some(Rc::clone(&module));
+        }
+        modules.insert(module.file.clone(), module);
+    }
+    let Some(entry_module) = entry_module else {
+        unreachable!("the entry script is always loaded first");
+    };
+
+    // The first pass only finds out what each top-level declaration uses;
+    // the second writes out the declarations something uses.
+    let mut bundle = Bundle::new(modules, Rc::clone(&entry_module));
+    bundle.inlined.insert(entry_module.file.clone());
+    let (_, first_pass) =
+        Minifier::new(identifiers.clone()).bundle(&entry_module.statements, bundle)?;
+    let live = first_pass.live();
+
+    let mut bundle = Bundle::new(first_pass.modules, Rc::clone(&entry_module));
+    bundle.inlined.insert(entry_module.file.clone());
+    bundle.live = Some(live);
+    let (bundled, _) = Minifier::new(identifiers).bundle(&entry_module.statements, bundle)?;
+    Ok(bundled)
+}
+
+/// A module being bundled.
+pub(crate) struct BundledModule {
+    /// The path the module was read from, which its imports are relative to.
+    pub(crate) file: PathBuf,
+    pub(crate) is_entry: bool,
+    pub(crate) statements: Vec<Rc<Stmt>>,
+    /// The name each top-level name is written as. The entry script keeps
+    /// its names, so it has none.
+    pub(crate) globals: HashMap<String, String>,
+    pub(crate) exports: HashSet<String>,
+    /// The file each imported namespace refers to, by namespace name.
+    pub(crate) namespaces: HashMap<String, PathBuf>,
+}
+
+impl BundledModule {
+    /// The path of the module imported by `stmt`, worked out the way the
+    /// interpreter does.
+    pub(crate) fn import_path(&self, stmt: &ImportStmt) -> PathBuf {
+        import_path(Some(&self.file), &stmt.path)
+    }
+
+    pub(crate) fn global(&self, name: &str) -> String {
+        self.globals
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+/// The state a [`Minifier`] keeps while writing a bundle.
+pub(crate) struct Bundle {
+    pub(crate) modules: HashMap<PathBuf, Rc<BundledModule>>,
+    /// The module whose code is being written.
+    pub(crate) current: Rc<BundledModule>,
+    /// The modules already written out.
+    pub(crate) inlined: HashSet<PathBuf>,
+    /// The globals the statement being written refers to, by written name.
+    pub(crate) references: HashSet<String>,
+    /// The globals each removable top-level declaration refers to.
+    pub(crate) dependencies: HashMap<String, HashSet<String>>,
+    /// The globals statements that are always kept refer to.
+    pub(crate) roots: HashSet<String>,
+    /// The declarations to keep, once the first pass has worked them out.
+    pub(crate) live: Option<HashSet<String>>,
+}
+
+impl Bundle {
+    fn new(modules: HashMap<PathBuf, Rc<BundledModule>>, entry: Rc<BundledModule>) -> Self {
+        Bundle {
+            modules,
+            current: entry,
+            inlined: HashSet::new(),
+            references: HashSet::new(),
+            dependencies: HashMap::new(),
+            roots: HashSet::new(),
+            live: None,
+        }
+    }
+
+    /// Every global reachable from the statements that are always kept.
+    fn live(&self) -> HashSet<String> {
+        let mut live = HashSet::new();
+        let mut pending: Vec<_> = self.roots.iter().cloned().collect();
+        while let Some(name) = pending.pop() {
+            if !live.insert(name.clone()) {
+                continue;
+            }
+            if let Some(dependencies) = self.dependencies.get(&name) {
+                pending.extend(dependencies.iter().cloned());
+            }
+        }
+        live
+    }
+}
+
+/// The name a top-level statement declares, if it can be left out when
+/// nothing uses it. Variables only can when initializing them can't do
+/// anything else.
+pub(crate) fn removable_declaration(statement: &Rc<Stmt>) -> Option<&str> {
+    match statement.as_ref() {
+        Stmt::Function(function) => Some(&function.name.lexeme),
+        Stmt::Class(class) => Some(&class.name.lexeme),
+        Stmt::Var(var) => match &var.var_value {
+            Some(value) if !is_constant(value) => None,
+            _ => Some(&var.var_name.lexeme),
+        },
+        _ => None,
+    }
+}
+
+/// Whether evaluating `expr` can only produce a value: literals and
+/// collections of them.
+fn is_constant(expr: &Rc<Expr>) -> bool {
+    match expr.as_ref() {
+        Expr::Literal(_) => true,
+        Expr::Grouping(grouping) => is_constant(&grouping.expression),
+        Expr::Array(array) => array.elements.iter().all(is_constant),
+        Expr::Map(map) => map
+            .entries
+            .iter()
+            .all(|(key, value)| is_constant(key) && is_constant(value)),
+        _ => false,
+    }
+}
+
+/// A module read and parsed, before its names are prefixed.
+struct LoadedModule {
+    file: PathBuf,
+    statements: Vec<Rc<Stmt>>,
+}
+
+impl LoadedModule {
+    fn finish(self, is_entry: bool, taken: &mut HashSet<String>) -> BundledModule {
+        let mut names = vec![];
+        let mut exports = HashSet::new();
+        let mut namespaces = HashMap::new();
+        for statement in &self.statements {
+            let exported = matches!(statement.as_ref(), Stmt::Export(_));
+            let name = match statement.unexported() {
+                Stmt::Import(import) => {
+                    if !import.path.starts_with("std/") {
+                        let file = import_path(Some(&self.file), &import.path);
+                        namespaces.insert(import.name.clone(), file);
+                    }
+                    import.name.clone()
+                }
+                Stmt::Function(function) => function.name.lexeme.to_string(),
+                Stmt::Class(class) => class.name.lexeme.to_string(),
+                Stmt::Var(var) => var.var_name.lexeme.to_string(),
+                _ => continue,
+            };
+            if exported {
+                exports.insert(name.clone());
+            }
+            names.push(name);
+        }
+
+        let globals = if is_entry {
+            HashMap::new()
+        } else {
+            let prefix = prefix(&self.file, &names, taken);
+            names
+                .into_iter()
+                .map(|name| {
+                    let global = format!("{prefix}{name}");
+                    taken.insert(global.clone());
+                    (name, global)
+                })
+                .collect()
+        };
+        BundledModule {
+            file: self.file,
+            is_entry,
+            statements: self.statements,
+            globals,
+            exports,
+            namespaces,
+        }
+    }
+}
+
+/// A prefix based on the module's file name that gives none of `names` a
+/// name already `taken`.
+fn prefix(file: &Path, names: &[String], taken: &HashSet<String>) -> String {
+    let stem: String = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let stem = match stem.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => stem,
+        _ => format!("m{stem}"),
+    };
+    let mut prefix = format!("{stem}__");
+    let mut n = 2;
+    while names
+        .iter()
+        .any(|name| taken.contains(&format!("{prefix}{name}")))
+    {
+        prefix = format!("{stem}{n}__");
+        n += 1;
+    }
+    prefix
+}
+
+struct Loader<'a> {
+    sources: &'a dyn SourceProvider,
+    /// Every module read so far, the entry script first.
+    loaded: Vec<LoadedModule>,
+    /// Every identifier in any of them.
+    identifiers: HashSet<String>,
+}
+
+impl Loader<'_> {
+    /// Reads, parses and resolves the module at `file` and then the modules
+    /// it imports.
+    fn load(&mut self, file: PathBuf) -> Result<()> {
+        if self.loaded.iter().any(|module| module.file == file) {
+            return Ok(());
+        }
+        let source = self.sources.read(&file)?;
+        let tokens = scanner::run(source, self.loaded.len() as u32 + 1)?;
+        self.identifiers.extend(
+            tokens
+                .iter()
+                .filter(|token| token.token_type == TokenType::IDENTIFIER)
+                .map(|token| token.lexeme.to_string()),
+        );
+        let statements = Parser::new(tokens).parse()?;
+        let interpreter = Interpreter::new(Limits::default());
+        Resolver::new(&interpreter).resolve(statements.clone())?;
+
+        let imports: Vec<_> = statements
+            .iter()
+            .filter_map(|statement| match statement.as_ref() {
+                Stmt::Import(import) => Some(import),
+                _ => None,
+            })
+            .filter(|import| !import.path.starts_with("std/"))
+            .map(|import| import_path(Some(&file), &import.path))
+            .collect();
+        self.loaded.push(LoadedModule { file, statements });
+        for import in imports {
+            self.load(import)?;
+        }
+        Ok(())
+    }
+}
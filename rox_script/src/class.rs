@@ -2,25 +2,31 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
+use std::mem;
+use std::rc::Rc;
 
-use anyhow::anyhow;
 use anyhow::Result;
 
+use crate::error::LoxError;
+use crate::explain;
 use crate::functions::{LoxCallable, LoxFunction};
 use crate::interpreter::Interpreter;
+use crate::memory::{self, charge_shared, Charge};
+use crate::profile::{track_shared, Allocation, Kind};
+use crate::symbol::Symbol;
 use crate::token::{DataType, Token};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LoxClass {
     pub name: String,
     pub super_class: Option<Box<LoxClass>>,
-    pub methods: HashMap<String, LoxFunction>,
+    pub methods: HashMap<Symbol, LoxFunction>,
 }
 
 impl LoxClass {
-    pub fn find_method(&self, name: String) -> Option<LoxFunction> {
-        if self.methods.contains_key(&name) {
-            return Some(self.methods.get(&name).unwrap().clone());
+    pub fn find_method(&self, name: &str) -> Option<LoxFunction> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(method.clone());
         }
 
         if let Some(superclass) = &self.super_class {
@@ -31,52 +37,82 @@ impl LoxClass {
     }
 }
 
-#[derive(Debug, Clone)]
+/// A handle to an instance. Clones share the same fields, so an instance
+/// captured as `this` by a bound method sees every later write to it.
+#[derive(Clone)]
 pub struct LoxInstance {
     class: LoxClass,
-    fields: RefCell<HashMap<String, DataType>>,
+    fields: Rc<RefCell<HashMap<Symbol, DataType>>>,
+    _allocation: Option<Rc<Allocation>>,
+    memory: Option<Rc<Charge>>,
 }
 
 impl LoxInstance {
+    pub fn class(&self) -> &LoxClass {
+        &self.class
+    }
+
+    pub(crate) fn fields(&self) -> &Rc<RefCell<HashMap<Symbol, DataType>>> {
+        &self.fields
+    }
+
     pub fn get(&self, name: &Token) -> Result<DataType> {
-        if self.fields.borrow().contains_key(&name.lexeme) {
-            return Ok(self
-                .fields
-                .borrow()
-                .get(&name.lexeme)
-                .ok_or(anyhow!("Can't find property"))?
-                .clone());
+        if let Some(value) = self.fields.borrow().get(&name.lexeme) {
+            return Ok(value.clone());
         }
 
-        let method = self.class.find_method(name.lexeme.clone());
+        let method = self.class.find_method(&name.lexeme);
 
         if let Some(method) = method {
             return Ok(DataType::Function(method.bind(self.clone())));
         }
 
-        Err(anyhow!("Undefined property"))
+        Err(LoxError::runtime(
+            name,
+            format!("Undefined property '{}' on {}.", name.lexeme, self),
+        )
+        .into())
     }
 
     pub fn set(&self, name: &Token, value: DataType) {
-        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+        let added = mem::size_of::<Symbol>() + memory::size_of(&value);
+        let previous = self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+        if let Some(charge) = &self.memory {
+            let removed = previous.map_or(0, |previous| {
+                mem::size_of::<Symbol>() + memory::size_of(&previous)
+            });
+            charge.resize(removed, added);
+        }
     }
 }
 
 impl fmt::Display for LoxClass {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "<Class {}>", self.name)
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
     }
 }
 
 impl fmt::Display for LoxInstance {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "<Instance {}>", self.class.name)
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl Debug for LoxInstance {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
     }
 }
 
 impl LoxCallable for LoxClass {
     fn arity(&self) -> usize {
-        if let Some(initializer) = self.find_method("init".to_string()) {
+        if let Some(initializer) = self.find_method("init") {
             initializer.arity()
         } else {
             0
@@ -86,9 +122,12 @@ impl LoxCallable for LoxClass {
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<DataType>) -> Result<DataType> {
         let lox_instance = LoxInstance {
             class: self.clone(),
-            fields: RefCell::new(HashMap::new()),
+            fields: Rc::new(RefCell::new(HashMap::new())),
+            _allocation: track_shared(Kind::Instance),
+            memory: charge_shared(mem::size_of::<HashMap<Symbol, DataType>>),
         };
-        if let Some(initializer) = self.find_method("init".to_string()) {
+        explain::say(|| format!("creating instance of {}", self.name));
+        if let Some(initializer) = self.find_method("init") {
             initializer
                 .bind(lox_instance.clone())
                 .call(interpreter, arguments)?;
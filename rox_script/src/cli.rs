@@ -0,0 +1,527 @@
+//! The tree-walking interpreter's command line: running scripts, the REPL
+//! and the commands that work on scripts without running them.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{env, io, process};
+
+use rustyline::error::ReadlineError;
+use rustyline::{DefaultEditor, Result};
+use signal_hook::consts::SIGINT;
+
+use crate::bundle::bundle;
+use crate::debugger::Debugger;
+use crate::diagnostics::{report, report_warning, ErrorFormat};
+use crate::error::{ErrorKind, Exit, LoxError};
+use crate::explain;
+use crate::features;
+use crate::limits::Limits;
+use crate::minify::minify;
+use crate::pack::{pack, packed_script};
+use crate::printer::{print_ast, print_resolved_ast};
+use crate::profile;
+use crate::source::{FileSystem, SourceProvider};
+use crate::timing;
+use crate::token::DataType;
+use crate::trace;
+use crate::typecheck::typecheck;
+use crate::Rox;
+
+/// If this executable was made by `rox pack`, runs the script packed into
+/// it with every argument it was given and exits. Otherwise does nothing.
+pub fn run_if_packed() -> Result<()> {
+    if let Some(script) = env::current_exe()
+        .and_then(|exe| packed_script(&exe))
+        .ok()
+        .flatten()
+    {
+        run_packed(&script)?;
+    }
+    Ok(())
+}
+
+/// Runs the tree-walking interpreter's command line with `arguments`, the
+/// ones after the executable's name.
+pub fn main(arguments: Vec<String>) -> Result<()> {
+    let mut error_format = ErrorFormat::Human;
+    let mut limits = Limits::default();
+    let mut print_tree = false;
+    let mut resolve_tree = false;
+    let mut type_checks = true;
+    let mut strict = false;
+    let mut optimize = false;
+    let mut seed = None;
+    let mut lang_version = features::LATEST;
+    let mut warnings = false;
+    let mut profile_memory = false;
+    let mut profile_time = false;
+    let mut explain = false;
+    let mut trace_execution = false;
+    let mut debug = false;
+    let mut runs = 10;
+    let mut args: Vec<String> = vec![];
+    // Everything after the script's path is passed to the script as is, so
+    // its own flags aren't taken for the interpreter's.
+    let mut script_args: Vec<String> = vec![];
+    for arg in arguments {
+        if has_script(&args) {
+            script_args.push(arg);
+            continue;
+        }
+        if arg == "--print-ast" {
+            print_tree = true;
+            continue;
+        }
+        if arg == "--resolve" {
+            resolve_tree = true;
+            continue;
+        }
+        if arg == "--strict" {
+            strict = true;
+            continue;
+        }
+        if arg == "--warnings" {
+            warnings = true;
+            continue;
+        }
+        if arg == "--opt" {
+            optimize = true;
+            continue;
+        }
+        if arg == "--debug" {
+            debug = true;
+            continue;
+        }
+        if arg == "--trace" {
+            trace_execution = true;
+            continue;
+        }
+        if arg == "--explain" {
+            explain = true;
+            continue;
+        }
+        if arg == "--profile" {
+            profile_time = true;
+            continue;
+        }
+        if arg == "--profile-memory" {
+            profile_memory = true;
+            continue;
+        }
+        if arg == "--no-type-checks" {
+            type_checks = false;
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-string-length=") {
+            limits.max_string_length = Some(parse_limit("string length", max));
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-collection-length=") {
+            limits.max_collection_length = Some(parse_limit("collection length", max));
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-call-depth=") {
+            limits.max_call_depth = Some(parse_limit("call depth", max));
+            continue;
+        }
+        if let Some(n) = arg.strip_prefix("--runs=") {
+            runs = parse_limit("runs", n).max(1);
+            continue;
+        }
+        if arg == "--deterministic" {
+            seed = Some(0);
+            continue;
+        }
+        if let Some(value) = arg.strip_prefix("--deterministic=") {
+            seed = Some(value.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid seed '{value}', expected a whole number.");
+                process::exit(64);
+            }));
+            continue;
+        }
+        if let Some(version) = arg.strip_prefix("--lang-version=") {
+            lang_version = match version.parse() {
+                Ok(version) if features::is_known(version) => version,
+                _ => {
+                    eprintln!(
+                        "Unknown lang version '{version}', expected {} to {}.",
+                        features::FIRST,
+                        features::LATEST
+                    );
+                    process::exit(64);
+                }
+            };
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-steps=") {
+            limits.max_steps = Some(parse_limit("step", max) as u64);
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-memory=") {
+            limits.max_memory = Some(parse_limit("memory", max));
+            continue;
+        }
+        if let Some(max) = arg.strip_prefix("--max-run-time=") {
+            let ms = parse_limit("run time", max) as u64;
+            limits.max_run_time = Some(Duration::from_millis(ms));
+            continue;
+        }
+        match arg.strip_prefix("--error-format=") {
+            Some("human") => error_format = ErrorFormat::Human,
+            Some("pretty") => error_format = ErrorFormat::Pretty,
+            Some("json") => error_format = ErrorFormat::Json,
+            Some(other) => {
+                eprintln!("Unknown error format '{other}', expected 'human', 'pretty' or 'json'.");
+                process::exit(64);
+            }
+            None => args.push(arg),
+        }
+    }
+
+    // `rox ast script` prints the script's tree like `--print-ast`, and
+    // `rox typecheck script` checks the script's annotations, `rox minify
+    // script` prints it minified and `rox bundle script` prints it and the
+    // modules it imports as one script, all without running it. `rox bench
+    // script` runs it `--runs=N` times and prints how long that took, and
+    // `rox pack script -o tool` writes the bundled script into a copy of
+    // this executable that runs it.
+    let command = if args.len() == 2 && is_command(&args[0]) {
+        Some(args.remove(0))
+    } else {
+        None
+    };
+    if command.as_deref() == Some("ast") {
+        print_tree = true;
+    }
+    let print_tree_of = if resolve_tree {
+        print_resolved_ast
+    } else {
+        print_ast
+    };
+
+    let usage_error = match command.as_deref() {
+        Some("pack") => pack_output(&args[0], &script_args).is_none(),
+        Some(_) => !script_args.is_empty(),
+        None => false,
+    };
+    if args.len() > 1 || usage_error {
+        println!("Usage: rox [--error-format=human|pretty|json] [--max-string-length=N] [--max-collection-length=N] [--max-call-depth=N] [--max-run-time=MS] [--max-steps=N] [--max-memory=BYTES] [--print-ast] [--no-type-checks] [--strict] [--opt] [--warnings] [--deterministic[=SEED]] [--lang-version=N] [--debug] [--trace] [--explain] [--profile] [--profile-memory] [script [args...]]");
+        println!("       rox [--error-format=human|pretty|json] typecheck|minify|bundle script");
+        println!("       rox [--resolve] ast script");
+        println!("       rox [--runs=N] bench script");
+        println!("       rox pack script [-o executable]");
+        process::exit(64);
+    }
+
+    // Ctrl-C cancels the running script. A second Ctrl-C, for a script stuck
+    // somewhere cancellation isn't checked, exits straight away.
+    let cancelled = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&cancelled))?;
+    signal_hook::flag::register(SIGINT, Arc::clone(&cancelled))?;
+
+    if profile_memory {
+        profile::enable();
+    }
+    if profile_time {
+        timing::enable();
+    }
+    if explain {
+        explain::enable();
+    }
+    if trace_execution {
+        trace::enable();
+    }
+
+    if args.len() == 1 {
+        let path = args.remove(0);
+        if command.as_deref() == Some("typecheck") {
+            let errors = match FileSystem.read(Path::new(&path)).and_then(typecheck) {
+                Ok(errors) => errors,
+                Err(err) => {
+                    report(&err, &path, error_format);
+                    process::exit(exit_code(&err));
+                }
+            };
+            let failed = !errors.is_empty();
+            for error in errors {
+                report(&error.into(), &path, error_format);
+            }
+            if failed {
+                process::exit(65);
+            }
+            return Ok(());
+        }
+        if command.as_deref() == Some("minify") {
+            match FileSystem.read(Path::new(&path)).and_then(minify) {
+                Ok(minified) => print!("{minified}"),
+                Err(err) => {
+                    report(&err, &path, error_format);
+                    process::exit(exit_code(&err));
+                }
+            }
+            return Ok(());
+        }
+        if command.as_deref() == Some("bundle") {
+            match bundle(Path::new(&path), &FileSystem) {
+                Ok(bundled) => print!("{bundled}"),
+                Err(err) => {
+                    report(&err, &path, error_format);
+                    process::exit(exit_code(&err));
+                }
+            }
+            return Ok(());
+        }
+        if command.as_deref() == Some("pack") {
+            let output = pack_output(&path, &script_args).unwrap_or_default();
+            let packed = bundle(Path::new(&path), &FileSystem)
+                .and_then(|bundled| Ok(pack(&env::current_exe()?, &bundled, &output)?));
+            if let Err(err) = packed {
+                report(&err, &path, error_format);
+                process::exit(exit_code(&err));
+            }
+            return Ok(());
+        }
+        if command.as_deref() == Some("bench") {
+            let mut times = vec![];
+            for _ in 0..runs {
+                // Each run gets a fresh interpreter, so none reuses the
+                // modules or globals of the one before.
+                let mut rox = Rox::with_limits(limits);
+                rox.interpreter()
+                    .set_cancellation_flag(Arc::clone(&cancelled));
+                rox.interpreter().set_type_checks(type_checks);
+                rox.interpreter().set_strict(strict);
+                rox.interpreter().set_optimize(optimize);
+                rox.interpreter().set_lang_version(lang_version);
+                rox.interpreter().set_deterministic(seed);
+                rox.set_output(io::sink());
+                let started = Instant::now();
+                if let Err(err) = rox.run_file(&path) {
+                    report(&err, &path, error_format);
+                    process::exit(exit_code(&err));
+                }
+                times.push(started.elapsed());
+            }
+            print_timings(&path, &times);
+            return Ok(());
+        }
+        if print_tree {
+            match FileSystem.read(Path::new(&path)).and_then(print_tree_of) {
+                Ok(tree) => print!("{tree}"),
+                Err(err) => {
+                    report(&err, &path, error_format);
+                    process::exit(exit_code(&err));
+                }
+            }
+            return Ok(());
+        }
+        let mut rox = Rox::with_limits(limits);
+        rox.interpreter().set_cancellation_flag(cancelled);
+        rox.interpreter().set_type_checks(type_checks);
+        rox.interpreter().set_strict(strict);
+        rox.interpreter().set_optimize(optimize);
+        rox.interpreter().set_lang_version(lang_version);
+        rox.interpreter().set_deterministic(seed);
+        if debug {
+            let commands = io::BufReader::new(io::stdin());
+            rox.interpreter()
+                .set_debugger(Debugger::new(commands, io::stderr()));
+        }
+        rox.set_args(script_args);
+        let mut result = rox.run_file(&path).map(|_| ());
+        let shutdown = rox.shutdown();
+        if result.is_ok() {
+            result = shutdown;
+        }
+        if warnings {
+            for warning in rox.take_warnings() {
+                report_warning(&warning, error_format);
+            }
+        }
+        match &result {
+            Err(err) if !err.is::<Exit>() => report(err, &path, error_format),
+            _ => {}
+        }
+        drop(rox);
+        report_profiles();
+        if let Err(err) = result {
+            process::exit(exit_code(&err));
+        }
+        return Ok(());
+    }
+
+    let mut rl = DefaultEditor::new()?;
+    let history = history_path();
+    rl.load_history(&history).ok();
+    let mut exited = None;
+
+    loop {
+        let readline = rl.readline(">> ");
+        match readline {
+            Ok(line) => {
+                rl.add_history_entry(line.as_str())?;
+                if print_tree {
+                    match print_tree_of(line) {
+                        Ok(tree) => print!("{tree}"),
+                        Err(err) => report(&err, "<repl>", error_format),
+                    }
+                    continue;
+                }
+                let mut rox = Rox::with_limits(limits);
+                cancelled.store(false, Ordering::Relaxed);
+                rox.interpreter()
+                    .set_cancellation_flag(Arc::clone(&cancelled));
+                rox.interpreter().set_type_checks(type_checks);
+                rox.interpreter().set_strict(strict);
+                rox.interpreter().set_optimize(optimize);
+                rox.interpreter().set_lang_version(lang_version);
+                rox.interpreter().set_deterministic(seed);
+                match rox.eval(&line) {
+                    Ok(DataType::Nil) => {}
+                    Ok(value) => println!("{value}"),
+                    Err(err) => match err.downcast_ref::<Exit>() {
+                        Some(exit) => {
+                            exited = Some(exit.code);
+                            break;
+                        }
+                        None => report(&err, "<repl>", error_format),
+                    },
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+    }
+    rl.save_history(&history).ok();
+    report_profiles();
+    if let Some(code) = exited {
+        process::exit(code);
+    }
+    Ok(())
+}
+
+/// Runs the script packed into this executable with every argument it was
+/// given, then exits.
+fn run_packed(script: &str) -> Result<()> {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&cancelled))?;
+    signal_hook::flag::register(SIGINT, Arc::clone(&cancelled))?;
+
+    let mut args = env::args();
+    let name = args.next().unwrap_or_default();
+    let mut rox = Rox::new();
+    rox.interpreter().set_cancellation_flag(cancelled);
+    rox.set_args(args.collect());
+    let mut result = rox.eval(script).map(|_| ());
+    let shutdown = rox.shutdown();
+    if result.is_ok() {
+        result = shutdown;
+    }
+    match result {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            if !err.is::<Exit>() {
+                report(&err, &name, ErrorFormat::Human);
+            }
+            process::exit(exit_code(&err));
+        }
+    }
+}
+
+/// Where `rox pack script` writes the executable: the path after `-o`, or
+/// the script's name without its extension, in the working directory.
+/// `None` if the arguments after the script aren't either.
+fn pack_output(script: &str, args: &[String]) -> Option<PathBuf> {
+    match args {
+        [] => {
+            let name = Path::new(script).file_stem()?.to_string_lossy();
+            Some(PathBuf::from(format!("{name}{}", env::consts::EXE_SUFFIX)))
+        }
+        [flag, output] if flag == "-o" => Some(PathBuf::from(output)),
+        _ => None,
+    }
+}
+
+/// Where the REPL keeps its history: `.rox_history` in the user's home
+/// directory, or `history.txt` in the working directory if there isn't one.
+fn history_path() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".rox_history"))
+        .unwrap_or_else(|| PathBuf::from("history.txt"))
+}
+
+/// Prints what `--profile` timed and `--profile-memory` counted. By the
+/// time it runs the interpreters are gone, so whatever is still live was
+/// leaked by a reference cycle.
+fn report_profiles() {
+    if let Some(timings) = timing::report() {
+        eprint!("Time profile:\n{timings}");
+    }
+    if let Some(counts) = profile::report() {
+        eprint!("Memory profile:\n{counts}");
+    }
+}
+
+/// Prints the fastest and average of the `times` a script took to run.
+fn print_timings(path: &str, times: &[Duration]) {
+    let min = times.iter().min().copied().unwrap_or_default();
+    let total: Duration = times.iter().sum();
+    let avg = total / times.len() as u32;
+    println!("{} runs of {path}", times.len());
+    println!("min {:>10.3} ms", min.as_secs_f64() * 1000.0);
+    println!("avg {:>10.3} ms", avg.as_secs_f64() * 1000.0);
+}
+
+fn is_command(arg: &str) -> bool {
+    matches!(
+        arg,
+        "ast" | "typecheck" | "minify" | "bundle" | "bench" | "pack"
+    )
+}
+
+/// Whether the positional arguments seen so far end with a script's path,
+/// either alone or after a command.
+fn has_script(args: &[String]) -> bool {
+    match args {
+        [] => false,
+        [first] => !is_command(first),
+        _ => true,
+    }
+}
+
+/// Parses the value of a `--max-...=N` flag, exiting with a usage error if
+/// it isn't a number.
+fn parse_limit(limit: &str, value: &str) -> usize {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid {limit} limit '{value}', expected a number.");
+        process::exit(64);
+    })
+}
+
+/// Exit codes follow sysexits: 65 for errors in the input, 66 when the script
+/// can't be read and 70 for runtime failures. A script that calls `exit`
+/// exits with the code it passed.
+fn exit_code(error: &anyhow::Error) -> i32 {
+    if let Some(exit) = error.downcast_ref::<Exit>() {
+        return exit.code;
+    }
+    match error.downcast_ref::<LoxError>() {
+        Some(error) if error.kind != ErrorKind::Runtime => 65,
+        Some(_) => 70,
+        None if error.downcast_ref::<io::Error>().is_some() => 66,
+        None => 70,
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::mem;
+use std::rc::Rc;
+
+use crate::memory::{self, charge_shared, Charge};
+use crate::profile::{track_shared, Allocation, Kind};
+use crate::token::DataType;
+
+thread_local! {
+    /// The collections currently being printed, so a collection that contains
+    /// itself prints as `[...]` or `{...}` instead of recursing forever.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Prints a collection through `write` unless it is already being printed
+/// further up, in which case `cycle` is printed instead.
+fn print_once(
+    f: &mut Formatter,
+    id: *const (),
+    cycle: &str,
+    write: impl FnOnce(&mut Formatter) -> fmt::Result,
+) -> fmt::Result {
+    if PRINTING.with(|printing| printing.borrow().contains(&id)) {
+        return write!(f, "{cycle}");
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(id));
+    let result = write(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
+/// A handle to an array. Clones share the same elements, so an array passed
+/// to a function or stored elsewhere sees every later change to it.
+#[derive(Clone, Default)]
+pub struct LoxArray {
+    pub elements: Rc<RefCell<Vec<DataType>>>,
+    _allocation: Option<Rc<Allocation>>,
+    memory: Option<Rc<Charge>>,
+}
+
+impl LoxArray {
+    pub fn new(elements: Vec<DataType>) -> Self {
+        let memory = charge_shared(|| {
+            mem::size_of::<Vec<DataType>>() + elements.iter().map(memory::size_of).sum::<usize>()
+        });
+        LoxArray {
+            elements: Rc::new(RefCell::new(elements)),
+            _allocation: track_shared(Kind::Array),
+            memory,
+        }
+    }
+
+    pub fn push(&self, value: DataType) {
+        if let Some(charge) = &self.memory {
+            charge.resize(0, memory::size_of(&value));
+        }
+        self.elements.borrow_mut().push(value);
+    }
+
+    /// Replaces the element at `index`, which must be in bounds.
+    pub fn set(&self, index: usize, value: DataType) {
+        let mut elements = self.elements.borrow_mut();
+        if let Some(charge) = &self.memory {
+            charge.resize(memory::size_of(&elements[index]), memory::size_of(&value));
+        }
+        elements[index] = value;
+    }
+
+    /// Whether both handles refer to the same array.
+    pub fn same(&self, other: &LoxArray) -> bool {
+        Rc::ptr_eq(&self.elements, &other.elements)
+    }
+}
+
+impl Display for LoxArray {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        print_once(f, Rc::as_ptr(&self.elements) as *const (), "[...]", |f| {
+            write!(f, "[")?;
+            for (i, element) in self.elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", element.repr())?;
+            }
+            write!(f, "]")
+        })
+    }
+}
+
+impl Debug for LoxArray {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// The values that can key a map. Keys compare the way `==` compares them,
+/// so `0` and `-0` are the same key.
+#[derive(Clone, Debug)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl MapKey {
+    /// The key for `value`, or `None` if values of its type can't be keys.
+    pub fn from_value(value: &DataType) -> Option<MapKey> {
+        match value {
+            DataType::Nil => Some(MapKey::Nil),
+            DataType::Bool(b) => Some(MapKey::Bool(*b)),
+            DataType::Number(n) if *n == 0.0 => Some(MapKey::Number(0.0)),
+            DataType::Number(n) => Some(MapKey::Number(*n)),
+            DataType::String(s) => Some(MapKey::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> DataType {
+        match self {
+            MapKey::Nil => DataType::Nil,
+            MapKey::Bool(b) => DataType::Bool(*b),
+            MapKey::Number(n) => DataType::Number(*n),
+            MapKey::String(s) => DataType::String(s.clone()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            MapKey::Nil => 0,
+            MapKey::Bool(_) => 1,
+            MapKey::Number(_) => 2,
+            MapKey::String(_) => 3,
+        }
+    }
+}
+
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Bool(l), MapKey::Bool(r)) => l.cmp(r),
+            (MapKey::Number(l), MapKey::Number(r)) => l.total_cmp(r),
+            (MapKey::String(l), MapKey::String(r)) => l.cmp(r),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+/// A handle to a map. Like arrays, clones share the same entries. Entries
+/// are kept sorted by key, nil first, then booleans, numbers and strings, so
+/// printing and iterating a map is deterministic.
+#[derive(Clone, Default)]
+pub struct LoxMap {
+    pub entries: Rc<RefCell<BTreeMap<MapKey, DataType>>>,
+    _allocation: Option<Rc<Allocation>>,
+    memory: Option<Rc<Charge>>,
+}
+
+impl LoxMap {
+    pub fn new(entries: BTreeMap<MapKey, DataType>) -> Self {
+        let memory = charge_shared(|| {
+            mem::size_of::<BTreeMap<MapKey, DataType>>()
+                + entries
+                    .iter()
+                    .map(|(key, value)| entry_size(key, value))
+                    .sum::<usize>()
+        });
+        LoxMap {
+            entries: Rc::new(RefCell::new(entries)),
+            _allocation: track_shared(Kind::Map),
+            memory,
+        }
+    }
+
+    pub fn insert(&self, key: MapKey, value: DataType) {
+        let added = entry_size(&key, &value);
+        let previous = self.entries.borrow_mut().insert(key.clone(), value);
+        if let Some(charge) = &self.memory {
+            let removed = previous.map_or(0, |previous| entry_size(&key, &previous));
+            charge.resize(removed, added);
+        }
+    }
+
+    /// Whether both handles refer to the same map.
+    pub fn same(&self, other: &LoxMap) -> bool {
+        Rc::ptr_eq(&self.entries, &other.entries)
+    }
+}
+
+/// Roughly what an entry of a map costs, for [`memory`] accounting.
+fn entry_size(key: &MapKey, value: &DataType) -> usize {
+    let text = match key {
+        MapKey::String(s) => s.len(),
+        _ => 0,
+    };
+    mem::size_of::<MapKey>() + text + memory::size_of(value)
+}
+
+impl Display for LoxMap {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        print_once(f, Rc::as_ptr(&self.entries) as *const (), "{...}", |f| {
+            write!(f, "{{")?;
+            for (i, (key, value)) in self.entries.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", key.to_value().repr(), value.repr())?;
+            }
+            write!(f, "}}")
+        })
+    }
+}
+
+impl Debug for LoxMap {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,177 @@
+//! Conversions between script values and Rust types, for natives written as
+//! closures. Rust values convert into script values with `From`, and script
+//! values convert back with `TryFrom<&DataType>`, as in
+//! `f64::try_from(&arguments[0])?`, failing with an error that names the
+//! type the value had.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Error, Result};
+
+use crate::collections::{LoxArray, LoxMap, MapKey};
+use crate::token::DataType;
+use crate::types::type_name;
+
+impl From<f64> for DataType {
+    fn from(n: f64) -> Self {
+        DataType::Number(n)
+    }
+}
+
+impl From<i32> for DataType {
+    fn from(n: i32) -> Self {
+        DataType::Number(n.into())
+    }
+}
+
+/// Integers beyond 2^53 lose precision, as every number is an `f64`.
+impl From<i64> for DataType {
+    fn from(n: i64) -> Self {
+        DataType::Number(n as f64)
+    }
+}
+
+/// Sizes beyond 2^53 lose precision, as every number is an `f64`.
+impl From<usize> for DataType {
+    fn from(n: usize) -> Self {
+        DataType::Number(n as f64)
+    }
+}
+
+impl From<bool> for DataType {
+    fn from(b: bool) -> Self {
+        DataType::Bool(b)
+    }
+}
+
+impl From<String> for DataType {
+    fn from(s: String) -> Self {
+        DataType::String(s)
+    }
+}
+
+impl From<&str> for DataType {
+    fn from(s: &str) -> Self {
+        DataType::String(s.to_string())
+    }
+}
+
+impl From<()> for DataType {
+    fn from(_: ()) -> Self {
+        DataType::Nil
+    }
+}
+
+/// `None` becomes nil.
+impl<T: Into<DataType>> From<Option<T>> for DataType {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(DataType::Nil, Into::into)
+    }
+}
+
+impl<T: Into<DataType>> From<Vec<T>> for DataType {
+    fn from(elements: Vec<T>) -> Self {
+        DataType::Array(LoxArray::new(
+            elements.into_iter().map(Into::into).collect(),
+        ))
+    }
+}
+
+impl<T: Into<DataType>> From<HashMap<String, T>> for DataType {
+    fn from(entries: HashMap<String, T>) -> Self {
+        DataType::Map(LoxMap::new(
+            entries
+                .into_iter()
+                .map(|(key, value)| (MapKey::String(key), value.into()))
+                .collect(),
+        ))
+    }
+}
+
+fn mismatch(expected: &str, value: &DataType) -> Error {
+    anyhow!("Expected {expected}, got {}.", type_name(value))
+}
+
+impl TryFrom<&DataType> for f64 {
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        match value {
+            DataType::Number(n) => Ok(*n),
+            other => Err(mismatch("a number", other)),
+        }
+    }
+}
+
+/// Only numbers with no fractional part that fit in an `i64` convert.
+impl TryFrom<&DataType> for i64 {
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        match value {
+            DataType::Number(n) if n.fract() == 0.0 && n.abs() <= i64::MAX as f64 => Ok(*n as i64),
+            DataType::Number(n) => Err(anyhow!("Expected an integer, got {n}.")),
+            other => Err(mismatch("an integer", other)),
+        }
+    }
+}
+
+impl TryFrom<&DataType> for bool {
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        match value {
+            DataType::Bool(b) => Ok(*b),
+            other => Err(mismatch("a bool", other)),
+        }
+    }
+}
+
+impl TryFrom<&DataType> for String {
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        match value {
+            DataType::String(s) => Ok(s.clone()),
+            other => Err(mismatch("a string", other)),
+        }
+    }
+}
+
+/// Converts each element, failing on the first that doesn't convert.
+impl<T> TryFrom<&DataType> for Vec<T>
+where
+    T: for<'a> TryFrom<&'a DataType, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        match value {
+            DataType::Array(array) => array.elements.borrow().iter().map(T::try_from).collect(),
+            other => Err(mismatch("an array", other)),
+        }
+    }
+}
+
+/// Converts a map whose keys are all strings, with values converted like
+/// [`Vec`] elements.
+impl<T> TryFrom<&DataType> for HashMap<String, T>
+where
+    T: for<'a> TryFrom<&'a DataType, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: &DataType) -> Result<Self> {
+        let DataType::Map(map) = value else {
+            return Err(mismatch("a map", value));
+        };
+        let entries = map.entries.borrow();
+        entries
+            .iter()
+            .map(|(key, value)| match key {
+                MapKey::String(key) => Ok((key.clone(), T::try_from(value)?)),
+                other => Err(mismatch("string keys", &other.to_value())),
+            })
+            .collect()
+    }
+}
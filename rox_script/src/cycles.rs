@@ -0,0 +1,284 @@
+//! Freeing the reference cycles closures create. A closure holds the scope
+//! it was declared in, and that scope usually holds the closure, so once
+//! nothing else refers to either, reference counting alone never frees them.
+//!
+//! Every scope a closure captures is remembered, and [`collect`] looks for
+//! the ones that are garbage by trial deletion: it walks everything those
+//! scopes reach, counts how many references each scope, instance, array and
+//! map gets from the others, and treats any with more references than that
+//! as alive, since something outside the graph, like the interpreter or a
+//! Rust caller, holds it. Whatever the live ones can't reach is garbage and
+//! is emptied, which breaks its cycles and lets reference counting free it.
+//!
+//! Cycles made only of instances, arrays and maps, like an array pushed into
+//! itself, are found only if a closure's scope reaches them.
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::{Rc, Weak};
+
+use crate::class::LoxClass;
+use crate::collections::MapKey;
+use crate::environment::Environment;
+use crate::explain;
+use crate::symbol::Symbol;
+use crate::token::DataType;
+
+/// How many captured scopes there can be before the first collection.
+const MIN_THRESHOLD: usize = 1024;
+
+thread_local! {
+    /// Every scope a closure captured, as long as it is alive.
+    static CAPTURED: RefCell<Vec<Weak<RefCell<Environment>>>> = const { RefCell::new(Vec::new()) };
+    /// How many entries `CAPTURED` can have before the dead ones are dropped.
+    static PRUNE_AT: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+    /// How many live captured scopes there can be before the next collection.
+    static COLLECT_AT: Cell<usize> = const { Cell::new(MIN_THRESHOLD) };
+    static DUE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Remembers that a closure captured `scope`.
+pub fn captured(scope: &Rc<RefCell<Environment>>) {
+    CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        captured.push(Rc::downgrade(scope));
+        if captured.len() < PRUNE_AT.get() {
+            return;
+        }
+        // Most captured scopes, like those of bound methods, die young, so
+        // dropping them is usually enough to stay under the threshold.
+        captured.retain(|scope| scope.strong_count() > 0);
+        PRUNE_AT.set(MIN_THRESHOLD.max(captured.len() * 2));
+        if captured.len() >= COLLECT_AT.get() {
+            DUE.set(true);
+        }
+    });
+}
+
+/// Whether enough captured scopes are alive that [`collect`] should run.
+pub fn due() -> bool {
+    DUE.get()
+}
+
+/// Frees every cycle of captured scopes on this thread that nothing outside
+/// the cycles refers to any more.
+pub fn collect() {
+    DUE.set(false);
+    let roots: Vec<_> = CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        captured.retain(|scope| scope.strong_count() > 0);
+        captured.iter().filter_map(Weak::upgrade).collect()
+    });
+
+    // Find everything the captured scopes reach, holding one reference to
+    // each, and count the references they hold to each other.
+    let mut nodes: HashMap<*const (), Node> = HashMap::new();
+    let mut pending: Vec<*const ()> = vec![];
+    for scope in roots {
+        let handle = Handle::Scope(scope);
+        let id = handle.id();
+        if let Entry::Vacant(entry) = nodes.entry(id) {
+            entry.insert(Node::new(handle));
+            pending.push(id);
+        }
+    }
+    while let Some(id) = pending.pop() {
+        let mut children = vec![];
+        let busy = !nodes[&id]
+            .handle
+            .children(&mut |child| children.push(child));
+        nodes.get_mut(&id).unwrap().busy = busy;
+        for child in children {
+            let child_id = child.id();
+            let node = nodes.entry(child_id).or_insert_with(|| {
+                pending.push(child_id);
+                Node::new(child)
+            });
+            node.internal += 1;
+        }
+    }
+
+    // Anything with references from outside the graph is alive, and so is
+    // everything it reaches.
+    let mut live: Vec<*const ()> = nodes
+        .iter()
+        .filter(|(_, node)| node.busy || node.handle.strong_count() > node.internal + 1)
+        .map(|(id, _)| *id)
+        .collect();
+    for id in &live {
+        nodes.get_mut(id).unwrap().live = true;
+    }
+    while let Some(id) = live.pop() {
+        let mut children = vec![];
+        nodes[&id]
+            .handle
+            .children(&mut |child| children.push(child.id()));
+        for child in children {
+            let node = nodes.get_mut(&child).unwrap();
+            if !node.live {
+                node.live = true;
+                live.push(child);
+            }
+        }
+    }
+
+    let mut garbage = 0;
+    for node in nodes.values().filter(|node| !node.live) {
+        node.handle.clear();
+        garbage += 1;
+    }
+    drop(nodes);
+    if garbage > 0 {
+        explain::say(|| {
+            format!("collecting garbage, freeing {garbage} unreachable scopes and objects kept alive by cycles")
+        });
+    }
+
+    let survivors = CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        captured.retain(|scope| scope.strong_count() > 0);
+        captured.len()
+    });
+    COLLECT_AT.set(MIN_THRESHOLD.max(survivors * 2));
+    PRUNE_AT.set(MIN_THRESHOLD.max(survivors * 2));
+}
+
+struct Node {
+    handle: Handle,
+    /// How many references the other nodes hold to this one.
+    internal: usize,
+    /// Borrowed while collecting, so its references couldn't be counted.
+    busy: bool,
+    live: bool,
+}
+
+impl Node {
+    fn new(handle: Handle) -> Node {
+        Node {
+            handle,
+            internal: 0,
+            busy: false,
+            live: false,
+        }
+    }
+}
+
+/// A reference to something that can be part of a cycle.
+enum Handle {
+    Scope(Rc<RefCell<Environment>>),
+    Fields(Rc<RefCell<HashMap<Symbol, DataType>>>),
+    Array(Rc<RefCell<Vec<DataType>>>),
+    Map(Rc<RefCell<BTreeMap<MapKey, DataType>>>),
+}
+
+impl Handle {
+    fn id(&self) -> *const () {
+        match self {
+            Handle::Scope(scope) => Rc::as_ptr(scope) as *const (),
+            Handle::Fields(fields) => Rc::as_ptr(fields) as *const (),
+            Handle::Array(elements) => Rc::as_ptr(elements) as *const (),
+            Handle::Map(entries) => Rc::as_ptr(entries) as *const (),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Handle::Scope(scope) => Rc::strong_count(scope),
+            Handle::Fields(fields) => Rc::strong_count(fields),
+            Handle::Array(elements) => Rc::strong_count(elements),
+            Handle::Map(entries) => Rc::strong_count(entries),
+        }
+    }
+
+    /// Calls `visit` with every reference this holds, once per reference.
+    /// Returns false without visiting any if it is being written to.
+    fn children(&self, visit: &mut dyn FnMut(Handle)) -> bool {
+        match self {
+            Handle::Scope(scope) => {
+                let Ok(scope) = scope.try_borrow() else {
+                    return false;
+                };
+                if let Some(parent) = &scope.parent_environment {
+                    visit(Handle::Scope(Rc::clone(parent)));
+                }
+                scope.values().for_each(|value| references(value, visit));
+            }
+            Handle::Fields(fields) => {
+                let Ok(fields) = fields.try_borrow() else {
+                    return false;
+                };
+                fields.values().for_each(|value| references(value, visit));
+            }
+            Handle::Array(elements) => {
+                let Ok(elements) = elements.try_borrow() else {
+                    return false;
+                };
+                elements.iter().for_each(|value| references(value, visit));
+            }
+            Handle::Map(entries) => {
+                let Ok(entries) = entries.try_borrow() else {
+                    return false;
+                };
+                entries.values().for_each(|value| references(value, visit));
+            }
+        }
+        true
+    }
+
+    /// Empties a node that is garbage.
+    fn clear(&self) {
+        match self {
+            Handle::Scope(scope) => {
+                if let Ok(mut scope) = scope.try_borrow_mut() {
+                    scope.clear();
+                }
+            }
+            Handle::Fields(fields) => {
+                if let Ok(mut fields) = fields.try_borrow_mut() {
+                    fields.clear();
+                }
+            }
+            Handle::Array(elements) => {
+                if let Ok(mut elements) = elements.try_borrow_mut() {
+                    elements.clear();
+                }
+            }
+            Handle::Map(entries) => {
+                if let Ok(mut entries) = entries.try_borrow_mut() {
+                    entries.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Calls `visit` with every reference `value` holds. Natives are opaque, so
+/// whatever they refer to counts as referenced from outside.
+fn references(value: &DataType, visit: &mut dyn FnMut(Handle)) {
+    match value {
+        DataType::Function(function) => visit(Handle::Scope(Rc::clone(function.closure()))),
+        DataType::Class(class) => class_references(class, visit),
+        DataType::Instance(instance) => {
+            visit(Handle::Fields(Rc::clone(instance.fields())));
+            class_references(instance.class(), visit);
+        }
+        DataType::Module(module) => visit(Handle::Scope(Rc::clone(module.environment()))),
+        DataType::Array(array) => visit(Handle::Array(Rc::clone(&array.elements))),
+        DataType::Map(map) => visit(Handle::Map(Rc::clone(&map.entries))),
+        DataType::String(_)
+        | DataType::Number(_)
+        | DataType::Bool(_)
+        | DataType::Nil
+        | DataType::NativeFunction(_) => {}
+    }
+}
+
+fn class_references(class: &LoxClass, visit: &mut dyn FnMut(Handle)) {
+    for method in class.methods.values() {
+        visit(Handle::Scope(Rc::clone(method.closure())));
+    }
+    if let Some(super_class) = &class.super_class {
+        class_references(super_class, visit);
+    }
+}
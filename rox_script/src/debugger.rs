@@ -0,0 +1,223 @@
+//! The interactive debugger behind `--debug`. The interpreter hands it every
+//! statement before running it, and it decides whether to stop there. It
+//! stops before the first statement, then wherever the last command asked:
+//!
+//! - `step` (`s`) stops at the next line run, even inside a call.
+//! - `next` (`n`) stops at the next line run without going into calls.
+//! - `continue` (`c`) runs until a breakpoint.
+//! - `break [file:]line` (`b`) sets a breakpoint at a line of `file`, or of
+//!   any script if no file is given.
+//! - `env` prints the variables of every scope, innermost first.
+//! - `quit` (`q`) stops the script.
+//!
+//! Execution stops at most once per line: statements that run on the line
+//! it last stopped at, like the rest of a loop on one line, don't stop it.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::interpreter::Interpreter;
+use crate::stmt::Stmt;
+
+pub struct Debugger {
+    commands: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    /// The file and text of every source run so far, by source id.
+    sources: HashMap<u32, Source>,
+    /// The source id and line of the last statement run, to tell when
+    /// execution reaches a new line.
+    previous: Option<(u32, u32)>,
+}
+
+struct Source {
+    file: Option<PathBuf>,
+    text: Rc<str>,
+}
+
+struct Breakpoint {
+    file: Option<PathBuf>,
+    line: u32,
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Step,
+    /// Stepping over calls made at more than this call depth.
+    Next(usize),
+    Continue,
+}
+
+impl Debugger {
+    /// A debugger reading commands from `commands` and writing what it
+    /// shows to `output`. Running out of commands lets the script run to
+    /// the end.
+    pub fn new(commands: impl BufRead + 'static, output: impl Write + 'static) -> Debugger {
+        Debugger {
+            commands: Box::new(commands),
+            output: Box::new(output),
+            breakpoints: vec![],
+            mode: Mode::Step,
+            sources: HashMap::new(),
+            previous: None,
+        }
+    }
+
+    /// Records the source the interpreter is about to run, so stops in it
+    /// can show its file and lines.
+    pub(crate) fn loaded(&mut self, source_id: u32, file: Option<PathBuf>, text: &str) {
+        let text = Rc::from(text);
+        self.sources.insert(source_id, Source { file, text });
+    }
+
+    /// Called before `statement` runs. Stops there if it should, reading
+    /// commands until one resumes the script.
+    pub(crate) fn before(&mut self, statement: &Stmt, interpreter: &Interpreter) -> Result<()> {
+        let Some(token) = statement.location() else {
+            return Ok(());
+        };
+        let location = (token.source_id, token.line);
+        if self.previous == Some(location) {
+            return Ok(());
+        }
+        self.previous = Some(location);
+
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => interpreter.call_depth() <= depth,
+            Mode::Continue => self.breakpoints.iter().any(|breakpoint| {
+                breakpoint.line == token.line
+                    && match (&breakpoint.file, self.file(token.source_id)) {
+                        (None, _) => true,
+                        (Some(wanted), Some(file)) => file.ends_with(wanted),
+                        (Some(_), None) => false,
+                    }
+            }),
+        };
+        if stop {
+            self.stop(token.source_id, token.line, interpreter)?;
+        }
+        Ok(())
+    }
+
+    fn file(&self, source_id: u32) -> Option<&Path> {
+        self.sources.get(&source_id)?.file.as_deref()
+    }
+
+    fn stop(&mut self, source_id: u32, line: u32, interpreter: &Interpreter) -> Result<()> {
+        let file = self
+            .file(source_id)
+            .map_or("<script>".to_string(), |file| file.display().to_string());
+        let text = self
+            .sources
+            .get(&source_id)
+            .and_then(|source| source.text.lines().nth(line as usize - 1))
+            .unwrap_or_default()
+            .trim_end_matches('\r')
+            .to_string();
+        writeln!(self.output, "Stopped at {file}:{line}")?;
+        writeln!(self.output, "{line:>4} | {text}")?;
+
+        loop {
+            write!(self.output, "(rox) ")?;
+            self.output.flush()?;
+            let mut command = String::new();
+            if self.commands.read_line(&mut command)? == 0 {
+                // Nothing is left to ask, so let the script finish.
+                writeln!(self.output)?;
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                return Ok(());
+            }
+            let mut words = command.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("step" | "s"), None) => self.mode = Mode::Step,
+                (Some("next" | "n"), None) => self.mode = Mode::Next(interpreter.call_depth()),
+                (Some("continue" | "c"), None) => self.mode = Mode::Continue,
+                (Some("break" | "b"), Some(place)) => {
+                    self.set_breakpoint(place)?;
+                    continue;
+                }
+                (Some("env"), None) => {
+                    self.print_environment(interpreter)?;
+                    continue;
+                }
+                (Some("quit" | "q"), None) => {
+                    return Err(anyhow!("Execution stopped by the debugger."));
+                }
+                (Some("help" | "h"), None) => {
+                    writeln!(
+                        self.output,
+                        "Commands: step (s), next (n), continue (c), break [file:]line (b), env, quit (q)"
+                    )?;
+                    continue;
+                }
+                (None, _) => continue,
+                _ => {
+                    writeln!(
+                        self.output,
+                        "Unknown command '{}'. Try 'help'.",
+                        command.trim()
+                    )?;
+                    continue;
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    fn set_breakpoint(&mut self, place: &str) -> Result<()> {
+        let (file, line) = match place.rsplit_once(':') {
+            Some((file, line)) => (Some(PathBuf::from(file)), line),
+            None => (None, place),
+        };
+        match line.parse() {
+            Ok(line) if line > 0 => {
+                self.breakpoints.push(Breakpoint { file, line });
+                writeln!(
+                    self.output,
+                    "Breakpoint {} at {place}",
+                    self.breakpoints.len()
+                )?;
+            }
+            _ => writeln!(self.output, "Invalid line '{line}'.")?,
+        }
+        Ok(())
+    }
+
+    /// Prints the variables of each scope from the current one out to the
+    /// global scope, numbering local scopes by how far out they are.
+    fn print_environment(&mut self, interpreter: &Interpreter) -> Result<()> {
+        let mut scope = Some(Rc::clone(&interpreter.environment.borrow()));
+        let mut distance = 0;
+        while let Some(environment) = scope {
+            let environment = environment.borrow();
+            // The builtins hold nothing the script defined.
+            if environment.parent_environment.is_none() {
+                break;
+            }
+            let label = if environment.is_global() {
+                "global".to_string()
+            } else {
+                distance.to_string()
+            };
+            let mut variables: Vec<_> = environment
+                .variables()
+                .map(|(name, value)| format!("{name} = {}", value.repr()))
+                .collect();
+            variables.sort();
+            if variables.is_empty() {
+                variables.push("(empty)".to_string());
+            }
+            writeln!(self.output, "[{label}] {}", variables.join(", "))?;
+            scope = environment.parent_environment.clone();
+            distance += 1;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::Write;
+use std::io;
+
+use crate::error::{ErrorKind, LoxError, Snippet, Span};
+use crate::token::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// The one-line messages of the book's interpreters.
+    Human,
+    /// Messages with the offending source line and a caret under it.
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// Something the resolver warns about. Warnings never stop a script.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warning {
+    /// A local variable, function or class nothing reads.
+    Unused,
+    /// Statements after a `return` or `throw` in the same block.
+    Unreachable,
+    /// A local declaration hiding one of an enclosing scope.
+    Shadowed,
+}
+
+/// A machine-readable description of a problem found in a script.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,
+    pub line: Option<u32>,
+    /// The character of the line the problem starts at, counting from 1.
+    pub column: Option<u32>,
+    /// The byte offsets of the offending source text in its script.
+    pub span: Option<(usize, usize)>,
+    /// A suggestion for fixing the problem.
+    pub hint: Option<String>,
+    /// The line of source the problem is on, for [`Diagnostic::render`].
+    pub snippet: Option<Snippet>,
+}
+
+impl Diagnostic {
+    pub fn from_error(error: &anyhow::Error, file: &str) -> Self {
+        match error.downcast_ref::<LoxError>() {
+            Some(error) => Diagnostic {
+                code: code_for(error.kind),
+                severity: Severity::Error,
+                message: error.message.clone(),
+                file: file.to_string(),
+                line: Some(error.line),
+                column: Some(error.column),
+                span: error
+                    .span
+                    .map(|span| (span.start as usize, span.end as usize)),
+                hint: error.hint.clone(),
+                snippet: error.snippet.clone(),
+            },
+            None => Diagnostic {
+                code: code_for(ErrorKind::Runtime),
+                severity: Severity::Error,
+                message: format!("{error:#}"),
+                file: file.to_string(),
+                line: None,
+                column: None,
+                span: None,
+                hint: None,
+                snippet: None,
+            },
+        }
+    }
+
+    pub fn warning(warning: Warning, message: String, file: &str, token: &Token) -> Self {
+        let span = Span::of(token);
+        Diagnostic {
+            code: code_for_warning(warning),
+            severity: Severity::Warning,
+            message,
+            file: file.to_string(),
+            line: Some(token.line),
+            column: Some(token.column),
+            span: Some((span.start as usize, span.end as usize)),
+            hint: None,
+            snippet: None,
+        }
+    }
+
+    /// Fills in the snippet from `source`, the script the diagnostic's span
+    /// points into.
+    pub fn locate(&mut self, source: &str) {
+        let Some((start, end)) = self.span else {
+            return;
+        };
+        let span = Span {
+            source_id: 0,
+            start: start as u32,
+            end: end as u32,
+        };
+        self.snippet = Snippet::new(source, span);
+    }
+
+    /// Renders the diagnostic for people, with the offending source line
+    /// and a caret under the problem when the snippet is known:
+    ///
+    /// ```text
+    /// error[E0002]: Expect ';' after value.
+    ///  --> script.lox:2:8
+    ///   |
+    /// 2 | print 1
+    ///   |        ^
+    /// ```
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "{}[{}]: {}\n",
+            self.severity.as_str(),
+            self.code,
+            self.message
+        );
+        let location = match (self.line, self.column) {
+            (Some(line), Some(column)) => format!("{}:{line}:{column}", self.file),
+            (Some(line), None) => format!("{}:{line}", self.file),
+            _ => self.file.clone(),
+        };
+        let (Some(line), Some(snippet)) = (self.line, &self.snippet) else {
+            let _ = writeln!(out, " --> {location}");
+            if let Some(hint) = &self.hint {
+                let _ = writeln!(out, "  = hint: {hint}");
+            }
+            return out;
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        let _ = writeln!(out, "{gutter}--> {location}");
+        let _ = writeln!(out, "{gutter} |");
+        let _ = writeln!(out, "{line} | {}", snippet.text);
+        let _ = writeln!(
+            out,
+            "{gutter} | {}{}",
+            " ".repeat(snippet.column as usize - 1),
+            "^".repeat(snippet.width as usize)
+        );
+        if let Some(hint) = &self.hint {
+            let _ = writeln!(out, "{gutter} = hint: {hint}");
+        }
+        out
+    }
+
+    /// Renders the diagnostic as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        let span = match self.span {
+            Some((start, end)) => format!("[{start},{end}]"),
+            None => "null".to_string(),
+        };
+        let hint = match &self.hint {
+            Some(hint) => json_string(hint),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"code\":{},\"severity\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{},\"hint\":{}}}",
+            json_string(self.code),
+            json_string(self.severity.as_str()),
+            json_string(&self.message),
+            json_string(&self.file),
+            json_option(self.line),
+            json_option(self.column),
+            span,
+            hint
+        )
+    }
+}
+
+/// Prints `error` to stderr in the requested format.
+pub fn report(error: &anyhow::Error, file: &str, format: ErrorFormat) {
+    // Nothing is left to tell about a failure to write to stderr.
+    let _ = write_report(&mut io::stderr(), error, file, format);
+}
+
+/// Writes `error` to `out` in the requested format, for hosts that collect
+/// errors somewhere other than stderr.
+pub fn write_report(
+    out: &mut dyn io::Write,
+    error: &anyhow::Error,
+    file: &str,
+    format: ErrorFormat,
+) -> io::Result<()> {
+    match format {
+        ErrorFormat::Human => writeln!(out, "{error:#}"),
+        ErrorFormat::Pretty => write!(out, "{}", Diagnostic::from_error(error, file).render()),
+        ErrorFormat::Json => writeln!(out, "{}", Diagnostic::from_error(error, file).to_json()),
+    }
+}
+
+/// Prints a warning to stderr in the requested format.
+pub fn report_warning(warning: &Diagnostic, format: ErrorFormat) {
+    let out: &mut dyn io::Write = &mut io::stderr();
+    // Nothing is left to tell about a failure to write to stderr.
+    let _ = match (format, warning.line) {
+        (ErrorFormat::Human, Some(line)) => {
+            writeln!(out, "[line {line}] Warning: {}", warning.message)
+        }
+        (ErrorFormat::Human, None) => writeln!(out, "Warning: {}", warning.message),
+        (ErrorFormat::Pretty, _) => write!(out, "{}", warning.render()),
+        (ErrorFormat::Json, _) => writeln!(out, "{}", warning.to_json()),
+    };
+}
+
+fn code_for(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Scan => "E0001",
+        ErrorKind::Parse => "E0002",
+        ErrorKind::Resolve => "E0003",
+        ErrorKind::Runtime => "E0004",
+        ErrorKind::Type => "E0005",
+    }
+}
+
+fn code_for_warning(warning: Warning) -> &'static str {
+    match warning {
+        Warning::Unused => "W0001",
+        Warning::Unreachable => "W0002",
+        Warning::Shadowed => "W0003",
+    }
+}
+
+fn json_option(value: Option<u32>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
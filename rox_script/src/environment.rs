@@ -1,14 +1,27 @@
+use crate::error::LoxError;
+use crate::memory::{self, charge, Charge};
+use crate::profile::{track, Allocation, Kind};
+use crate::symbol::Symbol;
 use crate::token::{DataType, Token};
-use anyhow::anyhow;
 use anyhow::Result;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::rc::Rc;
 
-#[derive(Debug, Clone)]
+/// A scope's variables. Scopes are only ever shared through
+/// `Rc<RefCell<Environment>>` and never copied: a closure keeps a handle to
+/// the scope it was declared in, so it reads and writes the same variables
+/// as that scope and as every other closure over it, even after the scope's
+/// block has finished running.
+#[derive(Debug)]
 pub struct Environment {
     pub parent_environment: Option<Rc<RefCell<Environment>>>,
-    values: HashMap<String, Option<DataType>>,
+    values: HashMap<Symbol, Option<DataType>>,
+    /// What `--explain` calls this scope, or 0 until it is first mentioned.
+    pub(crate) number: Cell<usize>,
+    _allocation: Option<Allocation>,
+    memory: Option<Charge>,
 }
 
 impl Environment {
@@ -16,6 +29,9 @@ impl Environment {
         Self {
             parent_environment: None,
             values: HashMap::new(),
+            number: Cell::new(0),
+            _allocation: track(Kind::Environment),
+            memory: charge(mem::size_of::<Environment>),
         }
     }
     pub fn new_with_parent_environment(parent_environment: Rc<RefCell<Environment>>) -> Self {
@@ -23,27 +39,106 @@ impl Environment {
         Self {
             parent_environment,
             values: HashMap::new(),
+            number: Cell::new(0),
+            _allocation: track(Kind::Environment),
+            memory: charge(mem::size_of::<Environment>),
         }
     }
-    pub fn define(&mut self, name: String, value: Option<DataType>) {
-        self.values.insert(name, value);
+    pub fn define(&mut self, name: impl Into<Symbol>, value: Option<DataType>) {
+        let added = slot_size(value.as_ref());
+        let previous = self.values.insert(name.into(), value);
+        self.resized(
+            previous
+                .as_ref()
+                .map(|previous| slot_size(previous.as_ref())),
+            added,
+        );
+    }
+
+    /// Updates the memory charged for a slot that held `removed` bytes, or
+    /// didn't exist, and now holds `added`.
+    fn resized(&self, removed: Option<usize>, added: usize) {
+        if let Some(charge) = &self.memory {
+            charge.resize(removed.unwrap_or(0), added);
+        }
     }
 
+    /// Looks `name` up in the nearest scope that declares it. A scope that
+    /// declares it hides the parents' even while it has no value yet, so a
+    /// global named like a native never reads the native.
     pub fn get(&self, name: &str) -> Option<DataType> {
-        if let Some(Some(value)) = self.values.get(name) {
-            Some(value.to_owned())
-        } else {
-            // check parent
-            match &self.parent_environment {
-                Some(parent_env) => parent_env.borrow().get(name),
-                None => None,
+        if let Some(slot) = self.values.get(name) {
+            return slot.clone();
+        }
+        match &self.parent_environment {
+            Some(parent_env) => parent_env.borrow().get(name),
+            None => None,
+        }
+    }
+
+    /// Whether `name` is defined directly in this environment, ignoring parents.
+    pub fn defines(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    /// Looks `name` up in the top-level scope of the module this environment
+    /// belongs to, which is the outermost one below the shared builtins.
+    pub fn get_global(&self, name: &str) -> Option<DataType> {
+        match &self.parent_environment {
+            Some(parent) if parent.borrow().parent_environment.is_some() => {
+                parent.borrow().get_global(name)
+            }
+            _ => self.get(name),
+        }
+    }
+
+    /// Assigns `name` in the top-level scope of the module this environment
+    /// belongs to. See [`Environment::get_global`].
+    pub fn assign_global(&mut self, name: &Token, value: DataType) -> Result<()> {
+        match &self.parent_environment {
+            Some(parent) if parent.borrow().parent_environment.is_some() => {
+                parent.borrow_mut().assign_global(name, value)
             }
+            _ => self.assign(name, value),
         }
     }
 
+    /// The values defined directly in this environment.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &DataType> {
+        self.values.values().flatten()
+    }
+
+    /// Drops every value and the parent, so the scope no longer keeps
+    /// anything alive.
+    pub(crate) fn clear(&mut self) {
+        self.values.clear();
+        self.parent_environment = None;
+    }
+
+    /// The variables defined directly in this environment that have been
+    /// given a value.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &DataType)> {
+        self.values
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str(), value.as_ref()?)))
+    }
+
+    /// Whether this is the top-level scope of a module, nested directly in
+    /// the builtins.
+    pub fn is_global(&self) -> bool {
+        self.parent_environment
+            .as_ref()
+            .is_some_and(|parent| parent.borrow().parent_environment.is_none())
+    }
+
+    /// The names defined directly in this environment.
+    pub fn names(&self) -> HashSet<Symbol> {
+        self.values.keys().cloned().collect()
+    }
+
     pub fn get_at(&self, distance: usize, name: &str) -> Option<DataType> {
         if distance == 0 {
-            self.values.get(&name.to_string()).unwrap().clone()
+            self.values.get(name).unwrap().clone()
         } else {
             self.parent_environment
                 .as_ref()
@@ -53,10 +148,13 @@ impl Environment {
         }
     }
 
-    pub fn assign(&mut self, name: String, value: Option<DataType>) -> Result<()> {
-        if let std::collections::hash_map::Entry::Occupied(mut e) = self.values.entry(name.clone())
-        {
-            e.insert(value);
+    /// Assigns `name` in the nearest scope that defines it, failing with an
+    /// undefined variable error at `name` if none does.
+    pub fn assign(&mut self, name: &Token, value: DataType) -> Result<()> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            let (removed, added) = (slot_size(slot.as_ref()), slot_size(Some(&value)));
+            *slot = Some(value);
+            self.resized(Some(removed), added);
             Ok(())
         } else if self.parent_environment.is_some() {
             self.parent_environment
@@ -66,13 +164,13 @@ impl Environment {
                 .assign(name, value)?;
             Ok(())
         } else {
-            Err(anyhow!("variable does not exist"))
+            Err(undefined_variable(name).into())
         }
     }
 
     pub fn assign_at(&mut self, distance: usize, name: &Token, value: DataType) -> Result<()> {
         if distance == 0 {
-            self.values.insert(name.lexeme.to_string(), Some(value));
+            self.define(name.lexeme.clone(), Some(value));
             Ok(())
         } else {
             self.parent_environment
@@ -83,3 +181,14 @@ impl Environment {
         }
     }
 }
+
+/// The runtime error for reading or assigning `name` when no scope defines
+/// it, reported at the variable's line.
+pub fn undefined_variable(name: &Token) -> LoxError {
+    LoxError::runtime(name, format!("Undefined variable '{}'.", name.lexeme))
+}
+
+/// Roughly what a variable holding `value` costs, for memory accounting.
+fn slot_size(value: Option<&DataType>) -> usize {
+    mem::size_of::<(Symbol, Option<DataType>)>() + value.map_or(0, memory::size_of)
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+use crate::token::{Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    Scan,
+    Parse,
+    Resolve,
+    Type,
+    Runtime,
+}
+
+/// The bytes of one source an error or warning points at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    /// Which script or module, numbered as [`crate::token::Token`]s are.
+    pub source_id: u32,
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Span {
+    pub fn of(token: &Token) -> Span {
+        Span {
+            source_id: token.source_id,
+            start: token.offset,
+            end: token.offset + token.lexeme.len() as u32,
+        }
+    }
+}
+
+/// The line of source a span starts on, for showing it under a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    /// The line's text, without its line break.
+    pub text: String,
+    /// Which character of the line the span starts at, counting from 1.
+    pub column: u32,
+    /// How many characters of the line the span covers, at least 1.
+    pub width: u32,
+}
+
+impl Snippet {
+    /// The snippet of `source` that `span` points into.
+    pub fn new(source: &str, span: Span) -> Option<Snippet> {
+        let start = span.start as usize;
+        let end = (span.end as usize).max(start);
+        if end > source.len() || !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+            return None;
+        }
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |newline| start + newline);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        let column = source[line_start..start].chars().count() as u32 + 1;
+        let width = source[start..end.min(line_end)].chars().count().max(1) as u32;
+        Some(Snippet {
+            text: text.to_string(),
+            column,
+            width,
+        })
+    }
+}
+
+/// An error raised while scanning, parsing, resolving, type checking or
+/// running a script, carrying the source line it was raised at.
+#[derive(Debug)]
+pub struct LoxError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub line: u32,
+    /// Which character of the line the offending text starts at, counting
+    /// from 1.
+    pub column: u32,
+    /// The lexeme of the offending token, if the error points at one.
+    pub lexeme: Option<String>,
+    /// The offending token or characters.
+    pub span: Option<Span>,
+    /// The source line `span` is on, once the error has passed through
+    /// whatever ran that source. See [`LoxError::locate`].
+    pub snippet: Option<Snippet>,
+    /// A suggestion for fixing the error.
+    pub hint: Option<String>,
+    /// The function calls a runtime error was raised in, innermost first,
+    /// ending with the top level of the script. Empty for errors raised
+    /// outside any function.
+    pub trace: Vec<Frame>,
+    /// Whether a `throw` statement raised the error. The value it threw
+    /// stays with the interpreter, for a `catch` to bind.
+    pub thrown: bool,
+}
+
+impl LoxError {
+    pub fn scan(line: u32, column: u32, span: Span, message: impl Into<String>) -> Self {
+        LoxError {
+            kind: ErrorKind::Scan,
+            message: message.into(),
+            line,
+            column,
+            lexeme: None,
+            span: Some(span),
+            snippet: None,
+            hint: None,
+            trace: vec![],
+            thrown: false,
+        }
+    }
+
+    pub fn parse(token: &Token, message: impl Into<String>) -> Self {
+        Self::at_token(ErrorKind::Parse, token, message)
+    }
+
+    pub fn resolve(token: &Token, message: impl Into<String>) -> Self {
+        Self::at_token(ErrorKind::Resolve, token, message)
+    }
+
+    pub fn type_error(token: &Token, message: impl Into<String>) -> Self {
+        Self::at_token(ErrorKind::Type, token, message)
+    }
+
+    pub fn runtime(token: &Token, message: impl Into<String>) -> Self {
+        Self::at_token(ErrorKind::Runtime, token, message)
+    }
+
+    /// The error a `throw` statement unwinds with.
+    pub fn thrown(token: &Token, message: impl Into<String>) -> Self {
+        LoxError {
+            thrown: true,
+            ..Self::at_token(ErrorKind::Runtime, token, message)
+        }
+    }
+
+    fn at_token(kind: ErrorKind, token: &Token, message: impl Into<String>) -> Self {
+        let lexeme = if token.token_type == TokenType::EOF {
+            None
+        } else {
+            Some(token.lexeme.to_string())
+        };
+        LoxError {
+            kind,
+            message: message.into(),
+            line: token.line,
+            column: token.column,
+            lexeme,
+            span: Some(Span::of(token)),
+            snippet: None,
+            hint: None,
+            trace: vec![],
+            thrown: false,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Fills in the snippet of `error` if it is a [`LoxError`] pointing into
+    /// `source`, the source numbered `source_id`.
+    pub fn locate(mut error: anyhow::Error, source: &str, source_id: u32) -> anyhow::Error {
+        if let Some(lox_error) = error.downcast_mut::<LoxError>() {
+            if let Some(span) = lox_error.span {
+                if span.source_id == source_id && lox_error.snippet.is_none() {
+                    lox_error.snippet = Snippet::new(source, span);
+                }
+            }
+        }
+        error
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (self.kind, &self.lexeme) {
+            (ErrorKind::Runtime, _) => {
+                write!(f, "{}\n[line {}]", self.message, self.line)?;
+                write_trace(f, &self.trace)
+            }
+            (ErrorKind::Scan, _) => write!(f, "[line {}] Error: {}", self.line, self.message),
+            (_, Some(lexeme)) => write!(
+                f,
+                "[line {}] Error at '{}': {}",
+                self.line, lexeme, self.message
+            ),
+            (_, None) => write!(f, "[line {}] Error at end: {}", self.line, self.message),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {}
+
+/// Writes `trace` a frame per line. A run of frames that are all the same,
+/// as deep recursion leaves, is written once with how often it repeats.
+fn write_trace(f: &mut Formatter<'_>, trace: &[Frame]) -> fmt::Result {
+    let mut frames = trace.iter().peekable();
+    while let Some(frame) = frames.next() {
+        write!(f, "\n  {frame}")?;
+        let mut repeats = 0;
+        while frames.next_if_eq(&frame).is_some() {
+            repeats += 1;
+        }
+        match repeats {
+            0 => {}
+            1 => write!(f, "\n  ... repeated 1 more time")?,
+            _ => write!(f, "\n  ... repeated {repeats} more times")?,
+        }
+    }
+    Ok(())
+}
+
+/// A function call in progress when a runtime error was raised, and the
+/// line it had reached.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// The function's name, or `<script>` for the top level of a script.
+    pub function: String,
+    /// The script the line is in, if it was read from a file.
+    pub file: Option<PathBuf>,
+    pub line: u32,
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "at {} ({}:{})", self.function, file.display(), self.line),
+            None => write!(f, "at {} (line {})", self.function, self.line),
+        }
+    }
+}
+
+/// What a script calling `exit(code)` fails with. It unwinds the whole
+/// script, past every `catch` and `finally`, and leaves what exiting means
+/// to the host: the command line front end exits with `code`.
+#[derive(Debug)]
+pub struct Exit {
+    pub code: i32,
+}
+
+impl Display for Exit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Exited with code {}.", self.code)
+    }
+}
+
+impl std::error::Error for Exit {}
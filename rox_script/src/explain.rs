@@ -0,0 +1,52 @@
+//! Narration of what the interpreter does, for learners following along
+//! with Crafting Interpreters. Once [`enable`] is called, the interpreter
+//! describes on stderr the scopes it creates, the scopes closures capture,
+//! the instances `this` is bound to and the cycles it frees, as it goes.
+//!
+//! Scopes are numbered the first time they are mentioned, so the numbers
+//! follow the order a reader meets them in rather than how many scopes the
+//! interpreter made along the way.
+
+use std::cell::Cell;
+
+use crate::environment::Environment;
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static NEXT_SCOPE: Cell<usize> = const { Cell::new(1) };
+}
+
+/// Starts narrating what the interpreter does on this thread from now on.
+pub fn enable() {
+    ENABLED.set(true);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.get()
+}
+
+/// Prints the narration `message` builds, if narration is enabled. The
+/// message is only built when it is printed.
+pub(crate) fn say(message: impl FnOnce() -> String) {
+    if enabled() {
+        eprintln!("[explain] {}", message());
+    }
+}
+
+/// How narration refers to `environment`: `the global scope` for the top
+/// level of a module, otherwise `scope #N`.
+pub(crate) fn scope(environment: &Environment) -> String {
+    if environment.is_global() {
+        return "the global scope".to_string();
+    }
+    let number = match environment.number.get() {
+        0 => {
+            let number = NEXT_SCOPE.get();
+            NEXT_SCOPE.set(number + 1);
+            environment.number.set(number);
+            number
+        }
+        number => number,
+    };
+    format!("scope #{number}")
+}
@@ -1,16 +1,84 @@
-use std::any::Any;
 use std::fmt::{Debug, Formatter};
 use std::rc::Rc;
 
+use anyhow::Result;
+
 use crate::token::{DataType, Token};
 use crate::visitor::ExprVisitor;
 
-pub trait Expr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType;
-    fn as_any(&self) -> &dyn Any;
-}
-
-impl Debug for dyn Expr {
+/// An expression. Each variant wraps the node its visitor method takes, so
+/// passes can either match on the variants or go through a visitor.
+pub enum Expr {
+    Literal(LiteralExpr),
+    Unary(UnaryExpr),
+    Binary(BinaryExpr),
+    Call(CallExpr),
+    Grouping(GroupingExpr),
+    Var(VarExpr),
+    Assign(AssignExpr),
+    Logical(LogicalExpr),
+    Get(GetExpr),
+    Set(SetExpr),
+    This(ThisExpr),
+    Super(SuperExpr),
+    Array(ArrayExpr),
+    Map(MapExpr),
+    Index(IndexExpr),
+    SetIndex(SetIndexExpr),
+    Try(TryExpr),
+}
+
+impl Expr {
+    pub fn accept(&self, visitor: &mut dyn ExprVisitor) -> Result<DataType> {
+        match self {
+            Expr::Literal(expr) => visitor.visit_literal_expr(expr),
+            Expr::Unary(expr) => visitor.visit_unary_expr(expr),
+            Expr::Binary(expr) => visitor.visit_binary_expr(expr),
+            Expr::Call(expr) => visitor.visit_call_expr(expr),
+            Expr::Grouping(expr) => visitor.visit_grouping_expr(expr),
+            Expr::Var(expr) => visitor.visit_var_expr(expr),
+            Expr::Assign(expr) => visitor.visit_assign_expr(expr),
+            Expr::Logical(expr) => visitor.visit_logical_expr(expr),
+            Expr::Get(expr) => visitor.visit_get_expr(expr),
+            Expr::Set(expr) => visitor.visit_set_expr(expr),
+            Expr::This(expr) => visitor.visit_this_expr(expr),
+            Expr::Super(expr) => visitor.visit_super_expr(expr),
+            Expr::Array(expr) => visitor.visit_array_expr(expr),
+            Expr::Map(expr) => visitor.visit_map_expr(expr),
+            Expr::Index(expr) => visitor.visit_index_expr(expr),
+            Expr::SetIndex(expr) => visitor.visit_set_index_expr(expr),
+            Expr::Try(expr) => visitor.visit_try_expr(expr),
+        }
+    }
+}
+
+impl Expr {
+    /// The leftmost token of the expression, if it has one. Literals keep
+    /// no token, so neither do expressions that start with one.
+    pub fn first_token(&self) -> Option<&Token> {
+        match self {
+            Expr::Literal(_) => None,
+            Expr::Unary(expr) => Some(&expr.operator),
+            Expr::Binary(expr) => expr.left.first_token(),
+            Expr::Call(expr) => expr.callee.first_token(),
+            Expr::Grouping(expr) => expr.expression.first_token(),
+            Expr::Var(expr) => Some(&expr.var_name),
+            Expr::Assign(expr) => Some(&expr.var_name),
+            Expr::Logical(expr) => expr.left.first_token(),
+            Expr::Get(expr) => expr.object.first_token(),
+            Expr::Set(expr) => expr.object.first_token(),
+            Expr::This(expr) => Some(&expr.keyword),
+            Expr::Super(expr) => Some(&expr.keyword),
+            Expr::Array(expr) => expr.elements.first()?.first_token(),
+            Expr::Map(expr) => Some(&expr.brace),
+            Expr::Index(expr) => expr.object.first_token(),
+            Expr::SetIndex(expr) => expr.object.first_token(),
+            Expr::Try(expr) => Some(&expr.keyword),
+        }
+    }
+}
+
+impl Debug for Expr {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "self")
     }
@@ -19,53 +87,20 @@ impl Debug for dyn Expr {
 pub struct LiteralExpr {
     pub value: Option<DataType>,
 }
-impl Expr for LiteralExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_literal_expr(self).unwrap()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-}
 
 pub struct UnaryExpr {
     pub operator: Token,
-    pub right: Rc<dyn Expr>,
-}
-impl Expr for UnaryExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_unary_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub right: Rc<Expr>,
 }
 
 pub struct BinaryExpr {
-    pub left: Rc<dyn Expr>,
+    pub left: Rc<Expr>,
     pub operator: Token,
-    pub right: Rc<dyn Expr>,
-}
-impl Expr for BinaryExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_binary_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub right: Rc<Expr>,
 }
 
 pub struct GroupingExpr {
-    pub expression: Rc<dyn Expr>,
-}
-impl Expr for GroupingExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_grouping_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub expression: Rc<Expr>,
 }
 
 pub struct VarExpr {
@@ -74,116 +109,72 @@ pub struct VarExpr {
     pub var_name: Token,
 }
 
-impl Expr for VarExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_var_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-}
-
 pub struct AssignExpr {
     pub var_name: Token,
-    pub var_value: Option<Rc<dyn Expr>>,
-}
-
-impl Expr for AssignExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_assign_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub var_value: Rc<Expr>,
 }
 
 pub struct LogicalExpr {
-    pub left: Rc<dyn Expr>,
+    pub left: Rc<Expr>,
     pub operator: Token,
-    pub right: Rc<dyn Expr>,
-}
-
-impl Expr for LogicalExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_logical_expr(self).unwrap()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub right: Rc<Expr>,
 }
 
 pub struct CallExpr {
-    pub callee: Rc<dyn Expr>,
+    pub callee: Rc<Expr>,
     pub paren: Token,
-    pub arguments: Vec<Rc<dyn Expr>>,
-}
-
-impl Expr for CallExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_call_expr(self).unwrap()
-    }
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub arguments: Vec<Rc<Expr>>,
 }
 
 pub struct GetExpr {
-    pub object: Rc<dyn Expr>,
+    pub object: Rc<Expr>,
     pub name: Token,
 }
 
-impl Expr for GetExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_get_expr(self).unwrap()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
-}
-
 pub struct SetExpr {
-    pub object: Rc<dyn Expr>,
+    pub object: Rc<Expr>,
     pub name: Token,
-    pub value: Rc<dyn Expr>,
+    pub value: Rc<Expr>,
 }
 
-impl Expr for SetExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_set_expr(self).unwrap()
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct ThisExpr {
+    pub keyword: Token,
 }
 
-pub struct ThisExpr {
+pub struct SuperExpr {
     pub keyword: Token,
+    pub method: Token,
 }
 
-impl Expr for ThisExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_this_expr(self).unwrap()
-    }
+pub struct ArrayExpr {
+    pub bracket: Token,
+    pub elements: Vec<Rc<Expr>>,
+}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct MapExpr {
+    pub brace: Token,
+    pub entries: Vec<(Rc<Expr>, Rc<Expr>)>,
 }
 
-pub struct SuperExpr {
-    pub keyword: Token,
-    pub method: Token,
+/// `object[index]`, reading an element of an array, map or string.
+pub struct IndexExpr {
+    pub object: Rc<Expr>,
+    pub bracket: Token,
+    pub index: Rc<Expr>,
 }
 
-impl Expr for SuperExpr {
-    fn accept(&self, visitor: &mut dyn ExprVisitor) -> DataType {
-        visitor.visit_super_expr(self).unwrap()
-    }
+/// `object[index] = value`, storing into an array or map.
+pub struct SetIndexExpr {
+    pub object: Rc<Expr>,
+    pub bracket: Token,
+    pub index: Rc<Expr>,
+    pub value: Rc<Expr>,
+}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+/// `try expression`, which evaluates to `{"ok": value}`, or to
+/// `{"error": error}` with what a `catch` clause would bind if evaluating
+/// the expression fails.
+pub struct TryExpr {
+    pub keyword: Token,
+    pub expression: Rc<Expr>,
 }
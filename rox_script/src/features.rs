@@ -0,0 +1,55 @@
+//! Language versions and the syntax each one added to the book's Lox. A
+//! script is parsed at [`LATEST`] unless `--lang-version=N`, or a `#lang N`
+//! line at its very top, pins an older version, and syntax newer than that
+//! is a parse error. That way a script keeps meaning what it meant when it
+//! was written, however the language has grown since.
+
+pub type LangVersion = u32;
+
+/// The version of the book's Lox, with none of the features below.
+pub const FIRST: LangVersion = 1;
+pub const LATEST: LangVersion = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Feature {
+    /// `throw` and `try`/`catch`/`finally`.
+    Exceptions,
+    /// `import` and `export`.
+    Modules,
+    Assertions,
+    Switch,
+    /// Array and map literals and indexing.
+    Collections,
+    ForIn,
+    TypeAnnotations,
+    TryExpressions,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Exceptions => "exceptions",
+            Feature::Modules => "modules",
+            Feature::Assertions => "assert",
+            Feature::Switch => "switch",
+            Feature::Collections => "arrays and maps",
+            Feature::ForIn => "for-in loops",
+            Feature::TypeAnnotations => "type annotations",
+            Feature::TryExpressions => "try expressions",
+        }
+    }
+
+    /// The first version with the feature.
+    pub fn since(self) -> LangVersion {
+        match self {
+            Feature::Exceptions | Feature::Modules | Feature::Assertions | Feature::Switch => 2,
+            Feature::Collections | Feature::ForIn | Feature::TypeAnnotations => 3,
+            Feature::TryExpressions => 4,
+        }
+    }
+}
+
+/// Whether scripts can be written for `version`.
+pub fn is_known(version: LangVersion) -> bool {
+    (FIRST..=LATEST).contains(&version)
+}
@@ -1,14 +1,19 @@
 use crate::class::LoxInstance;
+use crate::cycles;
 use crate::environment::Environment;
+use crate::explain;
 use crate::interpreter::Interpreter;
+use crate::profile;
+use crate::profile::{track_shared, Allocation};
 use crate::stmt::{FunctionStmt, Stmt};
+use crate::timing;
 use crate::token::{DataType, Token};
+use crate::types::{type_name, Type};
 use anyhow::anyhow;
 use std::cell::RefCell;
 use std::fmt;
 use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
-use std::time::SystemTime;
 
 pub trait LoxCallable: Debug + Display {
     fn arity(&self) -> usize;
@@ -26,11 +31,16 @@ pub enum Kind {
 
 #[derive(Clone)]
 pub struct LoxFunction {
-    pub body: Rc<Vec<Rc<dyn Stmt>>>,
+    pub body: Rc<Vec<Rc<Stmt>>>,
     pub params: Rc<Vec<Token>>,
+    param_types: Rc<Vec<Option<Type>>>,
+    return_type: Option<Type>,
     name: Box<Token>,
+    /// The scope the function was declared in, shared rather than copied so
+    /// captured variables stay live.
     closure: Rc<RefCell<Environment>>,
     is_init: bool,
+    _allocation: Option<Rc<Allocation>>,
 }
 
 impl LoxFunction {
@@ -39,12 +49,23 @@ impl LoxFunction {
         closure: &Rc<RefCell<Environment>>,
         is_init: bool,
     ) -> LoxFunction {
+        cycles::captured(closure);
+        explain::say(|| {
+            format!(
+                "capturing {} in closure {}",
+                explain::scope(&closure.borrow()),
+                declaration.name.lexeme
+            )
+        });
         LoxFunction {
-            body: Rc::new(declaration.body.clone()),
-            params: Rc::new(declaration.params.clone()),
+            body: Rc::clone(&declaration.body),
+            params: Rc::clone(&declaration.params),
+            param_types: Rc::clone(&declaration.param_types),
+            return_type: declaration.return_type.clone(),
             name: Box::new(declaration.name.clone()),
             closure: Rc::clone(closure),
             is_init,
+            _allocation: track_shared(profile::Kind::Function),
         }
     }
 
@@ -52,120 +73,245 @@ impl LoxFunction {
         let env = RefCell::new(Environment::new_with_parent_environment(Rc::clone(
             &self.closure,
         )));
+        explain::say(|| {
+            format!(
+                "binding this to instance of {} in {} for method {}",
+                instance.class().name,
+                explain::scope(&env.borrow()),
+                self.name.lexeme
+            )
+        });
         env.borrow_mut()
-            .define("this".to_string(), Some(DataType::Instance(instance)));
+            .define("this", Some(DataType::Instance(instance)));
+        let closure = Rc::new(env);
+        cycles::captured(&closure);
         LoxFunction {
             body: Rc::clone(&self.body),
             params: Rc::clone(&self.params),
+            param_types: Rc::clone(&self.param_types),
+            return_type: self.return_type.clone(),
             name: self.name.clone(),
-            closure: Rc::new(env),
+            closure,
             is_init: self.is_init,
+            _allocation: track_shared(profile::Kind::Function),
         }
     }
-}
 
-impl Display for LoxFunction {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "<Function {}>", self.name.lexeme)
+    pub(crate) fn closure(&self) -> &Rc<RefCell<Environment>> {
+        &self.closure
     }
-}
 
-impl Debug for LoxFunction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let value = format!("<Function {}>", self.name.lexeme);
-        f.debug_struct("LoxFunction")
-            .field("name:", &value)
-            .finish()
+    /// Binds an already bound method to a different instance. The new `this`
+    /// replaces the old one instead of shadowing it, so the method body still
+    /// resolves its variables at the same depths. Returns `None` for functions
+    /// that were never bound to an instance.
+    pub fn rebind(&self, instance: LoxInstance) -> Option<LoxFunction> {
+        if !self.closure.borrow().defines("this") {
+            return None;
+        }
+        let method_closure = self.closure.borrow().parent_environment.clone()?;
+        let unbound = LoxFunction {
+            closure: method_closure,
+            ..self.clone()
+        };
+        Some(unbound.bind(instance))
     }
-}
 
-impl LoxCallable for LoxFunction {
-    fn arity(&self) -> usize {
-        self.params.len()
+    /// Runs the function, then any calls it returned in tail position. Each
+    /// of those replaces the frame that made it instead of nesting inside
+    /// it, so tail-recursive functions run in constant stack space.
+    fn run_with_tail_calls(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let mut returned = self.run(interpreter, arguments)?;
+        while let Some((function, arguments)) = interpreter.take_tail_call() {
+            interpreter.enter_tail_call(&function.name);
+            returned = function.run(interpreter, arguments)?;
+        }
+        Ok(returned)
     }
 
-    fn call(
+    /// Runs the body once with `arguments` bound to the parameters.
+    fn run(
         &self,
         interpreter: &mut Interpreter,
         arguments: Vec<DataType>,
     ) -> anyhow::Result<DataType> {
+        let _timed = timing::start(|| format!("{self} (line {})", self.name.line));
+        if interpreter.type_checks() {
+            self.check_arguments(&arguments)?;
+        }
         let mut environment = Environment::new_with_parent_environment(Rc::clone(&self.closure));
         for (i, token) in self.params.iter().enumerate() {
             let value = match arguments.get(i) {
                 Some(d) => d.clone(),
                 None => DataType::Nil,
             };
-            environment.define(token.lexeme.to_string(), Some(value));
+            environment.define(token.lexeme.clone(), Some(value));
         }
-        let statements = self.clone().body;
-
-        match interpreter.execute_block(&statements, environment) {
-            Ok(value) => {
-                if self.is_init {
-                    return self
-                        .closure
-                        .borrow()
-                        .get_at(0, "this")
-                        .ok_or(anyhow!("cannot find this"));
-                }
-                Ok(value)
-            }
-            Err(err) => {
-                if self.is_init {
-                    return self
-                        .closure
-                        .borrow()
-                        .get_at(0, "this")
-                        .ok_or(anyhow!("cannot find this"));
+        explain::say(|| {
+            format!(
+                "calling {}, creating {} for its parameters",
+                self.name.lexeme,
+                explain::scope(&environment)
+            )
+        });
+        let returned = interpreter.execute_block(&self.body, environment);
+        explain::say(|| format!("returning from {}", self.name.lexeme));
+        let returned = returned?;
+
+        // An initializer always hands back the instance it was bound to, both
+        // when the class is called and when `init` is called directly.
+        let returned = if self.is_init {
+            self.closure
+                .borrow()
+                .get_at(0, "this")
+                .ok_or(anyhow!("cannot find this"))?
+        } else {
+            returned.unwrap_or(DataType::Nil)
+        };
+        if interpreter.type_checks() {
+            self.check_return(&returned)?;
+        }
+        Ok(returned)
+    }
+
+    fn check_arguments(&self, arguments: &[DataType]) -> anyhow::Result<()> {
+        for ((param, expected), argument) in self
+            .params
+            .iter()
+            .zip(self.param_types.iter())
+            .zip(arguments)
+        {
+            match expected {
+                Some(expected) if !expected.accepts(argument) => {
+                    return Err(anyhow!(
+                        "Argument '{}' of {} must be {}, got {}.",
+                        param.lexeme,
+                        self,
+                        expected,
+                        type_name(argument)
+                    ));
                 }
-                Err(err)
+                _ => {}
             }
         }
+        Ok(())
+    }
+
+    fn check_return(&self, returned: &DataType) -> anyhow::Result<()> {
+        match &self.return_type {
+            Some(expected) if !expected.accepts(returned) => Err(anyhow!(
+                "{} must return {}, got {}.",
+                self,
+                expected,
+                type_name(returned)
+            )),
+            _ => Ok(()),
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct LoxNative {
-    pub function: Rc<dyn LoxCallable>,
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
 }
-impl fmt::Display for LoxNative {
+
+impl Debug for LoxFunction {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}", self.function)
+        Display::fmt(self, f)
     }
 }
 
-#[derive(Debug)]
-pub struct Clock {
-    name: String,
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        interpreter.enter_call(&self.name)?;
+        let returned = self.run_with_tail_calls(interpreter, arguments);
+        interpreter.leave_call(returned)
+    }
 }
 
-impl Clock {
-    pub fn new(name: String) -> Clock {
-        Clock { name }
+/// The callable behind `value`, if it is a function, class or native.
+pub fn as_callable(value: &DataType) -> Option<Rc<dyn LoxCallable>> {
+    match value {
+        DataType::Function(function) => Some(Rc::new(function.clone())),
+        DataType::Class(class) => Some(Rc::new(class.clone())),
+        DataType::NativeFunction(native) => Some(Rc::clone(&native.function)),
+        _ => None,
     }
 }
 
-impl LoxCallable for Clock {
-    fn arity(&self) -> usize {
-        0
+/// A handle to a script function, class or native that the host can keep
+/// and call later, such as a callback a script handed to a native.
+#[derive(Clone)]
+pub struct Callable {
+    function: Rc<dyn LoxCallable>,
+}
+
+impl Callable {
+    /// The handle for `value`, if it can be called.
+    pub fn from_value(value: &DataType) -> Option<Callable> {
+        as_callable(value).map(|function| Callable { function })
     }
 
-    fn call(
+    pub fn arity(&self) -> usize {
+        self.function.arity()
+    }
+
+    /// Calls the function with `arguments`, which must number its arity.
+    pub fn call(
         &self,
-        _: &mut Interpreter,
-        _: Vec<crate::token::DataType>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
     ) -> anyhow::Result<DataType> {
-        Ok(
-            match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-                Ok(n) => DataType::Number(n.as_millis() as f64),
-                Err(_) => DataType::Nil,
-            },
-        )
+        if self.arity() != arguments.len() {
+            return Err(anyhow!(
+                "Expected {} arguments but got {} when calling {}.",
+                self.arity(),
+                arguments.len(),
+                self.function
+            ));
+        }
+        let _metered = interpreter.enter_meter();
+        self.function.call(interpreter, arguments)
+    }
+}
+
+impl fmt::Display for Callable {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+impl Debug for Callable {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+#[derive(Clone)]
+pub struct LoxNative {
+    pub function: Rc<dyn LoxCallable>,
+}
+impl fmt::Display for LoxNative {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.function)
     }
 }
 
-impl Display for Clock {
+impl Debug for LoxNative {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "<Native-Function {}>", self.name)
+        Display::fmt(self, f)
     }
 }
@@ -1,89 +1,920 @@
-use crate::class::LoxClass;
-use crate::environment::Environment;
+use crate::class::{LoxClass, LoxInstance};
+use crate::collections::{LoxArray, LoxMap, MapKey};
+use crate::cycles;
+use crate::debugger::Debugger;
+use crate::diagnostics::Diagnostic;
+use crate::environment::{undefined_variable, Environment};
+use crate::error::{ErrorKind, Exit, Frame, LoxError};
+use crate::explain;
 use crate::expr::{
-    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, LiteralExpr, LogicalExpr,
-    SetExpr, SuperExpr, ThisExpr, UnaryExpr, VarExpr,
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
 };
-use crate::functions::{Clock, LoxCallable, LoxFunction, LoxNative};
+use crate::features::{self, LangVersion};
+use crate::functions::{as_callable, Callable, LoxCallable, LoxFunction, LoxNative};
+use crate::limits::Limits;
+use crate::memory::{Entered, Meter};
+use crate::module::LoxModule;
+use crate::natives::{define_natives, HostFunction};
+use crate::optimize::optimize;
+use crate::parser::Parser;
+use crate::program::Program;
+use crate::random::Random;
+use crate::resolver::Resolver;
+use crate::sandbox::RoxSandbox;
+use crate::scanner;
+use crate::source::{import_path, SourceProvider};
+use crate::stack;
+use crate::stdlib::native_module;
 use crate::stmt::{
-    BlockStmt, ClassStmt, ExprStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, VarStmt,
-    WhileStmt,
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
 };
+use crate::symbol::Symbol;
+use crate::time;
+use crate::time::Instant;
+use crate::timing;
 use crate::token::TokenType::OR;
-use crate::token::{DataType, Token, TokenType};
+use crate::token::{DataType, Token, TokenId, TokenType};
+use crate::trace;
+use crate::types::type_name;
 use crate::visitor::{ExprVisitor, StmtVisitor};
+use crate::worker;
+use crate::worker::{Channel, Message};
 use anyhow::{anyhow, Result};
+use rox_frontend::value::LoxValue;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long `sleep` and `receive` wait between checks for cancellation, so
+/// they wake soon after the host cancels the script.
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+/// A callback registered with `setTimeout`, waiting for `runPending`.
+struct Timer {
+    id: usize,
+    /// When the timer falls due, as [`Interpreter::elapsed`] measures it.
+    due: Duration,
+    callback: Rc<dyn LoxCallable>,
+}
 
 pub struct Interpreter {
-    pub globals: Rc<RefCell<Environment>>,
+    /// The native functions every module can see. The main script and each
+    /// imported module get their own top-level scope nested directly in it.
+    builtins: Rc<RefCell<Environment>>,
     pub environment: RefCell<Rc<RefCell<Environment>>>,
-    pub locals: RefCell<HashMap<String, usize>>,
+    pub locals: RefCell<HashMap<TokenId, usize>>,
+    pub limits: Limits,
+    /// The capabilities scripts may use, passed on to the workers they spawn.
+    sandbox: RoxSandbox,
+    /// The value of the `throw` statement currently unwinding, if any. The
+    /// error itself only carries a message and a mark that it was thrown,
+    /// so a `catch` picks the value up from here.
+    thrown: Option<DataType>,
+    sources: Box<dyn SourceProvider>,
+    /// The script currently running, which relative imports are resolved against.
+    current_file: Option<PathBuf>,
+    /// Every module already imported, so each one runs at most once.
+    imported: HashMap<PathBuf, LoxModule>,
+    /// Names exported by the module currently running.
+    exports: Rc<RefCell<HashSet<Symbol>>>,
+    /// Incremented for every source run, to tag its tokens.
+    next_source_id: u32,
+    /// A call in tail position waiting to be made by the function returning
+    /// from it, so the returning function's frame is gone by the time it runs.
+    tail_call: Option<(LoxFunction, Vec<DataType>)>,
+    /// The function calls in progress, innermost last, each with the line
+    /// it has reached, for the stack traces of runtime errors.
+    frames: Vec<CallFrame>,
+    /// The line the top level of the running script has reached.
+    top_level: CallFrame,
+    /// The file each source run so far was read from, by source id.
+    files: HashMap<u32, PathBuf>,
+    /// Set by the host to stop the script. Checked before every statement.
+    cancelled: Arc<AtomicBool>,
+    /// Whether a run is in progress, so runs of imported modules count
+    /// towards the limits of the run importing them.
+    running: bool,
+    /// When the current run exceeds `limits.max_run_time`, if it has one.
+    deadline: Option<Instant>,
+    /// How many statements the current run has executed.
+    steps: u64,
+    /// What the script's values are charged to, when memory is limited.
+    memory: Option<Meter>,
+    /// The callbacks registered with `setTimeout` that haven't run yet.
+    timers: Vec<Timer>,
+    next_timer_id: usize,
+    /// The listeners registered with `on`, by event name.
+    listeners: HashMap<String, Vec<Rc<dyn LoxCallable>>>,
+    /// Whether calls check their arguments and return values against the
+    /// function's type annotations.
+    type_checks: bool,
+    /// Whether reading or assigning a global nothing declares is a resolve
+    /// error rather than a runtime one.
+    strict: bool,
+    /// Whether scripts go through the optimizer before they are resolved.
+    optimize: bool,
+    /// The lang version scripts without a `#lang` directive are parsed at.
+    lang_version: LangVersion,
+    /// What the resolver warned about in the scripts run so far.
+    warnings: Vec<Diagnostic>,
+    /// Globals the host promises to define before they are used, with the
+    /// arity of the ones that are functions.
+    externals: HashMap<String, Option<usize>>,
+    /// What `args()` returns: the command line arguments after the script's
+    /// path, or whatever the host sets.
+    args: Vec<String>,
+    /// Where `print` and `std/io` write, stdout unless the host redirects it.
+    output: Box<dyn Write>,
+    /// The connections to the workers this interpreter spawned, by id, and
+    /// to its spawner under id 0 if it is a worker itself.
+    workers: HashMap<usize, Channel>,
+    /// What runs when the interpreter shuts down, in the order registered.
+    shutdown_hooks: Vec<ShutdownHook>,
+    /// When the interpreter was created, which timers are measured from.
+    started: Instant,
+    /// In a deterministic run, how much logical time has passed. It only
+    /// moves when the script reads the clock or sleeps.
+    virtual_time: Option<Duration>,
+    random: Random,
+    /// The debugger every statement goes through first, when debugging.
+    debugger: Option<Debugger>,
+}
+
+type ShutdownHook = Box<dyn FnOnce(&mut Interpreter) -> Result<()>>;
+
+struct CallFrame {
+    function: Symbol,
+    source_id: u32,
+    line: u32,
+}
+
+/// Scripts and imports come from the filesystem when there is one, and
+/// otherwise from nowhere until the host sets a source provider.
+fn default_sources(sandbox: &RoxSandbox) -> Box<dyn SourceProvider> {
+    #[cfg(feature = "fs")]
+    if sandbox.allows(crate::sandbox::Capability::Fs) {
+        return Box::new(crate::source::FileSystem);
+    }
+    #[cfg(not(feature = "fs"))]
+    let _ = sandbox;
+    Box::new(crate::source::InMemory::new())
+}
+
+/// The operands of an arithmetic or comparison operator, which must both be
+/// numbers.
+fn numbers(operator: &Token, left: &DataType, right: &DataType) -> Result<(f64, f64)> {
+    match (left, right) {
+        (DataType::Number(l), DataType::Number(r)) => Ok((*l, *r)),
+        _ => Err(operand_error(operator, "numbers", left, right)),
+    }
+}
+
+/// The error for applying `operator` to operands of the wrong types, naming
+/// what it takes and what it got.
+fn operand_error(
+    operator: &Token,
+    expected: &str,
+    left: &DataType,
+    right: &DataType,
+) -> anyhow::Error {
+    LoxError::runtime(
+        operator,
+        format!(
+            "Operands of '{}' must be {expected}, got {} and {}.",
+            operator.lexeme,
+            type_name(left),
+            type_name(right)
+        ),
+    )
+    .into()
 }
 
 impl Interpreter {
-    pub fn new() -> Self {
-        let globals = Rc::new(RefCell::new(Environment::new()));
+    pub fn new(limits: Limits) -> Self {
+        Interpreter::with_sandbox(limits, RoxSandbox::new())
+    }
 
-        let clock = DataType::NativeFunction(LoxNative {
-            function: Rc::new(Clock::new("Clock".to_string())),
-        });
-        globals
-            .borrow_mut()
-            .define("clock".to_string(), Some(clock));
+    /// An interpreter whose scripts can only use the capabilities `sandbox`
+    /// allows. Natives needing the others are never defined.
+    pub fn with_sandbox(limits: Limits, sandbox: RoxSandbox) -> Self {
+        let memory = limits.max_memory.map(|_| Meter::default());
+        let _metered = memory.as_ref().map(Meter::enter);
+        let builtins = Rc::new(RefCell::new(Environment::new()));
+        define_natives(&mut builtins.borrow_mut(), &sandbox);
+        let globals = Rc::new(RefCell::new(Environment::new_with_parent_environment(
+            Rc::clone(&builtins),
+        )));
 
         Self {
-            globals: Rc::clone(&globals),
-            environment: RefCell::new(Rc::clone(&globals)),
+            builtins,
+            environment: RefCell::new(globals),
             locals: RefCell::new(HashMap::new()),
+            limits,
+            sandbox,
+            thrown: None,
+            sources: default_sources(&sandbox),
+            current_file: None,
+            imported: HashMap::new(),
+            exports: Rc::new(RefCell::new(HashSet::new())),
+            next_source_id: 0,
+            tail_call: None,
+            frames: vec![],
+            top_level: CallFrame {
+                function: Symbol::from("<script>"),
+                source_id: 0,
+                line: 0,
+            },
+            files: HashMap::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            running: false,
+            deadline: None,
+            steps: 0,
+            memory,
+            timers: vec![],
+            next_timer_id: 0,
+            listeners: HashMap::new(),
+            type_checks: true,
+            strict: false,
+            optimize: false,
+            lang_version: features::LATEST,
+            warnings: vec![],
+            externals: HashMap::new(),
+            args: vec![],
+            output: Box::new(io::stdout()),
+            workers: HashMap::new(),
+            shutdown_hooks: vec![],
+            started: Instant::now(),
+            virtual_time: None,
+            random: Random::from_time(),
+            debugger: None,
         }
     }
 
-    pub fn interpret(&mut self, statements: Vec<Rc<dyn Stmt>>) -> Result<()> {
-        for statement in statements {
-            self.execute(statement)?;
+    /// Hands the interpreter a flag the host can set, from any thread, to
+    /// stop the running script. The next statement to run then fails with
+    /// an `Execution cancelled.` runtime error, which keeps failing any
+    /// statement the script tries to run after catching it.
+    pub fn set_cancellation_flag(&mut self, cancelled: Arc<AtomicBool>) {
+        self.cancelled = cancelled;
+    }
+
+    /// Fails with the error that stops the script if the host has cancelled
+    /// it or it has run out of time.
+    pub fn check_interrupted(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(anyhow!("Execution cancelled."));
+        }
+        if let (Some(deadline), Some(max)) = (self.deadline, self.limits.max_run_time) {
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "Execution time limit of {} ms exceeded.",
+                    max.as_millis()
+                ));
+            }
         }
         Ok(())
     }
 
+    /// Makes runs reproducible when given a seed: `random()` gives the same
+    /// numbers on every run with the same seed, time only passes when the
+    /// script reads `clock()` or sleeps, and natives whose results depend on
+    /// thread scheduling, like `spawnWorker`, fail. Without a seed, random
+    /// numbers and time are real again.
+    pub fn set_deterministic(&mut self, seed: Option<u64>) {
+        match seed {
+            Some(seed) => {
+                self.random = Random::new(seed);
+                self.virtual_time = Some(Duration::ZERO);
+            }
+            None => {
+                self.random = Random::from_time();
+                self.virtual_time = None;
+            }
+        }
+    }
+
+    pub fn deterministic(&self) -> bool {
+        self.virtual_time.is_some()
+    }
+
+    /// How much time has passed since the interpreter was created, or in a
+    /// deterministic run how much logical time.
+    pub fn elapsed(&self) -> Duration {
+        self.virtual_time.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// What `clock()` returns: milliseconds since the Unix epoch, or in a
+    /// deterministic run a logical clock that starts at 0 and ticks one
+    /// millisecond every time it is read.
+    pub fn clock(&mut self) -> f64 {
+        if let Some(time) = &mut self.virtual_time {
+            *time += Duration::from_millis(1);
+            return time.as_millis() as f64;
+        }
+        time::since_epoch().as_millis() as f64
+    }
+
+    /// The next number from the generator behind `random()`, at least 0
+    /// and less than 1.
+    pub fn random(&mut self) -> f64 {
+        self.random.next_f64()
+    }
+
+    /// Blocks for `duration`, waking early with the same error a statement
+    /// would fail with if the script is cancelled or runs out of time. In a
+    /// deterministic run it moves the logical clock on instead.
+    pub fn sleep(&mut self, duration: Duration) -> Result<()> {
+        self.check_interrupted()?;
+        if let Some(time) = &mut self.virtual_time {
+            *time += duration;
+            return Ok(());
+        }
+        let wake = Instant::now() + duration;
+        loop {
+            self.check_interrupted()?;
+            let now = Instant::now();
+            if now >= wake {
+                return Ok(());
+            }
+            time::sleep((wake - now).min(SLEEP_SLICE));
+        }
+    }
+
+    /// Registers `callback`, a function taking no arguments, to be called by
+    /// [`Interpreter::run_pending`] once `delay` has passed. Returns an id
+    /// for [`Interpreter::clear_timeout`].
+    pub fn set_timeout(&mut self, callback: Rc<dyn LoxCallable>, delay: Duration) -> usize {
+        self.next_timer_id += 1;
+        self.timers.push(Timer {
+            id: self.next_timer_id,
+            due: self.elapsed() + delay,
+            callback,
+        });
+        self.next_timer_id
+    }
+
+    /// Drops the timer `id` before it runs. Returns whether it was pending.
+    pub fn clear_timeout(&mut self, id: usize) -> bool {
+        let pending = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() < pending
+    }
+
+    /// Runs pending timers in the order they fall due, sleeping until each
+    /// one does, until none are left, and returns how many ran. Timers the
+    /// callbacks register run too. An error from a callback, or the script
+    /// being cancelled or running out of time while waiting, stops the
+    /// draining and is returned.
+    pub fn run_pending(&mut self) -> Result<usize> {
+        let _metered = self.enter_meter();
+        let mut ran = 0;
+        while let Some(next) = self
+            .timers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timer)| (timer.due, timer.id))
+            .map(|(index, _)| index)
+        {
+            let timer = self.timers.remove(next);
+            self.sleep(timer.due.saturating_sub(self.elapsed()))?;
+            timer.callback.call(self, vec![])?;
+            ran += 1;
+        }
+        Ok(ran)
+    }
+
+    /// Replaces where scripts and imports are read from, the local
+    /// filesystem by default.
+    pub fn set_source_provider(&mut self, sources: Box<dyn SourceProvider>) {
+        self.sources = sources;
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Sends what scripts print to `output` instead of stdout, so a host
+    /// can capture or redirect it.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    /// Where scripts' output goes, for natives that write to it.
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+
+    /// Defines a native function visible to the main script and every
+    /// module, which scripts can shadow like any other global.
+    pub fn define_native(&mut self, name: &str, function: Rc<dyn LoxCallable>) {
+        let native = DataType::NativeFunction(LoxNative { function });
+        self.builtins.borrow_mut().define(name, Some(native));
+    }
+
+    /// Defines a native function implemented by a Rust closure, without a
+    /// [`LoxCallable`] of its own. Calls with a number of arguments other
+    /// than `arity` fail before reaching it, and errors it returns become
+    /// runtime errors at the call.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[DataType]) -> Result<DataType> + 'static,
+    ) {
+        let native = HostFunction::new(name.to_string(), arity, function);
+        self.define_native(name, Rc::new(native));
+    }
+
+    /// Turns strict mode on or off. In strict mode a script that reads or
+    /// assigns a global that neither it, an earlier run, the builtins nor
+    /// [`Interpreter::declare_external`] declares fails to resolve.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Turns the optimizer on or off. When on, scripts and the modules they
+    /// import have literal arithmetic folded and branches that can't run
+    /// dropped before they are resolved.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    pub fn optimize(&self) -> bool {
+        self.optimize
+    }
+
+    /// Sets the lang version scripts and modules are parsed at unless they
+    /// start with a `#lang` directive of their own.
+    pub fn set_lang_version(&mut self, version: LangVersion) {
+        self.lang_version = version;
+    }
+
+    pub fn lang_version(&self) -> LangVersion {
+        self.lang_version
+    }
+
+    /// Registers `hook` to run when the interpreter shuts down: when a
+    /// script calls `exit`, or when the interpreter is dropped. Hooks run
+    /// once each, the most recently registered first, while the script's
+    /// globals are still there.
+    pub fn on_shutdown(&mut self, hook: impl FnOnce(&mut Interpreter) -> Result<()> + 'static) {
+        self.shutdown_hooks.push(Box::new(hook));
+    }
+
+    /// Runs the shutdown hooks registered so far. Every hook runs even if
+    /// an earlier one fails, and the first failure is returned. Dropping
+    /// the interpreter shuts it down too, but has to ignore failures.
+    pub fn shutdown(&mut self) -> Result<()> {
+        let _metered = self.enter_meter();
+        let mut result = Ok(());
+        while let Some(hook) = self.shutdown_hooks.pop() {
+            let ran = hook(self);
+            if result.is_ok() {
+                result = ran;
+            }
+        }
+        result
+    }
+
+    /// Runs every statement past `debugger` first, which can stop the
+    /// script to inspect it. Only sources run from then on can be stepped
+    /// through.
+    pub fn set_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Hands over the warnings about the scripts and modules run since the
+    /// last call: unused locals, unreachable code and shadowed names.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// The path of the script or module being run, if it came from a file.
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current_file.as_deref()
+    }
+
+    /// Declares a global the host will define at runtime, after scripts
+    /// using it have been resolved. Strict mode then accepts it, and when it
+    /// has an `arity` calls to it with a different number of arguments fail
+    /// to resolve.
+    pub fn declare_external(&mut self, name: &str, arity: Option<usize>) {
+        self.externals.insert(name.to_string(), arity);
+    }
+
+    /// The externals declared so far, with their arities.
+    pub fn externals(&self) -> &HashMap<String, Option<usize>> {
+        &self.externals
+    }
+
+    /// Whether `name` is already defined as a global or builtin, by an
+    /// earlier run or by the host.
+    pub fn defines_global(&self, name: &str) -> bool {
+        self.environment.borrow().borrow().defines(name) || self.builtins.borrow().defines(name)
+    }
+
+    /// The names of every global and builtin defined so far.
+    pub fn global_names(&self) -> HashSet<Symbol> {
+        let mut names = self.environment.borrow().borrow().names();
+        names.extend(self.builtins.borrow().names());
+        names
+    }
+
+    /// Starts a worker running `source` and returns its id.
+    pub fn spawn_worker(&mut self, source: String) -> Result<usize> {
+        let id = self.workers.keys().max().map_or(1, |id| id + 1);
+        self.workers.insert(
+            id,
+            worker::spawn_sandboxed(source, self.limits, self.sandbox)?,
+        );
+        Ok(id)
+    }
+
+    /// Waits for the next message from `channel` the way `sleep` waits,
+    /// waking early if the script is cancelled or runs out of time.
+    pub fn receive(&self, channel: &Channel) -> Result<Option<Message>> {
+        channel.receive_interruptibly(SLEEP_SLICE, || self.check_interrupted())
+    }
+
+    /// Makes this interpreter a worker, with `parent` as the connection to
+    /// whoever spawned it. Scripts refer to it as the global `parent`.
+    pub fn connect_parent(&mut self, parent: Channel) {
+        self.workers.insert(0, parent);
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define("parent".to_string(), Some(DataType::Number(0.0)));
+    }
+
+    pub fn worker(&self, id: usize) -> Option<&Channel> {
+        self.workers.get(&id)
+    }
+
+    /// The variables of the top-level scope of the module that is running.
+    pub fn globals(&self) -> BTreeMap<String, DataType> {
+        let mut scope = self.environment.borrow().clone();
+        while !scope.borrow().is_global() {
+            let Some(parent) = scope.borrow().parent_environment.clone() else {
+                break;
+            };
+            scope = parent;
+        }
+        let scope = scope.borrow();
+        scope
+            .variables()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    /// The variables visible from the code that is running other than its
+    /// module's globals, innermost first so shadowing ones win. At the top
+    /// level of a module, where there are none, the globals.
+    pub fn locals(&self) -> BTreeMap<String, DataType> {
+        let mut scope = self.environment.borrow().clone();
+        if scope.borrow().is_global() {
+            return self.globals();
+        }
+        let mut locals = BTreeMap::new();
+        loop {
+            for (name, value) in scope.borrow().variables() {
+                locals
+                    .entry(name.to_string())
+                    .or_insert_with(|| value.clone());
+            }
+            let Some(parent) = scope.borrow().parent_environment.clone() else {
+                break;
+            };
+            if parent.borrow().is_global() {
+                break;
+            }
+            scope = parent;
+        }
+        locals
+    }
+
+    /// A handle to the global function, class or native called `name`.
+    pub fn function(&self, name: &str) -> Result<Callable> {
+        let value = self
+            .environment
+            .borrow()
+            .borrow()
+            .get_global(name)
+            .ok_or_else(|| anyhow!("Undefined variable '{name}'."))?;
+        Callable::from_value(&value)
+            .ok_or_else(|| anyhow!("Can only call functions and classes, got {value}."))
+    }
+
+    /// Calls the global function, class or native called `name`.
+    pub fn call(&mut self, name: &str, arguments: Vec<DataType>) -> Result<DataType> {
+        self.function(name)?.call(self, arguments)
+    }
+
+    /// Turns the checking of type annotations on or off. Checks are on by
+    /// default; with them off annotations are ignored.
+    pub fn set_type_checks(&mut self, enabled: bool) {
+        self.type_checks = enabled;
+    }
+
+    pub fn type_checks(&self) -> bool {
+        self.type_checks
+    }
+
+    /// Scans, parses, resolves and runs `source`, returning the value of a
+    /// trailing expression statement so the REPL can echo it.
+    pub fn run(&mut self, source: String) -> Result<DataType> {
+        self.next_source_id += 1;
+        let source_id = self.next_source_id;
+        if let Some(file) = &self.current_file {
+            self.files.insert(source_id, file.clone());
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.loaded(source_id, self.current_file.clone(), &source);
+        }
+        self.run_source(&source, source_id)
+            .map_err(|error| LoxError::locate(error, &source, source_id))
+    }
+
+    fn run_source(&mut self, source: &str, source_id: u32) -> Result<DataType> {
+        let tokens = scanner::run(source.to_string(), source_id)?;
+        let mut parser = Parser::new(tokens);
+        parser.set_lang_version(self.lang_version);
+        let mut statements = parser.parse()?;
+        if self.optimize {
+            statements = optimize(&statements);
+        }
+        let mut resolver = Resolver::new(self);
+        resolver.resolve(statements.clone())?;
+        let mut warnings = resolver.into_warnings();
+        for warning in &mut warnings {
+            warning.locate(source);
+        }
+        self.warnings.extend(warnings);
+        self.interpret(&statements)
+    }
+
+    /// Runs a program compiled ahead of time, skipping the scanning, parsing
+    /// and resolving [`Interpreter::run`] would do.
+    pub fn run_program(&mut self, program: &Program) -> Result<DataType> {
+        self.locals.borrow_mut().extend(
+            program
+                .locals()
+                .iter()
+                .map(|(token, depth)| (*token, *depth)),
+        );
+        self.interpret(program.statements())
+    }
+
+    /// Reads the script at `path` through the source provider and runs it.
+    pub fn run_file(&mut self, path: &Path) -> Result<DataType> {
+        let source = self.sources.read(path)?;
+        let previous = self.current_file.replace(path.to_path_buf());
+        let result = self.run(source);
+        self.current_file = previous;
+        result
+    }
+
+    /// Executes the statements in order. The value of a trailing expression
+    /// statement is returned so callers such as the REPL can echo it.
+    ///
+    /// The time and step limits count from the start of the outermost run,
+    /// so imported modules share the budget of the script importing them.
+    pub fn interpret(&mut self, statements: &[Rc<Stmt>]) -> Result<DataType> {
+        let _metered = self.enter_meter();
+        let outermost = !self.running;
+        if outermost {
+            self.running = true;
+            self.deadline = self.limits.max_run_time.map(|max| Instant::now() + max);
+            self.steps = 0;
+        }
+        let result = self.interpret_statements(statements);
+        if outermost {
+            self.running = false;
+            self.deadline = None;
+        }
+        result
+    }
+
+    fn interpret_statements(&mut self, statements: &[Rc<Stmt>]) -> Result<DataType> {
+        let mut value = DataType::Nil;
+        for statement in statements {
+            value = match statement.as_ref() {
+                Stmt::Expr(expr_stmt) => {
+                    self.before(statement)?;
+                    self.evaluate(Rc::clone(&expr_stmt.expression))?
+                }
+                _ => {
+                    self.execute(Rc::clone(statement))?;
+                    DataType::Nil
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    /// Runs `statements` in `environment`, restoring the current environment
+    /// afterwards even if a statement fails. Stops early when a `return`
+    /// statement runs and hands its value back to the caller.
     pub fn execute_block(
         &mut self,
-        statements: &Rc<Vec<Rc<dyn Stmt>>>,
+        statements: &[Rc<Stmt>],
         environment: Environment,
-    ) -> Result<DataType> {
+    ) -> Result<Option<DataType>> {
         let previous = self.environment.replace(Rc::new(RefCell::new(environment)));
-        for statement in statements.as_ref() {
-            let returned = self.execute(statement.clone())?;
-            match returned {
-                DataType::Nil => continue,
-                _ => {
-                    self.environment.replace(previous);
-                    return Ok(returned);
-                }
+        let mut returned = Ok(None);
+        for statement in statements {
+            returned = self.execute(Rc::clone(statement));
+            if !matches!(returned, Ok(None)) {
+                break;
             }
         }
         self.environment.replace(previous);
-        Ok(DataType::Nil)
+        returned
     }
 
-    fn evaluate(&mut self, expression: Rc<dyn Expr>) -> DataType {
-        expression.accept(self)
+    /// Registers `listener` to be called with the payload of every later
+    /// emit of `event`.
+    pub fn on(&mut self, event: String, listener: Rc<dyn LoxCallable>) {
+        self.listeners.entry(event).or_default().push(listener);
     }
 
-    fn execute(&mut self, statement: Rc<dyn Stmt>) -> Result<DataType> {
-        statement.accept(self)
+    /// Synchronously calls every listener of `event` with `payload`, in the
+    /// order they were registered, and returns how many were called.
+    /// Listeners registered while the event is being emitted only hear later
+    /// emits. An error from a listener stops the emit and is returned.
+    pub fn emit(&mut self, event: &str, payload: DataType) -> Result<usize> {
+        let _metered = self.enter_meter();
+        let listeners = self.listeners.get(event).cloned().unwrap_or_default();
+        for listener in &listeners {
+            listener.call(self, vec![payload.clone()])?;
+        }
+        Ok(listeners.len())
     }
 
-    fn is_truthy(&self, value: &DataType) -> bool {
-        match value {
-            DataType::String(_) => true,
-            DataType::Number(_) => true,
-            DataType::Bool(_) => true,
-            DataType::Nil => false,
-            _ => false,
+    /// Makes the interpreter's meter the one values created on this thread
+    /// are charged to, until the returned guard is dropped. Every way into
+    /// running script code takes one, as interpreters may take turns on a
+    /// thread.
+    pub(crate) fn enter_meter(&self) -> Option<Entered> {
+        self.memory.as_ref().map(Meter::enter)
+    }
+
+    /// Fails at `token`, when there is one, if the memory the script is
+    /// keeping alive, with `extra` more bytes about to be allocated, would
+    /// exceed the limit.
+    fn check_memory(&self, token: Option<&Token>, extra: usize) -> Result<()> {
+        let Some(meter) = &self.memory else {
+            return Ok(());
+        };
+        self.limits
+            .check_memory(meter.used(), extra)
+            .map_err(|error| match token {
+                Some(token) => LoxError::runtime(token, error.to_string()).into(),
+                None => error,
+            })
+    }
+
+    /// Runs `statements` in a new scope nested in the current environment.
+    fn execute_scoped(&mut self, statements: &[Rc<Stmt>]) -> Result<Option<DataType>> {
+        let environment =
+            Environment::new_with_parent_environment(self.environment.borrow().clone());
+        explain::say(|| format!("entering block, creating {}", explain::scope(&environment)));
+        let returned = self.execute_block(statements, environment);
+        explain::say(|| "leaving block".to_string());
+        returned
+    }
+
+    /// The value a `catch` clause binds for `error`: the thrown value for a
+    /// `throw`, otherwise the message of the runtime error. A value left
+    /// over from a `throw` nothing caught is never bound to another error.
+    fn caught_value(&mut self, error: &anyhow::Error) -> DataType {
+        let thrown = self.thrown.take();
+        match error.downcast_ref::<LoxError>() {
+            Some(error) if error.thrown => thrown.unwrap_or(DataType::Nil),
+            Some(error) => DataType::String(error.message.clone()),
+            None => DataType::String(error.to_string()),
+        }
+    }
+
+    fn import_native(&mut self, stmt: &ImportStmt) -> Result<LoxModule> {
+        let key = PathBuf::from(&stmt.path);
+        if let Some(module) = self.imported.get(&key) {
+            return Ok(module.clone());
+        }
+
+        let members = native_module(&stmt.path, &self.sandbox).ok_or_else(|| {
+            LoxError::runtime(
+                &stmt.keyword,
+                format!("Unknown standard module '{}'.", stmt.path),
+            )
+        })?;
+        let mut environment = Environment::new();
+        for (name, member) in members.iter() {
+            environment.define(*name, Some(member.clone()));
+        }
+        let exports = environment.names();
+        let module = LoxModule::new(
+            stmt.path.clone(),
+            Rc::new(RefCell::new(environment)),
+            Rc::new(RefCell::new(exports)),
+        );
+        self.imported.insert(key, module.clone());
+        Ok(module)
+    }
+
+    /// Runs the script at the import path, relative to the importing script,
+    /// in a fresh top-level scope. The module is recorded before it runs, so
+    /// an import cycle sees the partly initialised module instead of looping.
+    fn import_file(&mut self, stmt: &ImportStmt) -> Result<LoxModule> {
+        let path = import_path(self.current_file.as_deref(), &stmt.path);
+        if let Some(module) = self.imported.get(&path) {
+            return Ok(module.clone());
+        }
+
+        let source = self.sources.read(&path).map_err(|err| {
+            LoxError::runtime(
+                &stmt.keyword,
+                format!("Could not import '{}': {}", stmt.path, err.root_cause()),
+            )
+        })?;
+
+        let environment = Rc::new(RefCell::new(Environment::new_with_parent_environment(
+            Rc::clone(&self.builtins),
+        )));
+        let module = LoxModule::new(
+            stmt.path.clone(),
+            Rc::clone(&environment),
+            Rc::new(RefCell::new(HashSet::new())),
+        );
+        self.imported.insert(path.clone(), module.clone());
+
+        let previous_environment = self.environment.replace(environment);
+        let previous_exports = std::mem::replace(&mut self.exports, Rc::clone(&module.exports));
+        let previous_file = self.current_file.replace(path);
+        let result = self.run(source);
+        self.current_file = previous_file;
+        self.exports = previous_exports;
+        self.environment.replace(previous_environment);
+        result?;
+        Ok(module)
+    }
+
+    fn evaluate(&mut self, expression: Rc<Expr>) -> Result<DataType> {
+        if trace::enabled() {
+            return trace::expression(&expression, || stack::grow(|| expression.accept(self)));
+        }
+        stack::grow(|| expression.accept(self))
+    }
+
+    fn execute(&mut self, statement: Rc<Stmt>) -> Result<Option<DataType>> {
+        self.before(&statement)?;
+        stack::grow(|| statement.accept(self))
+    }
+
+    /// What happens before every statement runs: checking whether to stop,
+    /// letting the debugger in, tracing it and noting the line reached.
+    fn before(&mut self, statement: &Stmt) -> Result<()> {
+        self.check_interrupted()?;
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps {
+            if self.steps > max {
+                return Err(anyhow!(
+                    "Execution step limit of {max} statements exceeded."
+                ));
+            }
+        }
+        self.check_memory(statement.location(), 0)?;
+        trace::statement(statement);
+        if let Some(mut debugger) = self.debugger.take() {
+            let resumed = debugger.before(statement, self);
+            self.debugger = Some(debugger);
+            resumed?;
+        }
+        if let Some(token) = statement.location() {
+            let frame = self.frames.last_mut().unwrap_or(&mut self.top_level);
+            frame.source_id = token.source_id;
+            frame.line = token.line;
+        }
+        if cycles::due() {
+            cycles::collect();
         }
+        Ok(())
     }
 
     fn is_equal(&self, left: DataType, right: DataType) -> bool {
@@ -96,90 +927,259 @@ impl Interpreter {
             (DataType::Number(_), _) => false,
             (DataType::String(l), DataType::String(r)) => l == r,
             (DataType::String(_), _) => false,
+            (DataType::Array(l), DataType::Array(r)) => l.same(&r),
+            (DataType::Map(l), DataType::Map(r)) => l.same(&r),
             _ => false,
         }
     }
 
-    fn get_hash_key(&self, expr: Rc<dyn Expr>) -> Result<String> {
-        if let Ok(var) = self.get_var_expr_hash(Rc::clone(&expr)) {
-            Ok(var)
-        } else if let Ok(assign) = self.get_assign_expr_hash(Rc::clone(&expr)) {
-            Ok(assign)
-        } else if let Ok(this) = self.get_this_expr_hash(Rc::clone(&expr)) {
-            Ok(this)
-        } else if let Ok(super_expr) = self.get_super_expr_hash(Rc::clone(&expr)) {
-            Ok(super_expr)
-        } else {
-            return Err(anyhow!("could not find hash of expr"));
+    /// Checks that `index` is a whole number addressing one of `length`
+    /// elements and converts it.
+    fn element_index(&self, bracket: &Token, index: &DataType, length: usize) -> Result<usize> {
+        match index {
+            DataType::Number(n) if n.fract() != 0.0 || n.is_nan() => Err(LoxError::runtime(
+                bracket,
+                format!("Index must be a whole number, got {n}."),
+            )
+            .into()),
+            DataType::Number(n) if *n < 0.0 || *n >= length as f64 => Err(LoxError::runtime(
+                bracket,
+                format!("Index {n} is out of bounds for length {length}."),
+            )
+            .into()),
+            DataType::Number(n) => Ok(*n as usize),
+            other => Err(LoxError::runtime(
+                bracket,
+                format!("Index must be a number, got {}.", other.repr()),
+            )
+            .into()),
         }
     }
 
-    pub fn resolve(&self, expr: Rc<dyn Expr>, depth: usize) -> Result<DataType> {
-        let hash: String = self.get_hash_key(expr)?;
-        self.locals.borrow_mut().insert(hash, depth);
-        Ok(DataType::Nil)
+    fn map_key(&self, token: &Token, key: &DataType) -> Result<MapKey> {
+        MapKey::from_value(key).ok_or_else(|| {
+            LoxError::runtime(
+                token,
+                format!("Map keys must be nil, booleans, numbers or strings, got {key}."),
+            )
+            .into()
+        })
     }
 
-    pub fn get_var_expr_hash(&self, expr: Rc<dyn Expr>) -> Result<String> {
-        if let Some(var) = expr.as_any().downcast_ref::<VarExpr>() {
-            let token = &var.var_name;
-            Ok(format!(
-                "{}-{}-{:?}",
-                token.lexeme, token.line, token.literal
-            ))
-        } else {
-            Err(anyhow!("Not a VarExpr"))
+    /// Calls the method `name` of `instance` without arguments, reporting
+    /// problems at `token`.
+    fn call_method(
+        &mut self,
+        instance: &LoxInstance,
+        name: &str,
+        token: &Token,
+    ) -> Result<DataType> {
+        let name = Token {
+            lexeme: name.into(),
+            ..token.clone()
+        };
+        match instance.get(&name)? {
+            DataType::Function(method) if method.arity() == 0 => method.call(self, vec![]),
+            DataType::Function(method) => Err(LoxError::runtime(
+                token,
+                format!(
+                    "Expected {} arguments but got 0 when calling {}.",
+                    method.arity(),
+                    method
+                ),
+            )
+            .into()),
+            other => Err(LoxError::runtime(
+                token,
+                format!("'{}' must be a method, got {}.", name.lexeme, other),
+            )
+            .into()),
         }
     }
 
-    pub fn get_assign_expr_hash(&self, expr: Rc<dyn Expr>) -> Result<String> {
-        if let Some(var) = expr.as_any().downcast_ref::<AssignExpr>() {
-            let token = &var.var_name;
-            Ok(format!(
-                "{}-{}-{:?}",
-                token.lexeme, token.line, token.literal
-            ))
-        } else {
-            Err(anyhow!("Not a AssignExpr"))
+    /// Evaluates the callee and arguments of `expr` and checks that the
+    /// callee can be called with them.
+    fn prepare_call(
+        &mut self,
+        expr: &CallExpr,
+    ) -> Result<(DataType, Rc<dyn LoxCallable>, Vec<DataType>)> {
+        let callee = self.evaluate(Rc::clone(&expr.callee))?;
+        let mut arguments = vec![];
+
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(Rc::clone(argument))?)
         }
+
+        let function = as_callable(&callee).ok_or_else(|| {
+            LoxError::runtime(
+                &expr.paren,
+                format!("Can only call functions and classes, got {callee}."),
+            )
+        })?;
+
+        if function.arity() != arguments.len() {
+            let msg = format!(
+                "Expected {} arguments but got {} when calling {}.",
+                function.arity(),
+                arguments.len(),
+                function
+            );
+            return Err(LoxError::runtime(&expr.paren, msg).into());
+        };
+
+        Ok((callee, function, arguments))
     }
 
-    pub fn get_this_expr_hash(&self, expr: Rc<dyn Expr>) -> Result<String> {
-        if let Some(var) = expr.as_any().downcast_ref::<ThisExpr>() {
-            let token = &var.keyword;
-            Ok(format!(
-                "{}-{}-{:?}",
-                token.lexeme, token.line, token.literal
-            ))
-        } else {
-            Err(anyhow!("Not a AssignExpr"))
+    fn finish_call(
+        &mut self,
+        callee: &DataType,
+        function: Rc<dyn LoxCallable>,
+        arguments: Vec<DataType>,
+        paren: &Token,
+    ) -> Result<DataType> {
+        // Script functions time themselves, so callbacks natives make are
+        // timed too.
+        let _timed = match callee {
+            DataType::NativeFunction(_) => timing::start(|| callee.to_string()),
+            _ => None,
+        };
+        // Natives report plain errors; give them the line of the call.
+        function.call(self, arguments).map_err(|err| {
+            if err.is::<LoxError>() || err.is::<Exit>() {
+                err
+            } else {
+                LoxError::runtime(paren, format!("{err:#}")).into()
+            }
+        })
+    }
+
+    /// Records a call to the function `name` starting, failing if it would
+    /// nest calls deeper than the limit allows. Each successful call must be
+    /// matched by a [`Interpreter::leave_call`].
+    pub fn enter_call(&mut self, name: &Token) -> Result<()> {
+        self.limits.check_call_depth(self.frames.len() + 1)?;
+        self.frames.push(CallFrame {
+            function: name.lexeme.clone(),
+            source_id: name.source_id,
+            line: name.line,
+        });
+        Ok(())
+    }
+
+    /// Records the innermost call making a tail call to the function `name`,
+    /// which takes its place.
+    pub(crate) fn enter_tail_call(&mut self, name: &Token) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.function = name.lexeme.clone();
         }
     }
 
-    pub fn get_super_expr_hash(&self, expr: Rc<dyn Expr>) -> Result<String> {
-        if let Some(var) = expr.as_any().downcast_ref::<SuperExpr>() {
-            let token = &var.keyword;
-            Ok(format!(
-                "{}-{}-{:?}",
-                token.lexeme, token.line, token.literal
-            ))
-        } else {
-            Err(anyhow!("Not a SuperExpr"))
+    /// Records the innermost call finishing with `returned`. A runtime error
+    /// leaving it gets the stack trace of where it was raised, unless a call
+    /// further in already gave it one.
+    pub fn leave_call(&mut self, returned: Result<DataType>) -> Result<DataType> {
+        let mut returned = returned;
+        if let Err(error) = &mut returned {
+            if let Some(error) = error.downcast_mut::<LoxError>() {
+                if error.kind == ErrorKind::Runtime && error.trace.is_empty() {
+                    error.trace = self.trace(error.line);
+                }
+            }
         }
+        self.frames.pop();
+        returned
+    }
+
+    /// The calls in progress, innermost first, the innermost at `line`.
+    fn trace(&self, line: u32) -> Vec<Frame> {
+        let mut trace: Vec<Frame> = self
+            .frames
+            .iter()
+            .rev()
+            .chain([&self.top_level])
+            .map(|frame| Frame {
+                function: frame.function.to_string(),
+                file: self.files.get(&frame.source_id).cloned(),
+                line: frame.line,
+            })
+            .collect();
+        trace[0].line = line;
+        trace
     }
 
-    fn look_up_variable(&self, name: &Token, expr: &Rc<dyn Expr>) -> Result<DataType> {
-        let local: String = self.get_hash_key(Rc::clone(expr))?;
-        let option = if let Some(distance) = self.locals.borrow().get(&local) {
+    /// How many function calls are in progress.
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Takes the call a `return` in tail position left for the function
+    /// that is returning to make in its place.
+    pub fn take_tail_call(&mut self) -> Option<(LoxFunction, Vec<DataType>)> {
+        self.tail_call.take()
+    }
+
+    /// Records that the variable `name` refers to is `depth` scopes out
+    /// from where it is used.
+    pub fn resolve(&self, name: &Token, depth: usize) {
+        self.locals.borrow_mut().insert(name.id(), depth);
+    }
+
+    fn concat(&self, operator: &Token, left: &str, right: &str) -> Result<DataType> {
+        self.limits
+            .check_string(operator, "String concatenation", left.len() + right.len())?;
+        self.check_memory(Some(operator), left.len() + right.len())?;
+        Ok(DataType::String(format!("{left}{right}")))
+    }
+
+    fn repeat_string(&self, operator: &Token, text: &str, count: f64) -> Result<DataType> {
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(LoxError::runtime(
+                operator,
+                format!(
+                    "Can only repeat a string a whole, non-negative number of times, got {count}."
+                ),
+            )
+            .into());
+        }
+        let length = (text.len() as f64) * count;
+        if length > isize::MAX as f64 {
+            return Err(LoxError::runtime(operator, "String repetition is too large.").into());
+        }
+        self.limits
+            .check_string(operator, "String repetition", length as usize)?;
+        self.check_memory(Some(operator), length as usize)?;
+        Ok(DataType::String(text.repeat(count as usize)))
+    }
+
+    fn look_up_variable(&self, name: &Token) -> Result<DataType> {
+        let option = if let Some(distance) = self.locals.borrow().get(&name.id()) {
             self.environment
                 .borrow()
                 .borrow()
                 .get_at(*distance, &name.lexeme)
         } else {
-            self.globals.borrow().get(&name.lexeme)
+            self.environment.borrow().borrow().get_global(&name.lexeme)
         };
 
-        option.ok_or(anyhow!("var not found"))
+        option.ok_or_else(|| undefined_variable(name).into())
+    }
+}
+
+impl Drop for Interpreter {
+    /// Runs the shutdown hooks, lets go of everything the interpreter
+    /// holds, then collects the
+    /// cycles its scripts' closures leave behind, which would otherwise
+    /// outlive it.
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+        // The builtins only hold natives, which can't be part of a cycle.
+        self.environment.replace(Rc::clone(&self.builtins));
+        self.imported.clear();
+        self.listeners.clear();
+        self.timers.clear();
+        self.thrown = None;
+        self.tail_call = None;
+        cycles::collect();
     }
 }
 
@@ -192,163 +1192,94 @@ impl ExprVisitor for Interpreter {
     }
 
     fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<DataType> {
-        let right = self.evaluate(Rc::clone(&expr.right));
+        let right = self.evaluate(Rc::clone(&expr.right))?;
         match expr.operator.token_type {
             TokenType::MINUS => match right {
-                DataType::Number(s) => Ok(DataType::Number(-1f64 + s)),
-                _ => Err(anyhow!("Can only negate numbers")),
+                DataType::Number(s) => Ok(DataType::Number(-s)),
+                _ => Err(LoxError::runtime(&expr.operator, "Can only negate numbers").into()),
             },
             TokenType::BANG => {
-                let value = !self.is_truthy(&right);
+                let value = !right.truthy();
                 Ok(DataType::Bool(value))
             }
-            _ => Err(anyhow!("Can only negate numbers or truthy values")),
+            _ => Err(
+                LoxError::runtime(&expr.operator, "Can only negate numbers or truthy values")
+                    .into(),
+            ),
         }
     }
 
     fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<DataType> {
-        let left = self.evaluate(Rc::clone(&expr.left));
-        let right = self.evaluate(Rc::clone(&expr.right));
+        let left = self.evaluate(Rc::clone(&expr.left))?;
+        let right = self.evaluate(Rc::clone(&expr.right))?;
 
-        match expr.operator.token_type {
+        let operator = &expr.operator;
+        match operator.token_type {
             TokenType::MINUS => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use - with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Number(left - right))
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Number(l - r))
             }
             TokenType::SLASH => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use / with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Number(left / right))
-            }
-            TokenType::STAR => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use / with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Number(left * right))
-            }
-            TokenType::PLUS => {
-                let left = match left {
-                    DataType::Number(_) | DataType::String(_) => left,
-                    _ => return Err(anyhow!("Can only use + with numbers and strings")),
-                };
-                let right = match right {
-                    DataType::Number(_) | DataType::String(_) => right,
-                    _ => return Err(anyhow!("")),
-                };
-
-                match (left, right) {
-                    (DataType::String(l), DataType::String(r)) => {
-                        Ok(DataType::String(format!("{}{}", l, r)))
-                    }
-                    (DataType::Number(l), DataType::Number(r)) => Ok(DataType::Number(l + r)),
-                    _ => Err(anyhow!("Both left and right should be number/string")),
-                }
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Number(l / r))
             }
+            TokenType::STAR => match (left, right) {
+                (DataType::Number(l), DataType::Number(r)) => Ok(DataType::Number(l * r)),
+                (DataType::String(text), DataType::Number(count))
+                | (DataType::Number(count), DataType::String(text)) => {
+                    self.repeat_string(operator, &text, count)
+                }
+                (left, right) => Err(operand_error(
+                    operator,
+                    "numbers, or a string and a number",
+                    &left,
+                    &right,
+                )),
+            },
+            TokenType::PLUS => match (left, right) {
+                (DataType::Number(l), DataType::Number(r)) => Ok(DataType::Number(l + r)),
+                (DataType::String(l), DataType::String(r)) => self.concat(operator, &l, &r),
+                (DataType::String(l), DataType::Number(r)) => {
+                    self.concat(operator, &l, &r.to_string())
+                }
+                (DataType::Number(l), DataType::String(r)) => {
+                    self.concat(operator, &l.to_string(), &r)
+                }
+                (left, right) => Err(operand_error(operator, "numbers or strings", &left, &right)),
+            },
             TokenType::GREATER => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use > with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Bool(left > right))
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Bool(l > r))
             }
             TokenType::GREATEREQUAL => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use >= with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Bool(left >= right))
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Bool(l >= r))
             }
             TokenType::LESS => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use < with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Bool(left < right))
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Bool(l < r))
             }
             TokenType::LESSEQUAL => {
-                let left = match left {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("Can only use <= with numbers")),
-                };
-                let right = match right {
-                    DataType::Number(n) => n,
-                    _ => return Err(anyhow!("")),
-                };
-                Ok(DataType::Bool(left <= right))
+                let (l, r) = numbers(operator, &left, &right)?;
+                Ok(DataType::Bool(l <= r))
             }
             TokenType::BANGEQUAL => Ok(DataType::Bool(!self.is_equal(left, right))),
             TokenType::EQUALEQUAL => Ok(DataType::Bool(self.is_equal(left, right))),
-            _ => Err(anyhow!("Unsupported operator")),
+            _ => Err(LoxError::runtime(operator, "Unsupported operator").into()),
         }
     }
 
     fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<DataType> {
-        let callee = self.evaluate(Rc::clone(&expr.callee));
-        let mut arguments = vec![];
-
-        for argument in &expr.arguments {
-            arguments.push(self.evaluate(Rc::clone(argument)))
-        }
-
-        let function: Rc<dyn LoxCallable> = match callee {
-            DataType::Function(f) => Rc::new(f),
-            DataType::Class(class) => Rc::new(class),
-            DataType::NativeFunction(nf) => nf.function,
-            _ => return Err(anyhow!("Can only call functions and classes.")),
-        };
-
-        if function.arity() != arguments.len() {
-            let msg = format!(
-                "Expected {} arguments but got {}.",
-                function.arity(),
-                arguments.len()
-            );
-            return Err(anyhow!(msg));
-        };
-
-        function.call(self, arguments)
+        let (callee, function, arguments) = self.prepare_call(expr)?;
+        self.finish_call(&callee, function, arguments, &expr.paren)
     }
 
     fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<DataType> {
-        Ok(self.evaluate(Rc::clone(&expr.expression)))
+        self.evaluate(Rc::clone(&expr.expression))
     }
 
     fn visit_var_expr(&mut self, expr: &VarExpr) -> Result<DataType> {
-        let var_name = expr.var_name.clone();
-        let expr: Rc<dyn Expr> = Rc::new(VarExpr {
-            var_name: var_name.clone(),
-        });
-        self.look_up_variable(&var_name, &expr)
+        self.look_up_variable(&expr.var_name)
         // self.environment
         //     .borrow()
         //     .borrow()
@@ -357,211 +1288,306 @@ impl ExprVisitor for Interpreter {
     }
 
     fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<DataType> {
-        let expr_rc: Rc<dyn Expr> = Rc::new(AssignExpr {
-            var_name: expr.var_name.clone(),
-            var_value: expr.var_value.clone(),
-        });
-        let value = self.evaluate(Rc::clone(expr.var_value.as_ref().unwrap()));
-        let local: String = self.get_hash_key(Rc::clone(&expr_rc))?;
-        if let Some(distance) = self.locals.borrow().get(&local) {
+        let value = self.evaluate(Rc::clone(&expr.var_value))?;
+        if let Some(distance) = self.locals.borrow().get(&expr.var_name.id()) {
             self.environment.borrow().borrow_mut().assign_at(
                 *distance,
                 &expr.var_name,
                 value.clone(),
             )?;
         } else {
-            self.globals
+            self.environment
+                .borrow()
                 .borrow_mut()
-                .assign(expr.var_name.lexeme.clone(), Some(value.clone()))?;
+                .assign_global(&expr.var_name, value.clone())?;
         }
 
         Ok(value)
     }
 
     fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<DataType> {
-        let left = self.evaluate(Rc::clone(&expr.left));
+        let left = self.evaluate(Rc::clone(&expr.left))?;
         if expr.operator.token_type == OR {
-            if self.is_truthy(&left) {
+            if left.truthy() {
                 return Ok(left);
             }
-        } else if !self.is_truthy(&left) {
+        } else if !left.truthy() {
             return Ok(left);
         }
 
-        Ok(self.evaluate(Rc::clone(&expr.right)))
+        self.evaluate(Rc::clone(&expr.right))
     }
 
     fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<DataType> {
-        let object = self.evaluate(Rc::clone(&expr.object));
+        let object = self.evaluate(Rc::clone(&expr.object))?;
         match object {
             DataType::Instance(instance) => instance.get(&expr.name),
-            _ => Err(anyhow!("Only instances have properties.")),
+            DataType::Module(module) => module.get(&expr.name),
+            other => Err(LoxError::runtime(
+                &expr.name,
+                format!("Only instances have properties, got {other}."),
+            )
+            .into()),
         }
     }
 
     fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<DataType> {
-        let object = self.evaluate(Rc::clone(&expr.object));
+        let object = self.evaluate(Rc::clone(&expr.object))?;
 
-        return match object {
+        match object {
             DataType::Instance(instance) => {
-                let value = self.evaluate(Rc::clone(&expr.value));
+                let value = self.evaluate(Rc::clone(&expr.value))?;
                 instance.set(&expr.name, value.clone());
-                let cloned = expr.object.clone();
-                let var_expr = cloned.as_any().downcast_ref::<VarExpr>().unwrap();
-                self.globals.borrow_mut().assign(
-                    var_expr.var_name.lexeme.clone(),
-                    Some(DataType::Instance(instance)),
-                )?;
                 Ok(value)
             }
-            _ => Err(anyhow!("Only instances have fields.")),
-        };
+            other => Err(LoxError::runtime(
+                &expr.name,
+                format!("Only instances have fields, got {other}."),
+            )
+            .into()),
+        }
     }
 
     fn visit_this_expr(&mut self, expr: &ThisExpr) -> Result<DataType> {
-        let keyword = expr.keyword.clone();
-
-        let expr: Rc<dyn Expr> = Rc::new(ThisExpr {
-            keyword: expr.keyword.clone(),
-        });
-        self.look_up_variable(&keyword, &expr)
+        self.look_up_variable(&expr.keyword)
     }
 
     fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<DataType> {
-        let expr_rc: Rc<dyn Expr> = Rc::new(SuperExpr {
-            keyword: expr.keyword.clone(),
-            method: expr.method.clone(),
-        });
-
-        let local: String = self.get_hash_key(Rc::clone(&expr_rc))?;
-        return if let Some(distance) = self.locals.borrow().get(&local) {
-            let super_class = match self
-                .environment
-                .borrow()
-                .borrow()
-                .get_at(*distance, "super")
-            {
-                Some(DataType::Class(lox_super_class)) => lox_super_class,
-                _ => return Err(anyhow!("Lox super class not found")),
-            };
+        let distance = self.locals.borrow().get(&expr.keyword.id()).copied();
+        let Some(distance) = distance else {
+            return Err(LoxError::runtime(&expr.keyword, "Unexpected error").into());
+        };
+        let super_class = match self.environment.borrow().borrow().get_at(distance, "super") {
+            Some(DataType::Class(lox_super_class)) => lox_super_class,
+            _ => return Err(LoxError::runtime(&expr.keyword, "Lox super class not found").into()),
+        };
 
-            let object = match self
-                .environment
-                .borrow()
-                .borrow()
-                .get_at(*distance - 1, "this")
-            {
-                Some(DataType::Instance(lox_instance)) => lox_instance,
-                _ => return Err(anyhow!("Lox instance not found")),
-            };
+        let object = match self
+            .environment
+            .borrow()
+            .borrow()
+            .get_at(distance - 1, "this")
+        {
+            Some(DataType::Instance(lox_instance)) => lox_instance,
+            _ => return Err(LoxError::runtime(&expr.keyword, "Lox instance not found").into()),
+        };
 
-            let found_method = super_class.find_method(expr.method.lexeme.clone());
-            if let Some(found_method) = found_method {
-                Ok(DataType::Function(found_method.bind(object)))
-            } else {
-                return Err(anyhow!("Undefined property {}", expr.method.lexeme));
-            }
+        let found_method = super_class.find_method(&expr.method.lexeme);
+        if let Some(found_method) = found_method {
+            Ok(DataType::Function(found_method.bind(object)))
         } else {
-            return Err(anyhow!("Unexpected error"));
-        };
+            Err(LoxError::runtime(
+                &expr.method,
+                format!(
+                    "Undefined property '{}' on {}.",
+                    expr.method.lexeme, super_class
+                ),
+            )
+            .into())
+        }
     }
-}
 
-impl StmtVisitor for Interpreter {
-    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<DataType> {
-        let value = self.evaluate(Rc::clone(&stmt.expression));
-        println!("{}", value.to_string());
-        Ok(DataType::Nil)
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> Result<DataType> {
+        self.limits
+            .check_collection("Array literal", expr.elements.len())
+            .map_err(|error| LoxError::runtime(&expr.bracket, error.to_string()))?;
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in &expr.elements {
+            elements.push(self.evaluate(Rc::clone(element))?);
+        }
+        Ok(DataType::Array(LoxArray::new(elements)))
     }
 
-    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<DataType> {
-        self.evaluate(Rc::clone(&stmt.expression));
-        Ok(DataType::Nil)
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<DataType> {
+        let mut entries = BTreeMap::new();
+        for (key, value) in &expr.entries {
+            let key = self.evaluate(Rc::clone(key))?;
+            let key = self.map_key(&expr.brace, &key)?;
+            entries.insert(key, self.evaluate(Rc::clone(value))?);
+        }
+        self.limits
+            .check_collection("Map literal", entries.len())
+            .map_err(|error| LoxError::runtime(&expr.brace, error.to_string()))?;
+        Ok(DataType::Map(LoxMap::new(entries)))
     }
 
-    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<DataType> {
-        match stmt.var_value.as_ref() {
-            None => self
-                .environment
-                .borrow()
-                .borrow_mut()
-                .define(stmt.var_name.lexeme.clone(), None),
-            Some(stmt_line) => {
-                let value = self.evaluate(stmt_line.clone());
-                self.environment
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<DataType> {
+        let object = self.evaluate(Rc::clone(&expr.object))?;
+        let index = self.evaluate(Rc::clone(&expr.index))?;
+        match object {
+            DataType::Array(array) => {
+                let elements = array.elements.borrow();
+                let i = self.element_index(&expr.bracket, &index, elements.len())?;
+                Ok(elements[i].clone())
+            }
+            DataType::Map(map) => {
+                let key = self.map_key(&expr.bracket, &index)?;
+                Ok(map
+                    .entries
                     .borrow()
-                    .borrow_mut()
-                    .define(stmt.var_name.lexeme.clone(), Some(value))
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or(DataType::Nil))
+            }
+            DataType::String(string) => {
+                let chars: Vec<char> = string.chars().collect();
+                let i = self.element_index(&expr.bracket, &index, chars.len())?;
+                Ok(DataType::String(chars[i].to_string()))
             }
+            other => Err(LoxError::runtime(
+                &expr.bracket,
+                format!("Can only index arrays, maps and strings, got {other}."),
+            )
+            .into()),
         }
-        Ok(DataType::Nil)
-    }
-
-    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<DataType> {
-        let env = Environment::new_with_parent_environment(self.environment.borrow().clone());
-        let statements = Rc::new(stmt.statements.clone());
-        self.execute_block(&statements, env)
     }
 
-    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<DataType> {
-        let condition = self.evaluate(Rc::clone(&stmt.condition));
-        let mut return_value: DataType = DataType::Nil;
-        match condition {
-            DataType::Bool(value) => {
-                if value {
-                    return_value = self.execute(Rc::clone(&stmt.then_branch))?
-                } else if let Some(else_branch) = stmt.else_branch.as_ref() {
-                    return_value = self.execute(Rc::clone(else_branch))?
-                } else {
-                    return_value = DataType::Nil
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> Result<DataType> {
+        let object = self.evaluate(Rc::clone(&expr.object))?;
+        let index = self.evaluate(Rc::clone(&expr.index))?;
+        let value = self.evaluate(Rc::clone(&expr.value))?;
+        match object {
+            DataType::Array(array) => {
+                let length = array.elements.borrow().len();
+                let i = self.element_index(&expr.bracket, &index, length)?;
+                array.set(i, value.clone());
+            }
+            DataType::Map(map) => {
+                let key = self.map_key(&expr.bracket, &index)?;
+                let entries = map.entries.borrow();
+                if !entries.contains_key(&key) {
+                    self.limits
+                        .check_collection("Map insertion", entries.len() + 1)
+                        .map_err(|error| LoxError::runtime(&expr.bracket, error.to_string()))?;
                 }
+                drop(entries);
+                map.insert(key, value.clone());
             }
-            _ => Err(anyhow!("condition not boolean"))?,
+            other => {
+                return Err(LoxError::runtime(
+                    &expr.bracket,
+                    format!("Can only assign elements of arrays and maps, got {other}."),
+                )
+                .into())
+            }
+        }
+        Ok(value)
+    }
+
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> Result<DataType> {
+        let (key, value) = match self.evaluate(Rc::clone(&expr.expression)) {
+            Ok(value) => ("ok", value),
+            Err(error) if error.is::<Exit>() => return Err(error),
+            Err(error) => ("error", self.caught_value(&error)),
         };
-        Ok(return_value)
+        let entries = BTreeMap::from([(MapKey::String(key.to_string()), value)]);
+        Ok(DataType::Map(LoxMap::new(entries)))
     }
+}
 
-    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<DataType> {
-        let mut condition = true;
+impl StmtVisitor for Interpreter {
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<Option<DataType>> {
+        let value = self.evaluate(Rc::clone(&stmt.expression))?;
+        writeln!(self.output, "{value}")?;
+        Ok(None)
+    }
 
-        while condition {
-            condition = match &self.evaluate(Rc::clone(&stmt.condition)) {
-                DataType::Bool(true_value) => *true_value,
-                _ => return Err(anyhow!("condition should be boolean")),
-            };
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<Option<DataType>> {
+        self.evaluate(Rc::clone(&stmt.expression))?;
+        Ok(None)
+    }
 
-            if condition {
-                self.execute(Rc::clone(&stmt.body))?;
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<Option<DataType>> {
+        // A variable declared without an initializer holds nil.
+        let value = match stmt.var_value.as_ref() {
+            None => DataType::Nil,
+            Some(stmt_line) => self.evaluate(stmt_line.clone())?,
+        };
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(stmt.var_name.lexeme.clone(), Some(value));
+        Ok(None)
+    }
+
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<Option<DataType>> {
+        self.execute_scoped(&stmt.statements)
+    }
+
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<Option<DataType>> {
+        if self.evaluate(Rc::clone(&stmt.condition))?.truthy() {
+            self.execute(Rc::clone(&stmt.then_branch))
+        } else if let Some(else_branch) = stmt.else_branch.as_ref() {
+            self.execute(Rc::clone(else_branch))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<Option<DataType>> {
+        while self.evaluate(Rc::clone(&stmt.condition))?.truthy() {
+            if let Some(returned) = self.execute(Rc::clone(&stmt.body))? {
+                return Ok(Some(returned));
             }
         }
 
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<DataType> {
+    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<Option<DataType>> {
         let function = LoxFunction::new(stmt, &self.environment.borrow(), false);
         self.environment
             .borrow()
             .borrow_mut()
             .define(stmt.name.lexeme.clone(), Some(DataType::Function(function)));
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<DataType> {
-        if stmt.value.is_some() {
-            Ok(self.evaluate(stmt.value.clone().unwrap()))
-        } else {
-            Err(anyhow!("return error"))
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<Option<DataType>> {
+        let tail_call =
+            stmt.value
+                .as_ref()
+                .filter(|_| stmt.tail_call)
+                .and_then(|value| match value.as_ref() {
+                    Expr::Call(call) => Some(call),
+                    _ => None,
+                });
+        if let Some(call) = tail_call {
+            let (callee, function, arguments) = self.prepare_call(call)?;
+            return match callee {
+                DataType::Function(callee) => {
+                    self.tail_call = Some((callee, arguments));
+                    Ok(Some(DataType::Nil))
+                }
+                _ => Ok(Some(self.finish_call(
+                    &callee,
+                    function,
+                    arguments,
+                    &call.paren,
+                )?)),
+            };
         }
+
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(Rc::clone(value))?,
+            None => DataType::Nil,
+        };
+        Ok(Some(value))
     }
 
-    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<DataType> {
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<Option<DataType>> {
         let mut super_class: Option<LoxClass> = None;
 
         if let Some(class) = &stmt.super_class {
-            match self.evaluate(Rc::clone(class)) {
+            match self.visit_var_expr(class)? {
                 DataType::Class(evaluated_class) => super_class = Some(evaluated_class),
-                _ => return Err(anyhow!("Superclass must be a class.")),
+                other => {
+                    return Err(LoxError::runtime(
+                        &stmt.name,
+                        format!("Superclass must be a class, got {other}."),
+                    )
+                    .into())
+                }
             }
         }
 
@@ -582,20 +1608,19 @@ impl StmtVisitor for Interpreter {
             );
         }
 
-        let mut methods: HashMap<String, LoxFunction> = HashMap::new();
+        let mut methods: HashMap<Symbol, LoxFunction> = HashMap::new();
 
         for method in &stmt.methods {
-            let function = method.as_any().downcast_ref::<FunctionStmt>().unwrap();
             let m = LoxFunction::new(
-                function,
+                method,
                 &self.environment.borrow(),
-                function.name.lexeme.eq_ignore_ascii_case("init"),
+                method.name.lexeme == "init",
             );
-            methods.insert(function.name.lexeme.clone(), m);
+            methods.insert(method.name.lexeme.clone(), m);
         }
 
         let lox_class: LoxClass = LoxClass {
-            name: stmt.name.lexeme.clone(),
+            name: stmt.name.lexeme.to_string(),
             super_class: super_class.clone().map(Box::new),
             methods,
         };
@@ -614,8 +1639,158 @@ impl StmtVisitor for Interpreter {
         self.environment
             .borrow()
             .borrow_mut()
-            .assign(stmt.name.lexeme.clone(), Some(DataType::Class(lox_class)))?;
+            .assign(&stmt.name, DataType::Class(lox_class))?;
+
+        Ok(None)
+    }
+
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> Result<Option<DataType>> {
+        let value = self.evaluate(Rc::clone(&stmt.value))?;
+        let message = format!("Uncaught exception: {value}");
+        self.thrown = Some(value);
+        Err(LoxError::thrown(&stmt.keyword, message).into())
+    }
+
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> Result<Option<DataType>> {
+        let mut outcome = self.execute_scoped(&stmt.body);
+
+        if matches!(&outcome, Err(error) if error.is::<Exit>()) {
+            return outcome;
+        }
+
+        if let (Err(error), Some(catch)) = (&outcome, &stmt.catch) {
+            let value = self.caught_value(error);
+            let mut environment =
+                Environment::new_with_parent_environment(self.environment.borrow().clone());
+            environment.define(catch.name.lexeme.clone(), Some(value));
+            outcome = self.execute_block(&catch.body, environment);
+        }
+
+        if let Some(finally) = &stmt.finally {
+            // A return or error from the finally block replaces the outcome of
+            // the try and catch blocks; otherwise that outcome carries on.
+            let pending = self.thrown.take();
+            let finished = self.execute_scoped(finally);
+            if !matches!(finished, Ok(None)) {
+                return finished;
+            }
+            self.thrown = pending;
+        }
+
+        outcome
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> Result<Option<DataType>> {
+        let module = if stmt.path.starts_with("std/") {
+            self.import_native(stmt)?
+        } else {
+            self.import_file(stmt)?
+        };
+        self.environment
+            .borrow()
+            .borrow_mut()
+            .define(stmt.name.clone(), Some(DataType::Module(module)));
+        Ok(None)
+    }
+
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> Result<Option<DataType>> {
+        if self.evaluate(Rc::clone(&stmt.condition))?.truthy() {
+            return Ok(None);
+        }
+        let message = match &stmt.message {
+            Some(message) => {
+                let message = self.evaluate(Rc::clone(message))?;
+                format!("Assertion failed: {} ({message})", stmt.source)
+            }
+            None => format!("Assertion failed: {}", stmt.source),
+        };
+        Err(LoxError::runtime(&stmt.keyword, message).into())
+    }
 
-        Ok(DataType::Nil)
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> Result<Option<DataType>> {
+        self.execute(Rc::clone(&stmt.declaration))?;
+        self.exports.borrow_mut().insert(stmt.name.lexeme.clone());
+        Ok(None)
+    }
+
+    /// Runs the first case whose value equals the subject, or the default
+    /// case if none does. Cases do not fall through into the next one.
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> Result<Option<DataType>> {
+        let subject = self.evaluate(Rc::clone(&stmt.subject))?;
+        for case in &stmt.cases {
+            let value = self.evaluate(Rc::clone(&case.value))?;
+            if self.is_equal(subject.clone(), value) {
+                return self.execute_scoped(&case.body);
+            }
+        }
+        match &stmt.default {
+            Some(default) => self.execute_scoped(default),
+            None => Ok(None),
+        }
+    }
+
+    /// Arrays, maps and strings are iterated over a snapshot of their
+    /// elements, keys or characters taken when the loop starts. An instance
+    /// is iterated by calling its `iterator()` method and then `next()` on
+    /// the result until it returns `nil`.
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> Result<Option<DataType>> {
+        let iterable = self.evaluate(Rc::clone(&stmt.iterable))?;
+        let (mut snapshot, iterator) = match iterable {
+            DataType::Array(array) => (array.elements.borrow().clone(), None),
+            DataType::Map(map) => (
+                map.entries.borrow().keys().map(MapKey::to_value).collect(),
+                None,
+            ),
+            DataType::String(string) => (
+                string
+                    .chars()
+                    .map(|c| DataType::String(c.to_string()))
+                    .collect(),
+                None,
+            ),
+            DataType::Instance(instance) => {
+                match self.call_method(&instance, "iterator", &stmt.keyword)? {
+                    DataType::Instance(iterator) => (vec![], Some(iterator)),
+                    other => {
+                        return Err(LoxError::runtime(
+                            &stmt.keyword,
+                            format!("'iterator' must return an instance, got {other}."),
+                        )
+                        .into())
+                    }
+                }
+            }
+            other => {
+                return Err(LoxError::runtime(
+                    &stmt.keyword,
+                    format!(
+                        "Can only iterate over arrays, maps, strings and instances, got {other}."
+                    ),
+                )
+                .into())
+            }
+        };
+        snapshot.reverse();
+
+        let body = std::slice::from_ref(&stmt.body);
+        loop {
+            let item = match &iterator {
+                Some(iterator) => match self.call_method(iterator, "next", &stmt.keyword)? {
+                    DataType::Nil => break,
+                    item => item,
+                },
+                None => match snapshot.pop() {
+                    Some(item) => item,
+                    None => break,
+                },
+            };
+            let mut environment =
+                Environment::new_with_parent_environment(self.environment.borrow().clone());
+            environment.define(stmt.name.lexeme.clone(), Some(item));
+            if let Some(returned) = self.execute_block(body, environment)? {
+                return Ok(Some(returned));
+            }
+        }
+        Ok(None)
     }
 }
@@ -0,0 +1,331 @@
+//! Converting between JSON text and values, for `std/json`. JSON objects
+//! become maps with string keys, arrays become arrays and `null` becomes
+//! nil. Only plain data converts back: nil, booleans, finite numbers,
+//! strings, and arrays and maps of them whose keys are strings.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::collections::{LoxArray, LoxMap, MapKey};
+use crate::token::DataType;
+
+/// How deeply arrays and objects may nest in text given to [`parse`], so
+/// deeply nested input fails instead of overflowing the stack.
+const MAX_DEPTH: usize = 512;
+
+/// The value `text` is the JSON for.
+pub fn parse(text: &str) -> Result<DataType> {
+    let mut parser = Parser {
+        text,
+        offset: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.offset < text.len() {
+        return Err(parser.error("expected the end of the text"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// The byte offset of the next character to read.
+    offset: usize,
+    /// How many arrays and objects the parser is inside of.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, expected: &str) -> anyhow::Error {
+        anyhow!("Invalid JSON at byte {}: {expected}.", self.offset)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.offset).copied()
+    }
+
+    fn next_is(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.next_is(byte) {
+            return Ok(());
+        }
+        Err(self.error(&format!("expected '{}'", byte as char)))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<DataType> {
+        match self.peek() {
+            Some(b'{') => self.nested(Parser::object),
+            Some(b'[') => self.nested(Parser::array),
+            Some(b'"') => Ok(DataType::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ => {
+                for (word, value) in [
+                    ("true", DataType::Bool(true)),
+                    ("false", DataType::Bool(false)),
+                    ("null", DataType::Nil),
+                ] {
+                    if self.text[self.offset..].starts_with(word) {
+                        self.offset += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Parser<'a>) -> Result<DataType>) -> Result<DataType> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(&format!(
+                "arrays and objects nest more than {MAX_DEPTH} deep"
+            )));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn array(&mut self) -> Result<DataType> {
+        self.expect(b'[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if !self.next_is(b']') {
+            loop {
+                self.skip_whitespace();
+                elements.push(self.value()?);
+                self.skip_whitespace();
+                if self.next_is(b']') {
+                    break;
+                }
+                self.expect(b',')?;
+            }
+        }
+        Ok(DataType::Array(LoxArray::new(elements)))
+    }
+
+    fn object(&mut self) -> Result<DataType> {
+        self.expect(b'{')?;
+        let mut entries = BTreeMap::new();
+        self.skip_whitespace();
+        if !self.next_is(b'}') {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some(b'"') {
+                    return Err(self.error("expected a string key"));
+                }
+                let key = self.string()?;
+                self.skip_whitespace();
+                self.expect(b':')?;
+                self.skip_whitespace();
+                entries.insert(MapKey::String(key), self.value()?);
+                self.skip_whitespace();
+                if self.next_is(b'}') {
+                    break;
+                }
+                self.expect(b',')?;
+            }
+        }
+        Ok(DataType::Map(LoxMap::new(entries)))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut string = String::new();
+        loop {
+            let Some(c) = self.text[self.offset..].chars().next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => {
+                    self.offset += 1;
+                    return Ok(string);
+                }
+                '\\' => {
+                    self.offset += 1;
+                    string.push(self.escape()?);
+                }
+                c if c < ' ' => return Err(self.error("control character in string")),
+                c => {
+                    self.offset += c.len_utf8();
+                    string.push(c);
+                }
+            }
+        }
+    }
+
+    /// The character the escape sequence after a backslash stands for.
+    fn escape(&mut self) -> Result<char> {
+        let escaped = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.offset += 1;
+                let unit = self.hex4()?;
+                // Characters outside the Basic Multilingual Plane are
+                // written as a surrogate pair of escapes.
+                let code = if (0xD800..0xDC00).contains(&unit)
+                    && self.text[self.offset..].starts_with("\\u")
+                {
+                    self.offset += 2;
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("expected a low surrogate"));
+                    }
+                    0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    unit
+                };
+                return char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"));
+            }
+            _ => return Err(self.error("invalid escape sequence")),
+        };
+        self.offset += 1;
+        Ok(escaped)
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("expected four hex digits"))?;
+        self.offset += 4;
+        Ok(u32::from_str_radix(digits, 16).expect("checked to be hex digits"))
+    }
+
+    fn number(&mut self) -> Result<DataType> {
+        let start = self.offset;
+        self.next_is(b'-');
+        if !self.next_is(b'0') && self.digits() == 0 {
+            return Err(self.error("expected a digit"));
+        }
+        if self.next_is(b'.') && self.digits() == 0 {
+            return Err(self.error("expected a digit after '.'"));
+        }
+        if self.next_is(b'e') || self.next_is(b'E') {
+            let _ = self.next_is(b'+') || self.next_is(b'-');
+            if self.digits() == 0 {
+                return Err(self.error("expected a digit in the exponent"));
+            }
+        }
+        let number = self.text[start..self.offset]
+            .parse()
+            .map_err(|_| self.error("invalid number"))?;
+        Ok(DataType::Number(number))
+    }
+
+    /// Skips a run of digits, returning how many there were.
+    fn digits(&mut self) -> usize {
+        let start = self.offset;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.offset += 1;
+        }
+        self.offset - start
+    }
+}
+
+/// The JSON for `value`, without whitespace between its tokens.
+pub fn stringify(value: &DataType) -> Result<String> {
+    let mut json = String::new();
+    write_value(value, &mut json, &mut vec![])?;
+    Ok(json)
+}
+
+/// Writes `value` to `json`, with `writing` holding the collections it is
+/// inside of so one that contains itself is refused instead of written
+/// forever.
+fn write_value(value: &DataType, json: &mut String, writing: &mut Vec<*const ()>) -> Result<()> {
+    match value {
+        DataType::Nil => json.push_str("null"),
+        DataType::Bool(b) => json.push_str(if *b { "true" } else { "false" }),
+        DataType::Number(n) if n.is_finite() => write!(json, "{n}")?,
+        DataType::String(s) => write_string(s, json),
+        DataType::Array(array) => {
+            enter(Rc::as_ptr(&array.elements) as *const (), writing)?;
+            json.push('[');
+            for (i, element) in array.elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_value(element, json, writing)?;
+            }
+            json.push(']');
+            writing.pop();
+        }
+        DataType::Map(map) => {
+            enter(Rc::as_ptr(&map.entries) as *const (), writing)?;
+            json.push('{');
+            for (i, (key, value)) in map.entries.borrow().iter().enumerate() {
+                let MapKey::String(key) = key else {
+                    return Err(anyhow!(
+                        "JSON object keys must be strings, got {}.",
+                        key.to_value().repr()
+                    ));
+                };
+                if i > 0 {
+                    json.push(',');
+                }
+                write_string(key, json);
+                json.push(':');
+                write_value(value, json, writing)?;
+            }
+            json.push('}');
+            writing.pop();
+        }
+        other => return Err(anyhow!("Can't convert {other} to JSON.")),
+    }
+    Ok(())
+}
+
+fn write_string(text: &str, json: &mut String) {
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn enter(id: *const (), writing: &mut Vec<*const ()>) -> Result<()> {
+    if writing.contains(&id) {
+        return Err(anyhow!(
+            "Can't convert a collection that contains itself to JSON."
+        ));
+    }
+    writing.push(id);
+    Ok(())
+}
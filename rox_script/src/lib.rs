@@ -0,0 +1,196 @@
+//! A tree-walking interpreter for Lox, usable as a library. [`Rox`] is the
+//! embedding API; [`cli`] is a command line front end over the same
+//! modules, run by the `rox_script` binary and by `rox --backend=ast`.
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::program::Program;
+use crate::sandbox::RoxSandbox;
+use crate::source::SourceProvider;
+
+pub mod bundle;
+mod class;
+#[cfg(feature = "cli")]
+pub mod cli;
+mod collections;
+mod convert;
+mod cycles;
+pub mod debugger;
+pub mod diagnostics;
+mod environment;
+pub mod error;
+pub mod explain;
+mod expr;
+pub mod features;
+mod functions;
+pub mod interpreter;
+mod json;
+pub mod limits;
+mod memory;
+pub mod minify;
+mod module;
+mod natives;
+mod optimize;
+#[cfg(feature = "fs")]
+pub mod pack;
+mod parser;
+pub mod precedence;
+mod predicate;
+pub mod printer;
+pub mod profile;
+pub mod program;
+mod random;
+mod resolver;
+pub mod sandbox;
+mod scanner;
+pub mod source;
+mod stack;
+mod stdlib;
+mod stmt;
+pub mod symbol;
+mod time;
+pub mod timing;
+pub mod token;
+pub mod trace;
+pub mod typecheck;
+mod types;
+mod visitor;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod worker;
+
+/// A value a script produced or that is passed to one.
+pub use crate::token::DataType as Value;
+
+pub use crate::functions::Callable;
+
+/// Gives [`Value`] its truthiness, as conditions in scripts see it.
+pub use rox_frontend::value::LoxValue;
+
+/// An interpreter session. Globals defined by one call to [`Rox::eval`] are
+/// visible to the next.
+pub struct Rox {
+    interpreter: Interpreter,
+}
+
+impl Rox {
+    pub fn new() -> Self {
+        Rox::with_limits(Limits::default())
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        Rox {
+            interpreter: Interpreter::new(limits),
+        }
+    }
+
+    /// A session whose scripts can only use the capabilities `sandbox`
+    /// allows, for running untrusted scripts.
+    pub fn with_sandbox(limits: Limits, sandbox: RoxSandbox) -> Self {
+        Rox {
+            interpreter: Interpreter::with_sandbox(limits, sandbox),
+        }
+    }
+
+    /// Runs `source` and returns the value of its trailing expression
+    /// statement, or nil if it doesn't end with one.
+    pub fn eval(&mut self, source: &str) -> Result<Value> {
+        self.interpreter.run(source.to_string())
+    }
+
+    /// Runs a script compiled once with [`Program::compile`], without
+    /// scanning, parsing or resolving it again.
+    pub fn run_program(&mut self, program: &Program) -> Result<Value> {
+        self.interpreter.run_program(program)
+    }
+
+    /// Runs the script at `path`, read through the source provider.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Value> {
+        self.interpreter.run_file(path.as_ref())
+    }
+
+    /// Makes `function` callable from scripts as `name`. Calls with a number
+    /// of arguments other than `arity` fail before reaching it, and errors
+    /// it returns become runtime errors at the call.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value> + 'static,
+    ) {
+        self.interpreter.register_native(name, arity, function);
+    }
+
+    /// Declares a global the host will define later, so scripts using it
+    /// resolve in strict mode. See [`Interpreter::declare_external`].
+    pub fn declare_external(&mut self, name: &str, arity: Option<usize>) {
+        self.interpreter.declare_external(name, arity);
+    }
+
+    /// Hands over what the resolver warned about in the scripts run since
+    /// the last call, like locals nothing reads.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        self.interpreter.take_warnings()
+    }
+
+    /// Calls the global function, class or native called `name`.
+    pub fn call(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value> {
+        self.interpreter.call(name, arguments)
+    }
+
+    /// A handle to the global function, class or native called `name`, to
+    /// call later with [`Callable::call`].
+    pub fn function(&self, name: &str) -> Result<Callable> {
+        self.interpreter.function(name)
+    }
+
+    /// Sets what the `args()` native returns, such as the command line
+    /// arguments after the script's path.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.interpreter.set_args(args);
+    }
+
+    /// Sends what scripts print to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.interpreter.set_output(output);
+    }
+
+    /// Reads scripts and imports from `sources` instead of the filesystem.
+    pub fn set_source_provider(&mut self, sources: impl SourceProvider + 'static) {
+        self.interpreter.set_source_provider(Box::new(sources));
+    }
+
+    /// Registers `hook` to run when the session shuts down: when a script
+    /// calls `exit`, when [`Rox::shutdown`] is called or when the session is
+    /// dropped, whichever comes first. Use it to flush or close what natives
+    /// opened.
+    pub fn on_shutdown(&mut self, hook: impl FnOnce() + 'static) {
+        self.interpreter.on_shutdown(|_| {
+            hook();
+            Ok(())
+        });
+    }
+
+    /// Runs the shutdown hooks, including the functions scripts registered
+    /// with `atExit`, returning the first error one of them failed with.
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.interpreter.shutdown()
+    }
+
+    /// The interpreter behind the session, for settings `Rox` doesn't wrap.
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+}
+
+impl Default for Rox {
+    fn default() -> Self {
+        Rox::new()
+    }
+}
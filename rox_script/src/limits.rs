@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::error::LoxError;
+use crate::token::Token;
+
+/// The call depth allowed unless configured otherwise.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// Ceilings on how large values built by a script may grow, how deeply it
+/// may recurse and how long or how many statements it may run, so a runaway
+/// or untrusted script fails with a runtime error instead of exhausting host
+/// memory or time. `None` leaves a value unbounded.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum length in bytes of a string produced by concatenation or
+    /// repetition.
+    pub max_string_length: Option<usize>,
+    /// Maximum number of elements an array, or entries a map, may hold.
+    pub max_collection_length: Option<usize>,
+    /// Maximum number of function calls in progress at once. A call in tail
+    /// position replaces its caller, so it doesn't add to the depth.
+    pub max_call_depth: Option<usize>,
+    /// Maximum wall-clock time a run may take, including time spent in
+    /// `sleep` and waiting for timers.
+    pub max_run_time: Option<Duration>,
+    /// Maximum number of statements a run may execute, counting each run
+    /// of a loop body's statements again. Unlike the run time, it doesn't
+    /// depend on how fast the host is, so a script stops at the same point
+    /// every time.
+    pub max_steps: Option<u64>,
+    /// Roughly how many bytes the arrays, maps, instances and variables a
+    /// script keeps alive may take up, strings stored in them included.
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_string_length: None,
+            max_collection_length: None,
+            max_call_depth: Some(DEFAULT_MAX_CALL_DEPTH),
+            max_run_time: None,
+            max_steps: None,
+            max_memory: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Fails if a string of `length` bytes, built by `construct` at `token`,
+    /// would exceed the configured ceiling.
+    pub fn check_string(&self, token: &Token, construct: &str, length: usize) -> Result<()> {
+        match self.max_string_length {
+            Some(max) if length > max => Err(LoxError::runtime(
+                token,
+                format!("{construct} would produce {length} bytes, over the limit of {max}."),
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if `construct` would leave an array or map holding `length`
+    /// elements or entries, more than the configured ceiling. Natives
+    /// report the error at their call; the interpreter at the literal or
+    /// assignment.
+    pub fn check_collection(&self, construct: &str, length: usize) -> Result<()> {
+        match self.max_collection_length {
+            Some(max) if length > max => Err(anyhow!(
+                "{construct} would hold {length} items, over the limit of {max}."
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a call would make `depth` calls in progress at once. The
+    /// error is reported at the call being made.
+    pub fn check_call_depth(&self, depth: usize) -> Result<()> {
+        match self.max_call_depth {
+            Some(max) if depth > max => {
+                Err(anyhow!("Stack overflow: max call depth {max} exceeded."))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if `used` bytes of memory, with `extra` more about to be
+    /// allocated, would exceed the configured ceiling. The interpreter
+    /// reports the error at the statement or operator allocating.
+    pub fn check_memory(&self, used: usize, extra: usize) -> Result<()> {
+        match self.max_memory {
+            Some(max) if used.saturating_add(extra) > max => Err(anyhow!(
+                "Out of memory: about {} bytes needed, over the limit of {max}.",
+                used.saturating_add(extra)
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Approximate memory accounting, for `Limits::max_memory`. An interpreter
+//! with a memory limit keeps a [`Meter`] and makes it the current one on its
+//! thread with [`Meter::enter`] while it runs. Every array, map, instance and
+//! environment created meanwhile is charged to that meter roughly the bytes
+//! it holds: a fixed amount for itself and for each of its slots, plus the
+//! text of the strings stored in them. A charge grows and shrinks as values
+//! are stored and is released when the value is dropped, so
+//! [`Meter::used`] is about what the interpreter's script is keeping alive.
+//!
+//! Strings are only charged once they are stored somewhere. A charge stays
+//! with the meter it was made against, so interpreters taking turns on one
+//! thread, such as a pooled worker's, never see each other's values.
+
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::rc::Rc;
+
+use crate::token::DataType;
+
+thread_local! {
+    static CURRENT: RefCell<Option<Meter>> = const { RefCell::new(None) };
+}
+
+/// The bytes charged to one interpreter and not yet released.
+#[derive(Debug, Clone, Default)]
+pub struct Meter {
+    used: Rc<Cell<usize>>,
+}
+
+impl Meter {
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// Charges the values created on this thread to this meter until the
+    /// returned guard is dropped, when the meter current before is restored.
+    pub fn enter(&self) -> Entered {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        Entered { previous }
+    }
+
+    fn add(&self, bytes: usize) {
+        self.used.set(self.used.get() + bytes);
+    }
+
+    fn remove(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_sub(bytes));
+    }
+}
+
+/// Keeps a meter current on its thread until dropped.
+pub struct Entered {
+    previous: Option<Meter>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        // Guards dropped while the thread exits may outlive the slot.
+        let _ = CURRENT.try_with(|current| current.replace(self.previous.take()));
+    }
+}
+
+/// Roughly what a slot holding `value` costs. Arrays, maps, instances and
+/// functions in it are charged separately, so only the handle counts here.
+pub fn size_of(value: &DataType) -> usize {
+    mem::size_of::<DataType>()
+        + match value {
+            DataType::String(s) => s.len(),
+            _ => 0,
+        }
+}
+
+/// Bytes charged to a meter until dropped. Values whose copies share their
+/// contents, like arrays, hold it in an `Rc` so the copies are charged once.
+#[derive(Debug)]
+pub struct Charge {
+    bytes: Cell<usize>,
+    meter: Meter,
+}
+
+/// Charges the bytes `size` works out for a new value to the current meter,
+/// or returns `None` when there is none, so the size is only worked out
+/// when it's needed.
+pub fn charge(size: impl FnOnce() -> usize) -> Option<Charge> {
+    let meter = CURRENT.with(|current| current.borrow().clone())?;
+    let bytes = size();
+    meter.add(bytes);
+    Some(Charge {
+        bytes: Cell::new(bytes),
+        meter,
+    })
+}
+
+/// [`charge`] for values whose copies share their contents.
+pub fn charge_shared(size: impl FnOnce() -> usize) -> Option<Rc<Charge>> {
+    charge(size).map(Rc::new)
+}
+
+impl Charge {
+    /// Updates the charge for a value that now holds `added` bytes more and
+    /// `removed` bytes less than before.
+    pub fn resize(&self, removed: usize, added: usize) {
+        let bytes = self.bytes.get();
+        let resized = (bytes + added).saturating_sub(removed);
+        self.bytes.set(resized);
+        self.meter.add(resized);
+        self.meter.remove(bytes);
+    }
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.meter.remove(self.bytes.get());
+    }
+}
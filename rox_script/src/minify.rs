@@ -0,0 +1,730 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::bundle::{removable_declaration, Bundle};
+use crate::error::LoxError;
+use crate::expr::{
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
+};
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner;
+use crate::stack;
+use crate::stmt::{
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::token::{DataType, Token, TokenType, KEYWORDS};
+use crate::types::Type;
+use crate::visitor::{ExprVisitor, StmtVisitor};
+
+/// Scans, parses and resolves `source` and writes it back out minified.
+pub fn minify(source: String) -> Result<String> {
+    let tokens = scanner::run(source, 1)?;
+    // The `#lang` line is kept so the script is parsed at the same version.
+    let directive = match tokens.first() {
+        Some(token) if token.token_type == TokenType::LANG => format!("{}\n", token.lexeme.trim()),
+        _ => String::new(),
+    };
+    let identifiers = tokens
+        .iter()
+        .filter(|token| token.token_type == TokenType::IDENTIFIER)
+        .map(|token| token.lexeme.to_string())
+        .collect();
+    let statements = Parser::new(tokens).parse()?;
+    let interpreter = Interpreter::new(Limits::default());
+    Resolver::new(&interpreter).resolve(statements.clone())?;
+    Ok(directive + &Minifier::new(identifiers).minify(&statements)?)
+}
+
+/// Writes a program back out as compact source: comments and whitespace are
+/// dropped, and local variables and parameters get the shortest names that
+/// don't clash with any identifier in the program.
+///
+/// Globals, properties, the names of functions and classes and annotated
+/// parameters are kept, since they can be seen from outside the script,
+/// when a value is printed or in type errors. `for` loops come out as the
+/// `while` loops they desugar to. Scripts that mention `locals` keep every
+/// name, as `locals()` shows them.
+pub struct Minifier {
+    out: String,
+    /// The new name of each local in scope, innermost scope last. Names not
+    /// found here are globals and keep their name.
+    scopes: Vec<HashMap<String, String>>,
+    /// How many short names the enclosing scopes have handed out. Names are
+    /// reused by sibling scopes but never by nested ones.
+    next_name: usize,
+    /// Every identifier in the program plus the keywords, none of which can
+    /// be used as a new name.
+    reserved: HashSet<String>,
+    rename_locals: bool,
+    /// Set when writing a bundle, in which case globals are renamed and
+    /// imports inlined.
+    bundle: Option<Bundle>,
+}
+
+impl Minifier {
+    pub fn new(identifiers: HashSet<String>) -> Self {
+        let rename_locals = !identifiers.contains("locals");
+        let mut reserved = identifiers;
+        reserved.extend(KEYWORDS.iter().map(|(keyword, _)| keyword.to_string()));
+        reserved.insert("in".to_string());
+        Minifier {
+            out: String::new(),
+            scopes: vec![],
+            next_name: 0,
+            reserved,
+            rename_locals,
+            bundle: None,
+        }
+    }
+
+    pub fn minify(mut self, statements: &[Rc<Stmt>]) -> Result<String> {
+        self.statements(statements)?;
+        self.out.push('\n');
+        Ok(self.out)
+    }
+
+    /// Writes the entry script of `bundle`, handing the bundle back with
+    /// what was learned about the globals.
+    pub(crate) fn bundle(
+        mut self,
+        statements: &[Rc<Stmt>],
+        bundle: Bundle,
+    ) -> Result<(String, Bundle)> {
+        self.bundle = Some(bundle);
+        self.statements(statements)?;
+        self.out.push('\n');
+        let Some(bundle) = self.bundle else {
+            unreachable!("the bundle was set above");
+        };
+        Ok((self.out, bundle))
+    }
+
+    /// Appends `text`, with a space first only where it would otherwise run
+    /// into the previous word.
+    fn emit(&mut self, text: &str) {
+        let word = |c: char| c.is_alphanumeric() || c == '_';
+        if let (Some(last), Some(first)) = (self.out.chars().last(), text.chars().next()) {
+            if word(last) && word(first) {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(text);
+    }
+
+    fn expr(&mut self, expr: &Rc<Expr>) -> Result<()> {
+        stack::grow(|| expr.accept(self))?;
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Rc<Stmt>) -> Result<()> {
+        stack::grow(|| stmt.accept(self))?;
+        Ok(())
+    }
+
+    fn statements(&mut self, statements: &[Rc<Stmt>]) -> Result<()> {
+        for statement in statements {
+            if self.scopes.is_empty() && self.bundle.is_some() {
+                self.top_level(statement)?;
+            } else {
+                self.stmt(statement)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a top-level statement of a bundle, recording the globals it
+    /// refers to. Declarations the first pass found nothing uses are left
+    /// out.
+    fn top_level(&mut self, statement: &Rc<Stmt>) -> Result<()> {
+        let Some(bundle) = &mut self.bundle else {
+            return self.stmt(statement);
+        };
+        // Exports of the entry script are kept for whoever runs it.
+        let removable = match statement.as_ref() {
+            Stmt::Export(_) if bundle.current.is_entry => None,
+            Stmt::Export(export) => removable_declaration(&export.declaration),
+            _ => removable_declaration(statement),
+        }
+        .map(|name| bundle.current.global(name));
+        if let (Some(name), Some(live)) = (&removable, &bundle.live) {
+            if !live.contains(name) {
+                return Ok(());
+            }
+        }
+
+        let enclosing = std::mem::take(&mut bundle.references);
+        self.stmt(statement)?;
+        let Some(bundle) = &mut self.bundle else {
+            return Ok(());
+        };
+        let references = std::mem::replace(&mut bundle.references, enclosing);
+        match removable {
+            Some(name) => bundle
+                .dependencies
+                .entry(name)
+                .or_default()
+                .extend(references),
+            None => bundle.roots.extend(references),
+        }
+        Ok(())
+    }
+
+    /// Emits `{ statements }` in a scope of its own.
+    fn block(&mut self, statements: &[Rc<Stmt>]) -> Result<()> {
+        self.emit("{");
+        self.scoped(|minifier| minifier.statements(statements))?;
+        self.emit("}");
+        Ok(())
+    }
+
+    fn scoped(&mut self, body: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        let enclosing_next_name = self.next_name;
+        self.scopes.push(HashMap::new());
+        let result = body(self);
+        self.scopes.pop();
+        self.next_name = enclosing_next_name;
+        result
+    }
+
+    /// Declares a local, returning the name to write for it. Globals keep
+    /// their name unless they belong to a bundled module.
+    fn declare(&mut self, name: &str) -> String {
+        if self.scopes.is_empty() {
+            return self.global(name);
+        }
+        if !self.rename_locals {
+            return self.keep(name);
+        }
+        let short = loop {
+            let candidate = short_name(self.next_name);
+            self.next_name += 1;
+            if !self.reserved.contains(&candidate) {
+                break candidate;
+            }
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), short.clone());
+        }
+        short
+    }
+
+    /// Declares a local that keeps its name, returning the name to write
+    /// for it. Globals keep theirs unless they belong to a bundled module.
+    fn keep(&mut self, name: &str) -> String {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.to_string(), name.to_string());
+                name.to_string()
+            }
+            None => self.global(name),
+        }
+    }
+
+    /// The name to write for the global `name`.
+    fn global(&self, name: &str) -> String {
+        match &self.bundle {
+            Some(bundle) => bundle.current.global(name),
+            None => name.to_string(),
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains_key(name))
+    }
+
+    fn lookup(&mut self, name: &str) -> String {
+        if let Some(local) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return local.clone();
+        }
+        let global = self.global(name);
+        if let Some(bundle) = &mut self.bundle {
+            bundle.references.insert(global.clone());
+        }
+        global
+    }
+
+    fn annotation(&mut self, annotation: &Option<Type>) {
+        let Some(annotation) = annotation else {
+            return;
+        };
+        self.emit(":");
+        match (annotation, &self.bundle) {
+            // Classes are matched by name, so annotations follow the class
+            // when a bundle renames it.
+            (Type::Class(name), Some(bundle)) => {
+                let class = if bundle.current.globals.contains_key(name) {
+                    bundle.current.global(name)
+                } else {
+                    bundle
+                        .modules
+                        .values()
+                        .find(|module| module.exports.contains(name))
+                        .map_or_else(|| name.clone(), |module| module.global(name))
+                };
+                self.emit(&class);
+            }
+            _ => self.emit(&annotation.to_string()),
+        }
+    }
+
+    /// Emits the parameters and body of a function or method.
+    fn function(&mut self, stmt: &FunctionStmt) -> Result<()> {
+        self.scoped(|minifier| {
+            minifier.emit("(");
+            for (i, (param, annotation)) in
+                stmt.params.iter().zip(stmt.param_types.iter()).enumerate()
+            {
+                if i > 0 {
+                    minifier.emit(",");
+                }
+                // Type errors name annotated parameters, so those keep theirs.
+                if annotation.is_some() {
+                    minifier.keep(&param.lexeme);
+                    minifier.emit(&param.lexeme);
+                } else {
+                    let name = minifier.declare(&param.lexeme);
+                    minifier.emit(&name);
+                }
+                minifier.annotation(annotation);
+            }
+            minifier.emit(")");
+            minifier.annotation(&stmt.return_type);
+            minifier.emit("{");
+            minifier.statements(&stmt.body)?;
+            minifier.emit("}");
+            Ok(())
+        })
+    }
+
+    fn list(&mut self, exprs: &[Rc<Expr>]) -> Result<()> {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.emit(",");
+            }
+            self.expr(expr)?;
+        }
+        Ok(())
+    }
+
+    fn name(&mut self, token: &Token) -> Result<()> {
+        if let Some(bundle) = &self.bundle {
+            if !self.is_local(&token.lexeme)
+                && bundle
+                    .current
+                    .namespaces
+                    .contains_key(token.lexeme.as_str())
+            {
+                return Err(LoxError::resolve(
+                    token,
+                    "Can only read exports from a module namespace when bundling.",
+                )
+                .into());
+            }
+        }
+        let name = self.lookup(&token.lexeme);
+        self.emit(&name);
+        Ok(())
+    }
+
+    /// The name to write for `namespace.name` when `namespace` is a module
+    /// that has been inlined into the bundle.
+    fn export(&mut self, expr: &GetExpr) -> Result<Option<String>> {
+        let Expr::Var(namespace) = expr.object.as_ref() else {
+            return Ok(None);
+        };
+        let namespace = &namespace.var_name.lexeme;
+        if self.is_local(namespace) {
+            return Ok(None);
+        }
+        let Some(bundle) = &mut self.bundle else {
+            return Ok(None);
+        };
+        let Some(file) = bundle.current.namespaces.get(namespace.as_str()) else {
+            return Ok(None);
+        };
+        let Some(module) = bundle.modules.get(file) else {
+            return Ok(None);
+        };
+        if !module.exports.contains(expr.name.lexeme.as_str()) {
+            return Err(LoxError::resolve(
+                &expr.name,
+                format!(
+                    "<module {}> does not export '{}'.",
+                    file.display(),
+                    expr.name.lexeme
+                ),
+            )
+            .into());
+        }
+        let global = module.global(&expr.name.lexeme);
+        bundle.references.insert(global.clone());
+        Ok(Some(global))
+    }
+}
+
+/// The `index`th shortest identifier: `a` to `z`, `A` to `Z`, then two
+/// letters and so on.
+fn short_name(mut index: usize) -> String {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut name = vec![LETTERS[index % LETTERS.len()]];
+    index /= LETTERS.len();
+    while index > 0 {
+        index -= 1;
+        name.push(LETTERS[index % LETTERS.len()]);
+        index /= LETTERS.len();
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+impl ExprVisitor for Minifier {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<DataType> {
+        let text = match &expr.value {
+            None | Some(DataType::Nil) => "nil".to_string(),
+            // `nan` and `inf` are globals a script may have shadowed.
+            Some(DataType::Number(n)) if n.is_nan() => "(0/0)".to_string(),
+            Some(DataType::Number(n)) if n.is_infinite() => format!("({}/0)", n.signum()),
+            Some(DataType::Number(n)) => n.to_string(),
+            Some(value) => value.repr(),
+        };
+        self.emit(&text);
+        Ok(DataType::Nil)
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<DataType> {
+        self.emit(&expr.operator.lexeme);
+        self.expr(&expr.right)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<DataType> {
+        self.expr(&expr.left)?;
+        self.emit(&expr.operator.lexeme);
+        self.expr(&expr.right)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<DataType> {
+        self.expr(&expr.callee)?;
+        self.emit("(");
+        self.list(&expr.arguments)?;
+        self.emit(")");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<DataType> {
+        self.emit("(");
+        self.expr(&expr.expression)?;
+        self.emit(")");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> Result<DataType> {
+        self.name(&expr.var_name)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<DataType> {
+        self.name(&expr.var_name)?;
+        self.emit("=");
+        self.expr(&expr.var_value)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<DataType> {
+        self.expr(&expr.left)?;
+        self.emit(&expr.operator.lexeme);
+        self.expr(&expr.right)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<DataType> {
+        if let Some(export) = self.export(expr)? {
+            self.emit(&export);
+            return Ok(DataType::Nil);
+        }
+        self.expr(&expr.object)?;
+        self.emit(".");
+        self.emit(&expr.name.lexeme);
+        Ok(DataType::Nil)
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<DataType> {
+        self.expr(&expr.object)?;
+        self.emit(".");
+        self.emit(&expr.name.lexeme);
+        self.emit("=");
+        self.expr(&expr.value)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ThisExpr) -> Result<DataType> {
+        self.emit("this");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<DataType> {
+        self.emit("super.");
+        self.emit(&expr.method.lexeme);
+        Ok(DataType::Nil)
+    }
+
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> Result<DataType> {
+        self.emit("[");
+        self.list(&expr.elements)?;
+        self.emit("]");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<DataType> {
+        self.emit("{");
+        for (i, (key, value)) in expr.entries.iter().enumerate() {
+            if i > 0 {
+                self.emit(",");
+            }
+            self.expr(key)?;
+            self.emit(":");
+            self.expr(value)?;
+        }
+        self.emit("}");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<DataType> {
+        self.expr(&expr.object)?;
+        self.emit("[");
+        self.expr(&expr.index)?;
+        self.emit("]");
+        Ok(DataType::Nil)
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> Result<DataType> {
+        self.expr(&expr.object)?;
+        self.emit("[");
+        self.expr(&expr.index)?;
+        self.emit("]=");
+        self.expr(&expr.value)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> Result<DataType> {
+        self.emit("try");
+        self.expr(&expr.expression)?;
+        Ok(DataType::Nil)
+    }
+}
+
+impl StmtVisitor for Minifier {
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<Option<DataType>> {
+        self.emit("print");
+        self.expr(&stmt.expression)?;
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.expression)?;
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<Option<DataType>> {
+        self.emit("var");
+        // The initializer can't see the variable it initializes, so it is
+        // written before the variable is declared.
+        let mut initializer = String::new();
+        if let Some(value) = &stmt.var_value {
+            let out = std::mem::take(&mut self.out);
+            self.emit("=");
+            self.expr(value)?;
+            initializer = std::mem::replace(&mut self.out, out);
+        }
+        let name = self.declare(&stmt.var_name.lexeme);
+        self.emit(&name);
+        self.emit(&initializer);
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<Option<DataType>> {
+        self.block(&stmt.statements)?;
+        Ok(None)
+    }
+
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<Option<DataType>> {
+        self.emit("if(");
+        self.expr(&stmt.condition)?;
+        self.emit(")");
+        self.stmt(&stmt.then_branch)?;
+        if let Some(else_branch) = &stmt.else_branch {
+            self.emit("else");
+            self.stmt(else_branch)?;
+        }
+        Ok(None)
+    }
+
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<Option<DataType>> {
+        self.emit("while(");
+        self.expr(&stmt.condition)?;
+        self.emit(")");
+        self.stmt(&stmt.body)?;
+        Ok(None)
+    }
+
+    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<Option<DataType>> {
+        self.emit("fun");
+        let name = self.keep(&stmt.name.lexeme);
+        self.emit(&name);
+        self.function(stmt)?;
+        Ok(None)
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<Option<DataType>> {
+        self.emit("return");
+        if let Some(value) = &stmt.value {
+            self.expr(value)?;
+        }
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<Option<DataType>> {
+        self.emit("class");
+        let name = self.keep(&stmt.name.lexeme);
+        self.emit(&name);
+        if let Some(super_class) = &stmt.super_class {
+            self.emit("<");
+            self.visit_var_expr(super_class)?;
+        }
+        self.emit("{");
+        for method in &stmt.methods {
+            self.emit(&method.name.lexeme);
+            self.function(method)?;
+        }
+        self.emit("}");
+        Ok(None)
+    }
+
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> Result<Option<DataType>> {
+        self.emit("throw");
+        self.expr(&stmt.value)?;
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> Result<Option<DataType>> {
+        self.emit("try");
+        self.block(&stmt.body)?;
+        if let Some(catch) = &stmt.catch {
+            self.scoped(|minifier| {
+                minifier.emit("catch(");
+                let name = minifier.declare(&catch.name.lexeme);
+                minifier.emit(&name);
+                minifier.emit(")");
+                minifier.block(&catch.body)
+            })?;
+        }
+        if let Some(finally) = &stmt.finally {
+            self.emit("finally");
+            self.block(finally)?;
+        }
+        Ok(None)
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> Result<Option<DataType>> {
+        if let Some(bundle) = &mut self.bundle {
+            if !stmt.path.starts_with("std/") {
+                // A module runs where it is first imported, so that is where
+                // its code goes.
+                let file = bundle.current.import_path(stmt);
+                if !bundle.inlined.insert(file.clone()) {
+                    return Ok(None);
+                }
+                let Some(module) = bundle.modules.get(&file).cloned() else {
+                    return Ok(None);
+                };
+                let importer = std::mem::replace(&mut bundle.current, Rc::clone(&module));
+                let result = self.statements(&module.statements);
+                if let Some(bundle) = &mut self.bundle {
+                    bundle.current = importer;
+                }
+                result?;
+                return Ok(None);
+            }
+        }
+        let name = self.global(&stmt.name);
+        self.emit("import");
+        self.emit(&DataType::String(stmt.path.clone()).repr());
+        self.emit("as");
+        self.emit(&name);
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> Result<Option<DataType>> {
+        self.emit("assert");
+        self.expr(&stmt.condition)?;
+        if let Some(message) = &stmt.message {
+            self.emit(",");
+            self.expr(message)?;
+        }
+        self.emit(";");
+        Ok(None)
+    }
+
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> Result<Option<DataType>> {
+        // Bundled modules' exports are read by their new names instead.
+        if self
+            .bundle
+            .as_ref()
+            .is_none_or(|bundle| bundle.current.is_entry)
+        {
+            self.emit("export");
+        }
+        self.stmt(&stmt.declaration)?;
+        Ok(None)
+    }
+
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> Result<Option<DataType>> {
+        self.emit("switch(");
+        self.expr(&stmt.subject)?;
+        self.emit("){");
+        for case in &stmt.cases {
+            self.emit("case");
+            self.expr(&case.value)?;
+            self.emit(":");
+            self.scoped(|minifier| minifier.statements(&case.body))?;
+        }
+        if let Some(default) = &stmt.default {
+            self.emit("default:");
+            self.scoped(|minifier| minifier.statements(default))?;
+        }
+        self.emit("}");
+        Ok(None)
+    }
+
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> Result<Option<DataType>> {
+        self.emit("for(");
+        // The iterable is evaluated outside the loop variable's scope.
+        let out = std::mem::take(&mut self.out);
+        self.emit("in");
+        self.expr(&stmt.iterable)?;
+        let iterable = std::mem::replace(&mut self.out, out);
+        self.scoped(|minifier| {
+            let name = minifier.declare(&stmt.name.lexeme);
+            minifier.emit(&name);
+            minifier.emit(&iterable);
+            minifier.emit(")");
+            minifier.stmt(&stmt.body)
+        })?;
+        Ok(None)
+    }
+}
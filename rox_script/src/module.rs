@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::environment::Environment;
+use crate::error::LoxError;
+use crate::symbol::Symbol;
+use crate::token::{DataType, Token};
+
+/// The namespace an `import` binds. It exposes the exported names of the
+/// module's top-level scope, read live so later updates inside the module
+/// are visible to importers.
+#[derive(Clone)]
+pub struct LoxModule {
+    pub path: String,
+    environment: Rc<RefCell<Environment>>,
+    pub exports: Rc<RefCell<HashSet<Symbol>>>,
+}
+
+impl LoxModule {
+    pub fn new(
+        path: String,
+        environment: Rc<RefCell<Environment>>,
+        exports: Rc<RefCell<HashSet<Symbol>>>,
+    ) -> Self {
+        LoxModule {
+            path,
+            environment,
+            exports,
+        }
+    }
+
+    pub(crate) fn environment(&self) -> &Rc<RefCell<Environment>> {
+        &self.environment
+    }
+
+    pub fn get(&self, name: &Token) -> Result<DataType> {
+        if !self.exports.borrow().contains(&name.lexeme) {
+            return Err(LoxError::runtime(
+                name,
+                format!("{} does not export '{}'.", self, name.lexeme),
+            )
+            .into());
+        }
+        self.environment.borrow().get(&name.lexeme).ok_or_else(|| {
+            LoxError::runtime(
+                name,
+                format!("'{}' in {} is not defined yet.", name.lexeme, self),
+            )
+            .into()
+        })
+    }
+}
+
+impl Display for LoxModule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<module {}>", self.path)
+    }
+}
+
+impl Debug for LoxModule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
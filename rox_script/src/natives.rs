@@ -0,0 +1,1057 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use rox_frontend::natives::{natives, Host, Native, CONSTANTS};
+
+use crate::collections::{LoxArray, LoxMap, MapKey};
+use crate::environment::Environment;
+use crate::error::Exit;
+use crate::functions::{as_callable, LoxCallable, LoxNative};
+use crate::interpreter::Interpreter;
+use crate::sandbox::{Capability, RoxSandbox};
+use crate::token::DataType;
+use crate::worker::{Channel, Message};
+
+/// Natives by name, as defined in an environment.
+pub type Members = Rc<[(&'static str, DataType)]>;
+
+thread_local! {
+    /// The natives every interpreter on this thread defines, made by the
+    /// first one so later ones, like a server's interpreter per request,
+    /// only copy the handles into their own builtins.
+    static PRELUDE: Members = prelude();
+}
+
+/// Defines every native function in `globals`, and the numbers `nan` and
+/// `inf`: the ones shared with the bytecode VM first, then the
+/// tree-walker's own.
+pub fn define_natives(globals: &mut Environment, sandbox: &RoxSandbox) {
+    PRELUDE.with(|prelude| {
+        for (name, native) in prelude.iter() {
+            globals.define(*name, Some(native.clone()));
+        }
+    });
+    if sandbox.allows(Capability::Env) {
+        globals.define("args", Some(native(Args::new("args".to_string()))));
+    }
+}
+
+fn prelude() -> Members {
+    let mut members: Vec<_> = CONSTANTS
+        .iter()
+        .map(|(name, number)| (*name, DataType::Number(*number)))
+        .collect();
+    for Native {
+        name,
+        arity,
+        function,
+    } in natives::<Interpreter>()
+    {
+        define(&mut members, name, |name| {
+            HostFunction::with_interpreter(name, arity, function)
+        });
+    }
+    define(&mut members, "bindThis", BindThis::new);
+    define(&mut members, "toNumber", ToNumber::new);
+    define(&mut members, "repr", Repr::new);
+    define(&mut members, "len", Len::new);
+    define(&mut members, "push", Push::new);
+    define(&mut members, "on", On::new);
+    define(&mut members, "emit", Emit::new);
+    define(&mut members, "spawnWorker", SpawnWorker::new);
+    define(&mut members, "send", SendMessage::new);
+    define(&mut members, "receive", ReceiveMessage::new);
+    define(&mut members, "sleep", Sleep::new);
+    define(&mut members, "setTimeout", SetTimeout::new);
+    define(&mut members, "clearTimeout", ClearTimeout::new);
+    define(&mut members, "runPending", RunPending::new);
+    define(&mut members, "globals", Globals::new);
+    define(&mut members, "locals", Locals::new);
+    define(&mut members, "exit", ExitScript::new);
+    define(&mut members, "atExit", AtExit::new);
+    members.into()
+}
+
+fn define<T: LoxCallable + 'static>(
+    members: &mut Vec<(&'static str, DataType)>,
+    name: &'static str,
+    new: impl FnOnce(String) -> T,
+) {
+    members.push((name, native(new(name.to_string()))));
+}
+
+fn native(function: impl LoxCallable + 'static) -> DataType {
+    DataType::NativeFunction(LoxNative {
+        function: Rc::new(function),
+    })
+}
+
+impl Host for Interpreter {
+    type Value = DataType;
+
+    fn number(&self, number: f64) -> DataType {
+        DataType::Number(number)
+    }
+
+    fn clock(&mut self) -> f64 {
+        Interpreter::clock(self)
+    }
+}
+
+/// `bindThis(method, instance)` returns `method` with `this` bound to
+/// `instance`. Only methods taken from an instance can be rebound.
+pub struct BindThis {
+    name: String,
+}
+
+impl BindThis {
+    pub fn new(name: String) -> BindThis {
+        BindThis { name }
+    }
+}
+
+impl LoxCallable for BindThis {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<DataType>) -> anyhow::Result<DataType> {
+        match (&arguments[0], &arguments[1]) {
+            (DataType::Function(method), DataType::Instance(instance)) => method
+                .rebind(instance.clone())
+                .map(DataType::Function)
+                .ok_or_else(|| anyhow!("{} can only rebind methods, got {}.", self, method)),
+            (method, instance) => Err(anyhow!(
+                "{} expects a method and an instance, got {} and {}.",
+                self,
+                method,
+                instance
+            )),
+        }
+    }
+}
+
+impl Display for BindThis {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for BindThis {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `toNumber(value)` converts a string to a number using the grammar of
+/// plain decimal literals, without underscores, hex or exponents, plus `nan`,
+/// `inf` and a leading sign. Only `.` is accepted as the decimal separator,
+/// whatever the host locale. Numbers are returned unchanged; anything that
+/// does not convert gives `nil`.
+pub struct ToNumber {
+    name: String,
+}
+
+impl ToNumber {
+    pub fn new(name: String) -> ToNumber {
+        ToNumber { name }
+    }
+}
+
+impl LoxCallable for ToNumber {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<DataType>) -> anyhow::Result<DataType> {
+        Ok(match &arguments[0] {
+            DataType::Number(n) => DataType::Number(*n),
+            DataType::String(s) => parse_number(s.trim()).map_or(DataType::Nil, DataType::Number),
+            _ => DataType::Nil,
+        })
+    }
+}
+
+impl Display for ToNumber {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for ToNumber {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// Parses `[+-]digits[.digits]`, `[+-]inf` or `nan`. The grammar is checked
+/// here rather than left to `f64::from_str`, which also accepts exponents and
+/// spellings such as `infinity`.
+fn parse_number(text: &str) -> Option<f64> {
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Some(b'-') => (true, &text[1..]),
+        Some(b'+') => (false, &text[1..]),
+        _ => (false, text),
+    };
+    let magnitude = match unsigned {
+        "nan" => f64::NAN,
+        "inf" => f64::INFINITY,
+        _ => {
+            let (whole, fraction) = match unsigned.split_once('.') {
+                Some((whole, fraction)) => (whole, Some(fraction)),
+                None => (unsigned, None),
+            };
+            let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+            if !digits(whole) || !fraction.is_none_or(digits) {
+                return None;
+            }
+            unsigned.parse().ok()?
+        }
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// `repr(value)` returns an unambiguous string form of `value`, with strings
+/// quoted and escaped, for use in debugging output.
+pub struct Repr {
+    name: String,
+}
+
+impl Repr {
+    pub fn new(name: String) -> Repr {
+        Repr { name }
+    }
+}
+
+impl LoxCallable for Repr {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<DataType>) -> anyhow::Result<DataType> {
+        Ok(DataType::String(arguments[0].repr()))
+    }
+}
+
+impl Display for Repr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Repr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `len(value)` returns the number of elements in an array, entries in a map
+/// or characters in a string.
+pub struct Len {
+    name: String,
+}
+
+impl Len {
+    pub fn new(name: String) -> Len {
+        Len { name }
+    }
+}
+
+impl LoxCallable for Len {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<DataType>) -> anyhow::Result<DataType> {
+        let length = match &arguments[0] {
+            DataType::Array(array) => array.elements.borrow().len(),
+            DataType::Map(map) => map.entries.borrow().len(),
+            DataType::String(string) => string.chars().count(),
+            other => {
+                return Err(anyhow!(
+                    "{} expects an array, map or string, got {}.",
+                    self,
+                    other
+                ))
+            }
+        };
+        Ok(DataType::Number(length as f64))
+    }
+}
+
+impl Display for Len {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Len {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `push(array, value)` appends `value` to the end of `array`.
+pub struct Push {
+    name: String,
+}
+
+impl Push {
+    pub fn new(name: String) -> Push {
+        Push { name }
+    }
+}
+
+impl LoxCallable for Push {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        match &arguments[0] {
+            DataType::Array(array) => {
+                let length = array.elements.borrow().len() + 1;
+                interpreter
+                    .limits
+                    .check_collection(&format!("{}()", self.name), length)?;
+                array.push(arguments[1].clone());
+                Ok(DataType::Nil)
+            }
+            other => Err(anyhow!("{} expects an array, got {}.", self, other)),
+        }
+    }
+}
+
+impl Display for Push {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Push {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `on(event, listener)` registers `listener`, a function taking one
+/// argument, to be called with the payload of every later `emit(event, ...)`.
+pub struct On {
+    name: String,
+}
+
+impl On {
+    pub fn new(name: String) -> On {
+        On { name }
+    }
+}
+
+impl LoxCallable for On {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let event = match &arguments[0] {
+            DataType::String(event) => event.clone(),
+            other => {
+                return Err(anyhow!(
+                    "{} expects an event name string, got {}.",
+                    self,
+                    other
+                ))
+            }
+        };
+        match as_callable(&arguments[1]) {
+            Some(listener) if listener.arity() == 1 => {
+                interpreter.on(event, listener);
+                Ok(DataType::Nil)
+            }
+            _ => Err(anyhow!(
+                "{} expects a listener taking one argument, got {}.",
+                self,
+                arguments[1]
+            )),
+        }
+    }
+}
+
+impl Display for On {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for On {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `emit(event, payload)` calls every listener of `event` with `payload` and
+/// returns how many there were.
+pub struct Emit {
+    name: String,
+}
+
+impl Emit {
+    pub fn new(name: String) -> Emit {
+        Emit { name }
+    }
+}
+
+impl LoxCallable for Emit {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        match &arguments[0] {
+            DataType::String(event) => {
+                let called = interpreter.emit(event, arguments[1].clone())?;
+                Ok(DataType::Number(called as f64))
+            }
+            other => Err(anyhow!(
+                "{} expects an event name string, got {}.",
+                self,
+                other
+            )),
+        }
+    }
+}
+
+impl Display for Emit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Emit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `spawnWorker(source)` runs `source` on another thread in an interpreter
+/// of its own and returns the worker's id, for `send` and `receive`.
+pub struct SpawnWorker {
+    name: String,
+}
+
+impl SpawnWorker {
+    pub fn new(name: String) -> SpawnWorker {
+        SpawnWorker { name }
+    }
+}
+
+impl LoxCallable for SpawnWorker {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        if interpreter.deterministic() {
+            return Err(anyhow!("{} can't be used in a deterministic run.", self));
+        }
+        match &arguments[0] {
+            DataType::String(source) => {
+                let id = interpreter.spawn_worker(source.clone())?;
+                Ok(DataType::Number(id as f64))
+            }
+            other => Err(anyhow!("{} expects a source string, got {}.", self, other)),
+        }
+    }
+}
+
+impl Display for SpawnWorker {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for SpawnWorker {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// The connection to the worker `value` is the id of, or to the spawner
+/// when it is `parent`.
+fn worker<'a>(
+    native: &dyn Display,
+    interpreter: &'a Interpreter,
+    value: &DataType,
+) -> anyhow::Result<&'a Channel> {
+    match value {
+        DataType::Number(id) if id.fract() == 0.0 && *id >= 0.0 => interpreter.worker(*id as usize),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("{} expects a worker, got {}.", native, value))
+}
+
+/// `send(worker, value)` sends a copy of `value`, which must be plain data,
+/// to `worker`. Workers send to whoever spawned them with `send(parent, value)`.
+pub struct SendMessage {
+    name: String,
+}
+
+impl SendMessage {
+    pub fn new(name: String) -> SendMessage {
+        SendMessage { name }
+    }
+}
+
+impl LoxCallable for SendMessage {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let message = Message::from_value(&arguments[1])?;
+        worker(self, interpreter, &arguments[0])?.send(message);
+        Ok(DataType::Nil)
+    }
+}
+
+impl Display for SendMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for SendMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `receive(worker)` waits for the next value `worker` sends. Once it has
+/// finished and everything it sent has been received, it returns nil. If
+/// its script failed, so does the `receive`. Like `sleep`, it stops waiting
+/// when the script is cancelled or runs out of time.
+pub struct ReceiveMessage {
+    name: String,
+}
+
+impl ReceiveMessage {
+    pub fn new(name: String) -> ReceiveMessage {
+        ReceiveMessage { name }
+    }
+}
+
+impl LoxCallable for ReceiveMessage {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let channel = worker(self, interpreter, &arguments[0])?;
+        let message = interpreter.receive(channel)?;
+        Ok(message.map_or(DataType::Nil, |message| message.to_value()))
+    }
+}
+
+impl Display for ReceiveMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for ReceiveMessage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// The delay `value` gives in milliseconds, which can't be negative.
+fn delay(native: &dyn Display, value: &DataType) -> anyhow::Result<Duration> {
+    match value {
+        DataType::Number(ms) if *ms >= 0.0 && ms.is_finite() => {
+            Ok(Duration::from_secs_f64(ms / 1000.0))
+        }
+        _ => Err(anyhow!(
+            "{} expects a delay in milliseconds, got {}.",
+            native,
+            value
+        )),
+    }
+}
+
+/// `sleep(ms)` pauses the script for `ms` milliseconds. Cancelling the
+/// script or it running out of time wakes it early with an error.
+pub struct Sleep {
+    name: String,
+}
+
+impl Sleep {
+    pub fn new(name: String) -> Sleep {
+        Sleep { name }
+    }
+}
+
+impl LoxCallable for Sleep {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        interpreter.sleep(delay(self, &arguments[0])?)?;
+        Ok(DataType::Nil)
+    }
+}
+
+impl Display for Sleep {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Sleep {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `setTimeout(callback, ms)` registers `callback`, a function taking no
+/// arguments, to be called by `runPending` once `ms` milliseconds have
+/// passed, and returns an id for `clearTimeout`.
+pub struct SetTimeout {
+    name: String,
+}
+
+impl SetTimeout {
+    pub fn new(name: String) -> SetTimeout {
+        SetTimeout { name }
+    }
+}
+
+impl LoxCallable for SetTimeout {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let delay = delay(self, &arguments[1])?;
+        match as_callable(&arguments[0]) {
+            Some(callback) if callback.arity() == 0 => {
+                let id = interpreter.set_timeout(callback, delay);
+                Ok(DataType::Number(id as f64))
+            }
+            _ => Err(anyhow!(
+                "{} expects a callback taking no arguments, got {}.",
+                self,
+                arguments[0]
+            )),
+        }
+    }
+}
+
+impl Display for SetTimeout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for SetTimeout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `clearTimeout(id)` drops the timer `setTimeout` returned `id` for before
+/// it runs, returning whether it was still pending.
+pub struct ClearTimeout {
+    name: String,
+}
+
+impl ClearTimeout {
+    pub fn new(name: String) -> ClearTimeout {
+        ClearTimeout { name }
+    }
+}
+
+impl LoxCallable for ClearTimeout {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        match &arguments[0] {
+            DataType::Number(id) if id.fract() == 0.0 && *id >= 0.0 => {
+                Ok(DataType::Bool(interpreter.clear_timeout(*id as usize)))
+            }
+            other => Err(anyhow!("{} expects a timer id, got {}.", self, other)),
+        }
+    }
+}
+
+impl Display for ClearTimeout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for ClearTimeout {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `runPending()` waits for and runs every pending `setTimeout` callback,
+/// including ones registered while it runs, in the order they fall due,
+/// and returns how many ran.
+pub struct RunPending {
+    name: String,
+}
+
+impl RunPending {
+    pub fn new(name: String) -> RunPending {
+        RunPending { name }
+    }
+}
+
+impl LoxCallable for RunPending {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: Vec<DataType>) -> anyhow::Result<DataType> {
+        let ran = interpreter.run_pending()?;
+        Ok(DataType::Number(ran as f64))
+    }
+}
+
+impl Display for RunPending {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for RunPending {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `args()` returns a new array of the command line arguments given after
+/// the script's path, as strings.
+pub struct Args {
+    name: String,
+}
+
+impl Args {
+    pub fn new(name: String) -> Args {
+        Args { name }
+    }
+}
+
+impl LoxCallable for Args {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: Vec<DataType>) -> anyhow::Result<DataType> {
+        Ok(DataType::Array(LoxArray::new(
+            interpreter
+                .args()
+                .iter()
+                .map(|arg| DataType::String(arg.clone()))
+                .collect(),
+        )))
+    }
+}
+
+impl Display for Args {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Args {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// A map from the names of `variables` to their printed values.
+fn printed(variables: BTreeMap<String, DataType>) -> DataType {
+    DataType::Map(LoxMap::new(
+        variables
+            .into_iter()
+            .map(|(name, value)| (MapKey::String(name), DataType::String(value.to_string())))
+            .collect(),
+    ))
+}
+
+/// `globals()` returns a map from the name of every variable, function and
+/// class in the module's top-level scope to its printed value.
+pub struct Globals {
+    name: String,
+}
+
+impl Globals {
+    pub fn new(name: String) -> Globals {
+        Globals { name }
+    }
+}
+
+impl LoxCallable for Globals {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: Vec<DataType>) -> anyhow::Result<DataType> {
+        Ok(printed(interpreter.globals()))
+    }
+}
+
+impl Display for Globals {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Globals {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `locals()` returns a map from the name of every variable visible where
+/// it is called, other than globals, to its printed value. At the top level
+/// it returns the same as `globals()`.
+pub struct Locals {
+    name: String,
+}
+
+impl Locals {
+    pub fn new(name: String) -> Locals {
+        Locals { name }
+    }
+}
+
+impl LoxCallable for Locals {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, _: Vec<DataType>) -> anyhow::Result<DataType> {
+        Ok(printed(interpreter.locals()))
+    }
+}
+
+impl Display for Locals {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for Locals {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// The signature of a closure implementing a native function.
+pub type NativeFn = dyn Fn(&mut Interpreter, &[DataType]) -> anyhow::Result<DataType>;
+
+/// A native implemented by a Rust closure, for hosts that embed the
+/// interpreter and for the functions of the standard modules. The closure
+/// gets exactly `arity` arguments.
+pub struct HostFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl HostFunction {
+    pub fn new(
+        name: String,
+        arity: usize,
+        function: impl Fn(&[DataType]) -> anyhow::Result<DataType> + 'static,
+    ) -> HostFunction {
+        HostFunction::with_interpreter(name, arity, move |_, arguments| function(arguments))
+    }
+
+    /// A native whose closure is also handed the interpreter calling it,
+    /// for output, random numbers and the like.
+    pub fn with_interpreter(
+        name: String,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, &[DataType]) -> anyhow::Result<DataType> + 'static,
+    ) -> HostFunction {
+        HostFunction {
+            name,
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl LoxCallable for HostFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        (self.function)(interpreter, &arguments)
+    }
+}
+
+impl Display for HostFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for HostFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `exit(code)` runs the shutdown hooks, including the functions registered
+/// with `atExit`, then stops the script with exit status `code`, which must
+/// be a whole number. Neither `catch` nor `finally` blocks see it.
+pub struct ExitScript {
+    name: String,
+}
+
+impl ExitScript {
+    pub fn new(name: String) -> ExitScript {
+        ExitScript { name }
+    }
+}
+
+impl LoxCallable for ExitScript {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        let code = match arguments[0] {
+            DataType::Number(code) if code.fract() == 0.0 && code.abs() <= i32::MAX as f64 => {
+                code as i32
+            }
+            ref other => {
+                return Err(anyhow!(
+                    "{} expects a whole number exit code, got {}.",
+                    self,
+                    other
+                ))
+            }
+        };
+        interpreter.shutdown()?;
+        Err(Exit { code }.into())
+    }
+}
+
+impl Display for ExitScript {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for ExitScript {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+/// `atExit(function)` registers a function taking no arguments to call when
+/// the interpreter shuts down, after the script finishes or calls `exit`.
+/// The functions run the most recently registered first.
+pub struct AtExit {
+    name: String,
+}
+
+impl AtExit {
+    pub fn new(name: String) -> AtExit {
+        AtExit { name }
+    }
+}
+
+impl LoxCallable for AtExit {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<DataType>,
+    ) -> anyhow::Result<DataType> {
+        match as_callable(&arguments[0]) {
+            Some(function) if function.arity() == 0 => {
+                interpreter
+                    .on_shutdown(move |interpreter| function.call(interpreter, vec![]).map(|_| ()));
+                Ok(DataType::Nil)
+            }
+            _ => Err(anyhow!(
+                "{} expects a function taking no arguments, got {}.",
+                self,
+                arguments[0]
+            )),
+        }
+    }
+}
+
+impl Display for AtExit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl Debug for AtExit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
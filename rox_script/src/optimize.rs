@@ -0,0 +1,312 @@
+//! The optional pass `--opt` runs between parsing and resolving. It folds
+//! arithmetic, comparisons and negations of literals into literals, drops
+//! the branches of `if` and `while` statements whose condition is a literal,
+//! and replaces `and` and `or` with a literal left operand by the operand
+//! they evaluate to.
+//!
+//! Only what can't fail is folded, so an optimized script fails where the
+//! original would. String concatenation and repetition are left alone since
+//! their results are checked against the string length limit. Code in a
+//! dropped branch is never resolved, so mistakes in it aren't reported.
+
+use std::rc::Rc;
+
+use crate::expr::{
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, TryExpr, UnaryExpr, VarExpr,
+};
+use crate::stmt::{
+    AssertStmt, BlockStmt, CatchClause, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt,
+    IfStmt, PrintStmt, ReturnStmt, Stmt, SwitchCase, SwitchStmt, ThrowStmt, TryStmt, VarStmt,
+    WhileStmt,
+};
+use crate::token::{DataType, TokenType};
+use rox_frontend::value::LoxValue;
+
+/// The optimized form of `statements`. Statements that disappear entirely
+/// are left out.
+pub fn optimize(statements: &[Rc<Stmt>]) -> Vec<Rc<Stmt>> {
+    statements
+        .iter()
+        .map(statement)
+        .filter(|stmt| !is_empty(stmt))
+        .collect()
+}
+
+fn statement(stmt: &Rc<Stmt>) -> Rc<Stmt> {
+    let optimized = match stmt.as_ref() {
+        Stmt::Print(stmt) => Stmt::Print(PrintStmt {
+            keyword: stmt.keyword.clone(),
+            expression: expression(&stmt.expression),
+        }),
+        Stmt::Expr(stmt) => Stmt::Expr(ExprStmt {
+            expression: expression(&stmt.expression),
+        }),
+        Stmt::Var(stmt) => Stmt::Var(VarStmt {
+            var_name: stmt.var_name.clone(),
+            var_value: stmt.var_value.as_ref().map(expression),
+        }),
+        Stmt::Block(stmt) => Stmt::Block(BlockStmt {
+            statements: optimize(&stmt.statements),
+        }),
+        Stmt::If(stmt) => {
+            let condition = expression(&stmt.condition);
+            match literal(&condition) {
+                Some(value) if value.truthy() => return statement(&stmt.then_branch),
+                Some(_) => match &stmt.else_branch {
+                    Some(else_branch) => return statement(else_branch),
+                    None => empty(),
+                },
+                None => Stmt::If(IfStmt {
+                    keyword: stmt.keyword.clone(),
+                    condition,
+                    then_branch: statement(&stmt.then_branch),
+                    else_branch: stmt.else_branch.as_ref().map(statement),
+                }),
+            }
+        }
+        Stmt::While(stmt) => {
+            let condition = expression(&stmt.condition);
+            match literal(&condition) {
+                Some(value) if !value.truthy() => empty(),
+                _ => Stmt::While(WhileStmt {
+                    keyword: stmt.keyword.clone(),
+                    condition,
+                    body: statement(&stmt.body),
+                }),
+            }
+        }
+        Stmt::Function(stmt) => Stmt::Function(function(stmt)),
+        Stmt::Return(stmt) => {
+            let value = stmt.value.as_ref().map(expression);
+            let tail_call = stmt.tail_call && matches!(value.as_deref(), Some(Expr::Call(_)));
+            Stmt::Return(ReturnStmt {
+                keyword: stmt.keyword.clone(),
+                value,
+                tail_call,
+            })
+        }
+        Stmt::Class(stmt) => Stmt::Class(ClassStmt {
+            name: stmt.name.clone(),
+            super_class: stmt.super_class.as_ref().map(|super_class| VarExpr {
+                var_name: super_class.var_name.clone(),
+            }),
+            methods: stmt.methods.iter().map(function).collect(),
+        }),
+        Stmt::Throw(stmt) => Stmt::Throw(ThrowStmt {
+            keyword: stmt.keyword.clone(),
+            value: expression(&stmt.value),
+        }),
+        Stmt::Try(stmt) => Stmt::Try(TryStmt {
+            body: optimize(&stmt.body),
+            catch: stmt.catch.as_ref().map(|catch| CatchClause {
+                name: catch.name.clone(),
+                body: optimize(&catch.body),
+            }),
+            finally: stmt.finally.as_deref().map(optimize),
+        }),
+        Stmt::Import(_) => return Rc::clone(stmt),
+        Stmt::Assert(stmt) => Stmt::Assert(AssertStmt {
+            keyword: stmt.keyword.clone(),
+            condition: expression(&stmt.condition),
+            message: stmt.message.as_ref().map(expression),
+            source: stmt.source.clone(),
+        }),
+        Stmt::Export(stmt) => Stmt::Export(ExportStmt {
+            keyword: stmt.keyword.clone(),
+            name: stmt.name.clone(),
+            declaration: statement(&stmt.declaration),
+        }),
+        Stmt::Switch(stmt) => Stmt::Switch(SwitchStmt {
+            subject: expression(&stmt.subject),
+            cases: stmt
+                .cases
+                .iter()
+                .map(|case| SwitchCase {
+                    value: expression(&case.value),
+                    body: optimize(&case.body),
+                })
+                .collect(),
+            default: stmt.default.as_deref().map(optimize),
+        }),
+        Stmt::ForIn(stmt) => Stmt::ForIn(ForInStmt {
+            name: stmt.name.clone(),
+            keyword: stmt.keyword.clone(),
+            iterable: expression(&stmt.iterable),
+            body: statement(&stmt.body),
+        }),
+    };
+    Rc::new(optimized)
+}
+
+fn function(stmt: &FunctionStmt) -> FunctionStmt {
+    FunctionStmt {
+        name: stmt.name.clone(),
+        params: Rc::clone(&stmt.params),
+        param_types: Rc::clone(&stmt.param_types),
+        return_type: stmt.return_type.clone(),
+        body: Rc::new(optimize(&stmt.body)),
+    }
+}
+
+/// What a dropped statement becomes where a statement is still needed, like
+/// the body of an `if`.
+fn empty() -> Stmt {
+    Stmt::Block(BlockStmt { statements: vec![] })
+}
+
+fn is_empty(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Block(block) if block.statements.is_empty())
+}
+
+fn expression(expr: &Rc<Expr>) -> Rc<Expr> {
+    let optimized = match expr.as_ref() {
+        Expr::Literal(_) | Expr::Var(_) | Expr::This(_) | Expr::Super(_) => return Rc::clone(expr),
+        Expr::Unary(expr) => {
+            let right = expression(&expr.right);
+            let folded =
+                literal(&right).and_then(|right| match (expr.operator.token_type, right) {
+                    (TokenType::MINUS, DataType::Number(n)) => Some(DataType::Number(-n)),
+                    (TokenType::BANG, value) => Some(DataType::Bool(!value.truthy())),
+                    _ => None,
+                });
+            match folded {
+                Some(value) => constant(value),
+                None => Expr::Unary(UnaryExpr {
+                    operator: expr.operator.clone(),
+                    right,
+                }),
+            }
+        }
+        Expr::Binary(expr) => {
+            let left = expression(&expr.left);
+            let right = expression(&expr.right);
+            let folded = match (literal(&left), literal(&right)) {
+                (Some(l), Some(r)) => binary(expr.operator.token_type, l, r),
+                _ => None,
+            };
+            match folded {
+                Some(value) => constant(value),
+                None => Expr::Binary(BinaryExpr {
+                    left,
+                    operator: expr.operator.clone(),
+                    right,
+                }),
+            }
+        }
+        Expr::Grouping(expr) => {
+            let inner = expression(&expr.expression);
+            if literal(&inner).is_some() {
+                return inner;
+            }
+            Expr::Grouping(GroupingExpr { expression: inner })
+        }
+        Expr::Logical(expr) => {
+            let left = expression(&expr.left);
+            let right = expression(&expr.right);
+            if let Some(value) = literal(&left) {
+                let short_circuits = if expr.operator.token_type == TokenType::OR {
+                    value.truthy()
+                } else {
+                    !value.truthy()
+                };
+                return if short_circuits { left } else { right };
+            }
+            Expr::Logical(LogicalExpr {
+                left,
+                operator: expr.operator.clone(),
+                right,
+            })
+        }
+        Expr::Call(expr) => Expr::Call(CallExpr {
+            callee: expression(&expr.callee),
+            paren: expr.paren.clone(),
+            arguments: expr.arguments.iter().map(expression).collect(),
+        }),
+        Expr::Assign(expr) => Expr::Assign(AssignExpr {
+            var_name: expr.var_name.clone(),
+            var_value: expression(&expr.var_value),
+        }),
+        Expr::Get(expr) => Expr::Get(GetExpr {
+            object: expression(&expr.object),
+            name: expr.name.clone(),
+        }),
+        Expr::Set(expr) => Expr::Set(SetExpr {
+            object: expression(&expr.object),
+            name: expr.name.clone(),
+            value: expression(&expr.value),
+        }),
+        Expr::Array(expr) => Expr::Array(ArrayExpr {
+            bracket: expr.bracket.clone(),
+            elements: expr.elements.iter().map(expression).collect(),
+        }),
+        Expr::Map(expr) => Expr::Map(MapExpr {
+            brace: expr.brace.clone(),
+            entries: expr
+                .entries
+                .iter()
+                .map(|(key, value)| (expression(key), expression(value)))
+                .collect(),
+        }),
+        Expr::Index(expr) => Expr::Index(IndexExpr {
+            object: expression(&expr.object),
+            bracket: expr.bracket.clone(),
+            index: expression(&expr.index),
+        }),
+        Expr::SetIndex(expr) => Expr::SetIndex(SetIndexExpr {
+            object: expression(&expr.object),
+            bracket: expr.bracket.clone(),
+            index: expression(&expr.index),
+            value: expression(&expr.value),
+        }),
+        Expr::Try(expr) => Expr::Try(TryExpr {
+            keyword: expr.keyword.clone(),
+            expression: expression(&expr.expression),
+        }),
+    };
+    Rc::new(optimized)
+}
+
+/// The value of `left operator right` if computing it can't fail.
+fn binary(operator: TokenType, left: DataType, right: DataType) -> Option<DataType> {
+    if let (DataType::Number(l), DataType::Number(r)) = (&left, &right) {
+        let (l, r) = (*l, *r);
+        let value = match operator {
+            TokenType::PLUS => DataType::Number(l + r),
+            TokenType::MINUS => DataType::Number(l - r),
+            TokenType::STAR => DataType::Number(l * r),
+            TokenType::SLASH => DataType::Number(l / r),
+            TokenType::GREATER => DataType::Bool(l > r),
+            TokenType::GREATEREQUAL => DataType::Bool(l >= r),
+            TokenType::LESS => DataType::Bool(l < r),
+            TokenType::LESSEQUAL => DataType::Bool(l <= r),
+            TokenType::EQUALEQUAL => DataType::Bool(l == r),
+            TokenType::BANGEQUAL => DataType::Bool(l != r),
+            _ => return None,
+        };
+        return Some(value);
+    }
+    let equal = match (left, right) {
+        (DataType::Nil, DataType::Nil) => true,
+        (DataType::Bool(l), DataType::Bool(r)) => l == r,
+        (DataType::String(l), DataType::String(r)) => l == r,
+        _ => false,
+    };
+    match operator {
+        TokenType::EQUALEQUAL => Some(DataType::Bool(equal)),
+        TokenType::BANGEQUAL => Some(DataType::Bool(!equal)),
+        _ => None,
+    }
+}
+
+/// The value of `expr` if it is a literal.
+fn literal(expr: &Expr) -> Option<DataType> {
+    match expr {
+        Expr::Literal(literal) => Some(literal.value.clone().unwrap_or(DataType::Nil)),
+        _ => None,
+    }
+}
+
+fn constant(value: DataType) -> Expr {
+    Expr::Literal(LiteralExpr { value: Some(value) })
+}
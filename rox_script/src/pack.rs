@@ -0,0 +1,65 @@
+//! Standalone executables for `rox pack`. A packed executable is a copy of
+//! the interpreter with a bundled script appended, followed by a trailer
+//! holding the script's length and [`MAGIC`]. The interpreter checks its own
+//! executable for the trailer when it starts and, if it finds one, runs the
+//! script in it instead of reading its arguments.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Marks the end of an executable with a script packed into it.
+const MAGIC: &[u8; 8] = b"ROXPACK1";
+/// The script's length as a little-endian u64, then [`MAGIC`].
+const TRAILER_LEN: u64 = 16;
+
+/// Writes to `output` a copy of the executable at `interpreter` that runs
+/// `script` when started. A script already packed into `interpreter` is
+/// replaced rather than kept.
+pub fn pack(interpreter: &Path, script: &str, output: &Path) -> io::Result<()> {
+    let mut executable = fs::read(interpreter)?;
+    if let Some((start, _)) = trailer(&mut io::Cursor::new(&executable))? {
+        executable.truncate(start as usize);
+    }
+    executable.extend_from_slice(script.as_bytes());
+    executable.extend_from_slice(&(script.len() as u64).to_le_bytes());
+    executable.extend_from_slice(MAGIC);
+    fs::write(output, executable)?;
+    // Copying the interpreter's permissions keeps the copy executable.
+    fs::set_permissions(output, fs::metadata(interpreter)?.permissions())
+}
+
+/// The script packed into the executable at `path`, if it has one.
+pub fn packed_script(path: &Path) -> io::Result<Option<String>> {
+    let mut file = File::open(path)?;
+    let Some((start, len)) = trailer(&mut file)? else {
+        return Ok(None);
+    };
+    let mut script = vec![0; len as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut script)?;
+    String::from_utf8(script)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Where the packed script in `executable` starts and how long it is, read
+/// from the trailer at its end.
+fn trailer(executable: &mut (impl Read + Seek)) -> io::Result<Option<(u64, u64)>> {
+    let size = executable.seek(SeekFrom::End(0))?;
+    if size < TRAILER_LEN {
+        return Ok(None);
+    }
+    let mut trailer = [0; TRAILER_LEN as usize];
+    executable.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    executable.read_exact(&mut trailer)?;
+    let (len, magic) = trailer.split_at(8);
+    if magic != MAGIC {
+        return Ok(None);
+    }
+    let len = u64::from_le_bytes(len.try_into().unwrap());
+    match (size - TRAILER_LEN).checked_sub(len) {
+        Some(start) => Ok(Some((start, len))),
+        None => Ok(None),
+    }
+}
@@ -1,49 +1,97 @@
+use std::path::Path;
 use std::rc::Rc;
 
 use anyhow::anyhow;
 use anyhow::Result;
 
+use crate::error::LoxError;
 use crate::expr::{
-    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, LiteralExpr, LogicalExpr,
-    SetExpr, SuperExpr, ThisExpr, UnaryExpr, VarExpr,
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
 };
+use crate::features::{self, Feature, LangVersion};
 use crate::functions::Kind;
+use crate::precedence::{binary_operator, Associativity, Precedence, LOWEST, UNARY_OPERATORS};
+use crate::stack;
 use crate::stmt::{
-    BlockStmt, ClassStmt, ExprStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, VarStmt,
-    WhileStmt,
+    AssertStmt, BlockStmt, CatchClause, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt,
+    IfStmt, ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchCase, SwitchStmt, ThrowStmt, TryStmt,
+    VarStmt, WhileStmt,
 };
 use crate::token::TokenType::{
-    AND, BANG, BANGEQUAL, CLASS, COMMA, DOT, ELSE, EOF, EQUAL, EQUALEQUAL, FALSE, FOR, FUN,
-    GREATER, GREATEREQUAL, IDENTIFIER, IF, LEFTBRACE, LEFTPAREN, LESS, LESSEQUAL, MINUS, NIL,
-    NUMBER, OR, PLUS, PRINT, RETURN, RIGHTBRACE, RIGHTPAREN, SEMICOLON, SLASH, STAR, STRING, SUPER,
-    THIS, TRUE, VAR, WHILE,
+    ASSERT, CASE, CATCH, CLASS, COLON, COMMA, DEFAULT, DOT, ELSE, EOF, EQUAL, EXPORT, FALSE,
+    FINALLY, FOR, FUN, IDENTIFIER, IF, IMPORT, LANG, LEFTBRACE, LEFTBRACKET, LEFTPAREN, LESS, NIL,
+    NUMBER, PRINT, RETURN, RIGHTBRACE, RIGHTBRACKET, RIGHTPAREN, SEMICOLON, STRING, SUPER, SWITCH,
+    THIS, THROW, TRUE, TRY, VAR, WHILE,
 };
-use crate::token::{DataType, Token, TokenType};
+use crate::token::{keyword, DataType, Token, TokenType};
+use crate::types::Type;
 
 #[derive(Default)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: u32,
+    /// How many nested declarations, statement bodies and expressions are
+    /// being parsed.
+    depth: usize,
+    /// How many try statements enclose the current point of the function
+    /// being parsed.
+    try_depth: usize,
+    /// Whether the function being parsed annotates its return type.
+    typed_return: bool,
+    /// Syntax newer than this version is an error.
+    lang_version: LangVersion,
 }
 
+/// How deeply statements and expressions may nest before the parser gives
+/// up with an error rather than building an arbitrarily deep tree.
+const MAX_NESTING: usize = 10_000;
+
 /**
- * expression → equality ;
- * equality → comparison ( ( "!=" | "==" ) comparison ) ;
- * comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
- * term → factor ( ( "-" | "+" ) factor )* ;
- * factor → unary ( ( "/" | "*" ) unary )* ;
+ * expression → binary ;
+ * binary → unary ( OPERATOR unary )* ;
+ *   with the operators and how they bind in `precedence::BINARY_OPERATORS`
  * unary → ( "!" | "-" ) unary
  * | primary ;
  * primary → NUMBER | STRING | "true" | "false" | "nil"
  * | "(" expression ")" ;
  */
-
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
-    }
-
-    pub fn parse(&mut self) -> Result<Vec<Rc<dyn Stmt>>> {
+        Parser {
+            tokens,
+            current: 0,
+            depth: 0,
+            try_depth: 0,
+            typed_return: false,
+            lang_version: features::LATEST,
+        }
+    }
+
+    /// Parses scripts without a `#lang` directive at `version` rather than
+    /// the latest version.
+    pub fn set_lang_version(&mut self, version: LangVersion) {
+        self.lang_version = version;
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Rc<Stmt>>> {
+        if self.match_token(&[LANG]) {
+            let directive = self.previous();
+            let version = match directive.literal {
+                Some(DataType::Number(version)) => version as LangVersion,
+                _ => 0,
+            };
+            if !features::is_known(version) {
+                let message = format!(
+                    "Unknown lang version {version}, expected {} to {}.",
+                    features::FIRST,
+                    features::LATEST
+                );
+                return Err(LoxError::parse(&directive, message).into());
+            }
+            self.lang_version = version;
+        }
         let mut statements = vec![];
         while !self.is_at_end() {
             statements.push(self.declaration()?)
@@ -52,115 +100,248 @@ impl Parser {
         Ok(statements)
     }
 
-    pub fn declaration(&mut self) -> Result<Rc<dyn Stmt>> {
-        let result = if self.match_token(vec![CLASS]) {
-            self.class_declaration()
-        } else if self.match_token(vec![FUN]) {
-            self.function(Kind::Function)
-        } else if self.match_token(vec![VAR]) {
-            self.var_declaration()
-        } else {
-            self.statement()
-        };
+    pub fn declaration(&mut self) -> Result<Rc<Stmt>> {
+        let result = self.nested(|parser| {
+            if parser.match_token(&[IMPORT]) {
+                parser.import_declaration()
+            } else if parser.match_token(&[EXPORT]) {
+                parser.export_declaration()
+            } else if parser.match_token(&[CLASS]) {
+                parser.class_declaration()
+            } else if parser.match_token(&[FUN]) {
+                Ok(Rc::new(Stmt::Function(parser.function(Kind::Function)?)))
+            } else if parser.match_token(&[VAR]) {
+                parser.var_declaration()
+            } else {
+                parser.statement()
+            }
+        });
 
         match result {
             Ok(res) => Ok(res),
             Err(err) => {
                 self.synchronise()?;
-                Err(anyhow!(err))
+                Err(err)
             }
         }
     }
 
-    fn class_declaration(&mut self) -> Result<Rc<dyn Stmt>> {
-        let name = self.consume(IDENTIFIER)?;
-        let mut super_class: Option<Rc<dyn Expr>> = None;
+    /// `import "path";` binds the module to the stem of its file name, and
+    /// `import "path" as name;` to `name`.
+    fn import_declaration(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.require(Feature::Modules, &keyword)?;
+        let path_token = self.consume(STRING, "Expect module path string after 'import'.")?;
+        let path = match &path_token.literal {
+            Some(DataType::String(path)) => path.clone(),
+            _ => return Err(LoxError::parse(&path_token, "Expect module path string.").into()),
+        };
 
-        if self.match_token(vec![LESS]) {
-            self.consume(IDENTIFIER)?;
-            super_class = Some(Rc::new(VarExpr {
+        let name = if self
+            .peek()
+            .is_some_and(|token| token.token_type == IDENTIFIER && token.lexeme == "as")
+        {
+            self.get_current_and_advance_cursor();
+            self.consume(IDENTIFIER, "Expect module name after 'as'.")?
+                .lexeme
+                .to_string()
+        } else {
+            let file = path.rsplit(['/', '\\']).next().unwrap_or_default();
+            let stem = Path::new(file)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            if !is_identifier(stem) {
+                return Err(LoxError::parse(
+                    &path_token,
+                    format!("Can't name a module '{stem}', add 'as <name>'."),
+                )
+                .into());
+            }
+            stem.to_string()
+        };
+
+        self.consume(SEMICOLON, "Expect ';' after import.")?;
+        Ok(Rc::new(Stmt::Import(ImportStmt {
+            keyword,
+            path,
+            name,
+        })))
+    }
+
+    fn export_declaration(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.require(Feature::Modules, &keyword)?;
+        // Every exportable declaration starts with a keyword and then its name.
+        let name = self.tokens.get(self.current as usize + 1).cloned();
+        let declaration = if self.match_token(&[CLASS]) {
+            self.class_declaration()?
+        } else if self.match_token(&[FUN]) {
+            Rc::new(Stmt::Function(self.function(Kind::Function)?))
+        } else if self.match_token(&[VAR]) {
+            self.var_declaration()?
+        } else {
+            return Err(
+                self.error("Expect class, function or variable declaration after 'export'.")
+            );
+        };
+        Ok(Rc::new(Stmt::Export(ExportStmt {
+            keyword,
+            name: name.ok_or_else(|| self.error("Expect declaration name."))?,
+            declaration,
+        })))
+    }
+
+    fn class_declaration(&mut self) -> Result<Rc<Stmt>> {
+        let name = self.consume(IDENTIFIER, "Expect class name.")?;
+        let mut super_class = None;
+
+        if self.match_token(&[LESS]) {
+            self.consume(IDENTIFIER, "Expect superclass name.")?;
+            super_class = Some(VarExpr {
                 var_name: self.previous(),
-            }));
+            });
         }
-        self.consume(LEFTBRACE)?;
+        self.consume(LEFTBRACE, "Expect '{' before class body.")?;
 
-        let mut methods: Vec<Rc<dyn Stmt>> = vec![];
+        let mut methods = vec![];
         while !self.check(RIGHTBRACE) && !self.is_at_end() {
             methods.push(self.function(Kind::Method)?);
         }
 
-        self.consume(RIGHTBRACE)?;
+        self.consume(RIGHTBRACE, "Expect '}' after class body.")?;
 
-        Ok(Rc::new(ClassStmt {
+        Ok(Rc::new(Stmt::Class(ClassStmt {
             name,
             super_class,
             methods,
-        }))
+        })))
     }
 
-    fn function(&mut self, _kind: Kind) -> Result<Rc<dyn Stmt>> {
-        let name = self.consume(IDENTIFIER)?;
-        self.consume(LEFTPAREN)?;
+    fn function(&mut self, kind: Kind) -> Result<FunctionStmt> {
+        let kind = match kind {
+            Kind::Function => "function",
+            Kind::Method => "method",
+        };
+        let name = self.consume(IDENTIFIER, &format!("Expect {kind} name."))?;
+        self.consume(LEFTPAREN, &format!("Expect '(' after {kind} name."))?;
         let mut params = vec![];
+        let mut param_types = vec![];
         if !self.check(RIGHTPAREN) {
             loop {
                 if params.len() >= 255 {
                     dbg!("Can't have more than 255 parameters.");
                 }
-                params.push(self.consume(IDENTIFIER)?);
-                if !self.match_token(vec![COMMA]) {
+                params.push(self.consume(IDENTIFIER, "Expect parameter name.")?);
+                param_types.push(self.type_annotation()?);
+                if !self.match_token(&[COMMA]) {
                     break;
                 }
             }
         }
-        self.consume(RIGHTPAREN)?;
-        self.consume(LEFTBRACE)?;
-        let body = self.block()?;
+        self.consume(RIGHTPAREN, "Expect ')' after parameters.")?;
+        let return_type = self.type_annotation()?;
+        self.consume(LEFTBRACE, &format!("Expect '{{' before {kind} body."))?;
+        // A try statement around the declaration doesn't enclose the body's
+        // returns, and only the body's own return type decides whether its
+        // calls can be made in tail position.
+        let enclosing_try_depth = std::mem::take(&mut self.try_depth);
+        let enclosing_typed_return =
+            std::mem::replace(&mut self.typed_return, return_type.is_some());
+        let body = self.block();
+        self.try_depth = enclosing_try_depth;
+        self.typed_return = enclosing_typed_return;
+        let body = body?;
+
+        Ok(FunctionStmt {
+            name,
+            params: Rc::new(params),
+            param_types: Rc::new(param_types),
+            return_type,
+            body: Rc::new(body),
+        })
+    }
 
-        Ok(Rc::new(FunctionStmt { name, params, body }))
+    /// An optional `: type` after a parameter or parameter list.
+    fn type_annotation(&mut self) -> Result<Option<Type>> {
+        if !self.match_token(&[COLON]) {
+            return Ok(None);
+        }
+        self.require(Feature::TypeAnnotations, &self.previous())?;
+        let name = if self.match_token(&[NIL]) {
+            self.previous()
+        } else {
+            self.consume(IDENTIFIER, "Expect type name after ':'.")?
+        };
+        Ok(Some(Type::from_name(&name.lexeme)))
     }
 
-    fn var_declaration(&mut self) -> Result<Rc<dyn Stmt>> {
-        let var_name: Token = self.consume(IDENTIFIER)?;
+    fn var_declaration(&mut self) -> Result<Rc<Stmt>> {
+        let var_name: Token = self.consume(IDENTIFIER, "Expect variable name.")?;
 
-        let var_value = if self.match_token(vec![EQUAL]) {
+        let var_value = if self.match_token(&[EQUAL]) {
             Some(self.expression()?)
         } else {
             None
         };
-        self.consume(SEMICOLON)?;
+        self.consume(SEMICOLON, "Expect ';' after variable declaration.")?;
 
-        Ok(Rc::new(VarStmt {
+        Ok(Rc::new(Stmt::Var(VarStmt {
             var_name,
             var_value,
-        }))
+        })))
     }
 
-    pub fn statement(&mut self) -> Result<Rc<dyn Stmt>> {
-        if self.match_token(vec![FOR]) {
+    pub fn statement(&mut self) -> Result<Rc<Stmt>> {
+        if self.match_token(&[FOR]) {
             self.for_statement()
-        } else if self.match_token(vec![IF]) {
+        } else if self.match_token(&[IF]) {
             self.if_statement()
-        } else if self.match_token(vec![PRINT]) {
+        } else if self.match_token(&[PRINT]) {
             self.print_statement()
-        } else if self.match_token(vec![RETURN]) {
+        } else if self.match_token(&[RETURN]) {
             self.return_statement()
-        } else if self.match_token(vec![WHILE]) {
+        } else if self.match_token(&[WHILE]) {
             self.while_statement()
-        } else if self.match_token(vec![LEFTBRACE]) {
-            Ok(Rc::new(BlockStmt {
+        } else if self.match_token(&[SWITCH]) {
+            self.switch_statement()
+        } else if self.match_token(&[ASSERT]) {
+            self.assert_statement()
+        } else if self.match_token(&[THROW]) {
+            self.throw_statement()
+        } else if self.check(TRY)
+            && self
+                .tokens
+                .get(self.current as usize + 1)
+                .is_some_and(|token| token.token_type == LEFTBRACE)
+        {
+            self.get_current_and_advance_cursor();
+            self.try_statement()
+        } else if self.match_token(&[LEFTBRACE]) {
+            Ok(Rc::new(Stmt::Block(BlockStmt {
                 statements: self.block()?,
-            }))
+            })))
         } else {
             self.expression_statement()
         }
     }
 
-    pub fn for_statement(&mut self) -> Result<Rc<dyn Stmt>> {
-        self.consume(LEFTPAREN)?;
-        let init = if self.match_token(vec![SEMICOLON]) {
+    pub fn for_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.consume(LEFTPAREN, "Expect '(' after 'for'.")?;
+        let is_for_in = self.check(IDENTIFIER)
+            && self
+                .tokens
+                .get(self.current as usize + 1)
+                .is_some_and(|token| token.token_type == IDENTIFIER && token.lexeme == "in");
+        if is_for_in {
+            self.require(Feature::ForIn, &keyword)?;
+            return self.for_in_statement();
+        }
+
+        let init = if self.match_token(&[SEMICOLON]) {
             None
-        } else if self.match_token(vec![VAR]) {
+        } else if self.match_token(&[VAR]) {
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
@@ -172,7 +353,7 @@ impl Parser {
             None
         };
 
-        self.consume(SEMICOLON)?;
+        self.consume(SEMICOLON, "Expect ';' after loop condition.")?;
 
         let increment = if !self.check(RIGHTPAREN) {
             Some(self.expression()?)
@@ -180,244 +361,375 @@ impl Parser {
             None
         };
 
-        self.consume(RIGHTPAREN)?;
+        self.consume(RIGHTPAREN, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
+        let mut body = self.nested(Self::statement)?;
 
-        if increment.is_some() {
-            body = Rc::new(BlockStmt {
+        if let Some(increment) = increment {
+            body = Rc::new(Stmt::Block(BlockStmt {
                 statements: vec![
                     body,
-                    Rc::new(ExprStmt {
-                        expression: increment.unwrap(),
-                    }),
+                    Rc::new(Stmt::Expr(ExprStmt {
+                        expression: increment,
+                    })),
                 ],
-            })
+            }))
         }
 
         if condition.is_none() {
-            condition = Some(Rc::new(LiteralExpr {
+            condition = Some(Rc::new(Expr::Literal(LiteralExpr {
                 value: Some(DataType::Bool(true)),
-            }))
+            })))
         };
 
-        body = Rc::new(WhileStmt {
+        body = Rc::new(Stmt::While(WhileStmt {
+            keyword,
             condition: condition.unwrap(),
             body,
-        });
+        }));
 
-        if init.is_some() {
-            body = Rc::new(BlockStmt {
-                statements: vec![init.unwrap(), body],
-            })
+        if let Some(init) = init {
+            body = Rc::new(Stmt::Block(BlockStmt {
+                statements: vec![init, body],
+            }))
         }
 
         Ok(body)
     }
 
-    pub fn while_statement(&mut self) -> Result<Rc<dyn Stmt>> {
-        self.consume(LEFTPAREN)?;
+    pub fn while_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.consume(LEFTPAREN, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
-        self.consume(RIGHTPAREN)?;
-        let body = self.statement()?;
-        Ok(Rc::new(WhileStmt { condition, body }))
+        self.consume(RIGHTPAREN, "Expect ')' after condition.")?;
+        let body = self.nested(Self::statement)?;
+        Ok(Rc::new(Stmt::While(WhileStmt {
+            keyword,
+            condition,
+            body,
+        })))
     }
 
-    pub fn if_statement(&mut self) -> Result<Rc<dyn Stmt>> {
-        self.consume(LEFTPAREN)?;
+    pub fn if_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.consume(LEFTPAREN, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
-        self.consume(RIGHTPAREN)?;
+        self.consume(RIGHTPAREN, "Expect ')' after if condition.")?;
 
-        let then_branch = self.statement()?;
-        let else_branch: Option<Rc<dyn Stmt>> = if self.match_token(vec![ELSE]) {
-            Some(self.statement()?)
+        let then_branch = self.nested(Self::statement)?;
+        let else_branch: Option<Rc<Stmt>> = if self.match_token(&[ELSE]) {
+            Some(self.nested(Self::statement)?)
         } else {
             None
         };
 
-        Ok(Rc::new(IfStmt {
+        Ok(Rc::new(Stmt::If(IfStmt {
+            keyword,
             condition,
             then_branch,
             else_branch,
-        }))
+        })))
+    }
+
+    /// `for (name in iterable) body`, once the opening paren is consumed.
+    /// `in` is only a keyword here, so it stays usable as a variable name.
+    fn for_in_statement(&mut self) -> Result<Rc<Stmt>> {
+        let name = self.consume(IDENTIFIER, "Expect loop variable name.")?;
+        let keyword = self.get_current_and_advance_cursor();
+        let iterable = self.expression()?;
+        self.consume(RIGHTPAREN, "Expect ')' after for-in clause.")?;
+        let body = self.nested(Self::statement)?;
+        Ok(Rc::new(Stmt::ForIn(ForInStmt {
+            name,
+            keyword,
+            iterable,
+            body,
+        })))
     }
 
-    pub fn block(&mut self) -> Result<Vec<Rc<dyn Stmt>>> {
+    pub fn block(&mut self) -> Result<Vec<Rc<Stmt>>> {
         let mut statements = vec![];
         while !self.check(RIGHTBRACE) && !self.is_at_end() {
             statements.push(self.declaration()?);
         }
-        self.consume(RIGHTBRACE)?;
+        self.consume(RIGHTBRACE, "Expect '}' after block.")?;
         Ok(statements)
     }
 
-    pub fn print_statement(&mut self) -> Result<Rc<dyn Stmt>> {
+    pub fn print_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
         let expr = self.expression()?;
-        self.consume(SEMICOLON)?;
-        Ok(Rc::new(PrintStmt { expression: expr }))
+        self.consume(SEMICOLON, "Expect ';' after value.")?;
+        Ok(Rc::new(Stmt::Print(PrintStmt {
+            keyword,
+            expression: expr,
+        })))
     }
 
-    pub fn return_statement(&mut self) -> Result<Rc<dyn Stmt>> {
+    pub fn return_statement(&mut self) -> Result<Rc<Stmt>> {
         let keyword = self.previous();
         let value = if !self.check(SEMICOLON) {
             Some(self.expression()?)
         } else {
             None
         };
-        self.consume(SEMICOLON)?;
-        Ok(Rc::new(ReturnStmt { keyword, value }))
-    }
-
-    pub fn expression_statement(&mut self) -> Result<Rc<dyn Stmt>> {
-        let expr = self.expression()?;
-        self.consume(SEMICOLON)?;
-        Ok(Rc::new(ExprStmt { expression: expr }))
-    }
-
-    // expression → equality
-    pub fn expression(&mut self) -> Result<Rc<dyn Expr>> {
-        self.assignment()
-    }
-
-    pub fn assignment(&mut self) -> Result<Rc<dyn Expr>> {
-        let expr = self.or()?;
-        if self.match_token(vec![EQUAL]) {
-            let _ = self.previous();
-            let value = self.assignment()?;
-
-            if expr.as_any().downcast_ref::<VarExpr>().is_some() {
-                let var_name = expr
-                    .as_any()
-                    .downcast_ref::<VarExpr>()
-                    .unwrap()
-                    .var_name
-                    .clone();
-                return Ok(Rc::new(AssignExpr {
-                    var_name,
-                    var_value: Some(value),
-                }));
-            } else if expr.as_any().downcast_ref::<GetExpr>().is_some() {
-                let get = expr.as_any().downcast_ref::<GetExpr>().unwrap().clone();
-                return Ok(Rc::new(SetExpr {
-                    object: Rc::clone(&get.object),
-                    name: get.name.clone(),
+        self.consume(SEMICOLON, "Expect ';' after return value.")?;
+        // Inside a try statement the call has to finish before the catch and
+        // finally clauses run, so it can't replace the caller's frame. Nor
+        // can it when the caller's own return type still has to be checked.
+        let tail_call = self.try_depth == 0
+            && !self.typed_return
+            && value
+                .as_ref()
+                .is_some_and(|value| matches!(value.as_ref(), Expr::Call(_)));
+        Ok(Rc::new(Stmt::Return(ReturnStmt {
+            keyword,
+            value,
+            tail_call,
+        })))
+    }
+
+    pub fn switch_statement(&mut self) -> Result<Rc<Stmt>> {
+        self.require(Feature::Switch, &self.previous())?;
+        self.consume(LEFTPAREN, "Expect '(' after 'switch'.")?;
+        let subject = self.expression()?;
+        self.consume(RIGHTPAREN, "Expect ')' after switch value.")?;
+        self.consume(LEFTBRACE, "Expect '{' before switch cases.")?;
+
+        let mut cases = vec![];
+        let mut default = None;
+        while !self.check(RIGHTBRACE) && !self.is_at_end() {
+            if self.match_token(&[CASE]) {
+                let value = self.expression()?;
+                self.consume(COLON, "Expect ':' after case value.")?;
+                cases.push(SwitchCase {
                     value,
-                }));
+                    body: self.case_body()?,
+                });
+            } else if self.match_token(&[DEFAULT]) {
+                if default.is_some() {
+                    return Err(LoxError::parse(
+                        &self.previous(),
+                        "A switch can only have one default case.",
+                    )
+                    .into());
+                }
+                self.consume(COLON, "Expect ':' after 'default'.")?;
+                default = Some(self.case_body()?);
             } else {
-                dbg!("error");
+                return Err(self.error("Expect 'case' or 'default' in switch."));
             }
         }
+        self.consume(RIGHTBRACE, "Expect '}' after switch cases.")?;
 
-        Ok(expr)
+        Ok(Rc::new(Stmt::Switch(SwitchStmt {
+            subject,
+            cases,
+            default,
+        })))
     }
 
-    pub fn or(&mut self) -> Result<Rc<dyn Expr>> {
-        let mut expr = self.and()?;
-        while self.match_token(vec![OR]) {
-            let operator: Token = self.previous();
-            let right = self.and()?;
-            expr = Rc::new(LogicalExpr {
-                left: expr,
-                operator,
-                right,
-            });
+    /// The statements of a case run up to the next case, default or the end
+    /// of the switch.
+    fn case_body(&mut self) -> Result<Vec<Rc<Stmt>>> {
+        let mut statements = vec![];
+        while !self.check(CASE)
+            && !self.check(DEFAULT)
+            && !self.check(RIGHTBRACE)
+            && !self.is_at_end()
+        {
+            statements.push(self.declaration()?);
         }
-        Ok(expr)
+        Ok(statements)
     }
 
-    pub fn and(&mut self) -> Result<Rc<dyn Expr>> {
-        let mut expr = self.equality()?;
-        while self.match_token(vec![AND]) {
-            let operator: Token = self.previous();
-            let right = self.equality()?;
-            expr = Rc::new(LogicalExpr {
-                left: expr,
-                operator,
-                right,
-            });
-        }
-        Ok(expr)
+    pub fn assert_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.require(Feature::Assertions, &keyword)?;
+        let start = self.current as usize;
+        let condition = self.expression()?;
+        let source = self.source_text(start, self.current as usize);
+        let message = if self.match_token(&[COMMA]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.consume(SEMICOLON, "Expect ';' after assertion.")?;
+        Ok(Rc::new(Stmt::Assert(AssertStmt {
+            keyword,
+            condition,
+            message,
+            source,
+        })))
     }
 
-    // equality → comparison ( ( "!=" | "==" ) comparison )
-    pub fn equality(&mut self) -> Result<Rc<dyn Expr>> {
-        let mut left = self.comparison()?;
+    pub fn throw_statement(&mut self) -> Result<Rc<Stmt>> {
+        let keyword = self.previous();
+        self.require(Feature::Exceptions, &keyword)?;
+        let value = self.expression()?;
+        self.consume(SEMICOLON, "Expect ';' after thrown value.")?;
+        Ok(Rc::new(Stmt::Throw(ThrowStmt { keyword, value })))
+    }
 
-        while self.match_token(vec![BANGEQUAL, EQUALEQUAL]) {
-            let operator = self.previous();
-            let right = self.comparison()?;
-            left = Rc::new(BinaryExpr {
-                left,
-                operator,
-                right,
-            });
+    pub fn try_statement(&mut self) -> Result<Rc<Stmt>> {
+        self.require(Feature::Exceptions, &self.previous())?;
+        self.try_depth += 1;
+        let statement = self.try_clauses();
+        self.try_depth -= 1;
+        statement
+    }
+
+    fn try_clauses(&mut self) -> Result<Rc<Stmt>> {
+        self.consume(LEFTBRACE, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let catch = if self.match_token(&[CATCH]) {
+            self.consume(LEFTPAREN, "Expect '(' after 'catch'.")?;
+            let name = self.consume(IDENTIFIER, "Expect exception variable name.")?;
+            self.consume(RIGHTPAREN, "Expect ')' after exception variable name.")?;
+            self.consume(LEFTBRACE, "Expect '{' before catch body.")?;
+            Some(CatchClause {
+                name,
+                body: self.block()?,
+            })
+        } else {
+            None
+        };
+
+        let finally = if self.match_token(&[FINALLY]) {
+            self.consume(LEFTBRACE, "Expect '{' after 'finally'.")?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error("Expect 'catch' or 'finally' after try block."));
         }
 
-        Ok(left)
+        Ok(Rc::new(Stmt::Try(TryStmt {
+            body,
+            catch,
+            finally,
+        })))
     }
 
-    pub fn comparison(&mut self) -> Result<Rc<dyn Expr>> {
-        let mut left = self.term()?;
-        while self.match_token(vec![GREATER, GREATEREQUAL, LESS, LESSEQUAL]) {
-            let operator = self.previous();
-            let right = self.term()?;
-            left = Rc::new(BinaryExpr {
-                left,
-                operator,
-                right,
-            });
-        }
-        Ok(left)
+    pub fn expression_statement(&mut self) -> Result<Rc<Stmt>> {
+        let expr = self.expression()?;
+        self.consume(SEMICOLON, "Expect ';' after expression.")?;
+        Ok(Rc::new(Stmt::Expr(ExprStmt { expression: expr })))
     }
 
-    pub fn term(&mut self) -> Result<Rc<dyn Expr>> {
-        let mut left = self.factor()?;
-        while self.match_token(vec![MINUS, PLUS]) {
-            let operator = self.previous();
-            let right = self.factor()?;
-            left = Rc::new(BinaryExpr {
-                left,
-                operator,
-                right,
-            });
+    // expression → equality
+    pub fn expression(&mut self) -> Result<Rc<Expr>> {
+        self.nested(Self::assignment)
+    }
+
+    pub fn assignment(&mut self) -> Result<Rc<Expr>> {
+        if self.match_token(&[TRY]) {
+            let keyword = self.previous();
+            self.require(Feature::TryExpressions, &keyword)?;
+            let expression = self.nested(Self::assignment)?;
+            return Ok(Rc::new(Expr::Try(TryExpr {
+                keyword,
+                expression,
+            })));
+        }
+        let expr = self.binary(LOWEST)?;
+        if self.match_token(&[EQUAL]) {
+            let equals = self.previous();
+            let value = self.nested(Self::assignment)?;
+
+            return match expr.as_ref() {
+                Expr::Var(var) => Ok(Rc::new(Expr::Assign(AssignExpr {
+                    var_name: var.var_name.clone(),
+                    var_value: value,
+                }))),
+                Expr::Get(get) => Ok(Rc::new(Expr::Set(SetExpr {
+                    object: Rc::clone(&get.object),
+                    name: get.name.clone(),
+                    value,
+                }))),
+                Expr::Index(index) => Ok(Rc::new(Expr::SetIndex(SetIndexExpr {
+                    object: Rc::clone(&index.object),
+                    bracket: index.bracket.clone(),
+                    index: Rc::clone(&index.index),
+                    value,
+                }))),
+                _ => Err(LoxError::parse(&equals, "Invalid assignment target.").into()),
+            };
         }
-        Ok(left)
+
+        Ok(expr)
     }
 
-    pub fn factor(&mut self) -> Result<Rc<dyn Expr>> {
+    /// Parses a chain of binary and logical operators whose precedence is
+    /// at least `min_precedence`, climbing the [`BINARY_OPERATORS`] table.
+    pub fn binary(&mut self, min_precedence: Precedence) -> Result<Rc<Expr>> {
         let mut left = self.unary()?;
-
-        while self.match_token(vec![SLASH, STAR]) {
-            let operator = self.previous();
-            let right = self.unary()?;
-            left = Rc::new(BinaryExpr {
-                left,
-                operator,
-                right,
-            });
+        while let Some(operator) = self
+            .peek()
+            .and_then(|token| binary_operator(token.token_type))
+        {
+            if operator.precedence < min_precedence {
+                break;
+            }
+            let token = self.get_current_and_advance_cursor();
+            self.deeper()?;
+            let right = match operator.associativity {
+                Associativity::Left => self.binary(operator.right_precedence())?,
+                Associativity::Right => {
+                    self.nested(|parser| parser.binary(operator.right_precedence()))?
+                }
+            };
+            left = if operator.logical {
+                Rc::new(Expr::Logical(LogicalExpr {
+                    left,
+                    operator: token,
+                    right,
+                }))
+            } else {
+                Rc::new(Expr::Binary(BinaryExpr {
+                    left,
+                    operator: token,
+                    right,
+                }))
+            };
         }
-
         Ok(left)
     }
 
-    pub fn unary(&mut self) -> Result<Rc<dyn Expr>> {
-        if self.match_token(vec![BANG, MINUS]) {
+    pub fn unary(&mut self) -> Result<Rc<Expr>> {
+        if self.match_token(UNARY_OPERATORS) {
             let operator = self.previous();
-            let right = self.unary()?;
-            return Ok(Rc::new(UnaryExpr { operator, right }));
+            let right = self.nested(Self::unary)?;
+            return Ok(Rc::new(Expr::Unary(UnaryExpr { operator, right })));
         }
 
         self.call()
     }
 
-    pub fn call(&mut self) -> Result<Rc<dyn Expr>> {
+    pub fn call(&mut self) -> Result<Rc<Expr>> {
         let mut expr = self.primary()?;
         loop {
-            if self.match_token(vec![LEFTPAREN]) {
+            if self.check(LEFTPAREN) || self.check(DOT) || self.check(LEFTBRACKET) {
+                self.deeper()?;
+            }
+            if self.match_token(&[LEFTPAREN]) {
                 expr = self.finish_call(&expr)?;
-            } else if self.match_token(vec![DOT]) {
-                let name = self.consume(IDENTIFIER)?;
-                expr = Rc::new(GetExpr { object: expr, name })
+            } else if self.match_token(&[DOT]) {
+                let name = self.consume(IDENTIFIER, "Expect property name after '.'.")?;
+                expr = Rc::new(Expr::Get(GetExpr { object: expr, name }))
+            } else if self.match_token(&[LEFTBRACKET]) {
+                let bracket = self.previous();
+                self.require(Feature::Collections, &bracket)?;
+                let index = self.expression()?;
+                self.consume(RIGHTBRACKET, "Expect ']' after index.")?;
+                expr = Rc::new(Expr::Index(IndexExpr {
+                    object: expr,
+                    bracket,
+                    index,
+                }))
             } else {
                 break;
             }
@@ -426,7 +738,7 @@ impl Parser {
         Ok(expr)
     }
 
-    pub fn finish_call(&mut self, callee: &Rc<dyn Expr>) -> Result<Rc<dyn Expr>> {
+    pub fn finish_call(&mut self, callee: &Rc<Expr>) -> Result<Rc<Expr>> {
         let mut arguments = vec![];
         if !self.check(RIGHTPAREN) {
             loop {
@@ -434,84 +746,177 @@ impl Parser {
                     dbg!("Can't have more than 255 arguments.");
                 }
                 arguments.push(self.expression()?);
-                if !self.match_token(vec![COMMA]) {
+                if !self.match_token(&[COMMA]) {
                     break;
                 }
             }
         }
 
-        let paren = self.consume(RIGHTPAREN)?;
+        let paren = self.consume(RIGHTPAREN, "Expect ')' after arguments.")?;
 
-        Ok(Rc::new(CallExpr {
+        Ok(Rc::new(Expr::Call(CallExpr {
             callee: Rc::clone(callee),
             paren,
             arguments,
-        }))
+        })))
     }
 
-    pub fn primary(&mut self) -> Result<Rc<dyn Expr>> {
-        if self.match_token(vec![TRUE]) {
-            return Ok(Rc::new(LiteralExpr {
+    pub fn primary(&mut self) -> Result<Rc<Expr>> {
+        if self.match_token(&[TRUE]) {
+            return Ok(Rc::new(Expr::Literal(LiteralExpr {
                 value: Some(DataType::Bool(true)),
-            }));
+            })));
         }
-        if self.match_token(vec![FALSE]) {
-            return Ok(Rc::new(LiteralExpr {
+        if self.match_token(&[FALSE]) {
+            return Ok(Rc::new(Expr::Literal(LiteralExpr {
                 value: Some(DataType::Bool(false)),
-            }));
+            })));
         }
-        if self.match_token(vec![NIL]) {
-            return Ok(Rc::new(LiteralExpr {
+        if self.match_token(&[NIL]) {
+            return Ok(Rc::new(Expr::Literal(LiteralExpr {
                 value: Some(DataType::Nil),
-            }));
+            })));
         }
-        if self.match_token(vec![NUMBER, STRING]) {
-            return Ok(Rc::new(LiteralExpr {
+        if self.match_token(&[NUMBER, STRING]) {
+            return Ok(Rc::new(Expr::Literal(LiteralExpr {
                 value: self.previous().literal,
-            }));
+            })));
         }
 
-        if self.match_token(vec![SUPER]) {
+        if self.match_token(&[SUPER]) {
             let keyword = self.previous();
-            self.consume(DOT)?;
-            let method = self.consume(IDENTIFIER)?;
-            return Ok(Rc::new(SuperExpr { keyword, method }));
+            self.consume(DOT, "Expect '.' after 'super'.")?;
+            let method = self.consume(IDENTIFIER, "Expect superclass method name.")?;
+            return Ok(Rc::new(Expr::Super(SuperExpr { keyword, method })));
         }
 
-        if self.match_token(vec![THIS]) {
-            return Ok(Rc::new(ThisExpr {
+        if self.match_token(&[THIS]) {
+            return Ok(Rc::new(Expr::This(ThisExpr {
                 keyword: self.previous(),
-            }));
+            })));
         }
 
-        if self.match_token(vec![IDENTIFIER]) {
-            return Ok(Rc::new(VarExpr {
+        if self.match_token(&[IDENTIFIER]) {
+            return Ok(Rc::new(Expr::Var(VarExpr {
                 var_name: self.previous(),
-            }));
+            })));
         }
 
-        if self.match_token(vec![LEFTPAREN]) {
+        if self.match_token(&[LEFTPAREN]) {
             let expression = self.expression()?;
-            if self.consume(RIGHTPAREN).is_ok() {
-                return Ok(Rc::new(GroupingExpr { expression }));
+            self.consume(RIGHTPAREN, "Expect ')' after expression.")?;
+            return Ok(Rc::new(Expr::Grouping(GroupingExpr { expression })));
+        }
+
+        if self.match_token(&[LEFTBRACKET]) {
+            let bracket = self.previous();
+            self.require(Feature::Collections, &bracket)?;
+            let mut elements = vec![];
+            while !self.check(RIGHTBRACKET) {
+                elements.push(self.expression()?);
+                if !self.match_token(&[COMMA]) {
+                    break;
+                }
             }
+            self.consume(RIGHTBRACKET, "Expect ']' after array elements.")?;
+            return Ok(Rc::new(Expr::Array(ArrayExpr { bracket, elements })));
+        }
+
+        // A '{' starting a statement is a block, so map literals only appear
+        // where an expression is expected.
+        if self.match_token(&[LEFTBRACE]) {
+            let brace = self.previous();
+            self.require(Feature::Collections, &brace)?;
+            let mut entries = vec![];
+            while !self.check(RIGHTBRACE) {
+                let key = self.expression()?;
+                self.consume(COLON, "Expect ':' after map key.")?;
+                entries.push((key, self.expression()?));
+                if !self.match_token(&[COMMA]) {
+                    break;
+                }
+            }
+            self.consume(RIGHTBRACE, "Expect '}' after map entries.")?;
+            return Ok(Rc::new(Expr::Map(MapExpr { brace, entries })));
         }
 
-        Err(anyhow!("Unknown token"))
+        Err(self.error("Expect expression."))
     }
 
-    fn consume(&mut self, token_type: TokenType) -> anyhow::Result<Token> {
+    fn consume(&mut self, token_type: TokenType, message: &str) -> anyhow::Result<Token> {
         if self.check(token_type) {
             Ok(self.get_current_and_advance_cursor())
         } else {
-            // TODO accept the error message
-            Err(anyhow!("error"))
+            Err(self.error(message))
+        }
+    }
+
+    /// Rebuilds the source text of the tokens in `start..end`. Whitespace,
+    /// newlines and comments between tokens collapse to a single space.
+    fn source_text(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut previous_end = None;
+        for token in &self.tokens[start..end] {
+            if previous_end.is_some_and(|end| token.offset > end) {
+                text.push(' ');
+            }
+            text.push_str(&token.lexeme);
+            previous_end = Some(token.offset + token.lexeme.len() as u32);
+        }
+        text
+    }
+
+    /// Parses one more level of nesting with `parse`, failing once the
+    /// script nests deeper than [`MAX_NESTING`].
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let depth = self.depth;
+        self.deeper()?;
+        let result = stack::grow(|| parse(self));
+        self.depth = depth;
+        result
+    }
+
+    /// Counts one more level of nesting. Left-associative chains such as
+    /// `a + b + c` or `a.b().c` call this for each link, since they nest the
+    /// tree as deeply as parentheses would. The enclosing [`Parser::nested`]
+    /// call resets the count once the chain is parsed.
+    fn deeper(&mut self) -> Result<()> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.error("Too much nesting."));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Fails at `token` unless the script's lang version has `feature`.
+    fn require(&self, feature: Feature, token: &Token) -> Result<()> {
+        if self.lang_version >= feature.since() {
+            return Ok(());
         }
+        let message = format!(
+            "Feature '{}' requires lang version {}.",
+            feature.name(),
+            feature.since()
+        );
+        let hint = format!(
+            "The script is at lang version {}; start it with '#lang {}' to use it.",
+            self.lang_version,
+            feature.since()
+        );
+        Err(LoxError::parse(token, message).with_hint(hint).into())
     }
 
-    fn match_token(&mut self, token_types: Vec<TokenType>) -> bool {
+    /// Builds a parse error pointing at the token about to be consumed.
+    fn error(&self, message: &str) -> anyhow::Error {
+        match self.peek() {
+            Some(token) => LoxError::parse(token, message).into(),
+            None => anyhow!(message.to_string()),
+        }
+    }
+
+    fn match_token(&mut self, token_types: &[TokenType]) -> bool {
         for token in token_types {
-            if self.check(token) {
+            if self.check(*token) {
                 self.get_current_and_advance_cursor();
                 return true;
             }
@@ -548,7 +953,7 @@ impl Parser {
         self.tokens.get(self.current as usize)
     }
 
-    fn previous(&mut self) -> Token {
+    fn previous(&self) -> Token {
         self.tokens
             .get((self.current - 1) as usize)
             .unwrap()
@@ -563,7 +968,8 @@ impl Parser {
             }
 
             match self.peek().ok_or(anyhow!("can't peek"))?.token_type {
-                CLASS | FUN | VAR | FOR | IF | WHILE | PRINT | RETURN => {
+                CLASS | FUN | VAR | FOR | IF | WHILE | PRINT | RETURN | THROW | TRY | IMPORT
+                | EXPORT | ASSERT | SWITCH => {
                     break;
                 }
                 _ => {
@@ -574,3 +980,12 @@ impl Parser {
         Ok(())
     }
 }
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && keyword(text).is_none()
+}
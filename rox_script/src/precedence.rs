@@ -0,0 +1,90 @@
+//! The binary operators and how they bind. The parser reads every binary
+//! and logical expression with one loop driven by [`BINARY_OPERATORS`], so
+//! adding an operator means adding a row here and a case wherever it is
+//! evaluated, rather than another parsing method.
+
+use crate::token::TokenType;
+use crate::token::TokenType::{
+    AND, BANG, BANGEQUAL, EQUALEQUAL, GREATER, GREATEREQUAL, LESS, LESSEQUAL, MINUS, OR, PLUS,
+    SLASH, STAR,
+};
+
+/// The prefix operators, which all bind tighter than any binary operator.
+pub const UNARY_OPERATORS: &[TokenType] = &[BANG, MINUS];
+
+/// How tightly a binary operator binds its operands. Higher binds tighter,
+/// so `a or b and c` is `a or (b and c)`.
+pub type Precedence = u8;
+
+/// The precedence below every binary operator's, to parse a whole binary
+/// expression with.
+pub const LOWEST: Precedence = 0;
+
+/// Which way a chain of operators of one precedence groups.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ** b ** c` would be `a ** (b ** c)`.
+    Right,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Operator {
+    pub token_type: TokenType,
+    pub precedence: Precedence,
+    pub associativity: Associativity,
+    /// Whether it short-circuits, making a logical rather than a binary
+    /// expression.
+    pub logical: bool,
+}
+
+const fn left(token_type: TokenType, precedence: Precedence) -> Operator {
+    Operator {
+        token_type,
+        precedence,
+        associativity: Associativity::Left,
+        logical: false,
+    }
+}
+
+const fn logical(token_type: TokenType, precedence: Precedence) -> Operator {
+    Operator {
+        logical: true,
+        ..left(token_type, precedence)
+    }
+}
+
+/// Every binary operator, loosest first.
+pub const BINARY_OPERATORS: &[Operator] = &[
+    logical(OR, 1),
+    logical(AND, 2),
+    left(BANGEQUAL, 3),
+    left(EQUALEQUAL, 3),
+    left(GREATER, 4),
+    left(GREATEREQUAL, 4),
+    left(LESS, 4),
+    left(LESSEQUAL, 4),
+    left(MINUS, 5),
+    left(PLUS, 5),
+    left(SLASH, 6),
+    left(STAR, 6),
+];
+
+/// The binary operator `token_type` stands for, if any.
+pub fn binary_operator(token_type: TokenType) -> Option<&'static Operator> {
+    BINARY_OPERATORS
+        .iter()
+        .find(|operator| operator.token_type == token_type)
+}
+
+impl Operator {
+    /// The least precedence an operator to the right must have to be part of
+    /// this one's right operand.
+    pub fn right_precedence(&self) -> Precedence {
+        match self.associativity {
+            Associativity::Left => self.precedence + 1,
+            Associativity::Right => self.precedence,
+        }
+    }
+}
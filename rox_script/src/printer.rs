@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::expr::{
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
+};
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner;
+use crate::stack;
+use crate::stmt::{
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::token::{DataType, Token, TokenId};
+use crate::visitor::{ExprVisitor, StmtVisitor};
+
+/// Scans and parses `source` and renders the resulting tree.
+pub fn print_ast(source: String) -> Result<String> {
+    let tokens = scanner::run(source, 1)?;
+    let statements = Parser::new(tokens).parse()?;
+    AstPrinter::default().print(&statements)
+}
+
+/// Like [`print_ast`], but also resolves the tree and marks every variable,
+/// `this` and `super` with where the interpreter will look it up: `x@1` is
+/// the `x` one scope out from the reference, `x@global` a global.
+pub fn print_resolved_ast(source: String) -> Result<String> {
+    let tokens = scanner::run(source, 1)?;
+    let statements = Parser::new(tokens).parse()?;
+    let interpreter = Interpreter::new(Limits::default());
+    Resolver::new(&interpreter).resolve(statements.clone())?;
+    let depths = interpreter.locals.borrow().clone();
+    AstPrinter {
+        depths: Some(depths),
+        ..AstPrinter::default()
+    }
+    .print(&statements)
+}
+
+/// How `--trace` shows a statement about to run: the first line of its
+/// tree, without the statements in its body.
+pub(crate) fn statement_header(statement: &Stmt) -> String {
+    let mut printer = AstPrinter::default();
+    if statement.accept(&mut printer).is_err() {
+        return String::new();
+    }
+    printer.out.lines().next().unwrap_or_default().to_string()
+}
+
+/// How `--trace` shows an expression it evaluated.
+pub(crate) fn expression(expression: &Expr) -> String {
+    match expression.accept(&mut AstPrinter::default()) {
+        Ok(DataType::String(text)) => text,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+/// Renders a parsed program for debugging the parser. Statements form an
+/// indented tree, one per line with their bodies nested two spaces deeper,
+/// and expressions are written as s-expressions so precedence is explicit:
+/// `1 + 2 * 3` prints as `(+ 1 (* 2 3))`.
+///
+/// The tree is printed as parsed, so `for` loops show up as the `while`
+/// loops they desugar to.
+#[derive(Default)]
+pub struct AstPrinter {
+    out: String,
+    indent: usize,
+    /// How many scopes out each resolved name is found, when printing
+    /// resolved trees.
+    depths: Option<HashMap<TokenId, usize>>,
+}
+
+impl AstPrinter {
+    pub fn print(mut self, statements: &[Rc<Stmt>]) -> Result<String> {
+        self.statements(statements)?;
+        Ok(self.out)
+    }
+
+    fn expr(&mut self, expr: &Rc<Expr>) -> Result<String> {
+        match stack::grow(|| expr.accept(self))? {
+            DataType::String(text) => Ok(text),
+            other => Ok(other.to_string()),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Rc<Stmt>) -> Result<()> {
+        stack::grow(|| stmt.accept(self))?;
+        Ok(())
+    }
+
+    fn statements(&mut self, statements: &[Rc<Stmt>]) -> Result<()> {
+        for statement in statements {
+            self.stmt(statement)?;
+        }
+        Ok(())
+    }
+
+    /// The name `token` refers to, marked with its scope depth when printing
+    /// a resolved tree.
+    fn reference(&self, token: &Token) -> String {
+        let Some(depths) = &self.depths else {
+            return token.lexeme.to_string();
+        };
+        match depths.get(&token.id()) {
+            Some(depth) => format!("{}@{depth}", token.lexeme),
+            None => format!("{}@global", token.lexeme),
+        }
+    }
+
+    /// Writes `text` on its own line at the current indentation.
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Prints `body` one level deeper than the current line.
+    fn nested(&mut self, body: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        self.indent += 1;
+        let result = body(self);
+        self.indent -= 1;
+        result
+    }
+
+    /// Renders `(name part...)`, the form every compound expression takes.
+    fn parenthesize(&mut self, name: &str, exprs: &[&Rc<Expr>]) -> Result<DataType> {
+        let mut text = format!("({name}");
+        for expr in exprs {
+            text.push(' ');
+            text.push_str(&self.expr(expr)?);
+        }
+        text.push(')');
+        Ok(DataType::String(text))
+    }
+}
+
+impl ExprVisitor for AstPrinter {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<DataType> {
+        let text = match expr.value.as_ref() {
+            None => "nil".to_string(),
+            Some(value) => value.repr(),
+        };
+        Ok(DataType::String(text))
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<DataType> {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.right])
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<DataType> {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<DataType> {
+        let mut parts = vec![&expr.callee];
+        parts.extend(expr.arguments.iter());
+        self.parenthesize("call", &parts)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<DataType> {
+        self.parenthesize("group", &[&expr.expression])
+    }
+
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> Result<DataType> {
+        Ok(DataType::String(self.reference(&expr.var_name)))
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<DataType> {
+        let value = self.expr(&expr.var_value)?;
+        Ok(DataType::String(format!(
+            "(= {} {})",
+            self.reference(&expr.var_name),
+            value
+        )))
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<DataType> {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<DataType> {
+        let object = self.expr(&expr.object)?;
+        Ok(DataType::String(format!(
+            "(. {} {})",
+            object, expr.name.lexeme
+        )))
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<DataType> {
+        let object = self.expr(&expr.object)?;
+        let value = self.expr(&expr.value)?;
+        Ok(DataType::String(format!(
+            "(= (. {} {}) {})",
+            object, expr.name.lexeme, value
+        )))
+    }
+
+    fn visit_this_expr(&mut self, expr: &ThisExpr) -> Result<DataType> {
+        Ok(DataType::String(self.reference(&expr.keyword)))
+    }
+
+    fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<DataType> {
+        Ok(DataType::String(format!(
+            "({} {})",
+            self.reference(&expr.keyword),
+            expr.method.lexeme
+        )))
+    }
+
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> Result<DataType> {
+        let elements: Vec<_> = expr.elements.iter().collect();
+        self.parenthesize("array", &elements)
+    }
+
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<DataType> {
+        let mut text = "(map".to_string();
+        for (key, value) in &expr.entries {
+            let key = self.expr(key)?;
+            let value = self.expr(value)?;
+            text.push_str(&format!(" (: {key} {value})"));
+        }
+        text.push(')');
+        Ok(DataType::String(text))
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<DataType> {
+        self.parenthesize("[]", &[&expr.object, &expr.index])
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> Result<DataType> {
+        let target = self.parenthesize("[]", &[&expr.object, &expr.index])?;
+        let value = self.expr(&expr.value)?;
+        Ok(DataType::String(format!("(= {target} {value})")))
+    }
+
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> Result<DataType> {
+        self.parenthesize("try", &[&expr.expression])
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<Option<DataType>> {
+        let value = self.expr(&stmt.expression)?;
+        self.line(&format!("print {value}"));
+        Ok(None)
+    }
+
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<Option<DataType>> {
+        let value = self.expr(&stmt.expression)?;
+        self.line(&format!("expr {value}"));
+        Ok(None)
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<Option<DataType>> {
+        match &stmt.var_value {
+            Some(value) => {
+                let value = self.expr(value)?;
+                self.line(&format!("var {} = {}", stmt.var_name.lexeme, value));
+            }
+            None => self.line(&format!("var {}", stmt.var_name.lexeme)),
+        }
+        Ok(None)
+    }
+
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<Option<DataType>> {
+        self.line("block");
+        self.nested(|printer| printer.statements(&stmt.statements))?;
+        Ok(None)
+    }
+
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<Option<DataType>> {
+        let condition = self.expr(&stmt.condition)?;
+        self.line(&format!("if {condition}"));
+        self.nested(|printer| printer.stmt(&stmt.then_branch))?;
+        if let Some(else_branch) = &stmt.else_branch {
+            self.line("else");
+            self.nested(|printer| printer.stmt(else_branch))?;
+        }
+        Ok(None)
+    }
+
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<Option<DataType>> {
+        let condition = self.expr(&stmt.condition)?;
+        self.line(&format!("while {condition}"));
+        self.nested(|printer| printer.stmt(&stmt.body))?;
+        Ok(None)
+    }
+
+    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<Option<DataType>> {
+        let params: Vec<_> = stmt
+            .params
+            .iter()
+            .zip(stmt.param_types.iter())
+            .map(|(param, annotation)| match annotation {
+                Some(annotation) => format!("{}: {}", param.lexeme, annotation),
+                None => param.lexeme.to_string(),
+            })
+            .collect();
+        let mut header = format!("fun {}({})", stmt.name.lexeme, params.join(", "));
+        if let Some(return_type) = &stmt.return_type {
+            header.push_str(&format!(": {return_type}"));
+        }
+        self.line(&header);
+        self.nested(|printer| printer.statements(&stmt.body))?;
+        Ok(None)
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<Option<DataType>> {
+        match &stmt.value {
+            Some(value) => {
+                let value = self.expr(value)?;
+                self.line(&format!("return {value}"));
+            }
+            None => self.line("return"),
+        }
+        Ok(None)
+    }
+
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<Option<DataType>> {
+        match &stmt.super_class {
+            Some(super_class) => {
+                let super_class = self.reference(&super_class.var_name);
+                self.line(&format!("class {} < {}", stmt.name.lexeme, super_class))
+            }
+            None => self.line(&format!("class {}", stmt.name.lexeme)),
+        }
+        self.nested(|printer| {
+            for method in &stmt.methods {
+                printer.visit_function_statement(method)?;
+            }
+            Ok(())
+        })?;
+        Ok(None)
+    }
+
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> Result<Option<DataType>> {
+        let value = self.expr(&stmt.value)?;
+        self.line(&format!("throw {value}"));
+        Ok(None)
+    }
+
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> Result<Option<DataType>> {
+        self.line("try");
+        self.nested(|printer| printer.statements(&stmt.body))?;
+        if let Some(catch) = &stmt.catch {
+            self.line(&format!("catch ({})", catch.name.lexeme));
+            self.nested(|printer| printer.statements(&catch.body))?;
+        }
+        if let Some(finally) = &stmt.finally {
+            self.line("finally");
+            self.nested(|printer| printer.statements(finally))?;
+        }
+        Ok(None)
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> Result<Option<DataType>> {
+        let path = DataType::String(stmt.path.clone()).repr();
+        self.line(&format!("import {} as {}", path, stmt.name));
+        Ok(None)
+    }
+
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> Result<Option<DataType>> {
+        let condition = self.expr(&stmt.condition)?;
+        match &stmt.message {
+            Some(message) => {
+                let message = self.expr(message)?;
+                self.line(&format!("assert {condition}, {message}"));
+            }
+            None => self.line(&format!("assert {condition}")),
+        }
+        Ok(None)
+    }
+
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> Result<Option<DataType>> {
+        self.line("export");
+        self.nested(|printer| printer.stmt(&stmt.declaration))?;
+        Ok(None)
+    }
+
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> Result<Option<DataType>> {
+        let subject = self.expr(&stmt.subject)?;
+        self.line(&format!("switch {subject}"));
+        self.nested(|printer| {
+            for case in &stmt.cases {
+                let value = printer.expr(&case.value)?;
+                printer.line(&format!("case {value}"));
+                printer.nested(|printer| printer.statements(&case.body))?;
+            }
+            if let Some(default) = &stmt.default {
+                printer.line("default");
+                printer.nested(|printer| printer.statements(default))?;
+            }
+            Ok(())
+        })?;
+        Ok(None)
+    }
+
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> Result<Option<DataType>> {
+        let iterable = self.expr(&stmt.iterable)?;
+        self.line(&format!("for {} in {}", stmt.name.lexeme, iterable));
+        self.nested(|printer| printer.stmt(&stmt.body))?;
+        Ok(None)
+    }
+}
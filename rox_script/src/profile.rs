@@ -0,0 +1,134 @@
+//! Allocation tracking, for finding out what a script keeps alive. Once
+//! [`enable`] is called, every environment, function, instance, array and
+//! map the interpreter creates on this thread is counted, along with how
+//! many of each are alive and the most that were alive at once.
+//!
+//! A closure keeps the scope it was declared in alive, and that scope often
+//! holds the closure, so some memory is never freed. Taking a [`report`]
+//! after dropping the interpreter shows how much: anything still alive then
+//! is only kept alive by such reference cycles.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// What an [`Allocation`] counts towards.
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Environment,
+    Function,
+    Instance,
+    Array,
+    Map,
+}
+
+impl Kind {
+    const ALL: [Kind; 5] = [
+        Kind::Environment,
+        Kind::Function,
+        Kind::Instance,
+        Kind::Array,
+        Kind::Map,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Environment => "environments",
+            Kind::Function => "functions",
+            Kind::Instance => "instances",
+            Kind::Array => "arrays",
+            Kind::Map => "maps",
+        }
+    }
+}
+
+/// The counts for one kind of allocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Count {
+    pub created: usize,
+    pub live: usize,
+    pub peak: usize,
+}
+
+/// The counts for every kind of allocation, as [`report`] returns them.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    counts: [Count; Kind::ALL.len()],
+}
+
+impl Report {
+    pub fn count(&self, kind: Kind) -> Count {
+        self.counts[kind as usize]
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "{:<14}{:>10}{:>10}{:>10}", "", "created", "peak", "live")?;
+        for kind in Kind::ALL {
+            let count = self.count(kind);
+            writeln!(
+                f,
+                "{:<14}{:>10}{:>10}{:>10}",
+                kind.label(),
+                count.created,
+                count.peak,
+                count.live
+            )?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static COUNTS: RefCell<Option<Report>> = const { RefCell::new(None) };
+}
+
+/// Starts counting allocations made on this thread from now on.
+pub fn enable() {
+    COUNTS.with(|counts| {
+        counts.borrow_mut().get_or_insert_with(Report::default);
+    });
+}
+
+/// The counts so far, or `None` if counting isn't enabled on this thread.
+pub fn report() -> Option<Report> {
+    COUNTS.with(|counts| counts.borrow().clone())
+}
+
+/// Counted as alive until dropped. Values that are shared between copies,
+/// like functions, hold it in an `Rc` so the copies count once.
+#[derive(Debug)]
+pub struct Allocation {
+    kind: Kind,
+}
+
+/// Counts a new allocation of `kind`, or returns `None` when counting isn't
+/// enabled, so untracked values cost nothing but the empty field.
+pub fn track(kind: Kind) -> Option<Allocation> {
+    COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        let count = &mut counts.as_mut()?.counts[kind as usize];
+        count.created += 1;
+        count.live += 1;
+        count.peak = count.peak.max(count.live);
+        Some(Allocation { kind })
+    })
+}
+
+/// [`track`] for values whose copies share one allocation.
+pub fn track_shared(kind: Kind) -> Option<Rc<Allocation>> {
+    track(kind).map(Rc::new)
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        // Values dropped while the thread exits may outlive the counts.
+        let _ = COUNTS.try_with(|counts| {
+            if let Some(counts) = counts.borrow_mut().as_mut() {
+                counts.counts[self.kind as usize].live -= 1;
+            }
+        });
+    }
+}
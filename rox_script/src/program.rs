@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use anyhow::Result;
+
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner;
+use crate::stmt::Stmt;
+use crate::token::TokenId;
+
+/// Source ids for programs count down from the top of the range, so they
+/// never meet the ids an interpreter hands out to the sources it runs,
+/// which count up from one.
+static NEXT_SOURCE_ID: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// A script scanned, parsed and resolved once, which any number of
+/// interpreters can then run without doing that work again, such as a
+/// server running the same script for every request in a fresh
+/// interpreter.
+///
+/// Programs are resolved without strict mode or declared externals, so an
+/// interpreter running one doesn't apply those.
+///
+/// The syntax tree is built from `Rc`s, like every value the interpreter
+/// works with, so a program can only be shared by interpreters on the thread
+/// that compiled it. A server running scripts on several threads compiles
+/// each script once per thread.
+#[derive(Clone)]
+pub struct Program {
+    statements: Rc<[Rc<Stmt>]>,
+    /// What resolving the program found: the scope distance of each local
+    /// variable use.
+    locals: Rc<HashMap<TokenId, usize>>,
+}
+
+impl Program {
+    pub fn compile(source: &str) -> Result<Program> {
+        let source_id = NEXT_SOURCE_ID.fetch_sub(1, Ordering::Relaxed);
+        let tokens = scanner::run(source.to_string(), source_id)?;
+        let statements = Parser::new(tokens).parse()?;
+        let resolver = Interpreter::new(Limits::default());
+        Resolver::new(&resolver).resolve(statements.clone())?;
+        Ok(Program {
+            statements: statements.into(),
+            locals: Rc::new(resolver.locals.take()),
+        })
+    }
+
+    pub(crate) fn statements(&self) -> &[Rc<Stmt>] {
+        &self.statements
+    }
+
+    pub(crate) fn locals(&self) -> &HashMap<TokenId, usize> {
+        &self.locals
+    }
+}
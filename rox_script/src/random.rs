@@ -0,0 +1,38 @@
+//! The generator behind `random()` in `std/math`. It is a xorshift64*
+//! generator: small, fast and good enough for games and simulations, but
+//! predictable, so not for anything that has to stay secret.
+
+use crate::time;
+
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// A generator that gives the same numbers every time it is given the
+    /// same `seed`.
+    pub fn new(seed: u64) -> Random {
+        const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+        // The state must never be zero, or every number after it would be.
+        let state = seed ^ MIX;
+        Random {
+            state: if state == 0 { MIX } else { state },
+        }
+    }
+
+    /// A generator seeded from the current time, giving different numbers
+    /// on every run where there is a clock to read.
+    pub fn from_time() -> Random {
+        Random::new(time::since_epoch().as_nanos() as u64)
+    }
+
+    /// The next number, at least 0 and less than 1.
+    pub fn next_f64(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let bits = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        // The top 53 bits, as many as an f64 holds exactly.
+        (bits >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -1,19 +1,22 @@
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
-use anyhow::anyhow;
-
+use crate::diagnostics::{Diagnostic, Warning};
+use crate::error::LoxError;
 use crate::expr::{
-    AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, LiteralExpr, LogicalExpr,
-    SetExpr, SuperExpr, ThisExpr, UnaryExpr, VarExpr,
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
 };
 use crate::interpreter::Interpreter;
+use crate::stack;
 use crate::stmt::{
-    BlockStmt, ClassStmt, ExprStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, Stmt, VarStmt,
-    WhileStmt,
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
 };
+use crate::symbol::Symbol;
 use crate::token::{DataType, Token};
 use crate::visitor::{ExprVisitor, StmtVisitor};
 
@@ -24,17 +27,46 @@ enum FunctionType {
     Method,
     Initializer,
 }
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 enum ClassType {
     None,
     Class,
+    /// A class with a superclass, whose methods can use `super`.
+    Subclass,
+}
+
+/// What the resolver knows about a variable in a local scope.
+struct Local {
+    /// Whether its initializer has been resolved, so it can be read.
+    defined: bool,
+    /// Whether anything reads it.
+    read: bool,
+    /// Where it is declared, if it is warned about when nothing reads it.
+    declaration: Option<Token>,
+}
+
+impl Local {
+    /// `this` and `super`, which are declared by the class around them.
+    fn implicit() -> Local {
+        Local {
+            defined: true,
+            read: false,
+            declaration: None,
+        }
+    }
 }
 
 pub struct Resolver<'a> {
     interpreter: &'a Interpreter,
-    scopes: RefCell<Vec<RefCell<HashMap<String, bool>>>>,
+    scopes: RefCell<Vec<RefCell<HashMap<Symbol, Local>>>>,
     current_function: RefCell<FunctionType>,
     current_class: RefCell<ClassType>,
+    /// The globals the statements being resolved declare at the top level,
+    /// which may be used before the declaration runs.
+    globals: HashSet<Symbol>,
+    /// What the warnings found so far are reported against.
+    file: String,
+    warnings: Vec<Diagnostic>,
 }
 
 impl<'a> Resolver<'a> {
@@ -44,41 +76,150 @@ impl<'a> Resolver<'a> {
             scopes: RefCell::new(Vec::new()),
             current_function: RefCell::new(FunctionType::None),
             current_class: RefCell::new(ClassType::None),
+            globals: HashSet::new(),
+            file: match interpreter.current_file() {
+                Some(path) => path.display().to_string(),
+                None => "<script>".to_string(),
+            },
+            warnings: vec![],
         }
     }
 
-    pub fn resolve(&mut self, statements: Vec<Rc<dyn Stmt>>) -> anyhow::Result<()> {
-        for stmt in statements.iter() {
-            stmt.accept(self)?;
+    /// The warnings about the statements resolved so far.
+    pub fn into_warnings(self) -> Vec<Diagnostic> {
+        self.warnings
+    }
+
+    pub fn resolve(&mut self, statements: Vec<Rc<Stmt>>) -> anyhow::Result<()> {
+        self.globals
+            .extend(statements.iter().filter_map(declared_name));
+        self.resolve_statements(&statements)
+    }
+
+    fn resolve_block(&mut self, statements: &[Rc<Stmt>]) -> anyhow::Result<()> {
+        self.begin_scope();
+        self.resolve_statements(statements)?;
+        self.end_scope();
+        Ok(())
+    }
+
+    /// Resolves `statements` in the current scope, warning about any that
+    /// follow a `return` or `throw` and so can never run.
+    fn resolve_statements(&mut self, statements: &[Rc<Stmt>]) -> anyhow::Result<()> {
+        let mut warned = false;
+        for (index, statement) in statements.iter().enumerate() {
+            self.resolve_stmt(statement)?;
+            if warned || index + 1 == statements.len() {
+                continue;
+            }
+            let keyword = match statement.as_ref() {
+                Stmt::Return(stmt) => &stmt.keyword,
+                Stmt::Throw(stmt) => &stmt.keyword,
+                _ => continue,
+            };
+            let message = format!("Code after '{}' is unreachable.", keyword.lexeme);
+            self.warn(Warning::Unreachable, keyword, message);
+            warned = true;
         }
         Ok(())
     }
 
+    fn warn(&mut self, warning: Warning, token: &Token, message: String) {
+        self.warnings
+            .push(Diagnostic::warning(warning, message, &self.file, token));
+    }
+
+    fn resolve_expr(&mut self, expr: &Rc<Expr>) -> anyhow::Result<DataType> {
+        stack::grow(|| expr.accept(self))
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Rc<Stmt>) -> anyhow::Result<Option<DataType>> {
+        stack::grow(|| stmt.accept(self))
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.borrow_mut().push(RefCell::new(HashMap::new()));
     }
 
+    /// Leaves the innermost scope, warning about the declarations in it
+    /// nothing read. Names starting with `_` are meant to go unread.
     fn end_scope(&mut self) {
-        self.scopes.borrow_mut().pop();
+        let Some(scope) = self.scopes.borrow_mut().pop() else {
+            return;
+        };
+        let mut unread: Vec<Token> = scope
+            .into_inner()
+            .into_values()
+            .filter(|local| !local.read)
+            .filter_map(|local| local.declaration)
+            .filter(|name| !name.lexeme.starts_with('_'))
+            .collect();
+        unread.sort_by_key(Token::id);
+        for name in unread {
+            let message = format!("'{}' is declared but never used.", name.lexeme);
+            self.warn(Warning::Unused, &name, message);
+        }
     }
 
-    fn declare(&mut self, name: &Token) -> anyhow::Result<DataType> {
+    /// Adds `name` to the innermost scope. Variables, functions and classes
+    /// are `checked`: they are warned about if nothing reads them or they
+    /// shadow a global or a variable of an enclosing scope. Parameters and the names
+    /// `catch` and `for` bind aren't.
+    fn declare(&mut self, name: &Token, checked: bool) -> anyhow::Result<DataType> {
+        let scopes = self.scopes.borrow();
+        let shadows = checked
+            && !scopes.is_empty()
+            && (self.globals.contains(&name.lexeme)
+                || scopes
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .any(|scope| scope.borrow().contains_key(&name.lexeme)));
+        drop(scopes);
         if let Some(scope) = self.scopes.borrow().last() {
             if scope.borrow().contains_key(&name.lexeme) {
-                return Err(anyhow!("Already a variable with this name in this scope."));
+                return Err(LoxError::resolve(
+                    name,
+                    "Already a variable with this name in this scope.",
+                )
+                .into());
             }
-            scope.borrow_mut().insert(name.lexeme.to_string(), false);
+            let local = Local {
+                defined: false,
+                read: false,
+                declaration: checked.then(|| name.clone()),
+            };
+            scope.borrow_mut().insert(name.lexeme.clone(), local);
+        }
+        if shadows {
+            let message = format!(
+                "'{}' shadows a variable of an enclosing scope.",
+                name.lexeme
+            );
+            self.warn(Warning::Shadowed, name, message);
         }
         Ok(DataType::Nil)
     }
 
     fn define(&mut self, name: &Token) -> anyhow::Result<DataType> {
         if let Some(scope) = self.scopes.borrow().last() {
-            scope.borrow_mut().insert(name.lexeme.to_string(), true);
+            if let Some(local) = scope.borrow_mut().get_mut(&name.lexeme) {
+                local.defined = true;
+            }
         }
         Ok(DataType::Nil)
     }
 
+    /// Notes that the innermost variable called `name` is read.
+    fn read(&mut self, name: &Token) {
+        for scope in self.scopes.borrow().iter().rev() {
+            if let Some(local) = scope.borrow_mut().get_mut(&name.lexeme) {
+                local.read = true;
+                return;
+            }
+        }
+    }
+
     fn resolve_function(
         &mut self,
         stmt: &FunctionStmt,
@@ -87,26 +228,127 @@ impl<'a> Resolver<'a> {
         let enclosing_function = self.current_function.replace(function_type);
         self.begin_scope();
         for param in stmt.params.iter() {
-            self.declare(param)?;
+            self.declare(param, false)?;
             self.define(param)?;
         }
-        for body in &stmt.body {
-            body.accept(self)?;
-        }
+        self.resolve_statements(&stmt.body)?;
         self.end_scope();
         self.current_function.replace(enclosing_function);
         Ok(DataType::Nil)
     }
 
-    fn resolve_local(&mut self, expr: Rc<dyn Expr>, name: &Token) -> anyhow::Result<DataType> {
+    fn resolve_local(&mut self, name: &Token) -> anyhow::Result<DataType> {
         for (scope, map) in self.scopes.borrow().iter().rev().enumerate() {
             if map.borrow().contains_key(&name.lexeme) {
-                self.interpreter.resolve(expr, scope)?;
+                self.interpreter.resolve(name, scope);
                 return Ok(DataType::Nil);
             }
         }
+        if self.interpreter.strict() && !self.is_declared_global(&name.lexeme) {
+            let error = LoxError::resolve(name, format!("Undefined variable '{}'.", name.lexeme));
+            return Err(match self.similar_name(&name.lexeme) {
+                Some(similar) => error.with_hint(format!("did you mean '{similar}'?")),
+                None => error,
+            }
+            .into());
+        }
         Ok(DataType::Nil)
     }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes
+            .borrow()
+            .iter()
+            .any(|scope| scope.borrow().contains_key(name))
+    }
+
+    fn is_declared_global(&self, name: &str) -> bool {
+        self.globals.contains(name)
+            || self.interpreter.defines_global(name)
+            || self.interpreter.externals().contains_key(name)
+    }
+
+    /// The declared name closest to `name`, if one is close enough to be a
+    /// likely typo of it.
+    fn similar_name(&self, name: &str) -> Option<String> {
+        let mut candidates: Vec<String> = self
+            .scopes
+            .borrow()
+            .iter()
+            .flat_map(|scope| {
+                scope
+                    .borrow()
+                    .keys()
+                    .map(Symbol::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        candidates.extend(self.globals.iter().map(Symbol::to_string));
+        candidates.extend(
+            self.interpreter
+                .global_names()
+                .iter()
+                .map(Symbol::to_string),
+        );
+        candidates.extend(self.interpreter.externals().keys().cloned());
+        candidates.sort();
+        candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(name, &candidate), candidate))
+            .filter(|(distance, _)| *distance <= 2 && *distance < name.len())
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// Checks a direct call of an external function declared with an arity.
+    fn check_external_call(&self, expr: &CallExpr) -> anyhow::Result<()> {
+        let Expr::Var(callee) = expr.callee.as_ref() else {
+            return Ok(());
+        };
+        let name = &callee.var_name.lexeme;
+        if self.is_local(name) || self.globals.contains(name) {
+            return Ok(());
+        }
+        if let Some(Some(arity)) = self.interpreter.externals().get(name.as_str()) {
+            if *arity != expr.arguments.len() {
+                let message = format!(
+                    "Expected {} arguments but got {} when calling '{}'.",
+                    arity,
+                    expr.arguments.len(),
+                    name
+                );
+                return Err(LoxError::resolve(&expr.paren, message).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How many characters must be inserted, deleted or replaced to turn `a`
+/// into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a_char != *b_char);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The global a top-level statement declares, if any.
+fn declared_name(statement: &Rc<Stmt>) -> Option<Symbol> {
+    match statement.unexported() {
+        Stmt::Var(var) => Some(var.var_name.lexeme.clone()),
+        Stmt::Function(function) => Some(function.name.lexeme.clone()),
+        Stmt::Class(class) => Some(class.name.lexeme.clone()),
+        Stmt::Import(import) => Some(Symbol::from(&import.name)),
+        _ => None,
+    }
 }
 
 impl<'a> ExprVisitor for Resolver<'a> {
@@ -115,26 +357,27 @@ impl<'a> ExprVisitor for Resolver<'a> {
     }
 
     fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> anyhow::Result<DataType> {
-        expr.right.accept(self);
+        self.resolve_expr(&expr.right)?;
         Ok(DataType::Nil)
     }
 
     fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> anyhow::Result<DataType> {
-        expr.left.accept(self);
-        expr.right.accept(self);
+        self.resolve_expr(&expr.left)?;
+        self.resolve_expr(&expr.right)?;
         Ok(DataType::Nil)
     }
 
     fn visit_call_expr(&mut self, expr: &CallExpr) -> anyhow::Result<DataType> {
-        expr.callee.accept(self);
+        self.check_external_call(expr)?;
+        self.resolve_expr(&expr.callee)?;
         for arguments in &expr.arguments {
-            arguments.accept(self);
+            self.resolve_expr(arguments)?;
         }
         Ok(DataType::Nil)
     }
 
     fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> anyhow::Result<DataType> {
-        expr.expression.accept(self);
+        self.resolve_expr(&expr.expression)?;
         Ok(DataType::Nil)
     }
 
@@ -148,148 +391,183 @@ impl<'a> ExprVisitor for Resolver<'a> {
                 .unwrap()
                 .borrow()
                 .get(&token.lexeme)
-                == Some(&false)
+                .is_some_and(|local| !local.defined)
         {
-            return Err(anyhow!("Can't read local variable in its own initializer."));
+            return Err(LoxError::resolve(
+                token,
+                "Can't read local variable in its own initializer.",
+            )
+            .into());
         } else {
-            let expr: Rc<dyn Expr> = Rc::new(VarExpr {
-                var_name: expr.var_name.clone(),
-            });
-            self.resolve_local(expr, token)?;
+            self.resolve_local(token)?;
+            self.read(token);
         }
         Ok(DataType::Nil)
     }
 
     fn visit_assign_expr(&mut self, expr: &AssignExpr) -> anyhow::Result<DataType> {
-        expr.accept(self);
-
-        let rc_expr: Rc<dyn Expr> = Rc::new(AssignExpr {
-            var_name: expr.var_name.clone(),
-            var_value: expr.var_value.clone(),
-        });
+        self.resolve_expr(&expr.var_value)?;
 
-        self.resolve_local(rc_expr, &expr.var_name)?;
+        self.resolve_local(&expr.var_name)?;
         Ok(DataType::Nil)
     }
 
     fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> anyhow::Result<DataType> {
-        expr.left.accept(self);
-        expr.right.accept(self);
+        self.resolve_expr(&expr.left)?;
+        self.resolve_expr(&expr.right)?;
         Ok(DataType::Nil)
     }
 
     fn visit_get_expr(&mut self, expr: &GetExpr) -> anyhow::Result<DataType> {
-        expr.object.accept(self);
+        self.resolve_expr(&expr.object)?;
         Ok(DataType::Nil)
     }
 
     fn visit_set_expr(&mut self, expr: &SetExpr) -> anyhow::Result<DataType> {
-        expr.value.accept(self);
-        expr.object.accept(self);
+        self.resolve_expr(&expr.value)?;
+        self.resolve_expr(&expr.object)?;
         Ok(DataType::Nil)
     }
 
     fn visit_this_expr(&mut self, expr: &ThisExpr) -> anyhow::Result<DataType> {
         if *self.current_class.borrow() == ClassType::None {
-            return Err(anyhow!("Can't use 'this' outside of a class."));
+            return Err(
+                LoxError::resolve(&expr.keyword, "Can't use 'this' outside of a class.").into(),
+            );
         }
 
-        let rc_expr: Rc<dyn Expr> = Rc::new(ThisExpr {
-            keyword: expr.keyword.clone(),
-        });
-
-        self.resolve_local(rc_expr, &expr.keyword)?;
+        self.resolve_local(&expr.keyword)?;
         Ok(DataType::Nil)
     }
 
     fn visit_super_expr(&mut self, expr: &SuperExpr) -> anyhow::Result<DataType> {
-        let rc_expr: Rc<dyn Expr> = Rc::new(SuperExpr {
-            keyword: expr.keyword.clone(),
-            method: expr.method.clone(),
-        });
-        self.resolve_local(rc_expr, &expr.keyword)
+        let current_class = *self.current_class.borrow();
+        let message = match current_class {
+            ClassType::None => "Can't use 'super' outside of a class.",
+            ClassType::Class => "Can't use 'super' in a class with no superclass.",
+            ClassType::Subclass => return self.resolve_local(&expr.keyword),
+        };
+        Err(LoxError::resolve(&expr.keyword, message).into())
     }
-}
 
-impl<'a> StmtVisitor for Resolver<'a> {
-    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> anyhow::Result<DataType> {
-        stmt.expression.accept(self);
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> anyhow::Result<DataType> {
+        for element in &expr.elements {
+            self.resolve_expr(element)?;
+        }
+        Ok(DataType::Nil)
+    }
+
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> anyhow::Result<DataType> {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key)?;
+            self.resolve_expr(value)?;
+        }
         Ok(DataType::Nil)
     }
 
-    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> anyhow::Result<DataType> {
-        stmt.expression.accept(self);
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> anyhow::Result<DataType> {
+        self.resolve_expr(&expr.object)?;
+        self.resolve_expr(&expr.index)?;
         Ok(DataType::Nil)
     }
 
-    fn visit_var_statement(&mut self, stmt: &VarStmt) -> anyhow::Result<DataType> {
-        self.declare(&stmt.var_name)?;
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> anyhow::Result<DataType> {
+        self.resolve_expr(&expr.value)?;
+        self.resolve_expr(&expr.object)?;
+        self.resolve_expr(&expr.index)?;
+        Ok(DataType::Nil)
+    }
+
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> anyhow::Result<DataType> {
+        self.resolve_expr(&expr.expression)?;
+        Ok(DataType::Nil)
+    }
+}
+
+impl<'a> StmtVisitor for Resolver<'a> {
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.expression)?;
+        Ok(None)
+    }
+
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.expression)?;
+        Ok(None)
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> anyhow::Result<Option<DataType>> {
+        self.declare(&stmt.var_name, true)?;
         if let Some(initializer) = &stmt.var_value {
-            initializer.accept(self);
+            self.resolve_expr(initializer)?;
         }
         self.define(&stmt.var_name)?;
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> anyhow::Result<DataType> {
-        self.begin_scope();
-        for statement in &stmt.statements {
-            let _ = statement.accept(self)?;
-        }
-        self.end_scope();
-        Ok(DataType::Nil)
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_block(&stmt.statements)?;
+        Ok(None)
     }
 
-    fn visit_if_statement(&mut self, stmt: &IfStmt) -> anyhow::Result<DataType> {
-        stmt.condition.accept(self);
-        stmt.then_branch.accept(self)?;
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.condition)?;
+        self.resolve_stmt(&stmt.then_branch)?;
         if let Some(else_branch) = &stmt.else_branch {
-            else_branch.accept(self)?;
+            self.resolve_stmt(else_branch)?;
         }
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> anyhow::Result<DataType> {
-        stmt.condition.accept(self);
-        stmt.body.accept(self)?;
-        Ok(DataType::Nil)
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.condition)?;
+        self.resolve_stmt(&stmt.body)?;
+        Ok(None)
     }
 
-    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> anyhow::Result<DataType> {
-        self.declare(&stmt.name)?;
+    fn visit_function_statement(
+        &mut self,
+        stmt: &FunctionStmt,
+    ) -> anyhow::Result<Option<DataType>> {
+        self.declare(&stmt.name, true)?;
         self.define(&stmt.name)?;
         self.resolve_function(stmt, FunctionType::Function)?;
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> anyhow::Result<DataType> {
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> anyhow::Result<Option<DataType>> {
         if *self.current_function.borrow() == FunctionType::None {
-            return Err(anyhow!("Can't return from top-level code."));
+            return Err(
+                LoxError::resolve(&stmt.keyword, "Can't return from top-level code.").into(),
+            );
         }
         if let Some(return_value) = &stmt.value {
             if *self.current_function.borrow() == FunctionType::Initializer {
-                return Err(anyhow!("Can't return a value from an initializer."));
+                return Err(LoxError::resolve(
+                    &stmt.keyword,
+                    "Can't return a value from an initializer.",
+                )
+                .into());
             }
-            return_value.accept(self);
+            self.resolve_expr(return_value)?;
         }
-        Ok(DataType::Nil)
+        Ok(None)
     }
 
-    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> anyhow::Result<DataType> {
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> anyhow::Result<Option<DataType>> {
         let enclosing_class = self.current_class.replace(ClassType::Class);
-        self.declare(&stmt.name)?;
+        self.declare(&stmt.name, true)?;
         self.define(&stmt.name)?;
 
         if let Some(super_class) = &stmt.super_class {
-            let super_class = super_class.as_any().downcast_ref::<VarExpr>().unwrap();
-            if stmt
-                .name
-                .lexeme
-                .eq_ignore_ascii_case(&super_class.var_name.lexeme.to_string())
-            {
-                return Err(anyhow!("A class can't inherit from itself."));
+            if stmt.name.lexeme == super_class.var_name.lexeme {
+                return Err(LoxError::resolve(
+                    &super_class.var_name,
+                    "A class can't inherit from itself.",
+                )
+                .into());
             }
-            super_class.accept(self);
+            self.current_class.replace(ClassType::Subclass);
+            self.visit_var_expr(super_class)?;
         }
 
         if stmt.super_class.is_some() {
@@ -300,7 +578,7 @@ impl<'a> StmtVisitor for Resolver<'a> {
                 .borrow_mut()
                 .unwrap()
                 .borrow_mut()
-                .insert("super".to_string(), true);
+                .insert("super".into(), Local::implicit());
         }
 
         self.begin_scope();
@@ -311,12 +589,11 @@ impl<'a> StmtVisitor for Resolver<'a> {
             .borrow_mut()
             .unwrap()
             .borrow_mut()
-            .insert("this".to_string(), true);
+            .insert("this".into(), Local::implicit());
 
         for method in &stmt.methods {
-            let method = method.as_any().downcast_ref::<FunctionStmt>().unwrap();
             let mut declaration = FunctionType::Method;
-            if method.name.lexeme.eq_ignore_ascii_case("init") {
+            if method.name.lexeme == "init" {
                 declaration = FunctionType::Initializer;
             }
             self.resolve_function(method, declaration)?;
@@ -329,6 +606,78 @@ impl<'a> StmtVisitor for Resolver<'a> {
         }
 
         self.current_class.replace(enclosing_class);
-        Ok(DataType::Nil)
+        Ok(None)
+    }
+
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.value)?;
+        Ok(None)
+    }
+
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_block(&stmt.body)?;
+        if let Some(catch) = &stmt.catch {
+            self.begin_scope();
+            self.declare(&catch.name, false)?;
+            self.define(&catch.name)?;
+            self.resolve_statements(&catch.body)?;
+            self.end_scope();
+        }
+        if let Some(finally) = &stmt.finally {
+            self.resolve_block(finally)?;
+        }
+        Ok(None)
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> anyhow::Result<Option<DataType>> {
+        if !self.scopes.borrow().is_empty() {
+            return Err(LoxError::resolve(
+                &stmt.keyword,
+                "Can only import at the top level of a script.",
+            )
+            .into());
+        }
+        Ok(None)
+    }
+
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.condition)?;
+        if let Some(message) = &stmt.message {
+            self.resolve_expr(message)?;
+        }
+        Ok(None)
+    }
+
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> anyhow::Result<Option<DataType>> {
+        if !self.scopes.borrow().is_empty() {
+            return Err(LoxError::resolve(
+                &stmt.keyword,
+                "Can only export at the top level of a script.",
+            )
+            .into());
+        }
+        self.resolve_stmt(&stmt.declaration)
+    }
+
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.subject)?;
+        for case in &stmt.cases {
+            self.resolve_expr(&case.value)?;
+            self.resolve_block(&case.body)?;
+        }
+        if let Some(default) = &stmt.default {
+            self.resolve_block(default)?;
+        }
+        Ok(None)
+    }
+
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> anyhow::Result<Option<DataType>> {
+        self.resolve_expr(&stmt.iterable)?;
+        self.begin_scope();
+        self.declare(&stmt.name, false)?;
+        self.define(&stmt.name)?;
+        self.resolve_stmt(&stmt.body)?;
+        self.end_scope();
+        Ok(None)
     }
 }
@@ -0,0 +1,70 @@
+//! Which of the host's capabilities scripts may use. An interpreter made
+//! with a sandbox that denies a capability never registers the natives and
+//! standard modules that need it, so untrusted scripts can't reach them:
+//! using one fails the way using any undefined global or unknown module does.
+
+/// A capability policy, allowing everything until told otherwise, as in
+/// `RoxSandbox::new().deny_fs().deny_env()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoxSandbox {
+    fs: bool,
+    env: bool,
+    stdio: bool,
+}
+
+/// Something a script can reach outside the interpreter through natives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    /// Reading scripts from the local filesystem for `import`. A source
+    /// provider the host sets still serves imports.
+    Fs,
+    /// The host process's environment: `args()`.
+    Env,
+    /// The standard streams, through `std/io`.
+    Stdio,
+}
+
+impl RoxSandbox {
+    /// A sandbox allowing every capability.
+    pub fn new() -> Self {
+        RoxSandbox {
+            fs: true,
+            env: true,
+            stdio: true,
+        }
+    }
+
+    /// A sandbox allowing no capability.
+    pub fn deny_all() -> Self {
+        RoxSandbox::new().deny_fs().deny_env().deny_stdio()
+    }
+
+    pub fn deny_fs(mut self) -> Self {
+        self.fs = false;
+        self
+    }
+
+    pub fn deny_env(mut self) -> Self {
+        self.env = false;
+        self
+    }
+
+    pub fn deny_stdio(mut self) -> Self {
+        self.stdio = false;
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Fs => self.fs,
+            Capability::Env => self.env,
+            Capability::Stdio => self.stdio,
+        }
+    }
+}
+
+impl Default for RoxSandbox {
+    fn default() -> Self {
+        RoxSandbox::new()
+    }
+}
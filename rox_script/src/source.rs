@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// Where the interpreter reads scripts from. File execution and `import`
+/// both go through this, so embedders can serve sources from memory or a
+/// virtual filesystem instead of the local disk.
+pub trait SourceProvider {
+    /// Returns the source text stored at `path`.
+    fn read(&self, path: &Path) -> Result<String>;
+}
+
+/// The path of the script `import` names, relative to the directory of
+/// `importer`, the script importing it, if there is one. `/` and `\\` both
+/// separate directories in import paths so scripts import the same way on
+/// every platform, and `.` and `..` are applied, so every way of naming a
+/// module gives the same path.
+pub fn import_path(importer: Option<&Path>, import: &str) -> PathBuf {
+    if Path::new(import).is_absolute() {
+        return PathBuf::from(import);
+    }
+    let mut path = importer
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    for part in import.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." if matches!(path.components().next_back(), Some(Component::Normal(_))) => {
+                path.pop();
+            }
+            part => path.push(part),
+        }
+    }
+    path
+}
+
+/// Reads sources from the local filesystem.
+#[cfg(feature = "fs")]
+pub struct FileSystem;
+
+#[cfg(feature = "fs")]
+impl SourceProvider for FileSystem {
+    fn read(&self, path: &Path) -> Result<String> {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read '{}'", path.display()))
+    }
+}
+
+/// Serves sources from memory, keyed by path. Useful for tests and for
+/// hosts that bundle their scripts.
+#[derive(Default)]
+pub struct InMemory {
+    files: HashMap<PathBuf, String>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        InMemory::default()
+    }
+
+    /// Stores `source` at `path`, replacing anything already there.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, source: impl Into<String>) {
+        self.files.insert(path.into(), source.into());
+    }
+}
+
+impl SourceProvider for InMemory {
+    fn read(&self, path: &Path) -> Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+            .with_context(|| format!("Could not read '{}'", path.display()))
+    }
+}
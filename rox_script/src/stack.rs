@@ -0,0 +1,15 @@
+//! Deeply nested scripts recurse once per level of nesting in the parser,
+//! resolver and interpreter. Those passes recurse through [`grow`], which
+//! moves onto a fresh heap-allocated stack segment whenever the current one
+//! runs low, so nesting is bounded by memory rather than the thread's stack.
+
+/// How much of the current stack must be left before recursing further.
+const RED_ZONE: usize = 128 * 1024;
+/// The size of each extra stack segment.
+const SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// Runs `f`, first switching to a new stack segment if the current one is
+/// nearly exhausted.
+pub fn grow<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT_SIZE, f)
+}
@@ -0,0 +1,129 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::io;
+use std::io::BufRead;
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::functions::LoxNative;
+use crate::interpreter::Interpreter;
+use crate::json;
+use crate::natives::{HostFunction, Members};
+use crate::sandbox::{Capability, RoxSandbox};
+use crate::token::DataType;
+
+thread_local! {
+    /// The members of each native module imported on this thread, made on
+    /// its first import and shared by every interpreter importing it after.
+    static MODULES: RefCell<HashMap<&'static str, Members>> = RefCell::default();
+}
+
+/// Looks up the members of the native module a script asks for with
+/// `import "std/<name>";`, which go in the environment backing its
+/// namespace.
+/// Modules needing a capability `sandbox` denies aren't found.
+pub fn native_module(path: &str, sandbox: &RoxSandbox) -> Option<Members> {
+    let (path, define): (_, fn(&mut Vec<_>)) = match path {
+        "std/io" if sandbox.allows(Capability::Stdio) => ("std/io", define_io),
+        "std/json" => ("std/json", define_json),
+        "std/math" => ("std/math", define_math),
+        _ => return None,
+    };
+    let members = MODULES.with(|modules| {
+        let mut modules = modules.borrow_mut();
+        let members = modules.entry(path).or_insert_with(|| {
+            let mut members = vec![];
+            define(&mut members);
+            members.into()
+        });
+        Rc::clone(members)
+    });
+    Some(members)
+}
+
+fn define_io(members: &mut Vec<(&'static str, DataType)>) {
+    define(members, "readLine", 0, |_, _| {
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(DataType::Nil);
+        }
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Ok(DataType::String(line.to_string()))
+    });
+    define(members, "write", 1, |interpreter, arguments| {
+        let output = interpreter.output();
+        write!(output, "{}", arguments[0])?;
+        output.flush()?;
+        Ok(DataType::Nil)
+    });
+}
+
+fn define_json(members: &mut Vec<(&'static str, DataType)>) {
+    define(members, "parse", 1, |_, arguments| match &arguments[0] {
+        DataType::String(text) => json::parse(text),
+        other => Err(anyhow!("parse expects a string, got {other}.")),
+    });
+    define(members, "stringify", 1, |_, arguments| {
+        Ok(DataType::String(json::stringify(&arguments[0])?))
+    });
+}
+
+fn define_math(members: &mut Vec<(&'static str, DataType)>) {
+    members.push(("pi", DataType::Number(PI)));
+    define(members, "sqrt", 1, |_, arguments| {
+        Ok(DataType::Number(number("sqrt", &arguments[0])?.sqrt()))
+    });
+    define(members, "abs", 1, |_, arguments| {
+        Ok(DataType::Number(number("abs", &arguments[0])?.abs()))
+    });
+    define(members, "floor", 1, |_, arguments| {
+        Ok(DataType::Number(number("floor", &arguments[0])?.floor()))
+    });
+    define(members, "ceil", 1, |_, arguments| {
+        Ok(DataType::Number(number("ceil", &arguments[0])?.ceil()))
+    });
+    define(members, "random", 0, |interpreter, _| {
+        Ok(DataType::Number(interpreter.random()))
+    });
+    define(members, "round", 1, |_, arguments| {
+        Ok(DataType::Number(number("round", &arguments[0])?.round()))
+    });
+    define(members, "pow", 2, |_, arguments| {
+        let base = number("pow", &arguments[0])?;
+        Ok(DataType::Number(base.powf(number("pow", &arguments[1])?)))
+    });
+    define(members, "min", 2, |_, arguments| {
+        let left = number("min", &arguments[0])?;
+        Ok(DataType::Number(left.min(number("min", &arguments[1])?)))
+    });
+    define(members, "max", 2, |_, arguments| {
+        let left = number("max", &arguments[0])?;
+        Ok(DataType::Number(left.max(number("max", &arguments[1])?)))
+    });
+}
+
+fn number(function: &str, value: &DataType) -> Result<f64> {
+    match value {
+        DataType::Number(n) => Ok(*n),
+        other => Err(anyhow!("{function} expects a number, got {other}.")),
+    }
+}
+
+fn define(
+    members: &mut Vec<(&'static str, DataType)>,
+    name: &'static str,
+    arity: usize,
+    function: fn(&mut Interpreter, &[DataType]) -> Result<DataType>,
+) {
+    let native = DataType::NativeFunction(LoxNative {
+        function: Rc::new(HostFunction::with_interpreter(
+            name.to_string(),
+            arity,
+            function,
+        )),
+    });
+    members.push((name, native));
+}
@@ -1,147 +1,201 @@
-use std::any::Any;
 use std::rc::Rc;
 
 use anyhow::Result;
 
-use crate::expr::Expr;
+use crate::expr::{Expr, VarExpr};
 use crate::token::{DataType, Token};
+use crate::types::Type;
 use crate::visitor::StmtVisitor;
 
-pub trait Stmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType>;
-    fn as_any(&self) -> &dyn Any;
+/// A statement. Like [`Expr`], each variant wraps the node its visitor
+/// method takes.
+pub enum Stmt {
+    Print(PrintStmt),
+    Expr(ExprStmt),
+    Var(VarStmt),
+    Block(BlockStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    Function(FunctionStmt),
+    Return(ReturnStmt),
+    Class(ClassStmt),
+    Throw(ThrowStmt),
+    Try(TryStmt),
+    Import(ImportStmt),
+    Assert(AssertStmt),
+    Export(ExportStmt),
+    Switch(SwitchStmt),
+    ForIn(ForInStmt),
+}
+
+impl Stmt {
+    pub fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<Option<DataType>> {
+        match self {
+            Stmt::Print(stmt) => visitor.visit_print_statement(stmt),
+            Stmt::Expr(stmt) => visitor.visit_expr_statement(stmt),
+            Stmt::Var(stmt) => visitor.visit_var_statement(stmt),
+            Stmt::Block(stmt) => visitor.visit_block_statement(stmt),
+            Stmt::If(stmt) => visitor.visit_if_statement(stmt),
+            Stmt::While(stmt) => visitor.visit_while_statement(stmt),
+            Stmt::Function(stmt) => visitor.visit_function_statement(stmt),
+            Stmt::Return(stmt) => visitor.visit_return_statement(stmt),
+            Stmt::Class(stmt) => visitor.visit_class_statement(stmt),
+            Stmt::Throw(stmt) => visitor.visit_throw_statement(stmt),
+            Stmt::Try(stmt) => visitor.visit_try_statement(stmt),
+            Stmt::Import(stmt) => visitor.visit_import_statement(stmt),
+            Stmt::Assert(stmt) => visitor.visit_assert_statement(stmt),
+            Stmt::Export(stmt) => visitor.visit_export_statement(stmt),
+            Stmt::Switch(stmt) => visitor.visit_switch_statement(stmt),
+            Stmt::ForIn(stmt) => visitor.visit_for_in_statement(stmt),
+        }
+    }
+
+    /// The token the statement starts at, which places it in its source
+    /// for the debugger. Blocks and `try` statements have none, since the
+    /// statements in them are where execution stops, and neither does an
+    /// expression statement that starts with a literal.
+    pub fn location(&self) -> Option<&Token> {
+        match self {
+            Stmt::Print(stmt) => Some(&stmt.keyword),
+            Stmt::Expr(stmt) => stmt.expression.first_token(),
+            Stmt::Var(stmt) => Some(&stmt.var_name),
+            Stmt::Block(_) | Stmt::Try(_) => None,
+            Stmt::If(stmt) => Some(&stmt.keyword),
+            Stmt::While(stmt) => Some(&stmt.keyword),
+            Stmt::Function(stmt) => Some(&stmt.name),
+            Stmt::Return(stmt) => Some(&stmt.keyword),
+            Stmt::Class(stmt) => Some(&stmt.name),
+            Stmt::Throw(stmt) => Some(&stmt.keyword),
+            Stmt::Import(stmt) => Some(&stmt.keyword),
+            Stmt::Assert(stmt) => Some(&stmt.keyword),
+            Stmt::Export(stmt) => Some(&stmt.keyword),
+            Stmt::Switch(stmt) => stmt.subject.first_token(),
+            Stmt::ForIn(stmt) => Some(&stmt.name),
+        }
+    }
+
+    /// The declaration an `export` marks, or the statement itself.
+    pub fn unexported(&self) -> &Stmt {
+        match self {
+            Stmt::Export(export) => &export.declaration,
+            stmt => stmt,
+        }
+    }
 }
 
 pub struct PrintStmt {
-    pub expression: Rc<dyn Expr>,
-}
-impl Stmt for PrintStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_print_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub keyword: Token,
+    pub expression: Rc<Expr>,
 }
 
 pub struct ExprStmt {
-    pub expression: Rc<dyn Expr>,
-}
-
-impl Stmt for ExprStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_expr_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub expression: Rc<Expr>,
 }
 
 pub struct VarStmt {
     pub var_name: Token,
-    pub var_value: Option<Rc<dyn Expr>>,
-}
-
-impl Stmt for VarStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_var_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub var_value: Option<Rc<Expr>>,
 }
 
 pub struct BlockStmt {
-    pub statements: Vec<Rc<dyn Stmt>>,
-}
-
-impl Stmt for BlockStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_block_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+    pub statements: Vec<Rc<Stmt>>,
 }
 
 pub struct IfStmt {
-    pub condition: Rc<dyn Expr>,
-    pub then_branch: Rc<dyn Stmt>,
-    pub else_branch: Option<Rc<dyn Stmt>>,
+    pub keyword: Token,
+    pub condition: Rc<Expr>,
+    pub then_branch: Rc<Stmt>,
+    pub else_branch: Option<Rc<Stmt>>,
 }
 
-impl Stmt for IfStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_if_statement(self)
-    }
+pub struct WhileStmt {
+    /// The `while` token, or `for` for a for loop.
+    pub keyword: Token,
+    pub condition: Rc<Expr>,
+    pub body: Rc<Stmt>,
+}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct FunctionStmt {
+    pub name: Token,
+    /// The parameters, types and body are shared with every function value
+    /// made from the declaration, so making one doesn't copy them.
+    pub params: Rc<Vec<Token>>,
+    /// The annotated type of each parameter, `None` where it has none.
+    pub param_types: Rc<Vec<Option<Type>>>,
+    pub return_type: Option<Type>,
+    pub body: Rc<Vec<Rc<Stmt>>>,
 }
 
-pub struct WhileStmt {
-    pub condition: Rc<dyn Expr>,
-    pub body: Rc<dyn Stmt>,
+pub struct ReturnStmt {
+    pub keyword: Token,
+    pub value: Option<Rc<Expr>>,
+    /// Whether the value is a call in tail position, which the interpreter
+    /// makes without growing the stack.
+    pub tail_call: bool,
 }
 
-impl Stmt for WhileStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_while_statement(self)
-    }
+pub struct ClassStmt {
+    pub name: Token,
+    pub super_class: Option<VarExpr>,
+    pub methods: Vec<FunctionStmt>,
+}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct ThrowStmt {
+    pub keyword: Token,
+    pub value: Rc<Expr>,
 }
 
-pub struct FunctionStmt {
+/// The `catch (name) { ... }` part of a try statement.
+pub struct CatchClause {
     pub name: Token,
-    pub params: Vec<Token>,
-    pub body: Vec<Rc<dyn Stmt>>,
+    pub body: Vec<Rc<Stmt>>,
 }
 
-impl Stmt for FunctionStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_function_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct TryStmt {
+    pub body: Vec<Rc<Stmt>>,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<Vec<Rc<Stmt>>>,
 }
 
-pub struct ReturnStmt {
+pub struct ImportStmt {
     pub keyword: Token,
-    pub value: Option<Rc<dyn Expr>>,
+    pub path: String,
+    /// The variable the module's namespace is bound to.
+    pub name: String,
 }
 
-impl Stmt for ReturnStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_return_statement(self)
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct AssertStmt {
+    pub keyword: Token,
+    pub condition: Rc<Expr>,
+    pub message: Option<Rc<Expr>>,
+    /// The source text of the condition, quoted in the failure message.
+    pub source: String,
 }
 
-pub struct ClassStmt {
+/// A top-level declaration marked with `export`.
+pub struct ExportStmt {
+    pub keyword: Token,
     pub name: Token,
-    pub super_class: Option<Rc<dyn Expr>>,
-    pub methods: Vec<Rc<dyn Stmt>>,
+    pub declaration: Rc<Stmt>,
 }
 
-impl Stmt for ClassStmt {
-    fn accept(&self, visitor: &mut dyn StmtVisitor) -> Result<DataType> {
-        visitor.visit_class_statement(self)
-    }
+/// One `case value: ...` arm of a switch statement.
+pub struct SwitchCase {
+    pub value: Rc<Expr>,
+    pub body: Vec<Rc<Stmt>>,
+}
 
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
+pub struct SwitchStmt {
+    pub subject: Rc<Expr>,
+    pub cases: Vec<SwitchCase>,
+    pub default: Option<Vec<Rc<Stmt>>>,
+}
+
+/// `for (name in iterable) body`.
+pub struct ForInStmt {
+    pub name: Token,
+    /// The `in` token, which iteration errors are reported at.
+    pub keyword: Token,
+    pub iterable: Rc<Expr>,
+    pub body: Rc<Stmt>,
 }
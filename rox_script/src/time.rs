@@ -0,0 +1,76 @@
+//! The clocks behind `clock()`, the seed of `random()`, `sleep`, timers and
+//! the run time limit. `std::time` and `std::thread::sleep` panic on
+//! wasm32-unknown-unknown, so there time is read from the browser through
+//! js-sys instead, or stands still at the Unix epoch without the `wasm`
+//! feature.
+
+use std::time::Duration;
+
+/// How long it has been since the Unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn since_epoch() -> Duration {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub fn since_epoch() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now().max(0.0) / 1000.0)
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+pub fn since_epoch() -> Duration {
+    Duration::ZERO
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+/// A stand-in for [`std::time::Instant`] read from [`since_epoch`].
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(Duration);
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant(since_epoch())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        since_epoch().saturating_sub(self.0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant(self.0 + duration)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::ops::Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+}
+
+/// Blocks the thread for `duration`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sleep(duration: Duration) {
+    std::thread::sleep(duration);
+}
+
+/// A browser tab can't block, so this waits by checking the clock.
+#[cfg(target_arch = "wasm32")]
+pub fn sleep(duration: Duration) {
+    let wake = Instant::now() + duration;
+    while Instant::now() < wake {}
+}
@@ -0,0 +1,138 @@
+//! Time spent in each function, for finding a script's hot spots. Once
+//! [`enable`] is called, every call to a script function or native made on
+//! this thread is counted and timed, and [`report`] gives a table of them,
+//! the functions that took the most time of their own first.
+//!
+//! A function's total time includes the functions it calls, while its own
+//! time doesn't. A recursive call's time is counted once in the total, not
+//! again for every level of recursion.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// The calls to one function.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timing {
+    pub calls: usize,
+    pub total: Duration,
+    pub own: Duration,
+}
+
+/// The timings of every function called, as [`report`] returns them.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// Each function's name and timing, the most time of its own first.
+    pub functions: Vec<(String, Timing)>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+        writeln!(
+            f,
+            "{:<width$}{:>10}{:>14}{:>14}",
+            "function", "calls", "total ms", "own ms"
+        )?;
+        for (name, timing) in &self.functions {
+            writeln!(
+                f,
+                "{name:<width$}{:>10}{:>14.3}{:>14.3}",
+                timing.calls,
+                timing.total.as_secs_f64() * 1000.0,
+                timing.own.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Profile {
+    functions: HashMap<String, Timing>,
+    /// The calls in progress, innermost last.
+    calls: Vec<Call>,
+}
+
+struct Call {
+    name: String,
+    started: Instant,
+    /// How long the functions it called took.
+    callees: Duration,
+}
+
+thread_local! {
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// Starts timing calls made on this thread from now on.
+pub fn enable() {
+    PROFILE.with(|profile| {
+        profile.borrow_mut().get_or_insert_with(Profile::default);
+    });
+}
+
+/// The timings so far, or `None` if timing isn't enabled on this thread.
+pub fn report() -> Option<Report> {
+    PROFILE.with(|profile| {
+        let profile = profile.borrow();
+        let mut functions: Vec<_> = profile
+            .as_ref()?
+            .functions
+            .iter()
+            .map(|(name, timing)| (name.clone(), *timing))
+            .collect();
+        functions.sort_by(|(a_name, a), (b_name, b)| b.own.cmp(&a.own).then(a_name.cmp(b_name)));
+        Some(Report { functions })
+    })
+}
+
+/// Times a call until dropped.
+pub struct Timed(());
+
+/// Starts timing a call to the function `name` names, or returns `None`
+/// when timing isn't enabled, so the name is only built when it's needed.
+pub fn start(name: impl FnOnce() -> String) -> Option<Timed> {
+    PROFILE.with(|profile| {
+        profile.borrow_mut().as_mut()?.calls.push(Call {
+            name: name(),
+            started: Instant::now(),
+            callees: Duration::ZERO,
+        });
+        Some(Timed(()))
+    })
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        // Calls finishing while the thread exits may outlive the profile.
+        let _ = PROFILE.try_with(|profile| {
+            let mut profile = profile.borrow_mut();
+            let Some(profile) = profile.as_mut() else {
+                return;
+            };
+            let Some(call) = profile.calls.pop() else {
+                return;
+            };
+            let elapsed = call.started.elapsed();
+            let recursive = profile.calls.iter().any(|outer| outer.name == call.name);
+            if let Some(caller) = profile.calls.last_mut() {
+                caller.callees += elapsed;
+            }
+            let timing = profile.functions.entry(call.name).or_default();
+            timing.calls += 1;
+            timing.own += elapsed.saturating_sub(call.callees);
+            if !recursive {
+                timing.total += elapsed;
+            }
+        });
+    }
+}
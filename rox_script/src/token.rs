@@ -1,110 +1,61 @@
-use std::collections::HashMap;
 use std::fmt::Display;
 
 use crate::class::{LoxClass, LoxInstance};
+use crate::collections::{LoxArray, LoxMap};
 use crate::functions::{LoxFunction, LoxNative};
-use lazy_static::lazy_static;
+use crate::module::LoxModule;
+use crate::symbol::Symbol;
+use rox_frontend::value::{fmt_number, LoxValue};
 
-lazy_static! {
-    pub static ref KEYWORDS: HashMap<&'static str, TokenType> = {
-        let mut map = HashMap::new();
-        map.insert("and", TokenType::AND);
-        map.insert("class", TokenType::CLASS);
-        map.insert("else", TokenType::ELSE);
-        map.insert("false", TokenType::FALSE);
-        map.insert("for", TokenType::FOR);
-        map.insert("fun", TokenType::FUN);
-        map.insert("if", TokenType::IF);
-        map.insert("nil", TokenType::NIL);
-        map.insert("or", TokenType::OR);
-        map.insert("print", TokenType::PRINT);
-        map.insert("return", TokenType::RETURN);
-        map.insert("super", TokenType::SUPER);
-        map.insert("this", TokenType::THIS);
-        map.insert("true", TokenType::TRUE);
-        map.insert("var", TokenType::VAR);
-        map.insert("while", TokenType::WHILE);
-        map
-    };
-}
-
-#[derive(Clone, Debug, PartialEq, Copy)]
-#[allow(clippy::upper_case_acronyms)]
-pub enum TokenType {
-    // Single character token
-    LEFTPAREN,
-    RIGHTPAREN,
-    LEFTBRACE,
-    RIGHTBRACE,
-    COMMA,
-    DOT,
-    MINUS,
-    PLUS,
-    SEMICOLON,
-    SLASH,
-    STAR,
-
-    // One or two character token
-    BANG,
-    BANGEQUAL,
-    EQUAL,
-    EQUALEQUAL,
-    GREATER,
-    GREATEREQUAL,
-    LESS,
-    LESSEQUAL,
-
-    // Literals
-    // variable name?
-    IDENTIFIER,
-    STRING,
-    NUMBER,
-
-    // Keywords (can I see this as reserved identifiers?)
-    AND,
-    CLASS,
-    ELSE,
-    FALSE,
-    FUN,
-    FOR,
-    IF,
-    NIL,
-    OR,
-    PRINT,
-    RETURN,
-    SUPER,
-    THIS,
-    TRUE,
-    VAR,
-    WHILE,
-
-    EOF,
-}
+pub use rox_frontend::token::{keyword, TokenType, KEYWORDS};
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: Symbol,
     pub literal: Option<DataType>,
     pub line: u32,
+    /// Which character of its line the lexeme starts at, counting from 1.
+    pub column: u32,
+    /// Byte offset of the lexeme in the source, which tells apart tokens with
+    /// the same lexeme on the same line.
+    pub offset: u32,
+    /// Identifies the script the token was scanned from, so tokens at the same
+    /// offset in different imported files stay distinct.
+    pub source_id: u32,
 }
 
 impl Token {
     pub fn new(
         token_type: TokenType,
-        lexeme: String,
+        lexeme: impl Into<Symbol>,
         literal: Option<DataType>,
         line: u32,
+        column: u32,
+        offset: u32,
+        source_id: u32,
     ) -> Self {
         Token {
             token_type,
-            lexeme,
+            lexeme: lexeme.into(),
             literal,
             line,
+            column,
+            offset,
+            source_id,
         }
     }
+
+    /// Where the token was scanned, which tells it apart from every other
+    /// token an interpreter has seen.
+    pub fn id(&self) -> TokenId {
+        (self.source_id, self.offset)
+    }
 }
 
+/// A token's source id and offset, as [`Token::id`] returns them.
+pub type TokenId = (u32, u32);
+
 #[derive(Debug, Clone)]
 pub enum DataType {
     String(String),
@@ -115,19 +66,67 @@ pub enum DataType {
     NativeFunction(LoxNative),
     Class(LoxClass),
     Instance(LoxInstance),
+    Module(LoxModule),
+    Array(LoxArray),
+    Map(LoxMap),
+}
+
+impl LoxValue for DataType {
+    fn is_nil(&self) -> bool {
+        matches!(self, DataType::Nil)
+    }
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            DataType::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl DataType {
+    /// An unambiguous representation of the value. Strings are quoted and
+    /// escaped so that the result reads back as the same string literal;
+    /// other values use their printed form.
+    pub fn repr(&self) -> String {
+        match self {
+            DataType::String(s) => {
+                let mut repr = String::with_capacity(s.len() + 2);
+                repr.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => repr.push_str("\\\""),
+                        '\\' => repr.push_str("\\\\"),
+                        '\n' => repr.push_str("\\n"),
+                        '\t' => repr.push_str("\\t"),
+                        '\r' => repr.push_str("\\r"),
+                        '\0' => repr.push_str("\\0"),
+                        c if c.is_control() => repr.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                        c => repr.push(c),
+                    }
+                }
+                repr.push('"');
+                repr
+            }
+            other => other.to_string(),
+        }
+    }
 }
 
 impl Display for DataType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DataType::String(s) => write!(f, "{s}"),
-            DataType::Number(n) => write!(f, "{n}"),
+            DataType::Number(n) => fmt_number(*n, f),
             DataType::Bool(b) => write!(f, "{b}"),
-            DataType::Nil => write!(f, "NIL"),
+            DataType::Nil => write!(f, "nil"),
             DataType::Function(func) => write!(f, "{func}"),
             DataType::NativeFunction(func) => write!(f, "{func}"),
-            DataType::Class(class) => write!(f, "{class:?}"),
-            DataType::Instance(instance) => write!(f, "{instance:?}"),
+            DataType::Class(class) => write!(f, "{class}"),
+            DataType::Instance(instance) => write!(f, "{instance}"),
+            DataType::Module(module) => write!(f, "{module}"),
+            DataType::Array(array) => write!(f, "{array}"),
+            DataType::Map(map) => write!(f, "{map}"),
         }
     }
 }
@@ -0,0 +1,64 @@
+//! Execution tracing for `--trace`, the tree-walking counterpart of the VM's
+//! `debug_trace_execution`. Once [`enable`] is called, the interpreter
+//! writes to stderr every statement it is about to run and, after that,
+//! every expression it evaluated and the value it got, each with its line.
+//!
+//! Statements and expressions are shown as `rox ast` shows them. What runs
+//! inside an expression, such as a function's body during a call, is
+//! indented under it, and an expression is written after the ones it is
+//! made of. Literals are left out, as their value is already in the text.
+
+use std::cell::Cell;
+
+use anyhow::Result;
+
+use crate::expr::Expr;
+use crate::printer;
+use crate::stmt::Stmt;
+use crate::token::{DataType, Token};
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    /// How many expressions are being evaluated.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Starts tracing what the interpreter runs on this thread from now on.
+pub fn enable() {
+    ENABLED.set(true);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.get()
+}
+
+/// Traces `statement`, which is about to run.
+pub(crate) fn statement(statement: &Stmt) {
+    if enabled() {
+        write(statement.location(), &printer::statement_header(statement));
+    }
+}
+
+/// Evaluates `expression` with `evaluate`, tracing the value it gives.
+pub(crate) fn expression(
+    expression: &Expr,
+    evaluate: impl FnOnce() -> Result<DataType>,
+) -> Result<DataType> {
+    DEPTH.set(DEPTH.get() + 1);
+    let value = evaluate();
+    DEPTH.set(DEPTH.get() - 1);
+    if let (Ok(value), false) = (&value, matches!(expression, Expr::Literal(_))) {
+        let text = printer::expression(expression);
+        write(
+            expression.first_token(),
+            &format!("{text} => {}", value.repr()),
+        );
+    }
+    value
+}
+
+fn write(token: Option<&Token>, text: &str) {
+    let line = token.map_or(String::new(), |token| token.line.to_string());
+    let indent = "  ".repeat(DEPTH.get());
+    eprintln!("[trace] {line:>4} | {indent}{text}");
+}
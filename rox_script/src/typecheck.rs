@@ -0,0 +1,628 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::error::{LoxError, Snippet};
+use crate::expr::{
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, Expr, GetExpr, GroupingExpr, IndexExpr,
+    LiteralExpr, LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr,
+    UnaryExpr, VarExpr,
+};
+use crate::parser::Parser;
+use crate::scanner;
+use crate::stack;
+use crate::stmt::{
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, Stmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
+};
+use crate::token::{DataType, Token, TokenType};
+use crate::types::Type;
+use crate::visitor::{ExprVisitor, StmtVisitor};
+
+/// Scans and parses `source` and type checks the result without running it.
+/// Scan and parse errors are returned as the error; type errors are all
+/// collected and returned together.
+pub fn typecheck(source: String) -> Result<Vec<LoxError>> {
+    let statements = scanner::run(source.clone(), 1)
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .map_err(|error| LoxError::locate(error, &source, 1))?;
+    let mut errors = TypeChecker::new().check(&statements);
+    for error in &mut errors {
+        error.snippet = error.span.and_then(|span| Snippet::new(&source, span));
+    }
+    Ok(errors)
+}
+
+/// What a function's annotations promise about its calls.
+struct Signature {
+    /// The name calls are reported with, as the function prints: `<fn name>`.
+    name: String,
+    params: Vec<String>,
+    param_types: Vec<Option<Type>>,
+    return_type: Option<Type>,
+}
+
+impl Signature {
+    fn new(stmt: &FunctionStmt) -> Self {
+        Signature {
+            name: format!("<fn {}>", stmt.name.lexeme),
+            params: stmt.params.iter().map(|p| p.lexeme.to_string()).collect(),
+            param_types: stmt.param_types.to_vec(),
+            return_type: stmt.return_type.clone(),
+        }
+    }
+}
+
+struct ClassInfo {
+    name: String,
+    super_class: Option<String>,
+    methods: HashMap<String, Rc<Signature>>,
+}
+
+impl ClassInfo {
+    fn new(stmt: &ClassStmt) -> Self {
+        ClassInfo {
+            name: stmt.name.lexeme.to_string(),
+            super_class: stmt
+                .super_class
+                .as_ref()
+                .map(|super_class| super_class.var_name.lexeme.to_string()),
+            methods: stmt
+                .methods
+                .iter()
+                .map(|method| {
+                    (
+                        method.name.lexeme.to_string(),
+                        Rc::new(Signature::new(method)),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What a call expression calls, as far as the checker can tell.
+enum Callee {
+    Function(Rc<Signature>),
+    Class(Rc<ClassInfo>),
+    Unknown,
+}
+
+/// What the checker knows about a name in scope.
+#[derive(Clone)]
+enum Binding {
+    /// A variable, with its annotated type if it is an annotated parameter.
+    Value(Option<Type>),
+    Function(Rc<Signature>),
+    Class(Rc<ClassInfo>),
+}
+
+/// A gradual type checker. Types are known only where annotations or
+/// literals pin them down: literals, operators with a fixed result, calls to
+/// functions with a return annotation, class constructors and annotated
+/// parameters. Everything else is unknown and accepted anywhere, so
+/// unannotated code always passes.
+///
+/// Calls to functions and methods declared in the script have their
+/// argument counts and annotated parameter types checked, returns are
+/// checked against the enclosing function's return annotation, and
+/// assignments to annotated parameters against the parameter's type.
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    /// Every class declared in the script by name, for subclass checks.
+    classes: HashMap<String, Rc<ClassInfo>>,
+    /// The signature of each function being checked, innermost last.
+    functions: Vec<Rc<Signature>>,
+    /// The class whose methods are being checked, innermost last.
+    class_stack: Vec<Rc<ClassInfo>>,
+    /// The type of the expression visited last. The visitor traits return
+    /// runtime values, so expression types are handed back through here.
+    inferred: Option<Type>,
+    errors: Vec<LoxError>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            classes: HashMap::new(),
+            functions: vec![],
+            class_stack: vec![],
+            inferred: None,
+            errors: vec![],
+        }
+    }
+
+    /// Checks the program and returns every type error found, in source order.
+    pub fn check(mut self, statements: &[Rc<Stmt>]) -> Vec<LoxError> {
+        self.declarations(statements);
+        for statement in statements {
+            self.stmt(statement);
+        }
+        self.errors
+    }
+
+    fn expr(&mut self, expr: &Rc<Expr>) -> Option<Type> {
+        // The checker only ever records errors, so visiting can't fail.
+        let _ = stack::grow(|| expr.accept(self));
+        self.inferred.take()
+    }
+
+    fn stmt(&mut self, stmt: &Rc<Stmt>) {
+        let _ = stack::grow(|| stmt.accept(self));
+    }
+
+    fn block(&mut self, statements: &[Rc<Stmt>]) {
+        self.scopes.push(HashMap::new());
+        self.declarations(statements);
+        for statement in statements {
+            self.stmt(statement);
+        }
+        self.scopes.pop();
+    }
+
+    /// Records `ty` as the type of the expression being visited.
+    fn infer(&mut self, ty: Option<Type>) -> Result<DataType> {
+        self.inferred = ty;
+        Ok(DataType::Nil)
+    }
+
+    fn error(&mut self, token: &Token, message: String) {
+        self.errors.push(LoxError::type_error(token, message));
+    }
+
+    fn define(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Binds the functions and classes declared directly in `statements`
+    /// before any of them are checked, so calls can come before the
+    /// declaration they refer to.
+    fn declarations(&mut self, statements: &[Rc<Stmt>]) {
+        for statement in statements {
+            match statement.unexported() {
+                Stmt::Function(function) => {
+                    self.define_function(function);
+                }
+                Stmt::Class(class) => {
+                    self.define_class(class);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn define_function(&mut self, stmt: &FunctionStmt) -> Rc<Signature> {
+        let signature = Rc::new(Signature::new(stmt));
+        self.define(&stmt.name.lexeme, Binding::Function(Rc::clone(&signature)));
+        signature
+    }
+
+    fn define_class(&mut self, stmt: &ClassStmt) -> Rc<ClassInfo> {
+        let info = Rc::new(ClassInfo::new(stmt));
+        self.classes.insert(info.name.clone(), Rc::clone(&info));
+        self.define(&info.name, Binding::Class(Rc::clone(&info)));
+        info
+    }
+
+    /// Finds `name` on the class called `class` or its superclasses.
+    fn find_method(&self, class: &str, name: &str) -> Option<Rc<Signature>> {
+        let mut class = self.classes.get(class);
+        while let Some(info) = class {
+            if let Some(method) = info.methods.get(name) {
+                return Some(Rc::clone(method));
+            }
+            class = info.super_class.as_ref().and_then(|s| self.classes.get(s));
+        }
+        None
+    }
+
+    /// Whether a value of type `actual` can be used where `expected` is
+    /// annotated. Instances of classes the checker can't see the declaration
+    /// of are given the benefit of the doubt.
+    fn accepts(&self, expected: &Type, actual: &Type) -> bool {
+        match (expected, actual) {
+            (Type::Any, _) => true,
+            (Type::Class(expected), Type::Class(actual)) => {
+                let mut class = Some(actual.as_str());
+                while let Some(name) = class {
+                    if name == expected {
+                        return true;
+                    }
+                    match self.classes.get(name) {
+                        Some(info) => class = info.super_class.as_deref(),
+                        None => return true,
+                    }
+                }
+                false
+            }
+            (expected, actual) => expected == actual,
+        }
+    }
+
+    /// Checks a call's arguments against the signature of the function
+    /// being called.
+    fn check_call(
+        &mut self,
+        signature: &Signature,
+        paren: &Token,
+        arguments: &[Option<Type>],
+    ) -> Option<Type> {
+        if arguments.len() != signature.params.len() {
+            self.error(
+                paren,
+                format!(
+                    "Expected {} arguments but got {} when calling {}.",
+                    signature.params.len(),
+                    arguments.len(),
+                    signature.name
+                ),
+            );
+            return signature.return_type.clone();
+        }
+        for ((param, expected), actual) in signature
+            .params
+            .iter()
+            .zip(&signature.param_types)
+            .zip(arguments)
+        {
+            match (expected, actual) {
+                (Some(expected), Some(actual)) if !self.accepts(expected, actual) => {
+                    self.error(
+                        paren,
+                        format!(
+                            "Argument '{}' of {} must be {}, got {}.",
+                            param, signature.name, expected, actual
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        }
+        signature.return_type.clone()
+    }
+
+    /// Checks the callee of a call and works out what it calls: a function
+    /// or class by name, or a method on an instance of a known class.
+    fn callee(&mut self, callee: &Rc<Expr>) -> Callee {
+        let method = match callee.as_ref() {
+            Expr::Var(var) => {
+                return match self.lookup(&var.var_name.lexeme) {
+                    Some(Binding::Function(signature)) => Callee::Function(Rc::clone(signature)),
+                    Some(Binding::Class(class)) => Callee::Class(Rc::clone(class)),
+                    _ => Callee::Unknown,
+                };
+            }
+            Expr::Get(get) => match self.expr(&get.object) {
+                Some(Type::Class(class)) => self.find_method(&class, &get.name.lexeme),
+                _ => None,
+            },
+            Expr::Super(super_expr) => {
+                let super_class = self.class_stack.last().and_then(|c| c.super_class.clone());
+                super_class.and_then(|class| self.find_method(&class, &super_expr.method.lexeme))
+            }
+            _ => {
+                self.expr(callee);
+                None
+            }
+        };
+        method.map_or(Callee::Unknown, Callee::Function)
+    }
+
+    fn check_function(&mut self, stmt: &FunctionStmt, signature: Rc<Signature>) {
+        self.functions.push(signature);
+        self.scopes.push(HashMap::new());
+        for (param, ty) in stmt.params.iter().zip(stmt.param_types.iter()) {
+            self.define(&param.lexeme, Binding::Value(ty.clone()));
+        }
+        self.declarations(&stmt.body);
+        for statement in stmt.body.iter() {
+            self.stmt(statement);
+        }
+        self.scopes.pop();
+        self.functions.pop();
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor for TypeChecker {
+    fn visit_literal_expr(&mut self, expr: &LiteralExpr) -> Result<DataType> {
+        let ty = match &expr.value {
+            None | Some(DataType::Nil) => Type::Nil,
+            Some(DataType::Bool(_)) => Type::Bool,
+            Some(DataType::Number(_)) => Type::Number,
+            Some(DataType::String(_)) => Type::String,
+            Some(_) => return self.infer(None),
+        };
+        self.infer(Some(ty))
+    }
+
+    fn visit_unary_expr(&mut self, expr: &UnaryExpr) -> Result<DataType> {
+        self.expr(&expr.right);
+        match expr.operator.token_type {
+            TokenType::MINUS => self.infer(Some(Type::Number)),
+            _ => self.infer(Some(Type::Bool)),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, expr: &BinaryExpr) -> Result<DataType> {
+        let left = self.expr(&expr.left);
+        let right = self.expr(&expr.right);
+        let ty = match expr.operator.token_type {
+            TokenType::PLUS => match (left, right) {
+                (Some(Type::Number), Some(Type::Number)) => Some(Type::Number),
+                (Some(Type::String), Some(Type::String | Type::Number))
+                | (Some(Type::Number), Some(Type::String)) => Some(Type::String),
+                _ => None,
+            },
+            TokenType::MINUS | TokenType::STAR | TokenType::SLASH => Some(Type::Number),
+            _ => Some(Type::Bool),
+        };
+        self.infer(ty)
+    }
+
+    fn visit_call_expr(&mut self, expr: &CallExpr) -> Result<DataType> {
+        let callee = self.callee(&expr.callee);
+        let arguments: Vec<_> = expr
+            .arguments
+            .iter()
+            .map(|argument| self.expr(argument))
+            .collect();
+
+        let ty = match callee {
+            Callee::Function(signature) => self.check_call(&signature, &expr.paren, &arguments),
+            Callee::Class(class) => {
+                if let Some(init) = self.find_method(&class.name, "init") {
+                    self.check_call(&init, &expr.paren, &arguments);
+                }
+                Some(Type::Class(class.name.clone()))
+            }
+            Callee::Unknown => None,
+        };
+        self.infer(ty)
+    }
+
+    fn visit_grouping_expr(&mut self, expr: &GroupingExpr) -> Result<DataType> {
+        let ty = self.expr(&expr.expression);
+        self.infer(ty)
+    }
+
+    fn visit_var_expr(&mut self, expr: &VarExpr) -> Result<DataType> {
+        let ty = match self.lookup(&expr.var_name.lexeme) {
+            Some(Binding::Value(ty)) => ty.clone(),
+            Some(Binding::Function(_)) => Some(Type::Function),
+            Some(Binding::Class(_)) | None => None,
+        };
+        self.infer(ty)
+    }
+
+    fn visit_assign_expr(&mut self, expr: &AssignExpr) -> Result<DataType> {
+        let value = self.expr(&expr.var_value);
+        if let (Some(Binding::Value(Some(declared))), Some(actual)) =
+            (self.lookup(&expr.var_name.lexeme), &value)
+        {
+            if !self.accepts(declared, actual) {
+                let message = format!(
+                    "Can't assign {} to '{}', which is declared as {}.",
+                    actual, expr.var_name.lexeme, declared
+                );
+                self.error(&expr.var_name, message);
+            }
+        }
+        self.infer(value)
+    }
+
+    fn visit_logical_expr(&mut self, expr: &LogicalExpr) -> Result<DataType> {
+        let left = self.expr(&expr.left);
+        let right = self.expr(&expr.right);
+        let ty = if left == right { left } else { None };
+        self.infer(ty)
+    }
+
+    fn visit_get_expr(&mut self, expr: &GetExpr) -> Result<DataType> {
+        self.expr(&expr.object);
+        self.infer(None)
+    }
+
+    fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<DataType> {
+        self.expr(&expr.object);
+        let value = self.expr(&expr.value);
+        self.infer(value)
+    }
+
+    fn visit_this_expr(&mut self, _expr: &ThisExpr) -> Result<DataType> {
+        let ty = self
+            .class_stack
+            .last()
+            .map(|class| Type::Class(class.name.clone()));
+        self.infer(ty)
+    }
+
+    fn visit_super_expr(&mut self, _expr: &SuperExpr) -> Result<DataType> {
+        self.infer(None)
+    }
+
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> Result<DataType> {
+        for element in &expr.elements {
+            self.expr(element);
+        }
+        self.infer(Some(Type::Array))
+    }
+
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<DataType> {
+        for (key, value) in &expr.entries {
+            self.expr(key);
+            self.expr(value);
+        }
+        self.infer(Some(Type::Map))
+    }
+
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<DataType> {
+        self.expr(&expr.object);
+        self.expr(&expr.index);
+        self.infer(None)
+    }
+
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> Result<DataType> {
+        self.expr(&expr.object);
+        self.expr(&expr.index);
+        let value = self.expr(&expr.value);
+        self.infer(value)
+    }
+
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> Result<DataType> {
+        self.expr(&expr.expression);
+        self.infer(Some(Type::Map))
+    }
+}
+
+impl StmtVisitor for TypeChecker {
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.expression);
+        Ok(None)
+    }
+
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.expression);
+        Ok(None)
+    }
+
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<Option<DataType>> {
+        if let Some(value) = &stmt.var_value {
+            self.expr(value);
+        }
+        // Variables can be reassigned values of any type, so nothing is
+        // known about them.
+        self.define(&stmt.var_name.lexeme, Binding::Value(None));
+        Ok(None)
+    }
+
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<Option<DataType>> {
+        self.block(&stmt.statements);
+        Ok(None)
+    }
+
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.condition);
+        self.stmt(&stmt.then_branch);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.stmt(else_branch);
+        }
+        Ok(None)
+    }
+
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.condition);
+        self.stmt(&stmt.body);
+        Ok(None)
+    }
+
+    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<Option<DataType>> {
+        let signature = self.define_function(stmt);
+        self.check_function(stmt, signature);
+        Ok(None)
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<Option<DataType>> {
+        let value = match &stmt.value {
+            Some(value) => self.expr(value),
+            None => Some(Type::Nil),
+        };
+        let Some(function) = self.functions.last().cloned() else {
+            return Ok(None);
+        };
+        if let (Some(expected), Some(actual)) = (&function.return_type, &value) {
+            if !self.accepts(expected, actual) {
+                self.error(
+                    &stmt.keyword,
+                    format!(
+                        "{} must return {}, got {}.",
+                        function.name, expected, actual
+                    ),
+                );
+            }
+        }
+        Ok(None)
+    }
+
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<Option<DataType>> {
+        let info = self.define_class(stmt);
+        self.class_stack.push(Rc::clone(&info));
+        for method in &stmt.methods {
+            let signature = Rc::clone(&info.methods[method.name.lexeme.as_str()]);
+            self.check_function(method, signature);
+        }
+        self.class_stack.pop();
+        Ok(None)
+    }
+
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.value);
+        Ok(None)
+    }
+
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> Result<Option<DataType>> {
+        self.block(&stmt.body);
+        if let Some(catch) = &stmt.catch {
+            self.scopes.push(HashMap::new());
+            self.define(&catch.name.lexeme, Binding::Value(None));
+            self.block(&catch.body);
+            self.scopes.pop();
+        }
+        if let Some(finally) = &stmt.finally {
+            self.block(finally);
+        }
+        Ok(None)
+    }
+
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> Result<Option<DataType>> {
+        self.define(&stmt.name, Binding::Value(None));
+        Ok(None)
+    }
+
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.condition);
+        if let Some(message) = &stmt.message {
+            self.expr(message);
+        }
+        Ok(None)
+    }
+
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> Result<Option<DataType>> {
+        self.stmt(&stmt.declaration);
+        Ok(None)
+    }
+
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.subject);
+        for case in &stmt.cases {
+            self.expr(&case.value);
+            self.block(&case.body);
+        }
+        if let Some(default) = &stmt.default {
+            self.block(default);
+        }
+        Ok(None)
+    }
+
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> Result<Option<DataType>> {
+        self.expr(&stmt.iterable);
+        self.scopes.push(HashMap::new());
+        self.define(&stmt.name.lexeme, Binding::Value(None));
+        self.stmt(&stmt.body);
+        self.scopes.pop();
+        Ok(None)
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::token::DataType;
+
+/// A type named in an annotation such as `fun area(w: number): number`.
+/// Names other than the built-in ones refer to classes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Any,
+    Nil,
+    Bool,
+    Number,
+    String,
+    Function,
+    Array,
+    Map,
+    Class(String),
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Type {
+        match name {
+            "any" => Type::Any,
+            "nil" => Type::Nil,
+            "bool" => Type::Bool,
+            "number" => Type::Number,
+            "string" => Type::String,
+            "function" => Type::Function,
+            "array" => Type::Array,
+            "map" => Type::Map,
+            class => Type::Class(class.to_string()),
+        }
+    }
+
+    /// Whether `value` has this type. A class accepts instances of itself
+    /// and of its subclasses.
+    pub fn accepts(&self, value: &DataType) -> bool {
+        match (self, value) {
+            (Type::Any, _)
+            | (Type::Nil, DataType::Nil)
+            | (Type::Bool, DataType::Bool(_))
+            | (Type::Number, DataType::Number(_))
+            | (Type::String, DataType::String(_))
+            | (Type::Function, DataType::Function(_) | DataType::NativeFunction(_))
+            | (Type::Array, DataType::Array(_))
+            | (Type::Map, DataType::Map(_)) => true,
+            (Type::Class(name), DataType::Instance(instance)) => {
+                let mut class = Some(instance.class());
+                while let Some(current) = class {
+                    if current.name == *name {
+                        return true;
+                    }
+                    class = current.super_class.as_deref();
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Nil => write!(f, "nil"),
+            Type::Bool => write!(f, "bool"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Function => write!(f, "function"),
+            Type::Array => write!(f, "array"),
+            Type::Map => write!(f, "map"),
+            Type::Class(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The type of `value` as it is named in errors: the annotation it would
+/// satisfy, or the class name for instances.
+pub fn type_name(value: &DataType) -> String {
+    match value {
+        DataType::Nil => "nil".to_string(),
+        DataType::Bool(_) => "bool".to_string(),
+        DataType::Number(_) => "number".to_string(),
+        DataType::String(_) => "string".to_string(),
+        DataType::Function(_) | DataType::NativeFunction(_) => "function".to_string(),
+        DataType::Array(_) => "array".to_string(),
+        DataType::Map(_) => "map".to_string(),
+        DataType::Class(_) => "class".to_string(),
+        DataType::Module(_) => "module".to_string(),
+        DataType::Instance(instance) => instance.class().name.clone(),
+    }
+}
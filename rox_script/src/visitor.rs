@@ -1,11 +1,12 @@
 use anyhow::Result;
 
 use crate::expr::{
-    AssignExpr, BinaryExpr, CallExpr, GetExpr, GroupingExpr, LiteralExpr, LogicalExpr, SetExpr,
-    SuperExpr, ThisExpr, UnaryExpr, VarExpr,
+    ArrayExpr, AssignExpr, BinaryExpr, CallExpr, GetExpr, GroupingExpr, IndexExpr, LiteralExpr,
+    LogicalExpr, MapExpr, SetExpr, SetIndexExpr, SuperExpr, ThisExpr, TryExpr, UnaryExpr, VarExpr,
 };
 use crate::stmt::{
-    BlockStmt, ClassStmt, ExprStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt, VarStmt, WhileStmt,
+    AssertStmt, BlockStmt, ClassStmt, ExportStmt, ExprStmt, ForInStmt, FunctionStmt, IfStmt,
+    ImportStmt, PrintStmt, ReturnStmt, SwitchStmt, ThrowStmt, TryStmt, VarStmt, WhileStmt,
 };
 use crate::token::DataType;
 
@@ -22,16 +23,30 @@ pub trait ExprVisitor {
     fn visit_set_expr(&mut self, expr: &SetExpr) -> Result<DataType>;
     fn visit_this_expr(&mut self, expr: &ThisExpr) -> Result<DataType>;
     fn visit_super_expr(&mut self, expr: &SuperExpr) -> Result<DataType>;
+    fn visit_array_expr(&mut self, expr: &ArrayExpr) -> Result<DataType>;
+    fn visit_map_expr(&mut self, expr: &MapExpr) -> Result<DataType>;
+    fn visit_index_expr(&mut self, expr: &IndexExpr) -> Result<DataType>;
+    fn visit_set_index_expr(&mut self, expr: &SetIndexExpr) -> Result<DataType>;
+    fn visit_try_expr(&mut self, expr: &TryExpr) -> Result<DataType>;
 }
 
+/// Statements produce no value of their own. `Some` signals that a `return`
+/// statement ran and carries the value being returned out of the function.
 pub trait StmtVisitor {
-    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<DataType>;
-    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<DataType>;
-    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<DataType>;
-    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<DataType>;
-    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<DataType>;
-    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<DataType>;
-    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<DataType>;
-    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<DataType>;
-    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<DataType>;
+    fn visit_print_statement(&mut self, stmt: &PrintStmt) -> Result<Option<DataType>>;
+    fn visit_expr_statement(&mut self, stmt: &ExprStmt) -> Result<Option<DataType>>;
+    fn visit_var_statement(&mut self, stmt: &VarStmt) -> Result<Option<DataType>>;
+    fn visit_block_statement(&mut self, stmt: &BlockStmt) -> Result<Option<DataType>>;
+    fn visit_if_statement(&mut self, stmt: &IfStmt) -> Result<Option<DataType>>;
+    fn visit_while_statement(&mut self, stmt: &WhileStmt) -> Result<Option<DataType>>;
+    fn visit_function_statement(&mut self, stmt: &FunctionStmt) -> Result<Option<DataType>>;
+    fn visit_return_statement(&mut self, stmt: &ReturnStmt) -> Result<Option<DataType>>;
+    fn visit_class_statement(&mut self, stmt: &ClassStmt) -> Result<Option<DataType>>;
+    fn visit_throw_statement(&mut self, stmt: &ThrowStmt) -> Result<Option<DataType>>;
+    fn visit_try_statement(&mut self, stmt: &TryStmt) -> Result<Option<DataType>>;
+    fn visit_import_statement(&mut self, stmt: &ImportStmt) -> Result<Option<DataType>>;
+    fn visit_assert_statement(&mut self, stmt: &AssertStmt) -> Result<Option<DataType>>;
+    fn visit_export_statement(&mut self, stmt: &ExportStmt) -> Result<Option<DataType>>;
+    fn visit_switch_statement(&mut self, stmt: &SwitchStmt) -> Result<Option<DataType>>;
+    fn visit_for_in_statement(&mut self, stmt: &ForInStmt) -> Result<Option<DataType>>;
 }
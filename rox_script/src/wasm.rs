@@ -0,0 +1,121 @@
+//! The JavaScript API of the `wasm` build, for running rox in a browser
+//! playground. There is no filesystem there, so only `std/` modules can be
+//! imported.
+
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::token::DataType;
+use crate::Rox;
+
+/// Runs `source` in a fresh interpreter and returns everything it printed,
+/// followed by the value of its trailing expression or its error.
+#[wasm_bindgen]
+pub fn eval(source: &str) -> String {
+    Playground::new().eval(source)
+}
+
+/// An interpreter session kept between evaluations, like the REPL.
+#[wasm_bindgen]
+pub struct Playground {
+    rox: Rox,
+    /// What scripts printed since the last evaluation, unless printing goes
+    /// to a callback.
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Playground {
+        let mut rox = Rox::new();
+        let output = Rc::new(RefCell::new(vec![]));
+        rox.set_output(Buffer(Rc::clone(&output)));
+        Playground { rox, output }
+    }
+
+    /// Calls `callback` with each line as it is printed instead of returning
+    /// the lines from [`Playground::eval`].
+    #[wasm_bindgen(js_name = setPrintCallback)]
+    pub fn set_print_callback(&mut self, callback: js_sys::Function) {
+        self.rox.set_output(Lines {
+            callback,
+            line: vec![],
+        });
+    }
+
+    /// Runs `source` and returns what it printed, followed by the value of
+    /// its trailing expression or its error.
+    pub fn eval(&mut self, source: &str) -> String {
+        let result = self.rox.eval(source);
+        let mut out = String::from_utf8_lossy(&self.output.take()).into_owned();
+        match result {
+            Ok(DataType::Nil) => {}
+            Ok(value) => out.push_str(&format!("{value}\n")),
+            Err(error) => out.push_str(&format!("{error:#}\n")),
+        }
+        out
+    }
+}
+
+impl Default for Playground {
+    fn default() -> Self {
+        Playground::new()
+    }
+}
+
+/// Collects output to hand back from [`Playground::eval`].
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hands output to a JavaScript callback a line at a time. A partial line,
+/// as `std/io`'s `write` can leave, goes out when it is flushed.
+struct Lines {
+    callback: js_sys::Function,
+    line: Vec<u8>,
+}
+
+impl Lines {
+    fn send(&mut self) {
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+        // A throwing callback can't stop the script, so its error is dropped.
+        let _ = self
+            .callback
+            .call1(&JsValue::NULL, &JsValue::from_str(&line));
+    }
+}
+
+impl Write for Lines {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        for byte in bytes {
+            if *byte == b'\n' {
+                self.send();
+            } else {
+                self.line.push(*byte);
+            }
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.send();
+        }
+        Ok(())
+    }
+}
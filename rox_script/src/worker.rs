@@ -0,0 +1,247 @@
+//! Workers: scripts running in interpreters of their own on other threads.
+//! Workers share nothing with the script or host that spawned them and
+//! only exchange plain data, copied into [`Message`]s.
+//!
+//! Workers run on a pool of at most [`MAX_WORKERS`] threads shared by the
+//! whole process. A thread goes back to the pool when its script finishes
+//! and runs the next worker spawned. Spawning a worker while every thread
+//! is busy fails rather than waiting, since the busy workers may be waiting
+//! for the one being spawned.
+
+use std::collections::BTreeMap;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::collections::{LoxArray, LoxMap, MapKey};
+use crate::interpreter::Interpreter;
+use crate::limits::Limits;
+use crate::sandbox::RoxSandbox;
+use crate::token::DataType;
+
+/// A copy of a plain data value that can cross threads: nil, booleans,
+/// numbers, strings and arrays and maps of them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Message>),
+    Map(Vec<(Message, Message)>),
+}
+
+impl Message {
+    /// Copies `value`, failing if it is or contains something other than
+    /// plain data.
+    pub fn from_value(value: &DataType) -> Result<Message> {
+        copy(value, &mut vec![])
+    }
+
+    pub fn to_value(&self) -> DataType {
+        match self {
+            Message::Nil => DataType::Nil,
+            Message::Bool(b) => DataType::Bool(*b),
+            Message::Number(n) => DataType::Number(*n),
+            Message::String(s) => DataType::String(s.clone()),
+            Message::Array(elements) => DataType::Array(LoxArray::new(
+                elements.iter().map(Message::to_value).collect(),
+            )),
+            Message::Map(entries) => {
+                let entries: BTreeMap<_, _> = entries
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        Some((MapKey::from_value(&key.to_value())?, value.to_value()))
+                    })
+                    .collect();
+                DataType::Map(LoxMap::new(entries))
+            }
+        }
+    }
+}
+
+/// Copies `value`, with `copying` holding the collections it is inside of
+/// so one that contains itself is refused instead of copied forever.
+fn copy(value: &DataType, copying: &mut Vec<*const ()>) -> Result<Message> {
+    match value {
+        DataType::Nil => Ok(Message::Nil),
+        DataType::Bool(b) => Ok(Message::Bool(*b)),
+        DataType::Number(n) => Ok(Message::Number(*n)),
+        DataType::String(s) => Ok(Message::String(s.clone())),
+        DataType::Array(array) => {
+            let id = Rc::as_ptr(&array.elements) as *const ();
+            enter(id, copying)?;
+            let elements = array
+                .elements
+                .borrow()
+                .iter()
+                .map(|element| copy(element, copying))
+                .collect::<Result<_>>()?;
+            copying.pop();
+            Ok(Message::Array(elements))
+        }
+        DataType::Map(map) => {
+            let id = Rc::as_ptr(&map.entries) as *const ();
+            enter(id, copying)?;
+            let entries = map
+                .entries
+                .borrow()
+                .iter()
+                .map(|(key, value)| Ok((copy(&key.to_value(), copying)?, copy(value, copying)?)))
+                .collect::<Result<_>>()?;
+            copying.pop();
+            Ok(Message::Map(entries))
+        }
+        other => Err(anyhow!(
+            "Can only send plain data to a worker, got {other}."
+        )),
+    }
+}
+
+fn enter(id: *const (), copying: &mut Vec<*const ()>) -> Result<()> {
+    if copying.contains(&id) {
+        return Err(anyhow!("Can't send a collection that contains itself."));
+    }
+    copying.push(id);
+    Ok(())
+}
+
+/// What travels between a worker and whoever spawned it: a message, or
+/// the error the worker's script stopped with.
+type Envelope = std::result::Result<Message, String>;
+
+/// One end of the connection between a worker and whoever spawned it.
+pub struct Channel {
+    sender: Sender<Envelope>,
+    receiver: Receiver<Envelope>,
+}
+
+impl Channel {
+    pub fn send(&self, message: Message) {
+        // The other end having finished isn't an error: like a message
+        // nobody reads, it is dropped.
+        let _ = self.sender.send(Ok(message));
+    }
+
+    /// Waits for the next message. Returns `None` once the other end has
+    /// finished and every message it sent has been received, and an error
+    /// if it is a worker whose script failed.
+    pub fn receive(&self) -> Result<Option<Message>> {
+        open(self.receiver.recv().ok())
+    }
+
+    /// [`Channel::receive`] that calls `interrupted` every `slice` while it
+    /// waits, giving up with the error `interrupted` fails with. Scripts
+    /// receive this way, so cancelling them or running out of time wakes
+    /// them.
+    pub fn receive_interruptibly(
+        &self,
+        slice: Duration,
+        interrupted: impl Fn() -> Result<()>,
+    ) -> Result<Option<Message>> {
+        loop {
+            interrupted()?;
+            match self.receiver.recv_timeout(slice) {
+                Ok(envelope) => return open(Some(envelope)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return open(None),
+            }
+        }
+    }
+}
+
+/// What receiving `envelope` gives, `None` standing for the other end
+/// having finished.
+fn open(envelope: Option<Envelope>) -> Result<Option<Message>> {
+    match envelope {
+        Some(Ok(message)) => Ok(Some(message)),
+        Some(Err(error)) => Err(anyhow!("Worker failed: {error}")),
+        None => Ok(None),
+    }
+}
+
+/// The most workers that can run at once across the process, and so the
+/// most threads the pool starts.
+pub const MAX_WORKERS: usize = 64;
+
+/// A worker's script, ready to run on a pool thread.
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The threads workers run on.
+struct Pool {
+    /// How to hand a job to each thread waiting for one.
+    idle: Vec<Sender<Job>>,
+    /// How many threads the pool has started, busy or idle.
+    threads: usize,
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    idle: Vec::new(),
+    threads: 0,
+});
+
+/// Runs `job` on an idle pool thread, starting a new one if there is none
+/// and the pool isn't full.
+fn run_pooled(job: Job) -> Result<()> {
+    let mut pool = POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let job = match pool.idle.pop() {
+        Some(thread) => match thread.send(job) {
+            Ok(()) => return Ok(()),
+            Err(unsent) => unsent.0,
+        },
+        None => job,
+    };
+    if pool.threads >= MAX_WORKERS {
+        return Err(anyhow!(
+            "Can't run more than {MAX_WORKERS} workers at once."
+        ));
+    }
+    let (jobs, next_job) = channel::<Job>();
+    jobs.send(job).expect("the receiver is still here");
+    pool.threads += 1;
+    thread::spawn(move || {
+        for job in next_job.iter() {
+            // A panicking script takes down its worker, not the thread.
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            let mut pool = POOL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            pool.idle.push(jobs.clone());
+        }
+    });
+    Ok(())
+}
+
+/// Runs `source` in a new interpreter on a pool thread, where the global
+/// `parent` refers to the spawner. Returns the spawner's end of the
+/// connection, or an error if [`MAX_WORKERS`] workers are already running.
+pub fn spawn(source: String, limits: Limits) -> Result<Channel> {
+    spawn_sandboxed(source, limits, RoxSandbox::new())
+}
+
+/// [`spawn`] for a worker that can only use the capabilities `sandbox`
+/// allows.
+pub fn spawn_sandboxed(source: String, limits: Limits, sandbox: RoxSandbox) -> Result<Channel> {
+    let (to_worker, from_parent) = channel();
+    let (to_parent, from_worker) = channel();
+    run_pooled(Box::new(move || {
+        let parent = Channel {
+            sender: to_parent,
+            receiver: from_parent,
+        };
+        let errors = parent.sender.clone();
+        let mut interpreter = Interpreter::with_sandbox(limits, sandbox);
+        interpreter.connect_parent(parent);
+        if let Err(error) = interpreter.run(source) {
+            let _ = errors.send(Err(format!("{error:#}")));
+        }
+    }))?;
+    Ok(Channel {
+        sender: to_worker,
+        receiver: from_worker,
+    })
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::process::Output;
+
+use common::{rox_script, script, stdout};
+
+/// Writes `source` to a scratch file and runs it with `args` after its path.
+fn run(args: &[&str], source: &str) -> Output {
+    rox_script()
+        .arg(script(source))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+const ECHO: &str = r#"
+var arguments = args();
+print len(arguments);
+for (arg in arguments) print arg;
+"#;
+
+#[test]
+fn arguments_after_the_script_are_passed_to_it() {
+    let output = run(&["input.txt", "--verbose", "two words"], ECHO);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\ninput.txt\n--verbose\ntwo words\n");
+}
+
+#[test]
+fn scripts_without_arguments_get_an_empty_array() {
+    let output = run(&[], ECHO);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0\n");
+}
+
+#[test]
+fn commands_take_no_script_arguments() {
+    let output = rox_script()
+        .arg("minify")
+        .arg(script(ECHO))
+        .arg("extra")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::{run, run_path, script, stdout};
+
+#[test]
+fn bench_runs_the_script_and_prints_timings() {
+    let path = script(
+        "var total = 0;\nfor (var i = 0; i < 100; i = i + 1) total = total + i;\nprint total;\n",
+    );
+
+    let output = run_path(&["--runs=3", "bench"], &path);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{stdout}");
+    assert_eq!(lines[0], format!("3 runs of {}", path.display()));
+    assert!(lines[1].starts_with("min ") && lines[1].ends_with(" ms"));
+    assert!(lines[2].starts_with("avg ") && lines[2].ends_with(" ms"));
+}
+
+#[test]
+fn bench_reports_a_failing_run() {
+    let output = run(&["bench"], "print nope;\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "");
+}
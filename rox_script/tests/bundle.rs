@@ -0,0 +1,82 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{project, run_path, scratch_path, stderr, stdout};
+
+#[test]
+fn bundled_scripts_behave_the_same() {
+    let imports = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts/imports");
+    for script in [
+        "alias.rox",
+        "cycle.rox",
+        "module_globals.rox",
+        "relative_paths.rox",
+    ] {
+        let bundled = run_path(&["bundle"], &imports.join(script));
+        assert_eq!(bundled.status.code(), Some(0), "{script}");
+        let bundled_path = scratch_path(".rox");
+        fs::write(&bundled_path, &bundled.stdout).unwrap();
+
+        let original = run_path(&[], &imports.join(script));
+        let rerun = run_path(&[], &bundled_path);
+        assert_eq!(
+            String::from_utf8_lossy(&rerun.stdout),
+            String::from_utf8_lossy(&original.stdout),
+            "{script} bundled to\n{}",
+            String::from_utf8_lossy(&bundled.stdout)
+        );
+        assert_eq!(rerun.status.code(), Some(0));
+    }
+}
+
+#[test]
+fn unused_declarations_are_left_out() {
+    let dir = project(&[
+        (
+            "main.rox",
+            "import \"shapes.rox\";\nfun unused() {\n  return 1;\n}\nprint shapes.area(2);\n",
+        ),
+        (
+            "shapes.rox",
+            r#"
+var unit = 1;
+var sides = 4;
+var loaded = clock() > 0;
+
+fun square(n) {
+  return n * n * unit;
+}
+
+export fun area(n) {
+  return square(n);
+}
+
+// Only used by something nothing uses.
+export fun perimeter(n) {
+  return n * sides;
+}
+"#,
+        ),
+    ]);
+    let output = run_path(&["bundle"], &dir.join("main.rox"));
+    assert_eq!(
+        stdout(&output),
+        "var shapes__unit=1;var shapes__loaded=clock()>0;fun shapes__square(a){return a*a*shapes__unit;}fun shapes__area(a){return shapes__square(a);}print shapes__area(2);\n"
+    );
+}
+
+#[test]
+fn namespaces_can_only_be_used_to_read_exports() {
+    let dir = project(&[
+        ("main.rox", "import \"lib.rox\";\nvar l = lib;\n"),
+        ("lib.rox", "export var x = 1;\n"),
+    ]);
+    let output = run_path(&["bundle"], &dir.join("main.rox"));
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at 'lib': Can only read exports from a module namespace when bundling.\n"
+    );
+}
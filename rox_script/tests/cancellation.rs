@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+
+use common::{rox_script, script};
+
+/// Starts `source`, waits for it to print its first line, interrupts it and
+/// returns the exit code and stderr.
+fn interrupt(source: &str) -> (Option<i32>, String) {
+    let mut child = rox_script()
+        .arg(script(source))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "started\n");
+
+    let status = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = child.wait().unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    (status.code(), stderr)
+}
+
+#[test]
+fn interrupt_cancels_a_running_script() {
+    let (code, stderr) = interrupt(
+        r#"
+print "started";
+while (true) {}
+"#,
+    );
+    assert_eq!(code, Some(70));
+    assert!(stderr.starts_with("Execution cancelled.\n"), "{stderr}");
+}
+
+#[test]
+fn cancellation_cannot_be_swallowed_by_catch() {
+    let (code, stderr) = interrupt(
+        r#"
+print "started";
+while (true) {
+  try {
+    while (true) {}
+  } catch (e) {}
+}
+"#,
+    );
+    assert_eq!(code, Some(70));
+    assert!(stderr.starts_with("Execution cancelled.\n"), "{stderr}");
+}
+
+#[test]
+fn interrupt_wakes_a_sleeping_script() {
+    let (code, stderr) = interrupt(
+        r#"
+print "started";
+sleep(60000);
+print "never";
+"#,
+    );
+    assert_eq!(code, Some(70));
+    assert!(stderr.starts_with("Execution cancelled.\n"), "{stderr}");
+}
@@ -0,0 +1,62 @@
+//! Helpers the integration tests share for running scripts through the
+//! `rox_script` binary. Every test file compiles its own copy of this
+//! module and uses only some of it.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A command running the interpreter binary.
+pub fn rox_script() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_rox_script"))
+}
+
+/// A fresh path in the temporary directory, shared with no other call, so
+/// tests running at once in this or another test binary never write over
+/// each other's files.
+pub fn scratch_path(extension: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "rox_script_test_{}_{n}{extension}",
+        std::process::id()
+    ))
+}
+
+/// Writes `source` to a scratch file of its own, returning its path.
+pub fn script(source: &str) -> PathBuf {
+    let path = scratch_path(".lox");
+    fs::write(&path, source).unwrap();
+    path
+}
+
+/// Writes `files` into a scratch directory of their own, returning it.
+pub fn project(files: &[(&str, &str)]) -> PathBuf {
+    let dir = scratch_path("");
+    fs::create_dir_all(&dir).unwrap();
+    for (file, source) in files {
+        fs::write(dir.join(file), source).unwrap();
+    }
+    dir
+}
+
+/// Runs the script at `path` with the command line `flags` before it.
+pub fn run_path(flags: &[&str], path: &Path) -> Output {
+    rox_script().args(flags).arg(path).output().unwrap()
+}
+
+/// Writes `source` to a scratch file and runs it with the command line
+/// `flags` before its path.
+pub fn run(flags: &[&str], source: &str) -> Output {
+    run_path(flags, &script(source))
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
@@ -0,0 +1,85 @@
+use rox_script::profile;
+use rox_script::profile::Kind;
+use rox_script::Rox;
+
+#[test]
+fn functions_defined_in_a_loop_do_not_accumulate() {
+    profile::enable();
+    let mut rox = Rox::new();
+    rox.eval(
+        r#"
+for (var i = 0; i < 10000; i = i + 1) {
+  var local = [i];
+  fun f() { return local; }
+}
+"#,
+    )
+    .unwrap();
+    let report = profile::report().unwrap();
+    assert!(report.count(Kind::Function).peak < 3000, "{report}");
+    assert!(report.count(Kind::Environment).peak < 6000, "{report}");
+    assert!(report.count(Kind::Array).peak < 3000, "{report}");
+
+    drop(rox);
+    let report = profile::report().unwrap();
+    assert_eq!(report.count(Kind::Environment).live, 0, "{report}");
+    assert_eq!(report.count(Kind::Function).live, 0, "{report}");
+}
+
+#[test]
+fn closures_still_reachable_survive_collection() {
+    let mut rox = Rox::new();
+    let value = rox
+        .eval(
+            r#"
+fun counter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+var kept = counter();
+class Listener {
+  init() {
+    this.callback = this.handle;
+    this.seen = 0;
+  }
+  handle() {
+    this.seen = this.seen + 1;
+    return this.seen;
+  }
+}
+var listener = Listener();
+for (var i = 0; i < 2000; i = i + 1) {
+  counter()();
+  Listener().callback();
+  kept();
+  listener.callback();
+}
+kept() + listener.callback();
+"#,
+        )
+        .unwrap();
+    assert_eq!(value.to_string(), "4002");
+}
+
+#[test]
+fn functions_the_host_holds_outlive_the_interpreter() {
+    profile::enable();
+    let mut rox = Rox::new();
+    let value = rox
+        .eval("fun outer() { var x = 1; fun inner() { return x; } return inner; } outer();")
+        .unwrap();
+    drop(rox);
+    // `inner` keeps the global scope it is nested in alive, and with it
+    // `outer`.
+    let report = profile::report().unwrap();
+    assert_eq!(report.count(Kind::Function).live, 2, "{report}");
+    // Once the host lets go, the next collection on the thread frees them.
+    drop(value);
+    drop(Rox::new());
+    let report = profile::report().unwrap();
+    assert_eq!(report.count(Kind::Function).live, 0, "{report}");
+}
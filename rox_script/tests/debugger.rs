@@ -0,0 +1,93 @@
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+use common::{rox_script, script, stderr, stdout};
+
+/// Runs the script at `path` under `--debug`, typing `commands` at the
+/// debugger.
+fn debug(path: &Path, commands: &str) -> Output {
+    let mut child = rox_script()
+        .arg("--debug")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(commands.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+const SCRIPT: &str = "fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+var total = 0;
+for (var i = 0; i < 3; i = i + 1) {
+  total = add(total, i);
+}
+print total;
+";
+
+#[test]
+fn stepping_breakpoints_and_scopes() {
+    let path = script(SCRIPT);
+    let output = debug(&path, "n\nb 7\nc\nenv\ns\ns\nenv\nc\nc\n");
+    let path = path.display();
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "Stopped at {path}:1
+   1 | fun add(a, b) {{
+(rox) Stopped at {path}:5
+   5 | var total = 0;
+(rox) Breakpoint 1 at 7
+(rox) Stopped at {path}:7
+   7 |   total = add(total, i);
+(rox) [0] (empty)
+[1] (empty)
+[2] i = 0
+[global] add = <fn add>, total = 0
+(rox) Stopped at {path}:2
+   2 |   var sum = a + b;
+(rox) Stopped at {path}:3
+   3 |   return sum;
+(rox) [0] a = 0, b = 0, sum = 0
+[global] add = <fn add>, total = 0
+(rox) Stopped at {path}:7
+   7 |   total = add(total, i);
+(rox) Stopped at {path}:7
+   7 |   total = add(total, i);
+(rox) 
+"
+        )
+    );
+}
+
+#[test]
+fn breakpoints_can_name_the_file_and_quit_stops_the_script() {
+    let path = script(SCRIPT);
+    let file = path.file_name().unwrap().to_str().unwrap();
+    let output = debug(&path, &format!("b other.lox:2\nb {file}:2\nc\nq\n"));
+    assert_eq!(output.status.code(), Some(70));
+    assert!(output.stdout.is_empty());
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("   2 |   var sum = a + b;\n(rox) "),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("(rox) Execution stopped by the debugger."),
+        "{stderr}"
+    );
+}
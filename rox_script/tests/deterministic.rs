@@ -0,0 +1,55 @@
+mod common;
+
+use common::{run, stderr, stdout};
+
+const RANDOM: &str = r#"
+import "std/math";
+for (var i = 0; i < 5; i = i + 1) {
+  var n = math.random();
+  assert n >= 0 and n < 1;
+  print n;
+}
+"#;
+
+#[test]
+fn a_seed_gives_the_same_numbers_every_run() {
+    let first = run(&["--deterministic=42"], RANDOM);
+    let second = run(&["--deterministic=42"], RANDOM);
+    let other = run(&["--deterministic=7"], RANDOM);
+    assert!(first.status.success());
+    assert_eq!(first.stdout, second.stdout);
+    assert_ne!(first.stdout, other.stdout);
+
+    let unseeded = run(&[], RANDOM);
+    assert!(unseeded.status.success());
+    assert_ne!(unseeded.stdout, first.stdout);
+}
+
+#[test]
+fn time_only_passes_when_the_script_reads_the_clock_or_sleeps() {
+    let output = run(
+        &["--deterministic"],
+        r#"
+print clock();
+print clock();
+sleep(1000);
+print clock();
+fun late() { print "late " + repr(clock()); }
+fun early() { print "early " + repr(clock()); }
+setTimeout(late, 50);
+setTimeout(early, 10);
+runPending();
+"#,
+    );
+    assert_eq!(stdout(&output), "1\n2\n1003\nearly 1014\nlate 1054\n");
+}
+
+#[test]
+fn workers_are_refused() {
+    let output = run(&["--deterministic"], "spawnWorker(\"print 1;\");\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        "<native fn spawnWorker> can't be used in a deterministic run.\n[line 1]\n"
+    );
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::process::Output;
+
+use common::{run_path, script, stderr};
+
+/// Writes `source` to a scratch file and runs it with `flags`, returning
+/// the output and the path its errors name.
+fn run(flags: &[&str], source: &str) -> (Output, String) {
+    let path = script(source);
+    (run_path(flags, &path), path.display().to_string())
+}
+
+#[test]
+fn pretty_errors_show_the_line_with_a_caret() {
+    let (output, path) = run(
+        &["--strict", "--error-format=pretty"],
+        "var total = 1;\nprint totl + 1;\n",
+    );
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "error[E0003]: Undefined variable 'totl'.\n --> {path}:2:7\n  |\n2 | print totl + 1;\n  |       ^^^^\n  = hint: did you mean 'total'?\n"
+        )
+    );
+}
+
+#[test]
+fn scan_and_runtime_errors_have_columns_too() {
+    let (output, path) = run(&["--error-format=pretty"], "print 1;\nvar s = \"a\\q\";\n");
+    assert_eq!(
+        stderr(&output),
+        format!("error[E0001]: Invalid escape sequence.\n --> {path}:2:11\n  |\n2 | var s = \"a\\q\";\n  |           ^\n")
+    );
+
+    let (output, path) = run(&["--error-format=json"], "print nil + 1;\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        format!("{{\"code\":\"E0004\",\"severity\":\"error\",\"message\":\"Operands of '+' must be numbers or strings, got nil and number.\",\"file\":\"{path}\",\"line\":1,\"column\":11,\"span\":[10,11],\"hint\":null}}\n")
+    );
+}
+
+#[test]
+fn human_errors_keep_the_book_format() {
+    let (output, _) = run(&["--strict"], "var total = 1;\nprint totl + 1;\n");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at 'totl': Undefined variable 'totl'.\n"
+    );
+}
+
+#[test]
+fn pretty_warnings_point_at_the_declaration() {
+    let (output, path) = run(
+        &["--warnings", "--error-format=pretty"],
+        "fun f() {\n  var unused = 1;\n}\nf();\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stderr(&output),
+        format!("warning[W0001]: 'unused' is declared but never used.\n --> {path}:2:7\n  |\n2 |   var unused = 1;\n  |       ^^^^^^\n")
+    );
+}
+
+#[test]
+fn columns_count_characters_from_the_start_of_the_line() {
+    let (output, _) = run(
+        &["--error-format=json"],
+        "var s = \"é\nü\"; print s + nil;\n",
+    );
+    let stderr = stderr(&output);
+    assert!(stderr.contains("\"line\":2,\"column\":13,"), "{stderr}");
+}
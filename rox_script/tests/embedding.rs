@@ -0,0 +1,427 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+use rox_script::diagnostics::{write_report, ErrorFormat, Severity};
+use rox_script::limits::Limits;
+use rox_script::program::Program;
+use rox_script::sandbox::RoxSandbox;
+use rox_script::source::InMemory;
+use rox_script::worker;
+use rox_script::worker::Message;
+use rox_script::{Callable, Rox, Value};
+
+fn number(value: Value) -> f64 {
+    match value {
+        Value::Number(n) => n,
+        other => panic!("expected a number, got {other}"),
+    }
+}
+
+#[test]
+fn eval_returns_the_trailing_expression() {
+    let mut rox = Rox::new();
+    assert_eq!(number(rox.eval("1 + 2 * 3;").unwrap()), 7.0);
+    assert!(matches!(rox.eval("var x = 1;").unwrap(), Value::Nil));
+}
+
+#[test]
+fn globals_persist_between_evals() {
+    let mut rox = Rox::new();
+    rox.eval("fun square(n) { return n * n; } var base = 4;")
+        .unwrap();
+    assert_eq!(number(rox.eval("square(base);").unwrap()), 16.0);
+}
+
+#[test]
+fn errors_are_returned_not_printed() {
+    let mut rox = Rox::new();
+    let error = rox.eval("nil + 1;").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Operands of '+' must be numbers or strings, got nil and number.\n[line 1]"
+    );
+    let error = rox.eval("var;").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Error at ';': Expect variable name."
+    );
+    // Errors deep inside an expression come back the same way.
+    let error = rox.eval("var a; var b; a = b = -(1 < \"2\");").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Operands of '<' must be numbers, got number and string.\n[line 1]"
+    );
+}
+
+#[test]
+fn natives_can_be_rust_closures() {
+    let mut rox = Rox::new();
+    rox.define_native("add", 2, |arguments| match arguments {
+        [Value::Number(a), Value::Number(b)] => Ok(Value::Number(a + b)),
+        _ => Err(anyhow::anyhow!("add expects two numbers.")),
+    });
+    assert_eq!(number(rox.eval("add(2, 3);").unwrap()), 5.0);
+
+    let error = rox.eval("add(1, \"2\");").unwrap_err();
+    assert_eq!(error.to_string(), "add expects two numbers.\n[line 1]");
+    let error = rox.eval("add(1);").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected 2 arguments but got 1 when calling <native fn add>.\n[line 1]"
+    );
+}
+
+#[test]
+fn scripts_and_imports_can_come_from_memory() {
+    let mut sources = InMemory::new();
+    sources.insert("main.rox", "import \"lib/math.rox\";\nmath.double(21);");
+    sources.insert("lib/math.rox", "export fun double(n) { return n * 2; }");
+    let mut rox = Rox::new();
+    rox.set_source_provider(sources);
+    assert_eq!(number(rox.run_file("main.rox").unwrap()), 42.0);
+
+    let error = rox.run_file("missing.rox").unwrap_err();
+    assert_eq!(error.to_string(), "Could not read 'missing.rox'");
+}
+
+#[test]
+fn sandboxes_keep_scripts_from_the_host() {
+    let mut rox = Rox::with_sandbox(Limits::default(), RoxSandbox::deny_all());
+    rox.set_args(vec!["secret".to_string()]);
+    let error = rox.eval("args();").unwrap_err();
+    assert!(error.to_string().starts_with("Undefined variable 'args'."));
+    let error = rox.eval("import \"std/io\";").unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Unknown standard module 'std/io'."));
+    let error = rox.eval("import \"Cargo.toml\";").unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Could not import 'Cargo.toml'"));
+
+    // Imports the host serves itself are still allowed.
+    let mut sources = InMemory::new();
+    sources.insert("lib.rox", "export var answer = 42;");
+    rox.set_source_provider(sources);
+    let answer = rox.eval("import \"lib.rox\";\nlib.answer;").unwrap();
+    assert_eq!(number(answer), 42.0);
+
+    let mut rox = Rox::with_sandbox(Limits::default(), RoxSandbox::new().deny_env());
+    assert!(rox.eval("args();").is_err());
+    rox.set_output(io::sink());
+    rox.eval("import \"std/io\";\nio.write(\"allowed\");")
+        .unwrap();
+}
+
+#[test]
+fn natives_convert_arguments_and_results() {
+    let mut rox = Rox::new();
+    rox.interpreter().register_native("repeat", 2, |arguments| {
+        let text = String::try_from(&arguments[0])?;
+        let times = i64::try_from(&arguments[1])?;
+        Ok(Value::from(vec![text; times.max(0) as usize]))
+    });
+    rox.interpreter().register_native("total", 1, |arguments| {
+        let numbers = Vec::<f64>::try_from(&arguments[0])?;
+        Ok(Value::from(numbers.iter().sum::<f64>()))
+    });
+    assert_eq!(
+        rox.eval("repeat(\"ab\", 2);").unwrap().to_string(),
+        "[\"ab\", \"ab\"]"
+    );
+    assert_eq!(number(rox.eval("total([1, 2, 3.5]);").unwrap()), 6.5);
+
+    let error = rox.eval("repeat(\"ab\", 1.5);").unwrap_err();
+    assert_eq!(error.to_string(), "Expected an integer, got 1.5.\n[line 1]");
+    let error = rox.eval("total([1, \"2\"]);").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected a number, got string.\n[line 1]"
+    );
+}
+
+#[test]
+fn script_functions_can_be_called_from_rust() {
+    let mut rox = Rox::new();
+    rox.eval("fun greet(name) { return \"hello \" + name; } var answer = 42;")
+        .unwrap();
+    assert_eq!(
+        rox.call("greet", vec![Value::from("rox")])
+            .unwrap()
+            .to_string(),
+        "hello rox"
+    );
+
+    let error = rox.call("greet", vec![]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected 1 arguments but got 0 when calling <fn greet>."
+    );
+    let error = rox.call("answer", vec![]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Can only call functions and classes, got 42."
+    );
+    let error = rox.call("missing", vec![]).unwrap_err();
+    assert_eq!(error.to_string(), "Undefined variable 'missing'.");
+}
+
+#[test]
+fn callbacks_can_be_stored_and_called_later() {
+    let mut rox = Rox::new();
+    let callbacks = Rc::new(RefCell::new(vec![]));
+    let registered = Rc::clone(&callbacks);
+    rox.define_native("onTick", 1, move |arguments| {
+        let callback = Callable::from_value(&arguments[0])
+            .ok_or_else(|| anyhow::anyhow!("onTick expects a function."))?;
+        registered.borrow_mut().push(callback);
+        Ok(Value::Nil)
+    });
+    rox.eval("var ticks = 0; fun tick(n) { ticks = ticks + n; return ticks; } onTick(tick);")
+        .unwrap();
+
+    let callback = callbacks.borrow()[0].clone();
+    assert_eq!(callback.arity(), 1);
+    callback
+        .call(rox.interpreter(), vec![Value::from(2)])
+        .unwrap();
+    let ticks = callback
+        .call(rox.interpreter(), vec![Value::from(3)])
+        .unwrap();
+    assert_eq!(number(ticks), 5.0);
+    assert_eq!(number(rox.eval("ticks;").unwrap()), 5.0);
+}
+
+#[test]
+fn strict_mode_accepts_declared_externals() {
+    let mut rox = Rox::new();
+    rox.interpreter().set_strict(true);
+    let error = rox.eval("fun report() { log(\"hi\"); }").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Error at 'log': Undefined variable 'log'."
+    );
+
+    rox.declare_external("log", Some(1));
+    rox.eval("fun report() { return log(\"hi\"); }").unwrap();
+    let error = rox.eval("log(1, 2);").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "[line 1] Error at ')': Expected 1 arguments but got 2 when calling 'log'."
+    );
+
+    // The host defines the external after the script was resolved.
+    rox.define_native("log", 1, |arguments| Ok(arguments[0].clone()));
+    assert_eq!(rox.call("report", vec![]).unwrap().to_string(), "hi");
+}
+
+#[test]
+fn compiled_programs_run_in_fresh_interpreters() {
+    let program = Program::compile(
+        r#"
+fun counter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+var next = counter();
+next();
+next() + limit;
+"#,
+    )
+    .unwrap();
+    for limit in [10.0, 20.0] {
+        let mut rox = Rox::new();
+        rox.eval(&format!("var limit = {limit};")).unwrap();
+        assert_eq!(number(rox.run_program(&program).unwrap()), 2.0 + limit);
+        // Sources run afterwards don't clash with the program's resolution.
+        let sum = rox
+            .eval("fun plus(a) { var b = 2; return a + b; } plus(next());")
+            .unwrap();
+        assert_eq!(number(sum), 5.0);
+    }
+}
+
+/// Output shared with the test that handed it to the interpreter.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+#[test]
+fn output_can_be_captured() {
+    let mut rox = Rox::new();
+    let output = Captured::default();
+    rox.set_output(output.clone());
+    rox.eval("print 1 + 2; import \"std/io\"; io.write(\"no newline\"); print \"!\";")
+        .unwrap();
+    assert_eq!(output.text(), "3\nno newline!\n");
+
+    let errors = Captured::default();
+    let error = rox.eval("nil + 1;").unwrap_err();
+    write_report(&mut errors.clone(), &error, "<eval>", ErrorFormat::Human).unwrap();
+    assert_eq!(
+        errors.text(),
+        "Operands of '+' must be numbers or strings, got nil and number.\n[line 1]\n"
+    );
+}
+
+#[test]
+fn hosts_can_spawn_workers() {
+    let worker = worker::spawn(
+        "send(parent, receive(parent) + \"!\");".to_string(),
+        Limits::default(),
+    )
+    .unwrap();
+    worker.send(Message::String("hi".to_string()));
+    assert_eq!(
+        worker.receive().unwrap(),
+        Some(Message::String("hi!".to_string()))
+    );
+    assert_eq!(worker.receive().unwrap(), None);
+}
+
+#[test]
+fn resolver_warnings_are_collected_not_printed() {
+    let mut rox = Rox::new();
+    rox.eval(
+        "var limit = 3;\nfun f() {\n  var limit = 1;\n  var unused = 2;\n  return limit;\n  print limit;\n}\nf();\n",
+    )
+    .unwrap();
+    let warnings = rox.take_warnings();
+    let found: Vec<_> = warnings
+        .iter()
+        .map(|warning| {
+            (
+                warning.code,
+                warning.line.unwrap(),
+                warning.message.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                "W0003",
+                3,
+                "'limit' shadows a variable of an enclosing scope."
+            ),
+            ("W0002", 5, "Code after 'return' is unreachable."),
+            ("W0001", 4, "'unused' is declared but never used."),
+        ]
+    );
+    assert!(warnings
+        .iter()
+        .all(|warning| warning.severity == Severity::Warning && warning.file == "<script>"));
+    assert!(rox.take_warnings().is_empty());
+}
+
+#[test]
+fn shutdown_hooks_run_once_most_recent_first() {
+    let ran = Rc::new(RefCell::new(vec![]));
+    let mut rox = Rox::new();
+    let log = Rc::clone(&ran);
+    rox.on_shutdown(move || log.borrow_mut().push("host".to_string()));
+    let log = Rc::clone(&ran);
+    rox.define_native("log", 1, move |arguments| {
+        log.borrow_mut().push(arguments[0].to_string());
+        Ok(Value::Nil)
+    });
+    rox.eval("fun bye() { log(\"script\"); } atExit(bye);")
+        .unwrap();
+    assert!(ran.borrow().is_empty());
+
+    drop(rox);
+    assert_eq!(*ran.borrow(), ["script", "host"]);
+}
+
+#[test]
+fn memory_limits_stop_scripts_before_they_grow_the_host() {
+    let mut rox = Rox::with_limits(Limits {
+        max_memory: Some(1_000_000),
+        ..Limits::default()
+    });
+    let error = rox
+        .eval("var s = \"x\";\nwhile (true) s = s + s;")
+        .unwrap_err();
+    assert!(error.to_string().starts_with("Out of memory: "), "{error}");
+    // Dropping what the script held frees it up for the next run.
+    rox.eval("s = nil;").unwrap();
+    assert_eq!(rox.eval("\"ok\";").unwrap().to_string(), "ok");
+}
+
+#[test]
+fn interpreters_sharing_a_thread_count_memory_separately() {
+    let mut limited = Rox::with_limits(Limits {
+        max_memory: Some(100_000),
+        ..Limits::default()
+    });
+    let mut unlimited = Rox::new();
+    unlimited
+        .eval("var items = [];\nfor (var i = 0; i < 10000; i = i + 1) push(items, \"item \" + i);")
+        .unwrap();
+    assert_eq!(limited.eval("len([1, 2, 3]);").unwrap().to_string(), "3");
+
+    let error = limited
+        .eval("var items = [];\nwhile (true) push(items, \"item\");")
+        .unwrap_err();
+    assert!(error.to_string().starts_with("Out of memory: "), "{error}");
+    assert!(error.to_string().ends_with("\n[line 2]"), "{error}");
+}
+
+#[test]
+fn step_limits_stop_scripts_that_never_finish() {
+    let mut rox = Rox::with_limits(Limits {
+        max_steps: Some(1_000),
+        max_run_time: Some(std::time::Duration::from_secs(10)),
+        ..Limits::default()
+    });
+    let error = rox.eval("while (true) {}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Execution step limit of 1000 statements exceeded."
+    );
+    // Every run gets the whole budget again.
+    assert_eq!(
+        rox.eval("var i = 0;\ni = i + 1;\ni;").unwrap().to_string(),
+        "1"
+    );
+}
+
+#[test]
+fn uncaught_throws_leave_nothing_for_later_catches() {
+    let mut rox = Rox::new();
+    let error = rox.eval("throw \"stale value\";").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Uncaught exception: stale value\n[line 1]"
+    );
+    let caught = rox
+        .eval("var r; try { nil.x; } catch (e) { r = e; } r;")
+        .unwrap();
+    assert_eq!(
+        caught.to_string(),
+        "Only instances have properties, got nil."
+    );
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::{run, stdout};
+
+#[test]
+fn exit_runs_the_at_exit_functions_and_skips_catch_and_finally() {
+    let output = run(
+        &[],
+        r#"
+fun first() { print "first registered"; }
+fun second() { print "second registered"; }
+atExit(first);
+atExit(second);
+try {
+  exit(3);
+} catch (e) {
+  print "caught";
+} finally {
+  print "finally";
+}
+print "after";
+"#,
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "second registered\nfirst registered\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn at_exit_functions_run_when_the_script_finishes() {
+    let output = run(
+        &[],
+        "fun bye() { print \"bye\"; }\natExit(bye);\nprint \"done\";\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "done\nbye\n");
+}
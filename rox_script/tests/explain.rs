@@ -0,0 +1,48 @@
+mod common;
+
+use common::{run, stderr};
+
+/// Runs `source` with `--explain` and returns what it wrote to stderr.
+fn explain(source: &str) -> String {
+    let output = run(&["--explain"], source);
+    assert!(output.status.success());
+    stderr(&output)
+}
+
+#[test]
+fn narrates_scopes_closures_and_binding() {
+    let stderr = explain(
+        r#"
+class Point {
+  init(x) { this.x = x; }
+}
+fun makeCounter() {
+  var i = 0;
+  fun count() { i = i + 1; return i; }
+  return count;
+}
+{
+  var p = Point(1);
+  makeCounter()();
+}
+"#,
+    );
+    assert_eq!(
+        stderr,
+        "[explain] capturing the global scope in closure init
+[explain] capturing the global scope in closure makeCounter
+[explain] entering block, creating scope #1
+[explain] creating instance of Point
+[explain] binding this to instance of Point in scope #2 for method init
+[explain] calling init, creating scope #3 for its parameters
+[explain] returning from init
+[explain] calling makeCounter, creating scope #4 for its parameters
+[explain] capturing scope #4 in closure count
+[explain] returning from makeCounter
+[explain] calling count, creating scope #5 for its parameters
+[explain] returning from count
+[explain] leaving block
+[explain] collecting garbage, freeing 2 unreachable scopes and objects kept alive by cycles
+"
+    );
+}
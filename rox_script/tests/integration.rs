@@ -0,0 +1,157 @@
+//! Runs every script below `tests/scripts` and checks what it prints against
+//! the expectations in its comments, in the format of the official Crafting
+//! Interpreters test suite so its tests can be dropped in as they are:
+//!
+//! - `// expect: value` expects `value` as the next line of stdout.
+//! - `// expect runtime error: message` expects the script to stop with
+//!   `message`, reported at the comment's line, and exit with status 70.
+//!   Errors that carry a location of their own, like a failed worker's,
+//!   put it between the two.
+//! - `// Error at 'x': message` expects a compile error at the comment's
+//!   line, and `// [line N] Error...` or `// [java line N] Error...` one at
+//!   line `N`. Scripts with any must exit with status 65 after reporting
+//!   exactly those errors. `// [c line N]` errors are for clox and ignored.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+/// Collects every `.rox` and `.lox` file below `dir`, sorted so failures
+/// are reported in a stable order. Directories named `modules` hold scripts
+/// that tests import and are not run on their own.
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() && path.file_name().is_some_and(|name| name == "modules") {
+            continue;
+        } else if path.is_dir() {
+            found.extend(scripts(&path));
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "rox" || ext == "lox")
+        {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+/// What a script's comments say it should do.
+#[derive(Default)]
+struct Expectations {
+    /// The text after each `// expect: ` comment, in source order.
+    output: Vec<String>,
+    /// The message and line of a `// expect runtime error: ` comment.
+    runtime_error: Option<(String, usize)>,
+    /// Compile errors the way the reporter prints them, e.g.
+    /// `[line 3] Error at '=': Invalid assignment target.`.
+    compile_errors: Vec<String>,
+}
+
+fn expectations(source: &str) -> Expectations {
+    let mut expected = Expectations::default();
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        if let Some(at) = line.find(EXPECT) {
+            expected.output.push(line[at + EXPECT.len()..].to_string());
+        } else if let Some(at) = line.find(EXPECT_RUNTIME_ERROR) {
+            let message = line[at + EXPECT_RUNTIME_ERROR.len()..].to_string();
+            expected.runtime_error = Some((message, number));
+        } else if let Some(error) = compile_error(line, number) {
+            expected.compile_errors.push(error);
+        }
+    }
+    expected
+}
+
+/// The compile error a `// Error...`, `// [line N] Error...` or
+/// `// [java line N] Error...` comment on line `number` expects.
+fn compile_error(line: &str, number: usize) -> Option<String> {
+    let comment = &line[line.find("// ")? + 3..];
+    if comment.starts_with("Error") {
+        return Some(format!("[line {number}] {comment}"));
+    }
+    let rest = comment
+        .strip_prefix("[line ")
+        .or_else(|| comment.strip_prefix("[java line "))?;
+    let (at, error) = rest.split_once("] ")?;
+    error
+        .starts_with("Error")
+        .then(|| format!("[line {at}] {error}"))
+}
+
+/// Lines of `expected` and `actual` side by side, marking the first where
+/// they differ and everything after it.
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let same = expected
+        .iter()
+        .zip(actual)
+        .take_while(|(expected, actual)| expected == actual)
+        .count();
+    let mut out = String::new();
+    for line in &expected[..same] {
+        out.push_str(&format!("    {line}\n"));
+    }
+    for line in &expected[same..] {
+        out.push_str(&format!("  - {line}\n"));
+    }
+    for line in &actual[same..] {
+        out.push_str(&format!("  + {line}\n"));
+    }
+    out
+}
+
+#[test]
+fn scripts_print_expected_output() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut failures = vec![];
+
+    for script in scripts(&root) {
+        let source = fs::read_to_string(&script).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_rox_script"))
+            .arg(&script)
+            .output()
+            .unwrap();
+        let actual: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        let expected = expectations(&source);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let status_ok = match &expected.runtime_error {
+            Some((message, line)) => {
+                output.status.code() == Some(70)
+                    && stderr.lines().next() == Some(message)
+                    // The stack trace, if any, is indented under the line.
+                    && stderr.lines().rfind(|line| !line.starts_with("  "))
+                        == Some(&format!("[line {line}]"))
+            }
+            None if !expected.compile_errors.is_empty() => {
+                output.status.code() == Some(65)
+                    && stderr.lines().eq(expected.compile_errors.iter())
+            }
+            None => output.status.success(),
+        };
+
+        if actual != expected.output || !status_ok {
+            failures.push(format!(
+                "{} exited with {}\n{}  stderr:\n{}",
+                script.strip_prefix(&root).unwrap().display(),
+                output.status,
+                diff(&expected.output, &actual),
+                stderr
+                    .lines()
+                    .map(|line| format!("    {line}\n"))
+                    .collect::<String>(),
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::{run, stderr, stdout};
+
+const EXCEPTIONS: &str = "try {\n  throw \"thrown\";\n} catch (e) {\n  print e;\n}\n";
+
+#[test]
+fn the_flag_sets_the_version_of_scripts_without_a_directive() {
+    let output = run(&["--lang-version=1"], EXCEPTIONS);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at 'try': Feature 'exceptions' requires lang version 2.\n"
+    );
+
+    let output = run(&[], EXCEPTIONS);
+    assert_eq!(stdout(&output), "thrown\n");
+}
+
+#[test]
+fn a_directive_overrides_the_flag() {
+    let source = format!("#lang 2\n{EXCEPTIONS}");
+    let output = run(&["--lang-version=1"], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "thrown\n");
+}
+
+#[test]
+fn unknown_versions_are_a_usage_error() {
+    let output = run(&["--lang-version=5"], "");
+    assert_eq!(output.status.code(), Some(64));
+}
@@ -0,0 +1,239 @@
+mod common;
+
+use common::{run, run_path, script, stderr, stdout};
+
+const DOUBLING: &str = r#"
+var s = "ab";
+while (true) {
+  s = s + s;
+  print s;
+}
+"#;
+
+#[test]
+fn runaway_concatenation_hits_string_limit() {
+    let output = run(&["--max-string-length=16"], DOUBLING);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "abab\nabababab\nabababababababab\n");
+    assert_eq!(
+        stderr(&output),
+        "String concatenation would produce 32 bytes, over the limit of 16.\n[line 4]\n"
+    );
+}
+
+#[test]
+fn strings_up_to_the_limit_are_allowed() {
+    let output = run(&["--max-string-length=4"], "print \"ab\" + \"cd\";\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "abcd\n");
+}
+
+#[test]
+fn collections_stop_growing_at_the_limit() {
+    let source =
+        "var items = [];\nfor (var i = 0; i < 10; i = i + 1) {\n  push(items, i);\n  print i;\n}\n";
+    let output = run(&["--max-collection-length=3"], source);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "0\n1\n2\n");
+    assert_eq!(
+        stderr(&output),
+        "push() would hold 4 items, over the limit of 3.\n[line 3]\n"
+    );
+
+    let source = "var m = {};\nfor (var i = 0; i < 10; i = i + 1) {\n  m[i] = i;\n  m[0] = i;\n}\n";
+    let output = run(&["--max-collection-length=3"], source);
+    assert_eq!(
+        stderr(&output),
+        "Map insertion would hold 4 items, over the limit of 3.\n[line 3]\n"
+    );
+
+    let output = run(
+        &["--max-collection-length=2"],
+        "print [1, 2];\nprint {\"a\": 1, \"a\": 2};\nprint [1, 2, 3];\n",
+    );
+    assert_eq!(stdout(&output), "[1, 2]\n{\"a\": 2}\n");
+    assert_eq!(
+        stderr(&output),
+        "Array literal would hold 3 items, over the limit of 2.\n[line 3]\n"
+    );
+
+    let output = run(&["--max-collection-length=1"], "print {1: 1, 2: 2};\n");
+    assert_eq!(
+        stderr(&output),
+        "Map literal would hold 2 items, over the limit of 1.\n[line 1]\n"
+    );
+}
+
+#[test]
+fn invalid_limit_is_a_usage_error() {
+    let output = run(&["--max-string-length=lots"], "");
+    assert_eq!(output.status.code(), Some(64));
+}
+
+const RUNAWAY_RECURSION: &str = r#"
+fun down(n) {
+  return 1 + down(n + 1);
+}
+down(0);
+"#;
+
+#[test]
+fn runaway_recursion_hits_call_depth_limit() {
+    let path = script(RUNAWAY_RECURSION);
+    let output = run_path(&["--max-call-depth=50"], &path);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "Stack overflow: max call depth 50 exceeded.\n[line 3]\n  at down ({path}:3)\n  ... repeated 49 more times\n  at <script> ({path}:5)\n",
+            path = path.display()
+        )
+    );
+}
+
+#[test]
+fn call_depth_is_limited_by_default() {
+    let output = run(&[], RUNAWAY_RECURSION);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output).lines().take(2).collect::<Vec<_>>(),
+        ["Stack overflow: max call depth 10000 exceeded.", "[line 3]"]
+    );
+}
+
+#[test]
+fn call_depth_error_can_be_caught() {
+    let source = r#"
+fun down(n) {
+  return 1 + down(n + 1);
+}
+try {
+  down(0);
+} catch (error) {
+  print "caught: " + error;
+}
+fun depth(n) {
+  if (n == 0) return 0;
+  return 1 + depth(n - 1);
+}
+print depth(20);
+"#;
+    let output = run(&["--max-call-depth=30"], source);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "caught: Stack overflow: max call depth 30 exceeded.\n20\n"
+    );
+}
+
+#[test]
+fn tail_calls_do_not_count_towards_call_depth() {
+    let source = r#"
+fun count(n) {
+  if (n == 0) return "done";
+  return count(n - 1);
+}
+print count(1000);
+"#;
+    let output = run(&["--max-call-depth=10"], source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "done\n");
+}
+
+#[test]
+fn invalid_call_depth_is_a_usage_error() {
+    let output = run(&["--max-call-depth=-1"], "");
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn runaway_loop_hits_run_time_limit() {
+    let output = run(
+        &["--max-run-time=50"],
+        "print \"started\";\nwhile (true) {}\n",
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "started\n");
+    assert!(stderr(&output).starts_with("Execution time limit of 50 ms exceeded.\n"));
+}
+
+#[test]
+fn sleep_wakes_at_the_run_time_limit() {
+    let started = std::time::Instant::now();
+    let source = r#"
+fun never() {
+  print "never";
+}
+setTimeout(never, 10000);
+try {
+  runPending();
+} catch (e) {
+  print e;
+}
+"#;
+    let output = run(&["--max-run-time=50"], source);
+    assert!(started.elapsed().as_secs() < 5);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn receive_wakes_at_the_run_time_limit() {
+    let started = std::time::Instant::now();
+    let source = "var w = spawnWorker(\"print receive(parent);\");\nprint receive(w);\n";
+    let output = run(&["--max-run-time=200"], source);
+    assert!(started.elapsed().as_secs() < 5);
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("Execution time limit of 200 ms exceeded."),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn workers_are_capped() {
+    let source =
+        "var i = 0;\nwhile (true) {\n  spawnWorker(\"receive(parent);\");\n  i = i + 1;\n}\n";
+    let output = run(&[], source);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        "Can't run more than 64 workers at once.\n[line 3]\n"
+    );
+}
+
+#[test]
+fn growing_array_hits_memory_limit() {
+    let source = "var items = [];\nvar i = 0;\nwhile (true) {\n  push(items, \"item \" + i);\n  i = i + 1;\n}\n";
+    let output = run(&["--max-memory=100000"], source);
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = stderr(&output);
+    assert!(stderr.starts_with("Out of memory: about "), "{stderr}");
+    // Reported at whichever statement of the loop went over.
+    assert!(
+        stderr.contains(" bytes needed, over the limit of 100000.\n[line "),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn memory_freed_by_a_loop_is_not_counted_again() {
+    let source = "for (var i = 0; i < 10000; i = i + 1) {\n  var items = [\"a\", \"b\", \"c\"];\n  var s = \"text \" + i;\n}\nprint \"done\";\n";
+    let output = run(&["--max-memory=100000"], source);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(stdout(&output), "done\n");
+}
+
+#[test]
+fn runaway_loop_hits_step_limit_even_when_catching() {
+    let source = "var i = 0;\nwhile (true) {\n  try {\n    i = i + 1;\n  } catch (e) {\n    print e;\n  }\n}\n";
+    let output = run(&["--max-steps=100"], source);
+    assert_eq!(output.status.code(), Some(70));
+    // The catch block can't run either, as it is over the limit too.
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "Execution step limit of 100 statements exceeded.\n"
+    );
+}
@@ -0,0 +1,54 @@
+use std::path::{Path, PathBuf};
+
+use rox_script::error::LoxError;
+use rox_script::source::{import_path, InMemory};
+use rox_script::Rox;
+
+#[test]
+fn crlf_line_breaks_in_strings_read_as_newlines() {
+    let mut rox = Rox::new();
+    let value = rox.eval("var s = \"one\r\ntwo\";\r\ns;\r\n").unwrap();
+    assert_eq!(value.to_string(), "one\ntwo");
+}
+
+#[test]
+fn crlf_sources_report_the_right_lines_and_columns() {
+    let mut rox = Rox::new();
+    let error = rox
+        .eval("var a = 1;\r\nvar s = \"x\r\ny\";\r\nprint a + nil;\r\n")
+        .unwrap_err();
+    let error = error.downcast_ref::<LoxError>().unwrap();
+    assert_eq!((error.line, error.column), (4, 9));
+}
+
+#[test]
+fn import_paths_accept_either_separator() {
+    let importer = Path::new("scripts").join("main.rox");
+    let expected: PathBuf = ["scripts", "lib", "math.rox"].iter().collect();
+    assert_eq!(import_path(Some(&importer), "lib/math.rox"), expected);
+    assert_eq!(import_path(Some(&importer), "lib\\math.rox"), expected);
+    assert_eq!(
+        import_path(Some(&importer), "./lib/../lib/math.rox"),
+        expected
+    );
+    assert_eq!(
+        import_path(None, "../shared/util.rox"),
+        ["..", "shared", "util.rox"].iter().collect::<PathBuf>()
+    );
+}
+
+#[test]
+fn a_module_named_two_ways_runs_once() {
+    let mut sources = InMemory::new();
+    sources.insert(
+        "main.rox",
+        "import \"lib/count.rox\";\r\nimport \"./lib\\\\count.rox\";\r\ncount.runs;\r\n",
+    );
+    sources.insert(
+        Path::new("lib").join("count.rox"),
+        "export var runs = 0;\r\nruns = runs + 1;\r\n",
+    );
+    let mut rox = Rox::new();
+    rox.set_source_provider(sources);
+    assert_eq!(rox.run_file("main.rox").unwrap().to_string(), "1");
+}
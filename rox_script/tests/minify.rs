@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{run_path, scratch_path, script, stdout};
+
+/// The conformance scripts that run to completion on their own. Scripts
+/// expecting errors quote source text or names that minifying changes, and
+/// imports are resolved relative to the script, so both are left out.
+fn scripts(dir: &Path) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            if !path.ends_with("modules") && !path.ends_with("imports") {
+                found.extend(scripts(&path));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rox") {
+            let source = fs::read_to_string(&path).unwrap();
+            if !source.contains("// expect runtime error: ") && !source.contains("// [line ") {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+#[test]
+fn minified_scripts_behave_the_same() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut checked = 0;
+    for script in scripts(&root) {
+        let minified = run_path(&["minify"], &script);
+        assert_eq!(minified.status.code(), Some(0), "{}", script.display());
+        let minified_path = scratch_path(".rox");
+        fs::write(&minified_path, &minified.stdout).unwrap();
+
+        let original = run_path(&[], &script);
+        let rerun = run_path(&[], &minified_path);
+        assert_eq!(
+            String::from_utf8_lossy(&rerun.stdout),
+            String::from_utf8_lossy(&original.stdout),
+            "{} minified to\n{}",
+            script.display(),
+            String::from_utf8_lossy(&minified.stdout)
+        );
+        assert_eq!(rerun.status.code(), original.status.code());
+        checked += 1;
+    }
+    assert!(checked > 50, "only {checked} scripts checked");
+}
+
+#[test]
+fn comments_whitespace_and_local_names_are_dropped() {
+    let path = script(
+        r#"
+// Adds up the squares.
+var total = 0;
+fun sumOfSquares(numbers) {
+  var sum = 0;
+  for (n in numbers) {
+    sum = sum + n * n; // square
+  }
+  return sum;
+}
+print sumOfSquares([1, 2, 3]);
+"#,
+    );
+    let output = run_path(&["minify"], &path);
+    assert_eq!(
+        stdout(&output),
+        "var total=0;fun sumOfSquares(a){var b=0;for(c in a){b=b+c*c;}return b;}print sumOfSquares([1,2,3]);\n"
+    );
+}
+
+#[test]
+fn new_names_avoid_every_identifier_in_the_script() {
+    let path = script("var a = \"global\";\nfun f(x) {\n  return a + x;\n}\nprint f(\"!\");\n");
+    let output = run_path(&["minify"], &path);
+    assert_eq!(
+        stdout(&output),
+        "var a=\"global\";fun f(b){return a+b;}print f(\"!\");\n"
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{run, stderr, stdout};
+
+#[test]
+fn deeply_nested_groupings_evaluate() {
+    let depth = 5_000;
+    let source = format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+fn long_operator_chains_evaluate() {
+    let source = format!("print 0{};", " + 1".repeat(5_000));
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "5000\n");
+}
+
+#[test]
+fn deeply_nested_unary_operators_evaluate() {
+    let source = format!("print {}true;", "!".repeat(5_000));
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "true\n");
+}
+
+#[test]
+fn deeply_nested_statements_run() {
+    let depth = 3_000;
+    let source = format!(
+        "{}print \"inside\";{}",
+        "if (true) { ".repeat(depth),
+        " }".repeat(depth)
+    );
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "inside\n");
+}
+
+#[test]
+fn excessive_nesting_is_a_compile_error() {
+    let depth = 20_000;
+    let source = format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+    let output = run(&[], &source);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at '(': Too much nesting.\n"
+    );
+}
+
+#[test]
+fn excessive_operator_chain_is_a_compile_error() {
+    let source = format!("print 0{};", " + 1".repeat(20_000));
+    let output = run(&[], &source);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at '1': Too much nesting.\n"
+    );
+}
+
+#[test]
+fn deeply_nested_assignments_resolve_each_target() {
+    // Alternating a global and a local makes every level resolve to the
+    // right one or the prints disagree.
+    let source = format!(
+        "var g = 0; {{ var l = 0; l = {}7; print l; print g; }} print g;",
+        "g = l = ".repeat(1_000)
+    );
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "7\n7\n7\n");
+
+    let depth = 1_000;
+    let source = format!(
+        "var a; {{ var b; b = {}1{}; print a; print b; }}",
+        "(a = (b = ".repeat(depth),
+        "))".repeat(depth)
+    );
+    let output = run(&[], &source);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1\n1\n");
+}
+
+#[test]
+fn excessive_assignment_chain_is_a_compile_error() {
+    let source = format!("var a; a = {}1;", "a = ".repeat(10_000));
+    let output = run(&[], &source);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 1] Error at 'a': Too much nesting.\n"
+    );
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::path::Path;
+
+use common::{run_path, script, stderr, stdout};
+
+/// Runs the script at `path` with `flags`, returning its exit code, stdout
+/// and stderr.
+fn outcome(path: &Path, flags: &[&str]) -> (Option<i32>, String, String) {
+    let output = run_path(flags, path);
+    (output.status.code(), stdout(&output), stderr(&output))
+}
+
+#[test]
+fn optimized_scripts_print_what_unoptimized_ones_do() {
+    let path = script(
+        r#"
+var x = "x";
+print 2 * 3 + 1;
+print -(4 - 6) / 4;
+print 1 < 2 == !nil;
+print "a" == "a" != false;
+print true and x;
+print false and x;
+print nil or x;
+print 1 or x;
+if (false) print "then"; else print "else";
+if (1 > 2) print "dropped";
+while (false) print "never";
+fun f(n) {
+  if (true) return n * (2 + 2);
+}
+print f(3);
+print "ab" * (1 + 1);
+print "total: " + (10 - 4);
+"#,
+    );
+
+    let expected = "7\n0.5\ntrue\ntrue\nx\nfalse\nx\n1\nelse\n12\nabab\ntotal: 6\n";
+    assert_eq!(
+        outcome(&path, &[]),
+        (Some(0), expected.to_string(), String::new())
+    );
+    assert_eq!(
+        outcome(&path, &["--opt"]),
+        (Some(0), expected.to_string(), String::new())
+    );
+}
+
+#[test]
+fn optimized_scripts_fail_where_unoptimized_ones_do() {
+    let path = script("print 1;\nprint -\"one\" + 2;\n");
+
+    let expected = outcome(&path, &[]);
+    assert_eq!(expected.0, Some(70));
+    assert_eq!(outcome(&path, &["--opt"]), expected);
+}
+
+#[test]
+fn dropped_branches_are_not_resolved() {
+    let path = script("if (false) print missing;\nprint \"done\";\n");
+
+    let (code, _, stderr) = outcome(&path, &["--strict"]);
+    assert_eq!(code, Some(65));
+    assert_eq!(
+        stderr,
+        "[line 1] Error at 'missing': Undefined variable 'missing'.\n"
+    );
+
+    assert_eq!(
+        outcome(&path, &["--strict", "--opt"]),
+        (Some(0), "done\n".to_string(), String::new())
+    );
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{project, rox_script, stdout};
+
+#[test]
+fn packed_executables_run_without_the_script() {
+    let dir = project(&[
+        ("greet.rox", "export fun greet(name) {\n  return \"hi \" + name;\n}\n"),
+        (
+            "main.rox",
+            "import \"greet.rox\";\nimport \"std/math\";\nprint greet.greet(args()[0]);\nprint math.floor(2.5);\nexit(len(args()));\n",
+        ),
+    ]);
+    let tool = dir.join("tool");
+    let packed = rox_script()
+        .arg("pack")
+        .arg(dir.join("main.rox"))
+        .arg("-o")
+        .arg(&tool)
+        .output()
+        .unwrap();
+    assert_eq!(packed.status.code(), Some(0), "{packed:?}");
+    fs::remove_file(dir.join("greet.rox")).unwrap();
+    fs::remove_file(dir.join("main.rox")).unwrap();
+
+    // Flags the interpreter would take are the script's in a packed one.
+    let output = Command::new(&tool).args(["bob", "--opt"]).output().unwrap();
+    assert_eq!(stdout(&output), "hi bob\n2\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn pack_takes_nothing_but_an_output_after_the_script() {
+    let dir = project(&[("main.rox", "print 1;\n")]);
+    let output = rox_script()
+        .arg("pack")
+        .arg(dir.join("main.rox"))
+        .arg("--opt")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+}
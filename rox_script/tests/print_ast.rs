@@ -0,0 +1,102 @@
+mod common;
+
+use std::process::Output;
+
+use common::{run, stdout};
+
+/// Writes `source` to a scratch file and prints its syntax tree.
+fn print_ast(source: &str) -> Output {
+    run(&["--print-ast"], source)
+}
+
+#[test]
+fn expressions_show_their_precedence() {
+    let output = print_ast("print -1 + 2 * 3 - (4 - 5);\nprint a or b and !c == d < e;\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "print (- (+ (- 1) (* 2 3)) (group (- 4 5)))\n\
+         print (or a (and b (== (! c) (< d e))))\n"
+    );
+}
+
+#[test]
+fn statements_nest_their_bodies() {
+    let output = print_ast(
+        r#"
+class Point < Base {
+  init(x) { this.x = x; }
+}
+fun f(n) {
+  if (n > 0) return f(n - 1); else print [n, "done"];
+}
+for (x in {1: nil}) items[0] = x;
+"#,
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        r#"class Point < Base
+  fun init(x)
+    expr (= (. this x) x)
+fun f(n)
+  if (> n 0)
+    return (call f (- n 1))
+  else
+    print (array n "done")
+for x in (map (: 1 nil))
+  expr (= ([] items 0) x)
+"#
+    );
+}
+
+#[test]
+fn the_tree_is_printed_without_running_the_script() {
+    let output = print_ast("print undefined;\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "print undefined\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    let output = print_ast("print (1;\n");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn resolved_trees_show_where_names_are_looked_up() {
+    let output = run(
+        &["ast", "--resolve"],
+        r#"
+var count = 0;
+fun counter() {
+  var count = 10;
+  fun inc() {
+    count = count + 1;
+    return count;
+  }
+  return inc;
+}
+class B < A { hi() { return super.hi(this); } }
+print counter()() + count;
+"#,
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        r#"var count = 0
+fun counter()
+  var count = 10
+  fun inc()
+    expr (= count@1 (+ count@1 1))
+    return count@1
+  return inc@0
+class B < A@global
+  fun hi()
+    return (call (super@2 hi) this@1)
+print (+ (call (call counter@global)) count@global)
+"#
+    );
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{run, stderr};
+
+/// Runs `source` with `--profile-memory` and returns what it wrote to stderr.
+fn profile(source: &str) -> String {
+    let output = run(&["--profile-memory"], source);
+    assert!(output.status.success());
+    stderr(&output)
+}
+
+#[test]
+fn values_without_closures_are_all_freed() {
+    let stderr = profile(
+        r#"
+var a = [1, 2];
+var m = {"k": a};
+for (var i = 0; i < 3; i = i + 1) {
+  push(a, [i]);
+}
+"#,
+    );
+    assert_eq!(
+        stderr,
+        "Memory profile:
+                 created      peak      live
+environments           9         5         0
+functions              0         0         0
+instances              0         0         0
+arrays                 4         4         0
+maps                   1         1         0
+"
+    );
+}
+
+#[test]
+fn cycles_that_are_not_collected_are_reported_live() {
+    let stderr = profile(
+        r#"
+fun counter() {
+  var count = 0;
+  fun increment() {
+    count = count + 1;
+    return count;
+  }
+  return increment;
+}
+for (var i = 0; i < 10; i = i + 1) counter()();
+{
+  var a = [];
+  push(a, a);
+}
+"#,
+    );
+    assert_eq!(
+        stderr,
+        "Memory profile:
+                 created      peak      live
+environments          34        15         0
+functions             11        11         0
+instances              0         0         0
+arrays                 1         1         1
+maps                   0         0         0
+"
+    );
+}
+
+#[test]
+fn nothing_is_reported_without_the_flag() {
+    let output = run(&[], "print 1;\n");
+    assert_eq!(stderr(&output), "");
+}
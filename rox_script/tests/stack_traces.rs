@@ -0,0 +1,61 @@
+mod common;
+
+use common::{project, run_path, stderr, stdout};
+use rox_script::error::{Frame, LoxError};
+use rox_script::Rox;
+
+#[test]
+fn runtime_errors_list_the_calls_they_were_raised_in() {
+    let dir = project(&[
+        (
+            "main.rox",
+            "import \"shapes.rox\";\n\nfun describe(shape) {\n  var area = shapes.area(shape);\n  return \"area \" + area;\n}\n\nprint describe(2);\nprint describe(\"square\");\n",
+        ),
+        (
+            "shapes.rox",
+            "export fun area(side) {\n  return side * side;\n}\n",
+        ),
+    ]);
+    let output = run_path(&[], &dir.join("main.rox"));
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "area 4\n");
+    let (main, shapes) = (dir.join("main.rox"), dir.join("shapes.rox"));
+    assert_eq!(
+        stderr(&output),
+        format!(
+            "Operands of '*' must be numbers, or a string and a number, got string and string.
+[line 2]
+  at area ({}:2)
+  at describe ({}:4)
+  at <script> ({}:9)
+",
+            shapes.display(),
+            main.display(),
+            main.display()
+        )
+    );
+}
+
+#[test]
+fn errors_outside_functions_have_no_trace() {
+    let mut rox = Rox::new();
+    let error = rox.eval("var x = nil;\nprint -x;\n").unwrap_err();
+    assert!(error.downcast_ref::<LoxError>().unwrap().trace.is_empty());
+}
+
+#[test]
+fn tail_calls_show_the_function_they_called() {
+    let mut rox = Rox::new();
+    let error = rox
+        .eval("fun fail() {\n  return nil.field;\n}\nfun delegate() {\n  return fail();\n}\ndelegate();\n")
+        .unwrap_err();
+    let frame = |function: &str, line| Frame {
+        function: function.to_string(),
+        file: None,
+        line,
+    };
+    assert_eq!(
+        error.downcast_ref::<LoxError>().unwrap().trace,
+        [frame("fail", 2), frame("<script>", 7)]
+    );
+}
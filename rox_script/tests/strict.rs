@@ -0,0 +1,22 @@
+mod common;
+
+use common::{run_path, script, stderr, stdout};
+
+#[test]
+fn strict_flag_reports_undefined_globals_before_running() {
+    let path = script(
+        "print \"start\";\nfun later() { return count; }\nvar total = 1;\ntotal = totl + 1;\n",
+    );
+
+    let output = run_path(&["--strict"], &path);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Error at 'count': Undefined variable 'count'.\n"
+    );
+
+    let output = run_path(&[], &path);
+    assert_eq!(stdout(&output), "start\n");
+    assert_eq!(output.status.code(), Some(70));
+}
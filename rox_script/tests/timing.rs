@@ -0,0 +1,60 @@
+mod common;
+
+use common::{run, stderr};
+use rox_script::{timing, Rox};
+
+#[test]
+fn calls_are_counted_per_function() {
+    timing::enable();
+    let mut rox = Rox::new();
+    rox.eval(
+        "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nfun twice(f) {\n  f(3);\n  f(3);\n}\ntwice(fib);\n",
+    )
+    .unwrap();
+    let report = timing::report().unwrap();
+    let mut calls: Vec<_> = report
+        .functions
+        .iter()
+        .map(|(name, timing)| (name.as_str(), timing.calls))
+        .collect();
+    calls.sort();
+    assert_eq!(
+        calls,
+        [("<fn fib> (line 1)", 10), ("<fn twice> (line 5)", 1)]
+    );
+    for (_, timing) in &report.functions {
+        assert!(timing.own <= timing.total);
+    }
+}
+
+#[test]
+fn profile_prints_a_table_at_exit() {
+    let output = run(
+        &["--profile"],
+        "fun greet() {\n  return len(\"hi\");\n}\ngreet();\ngreet();\n",
+    );
+    assert!(output.status.success());
+    let stderr = stderr(&output);
+    let mut lines = stderr.lines();
+    assert_eq!(lines.next(), Some("Time profile:"));
+    let header = lines.next().unwrap();
+    assert!(header.starts_with("function"), "{stderr}");
+    assert!(
+        header.ends_with("calls      total ms        own ms"),
+        "{stderr}"
+    );
+    // Each row is the name, then the calls, total and own time.
+    let mut rows: Vec<_> = lines
+        .map(|line| {
+            let words: Vec<_> = line.split_whitespace().collect();
+            let (name, numbers) = words.split_at(words.len() - 3);
+            format!("{} {}", name.join(" "), numbers[0])
+        })
+        .collect();
+    rows.sort();
+    assert_eq!(
+        rows,
+        ["<fn greet> (line 1) 2", "<native fn len> 2"],
+        "{stderr}"
+    );
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::process::Output;
+
+use common::{run, stderr, stdout};
+
+/// Runs `source` with `--trace`.
+fn trace(source: &str) -> Output {
+    run(&["--trace"], source)
+}
+
+#[test]
+fn traces_statements_and_the_values_of_expressions() {
+    let output = trace(
+        "fun add(a, b) {
+  return a + b;
+}
+var x = 1;
+if (x > 0) {
+  print add(x, 2);
+}
+",
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(
+        stderr(&output),
+        "[trace]    1 | fun add(a, b)
+[trace]    4 | var x = 1
+[trace]    5 | if (> x 0)
+[trace]    5 |   x => 1
+[trace]    5 | (> x 0) => true
+[trace]      | block
+[trace]    6 | print (call add x 2)
+[trace]    6 |   add => <fn add>
+[trace]    6 |   x => 1
+[trace]    2 |   return (+ a b)
+[trace]    2 |     a => 1
+[trace]    2 |     b => 2
+[trace]    2 |   (+ a b) => 3
+[trace]    6 | (call add x 2) => 3
+"
+    );
+}
+
+#[test]
+fn the_trace_stops_at_a_failing_expression() {
+    let output = trace("var s = \"a\";\nprint s - 1;\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        "[trace]    1 | var s = \"a\"
+[trace]    2 | print (- s 1)
+[trace]    2 |   s => \"a\"
+Operands of '-' must be numbers, got string and number.
+[line 2]
+"
+    );
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{run, stdout};
+
+/// Writes `source` to a scratch file, runs it through the interpreter binary
+/// and returns everything it printed to stdout.
+fn printed(source: &str) -> String {
+    stdout(&run(&[], source))
+}
+
+#[test]
+fn only_nil_and_false_are_falsey() {
+    let output = printed(
+        r#"
+print !nil;
+print !false;
+print !true;
+print !0;
+print !"";
+print !"false";
+print !clock;
+"#,
+    );
+    assert_eq!(output, "true\ntrue\nfalse\nfalse\nfalse\nfalse\nfalse\n");
+}
+
+#[test]
+fn double_negation_normalises_to_bool() {
+    let output = printed("print !!nil;\nprint !!0;\nprint !!\"\";\n");
+    assert_eq!(output, "false\ntrue\ntrue\n");
+}
+
+#[test]
+fn or_returns_first_truthy_operand() {
+    let output = printed(
+        r#"
+print nil or "fallback";
+print false or 0;
+print 0 or "unused";
+print "" or "unused";
+print false or nil;
+"#,
+    );
+    assert_eq!(output, "fallback\n0\n0\n\nnil\n");
+}
+
+#[test]
+fn and_returns_first_falsey_operand() {
+    let output = printed(
+        r#"
+print false and "unused";
+print nil and "unused";
+print 0 and "zero is truthy";
+print "" and "empty string is truthy";
+print true and false;
+"#,
+    );
+    assert_eq!(
+        output,
+        "false\nnil\nzero is truthy\nempty string is truthy\nfalse\n"
+    );
+}
+
+#[test]
+fn classes_and_instances_are_truthy() {
+    let output = printed(
+        r#"
+class Box {}
+fun f() {}
+print !Box;
+print !Box();
+print !f;
+"#,
+    );
+    assert_eq!(output, "false\nfalse\nfalse\n");
+}
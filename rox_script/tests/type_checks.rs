@@ -0,0 +1,27 @@
+mod common;
+
+use common::{run, stderr, stdout};
+
+const MISMATCHES: &str = r#"
+fun twice(n: number): string {
+  return n + n;
+}
+print twice("ab");
+"#;
+
+#[test]
+fn annotations_are_checked_by_default() {
+    let output = run(&[], MISMATCHES);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        "Argument 'n' of <fn twice> must be number, got string.\n[line 5]\n"
+    );
+}
+
+#[test]
+fn checks_can_be_turned_off() {
+    let output = run(&["--no-type-checks"], MISMATCHES);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "abab\n");
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use std::process::Output;
+
+use common::{run, stderr};
+
+/// Writes `source` to a scratch file and type checks it.
+fn typecheck(source: &str) -> Output {
+    run(&["typecheck"], source)
+}
+
+#[test]
+fn mismatches_are_all_reported_without_running() {
+    let output = typecheck(
+        r#"
+print "not run";
+fun area(w: number, h: number): number {
+  return w * h;
+}
+area(2, "3");
+area(1);
+fun name(): string {
+  return 1 + 2;
+}
+fun scale(n: number) {
+  n = "big";
+}
+"#,
+    );
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        stderr(&output),
+        "[line 6] Error at ')': Argument 'h' of <fn area> must be number, got string.\n\
+         [line 7] Error at ')': Expected 2 arguments but got 1 when calling <fn area>.\n\
+         [line 9] Error at 'return': <fn name> must return string, got number.\n\
+         [line 12] Error at 'n': Can't assign string to 'n', which is declared as number.\n"
+    );
+}
+
+#[test]
+fn types_flow_through_calls_classes_and_methods() {
+    let output = typecheck(
+        r#"
+class Shape {}
+class Square < Shape {
+  init(side: number) {
+    this.side = side;
+  }
+
+  scaled(by: number): Square {
+    return Square(this.side * by);
+  }
+}
+fun describe(shape: Shape): string {
+  return "shape";
+}
+describe(Square(2).scaled(3));
+describe(Shape());
+describe(later());
+fun later(): number {
+  return 1;
+}
+Square(2).scaled("twice");
+"#,
+    );
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(
+        stderr(&output),
+        "[line 17] Error at ')': Argument 'shape' of <fn describe> must be Shape, got number.\n\
+         [line 21] Error at ')': Argument 'by' of <fn scaled> must be number, got string.\n"
+    );
+}
+
+#[test]
+fn unannotated_code_passes() {
+    let output = typecheck(
+        r#"
+var x = 1;
+x = "one";
+fun f(a, b) {
+  return a + b;
+}
+f(x, nil);
+fun area(w: number, h: number): number {
+  return w * h;
+}
+area(x, f(1, 2));
+"#,
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
@@ -0,0 +1,20 @@
+mod common;
+
+use common::{run_path, script, stderr, stdout};
+
+#[test]
+fn warnings_flag_reports_resolver_warnings() {
+    let path =
+        script("fun f() {\n  var unused = 1;\n  var _skipped = 2;\n  return 3;\n}\nprint f();\n");
+
+    let output = run_path(&["--warnings"], &path);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(
+        stderr(&output),
+        "[line 2] Warning: 'unused' is declared but never used.\n"
+    );
+
+    let output = run_path(&[], &path);
+    assert_eq!(stderr(&output), "");
+}